@@ -1,16 +1,36 @@
+// Must match MAX_SOURCES in scene.rs.
+const MAX_SOURCES: u32 = 4;
+
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    source_count: u32,
+    source_fuel: f32,
+    cell_size: vec3<f32>,
+    source_falloff: u32,
+    sources: array<vec4<f32>, MAX_SOURCES>,
+    buoyancy_coefficient: f32,
+    gravity: f32,
+    vorticity_strength: f32,
+    scene_sources_enabled: u32,
+    cursor_source: vec4<f32>,
+    viscosity: f32,
+    ambient_temperature: f32,
+    cooling_rate: f32,
+    max_speed: f32,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 // Texture bindings
 @group(1) @binding(0)
@@ -21,9 +41,18 @@ var velocity_vector_field_write: texture_storage_3d<rgba16float, write>;
 var scalar_field_read: texture_3d<f32>;
 
 const up = vec3<f32>(0.0, 1.0, 0.0);
-const beta = 0.01;
 
-@compute @workgroup_size(4,4,4)
+// How fast the turbulence pattern evolves (noise units/second).
+const TURBULENCE_SPEED: f32 = 0.5;
+// Finite difference step (in noise units) used to take the curl of the noise potential.
+const CURL_EPSILON: f32 = 0.1;
+
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
+@compute @workgroup_size(WG_X, WG_Y, WG_Z)
 fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (gid.x >= params.width || gid.y >= params.height || gid.z >= params.depth) {
         return;
@@ -31,13 +60,96 @@ fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
     let coord = vec3<i32>(gid);
 
     let velocity = textureLoad(velocity_vector_field_read, coord, 0).xyz;
-    let temperature = textureLoad(scalar_field_read, coord, 0).y;
+    let scalars = textureLoad(scalar_field_read, coord, 0);
+    let smoke = scalars.x;
+    let temperature = scalars.y;
+    let fuel = scalars.z;
+    let soot = scalars.w;
 
-    let buoyancy_force = beta * temperature * params.dt * up;
+    // Gas hotter than the ambient air rises and dense smoke sinks.
+    let lift = params.buoyancy_coefficient * (temperature - params.ambient_temperature) - params.gravity * (smoke + soot);
+    let buoyancy_force = lift * params.dt * up;
+    let turbulence_force = get_turbulence(gid, fuel) * params.dt;
 
     textureStore(
         velocity_vector_field_write,
         vec3<i32>(gid),
-        vec4<f32>(velocity + buoyancy_force, 0.0)
+        vec4<f32>(clamp_speed(velocity + buoyancy_force + turbulence_force), 0.0)
     );
 }
+
+// Scales the velocity down to params.max_speed if it is faster, so runaway forces can't overflow
+// the field to infinity.
+fn clamp_speed(velocity: vec3<f32>) -> vec3<f32> {
+    let speed = length(velocity);
+    if (speed > params.max_speed) {
+        return velocity * (params.max_speed / speed);
+    }
+    return velocity;
+}
+
+/* Turbulence injection helpers */
+
+fn hash3(p: vec3<f32>) -> f32 {
+    var q = fract(p * 0.1031);
+    q += dot(q, q.yzx + 33.33);
+    return fract((q.x + q.y) * q.z);
+}
+
+fn value_noise(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(mix(hash3(i + vec3(0.0, 0.0, 0.0)), hash3(i + vec3(1.0, 0.0, 0.0)), u.x),
+            mix(hash3(i + vec3(0.0, 1.0, 0.0)), hash3(i + vec3(1.0, 1.0, 0.0)), u.x), u.y),
+        mix(mix(hash3(i + vec3(0.0, 0.0, 1.0)), hash3(i + vec3(1.0, 0.0, 1.0)), u.x),
+            mix(hash3(i + vec3(0.0, 1.0, 1.0)), hash3(i + vec3(1.0, 1.0, 1.0)), u.x), u.y),
+        u.z
+    );
+}
+
+// Vector potential built from three decorrelated noise fields, each in [-1, 1].
+fn noise_potential(p: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        value_noise(p) * 2.0 - 1.0,
+        value_noise(p + vec3<f32>(31.4, 17.2, 5.9)) * 2.0 - 1.0,
+        value_noise(p + vec3<f32>(-11.7, 43.1, 27.3)) * 2.0 - 1.0
+    );
+}
+
+// Curl of the noise potential via central differences. The result is divergence free, so it
+// stirs the fluid without fighting the pressure projection.
+fn curl_noise(p: vec3<f32>) -> vec3<f32> {
+    let dx = vec3<f32>(CURL_EPSILON, 0.0, 0.0);
+    let dy = vec3<f32>(0.0, CURL_EPSILON, 0.0);
+    let dz = vec3<f32>(0.0, 0.0, CURL_EPSILON);
+
+    let px0 = noise_potential(p - dx);
+    let px1 = noise_potential(p + dx);
+    let py0 = noise_potential(p - dy);
+    let py1 = noise_potential(p + dy);
+    let pz0 = noise_potential(p - dz);
+    let pz1 = noise_potential(p + dz);
+
+    let curl_x = (py1.z - py0.z) - (pz1.y - pz0.y);
+    let curl_y = (pz1.x - pz0.x) - (px1.z - px0.z);
+    let curl_z = (px1.y - px0.y) - (py1.x - py0.x);
+
+    return vec3<f32>(curl_x, curl_y, curl_z) / (2.0 * CURL_EPSILON);
+}
+
+// Returns the turbulence acceleration at the given voxel. The force is weighted by the fuel
+// injected this step so that only the region around the source is stirred.
+fn get_turbulence(gid: vec3<u32>, fuel: f32) -> vec3<f32> {
+    let mask = clamp(fuel, 0.0, 1.0);
+    if (params.turbulence_strength <= 0.0 || mask <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+
+    // Position in reference voxel lengths, so the eddies keep their shape in a non-cubic box.
+    let position = (vec3<f32>(gid) + vec3<f32>(0.5)) * params.cell_size;
+    let p = position * params.turbulence_scale + vec3<f32>(0.0, 0.0, params.elapsed_time * TURBULENCE_SPEED);
+
+    return params.turbulence_strength * mask * curl_noise(p);
+}
@@ -1,28 +1,49 @@
+// Must match MAX_SOURCES in scene.rs.
+const MAX_SOURCES: u32 = 4;
+
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
     elapsed_time: f32,
-    _pad0: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    source_count: u32,
+    source_fuel: f32,
+    cell_size: vec3<f32>,
+    source_falloff: u32,
+    sources: array<vec4<f32>, MAX_SOURCES>,
+    buoyancy_coefficient: f32,
+    gravity: f32,
+    vorticity_strength: f32,
+    scene_sources_enabled: u32,
+    cursor_source: vec4<f32>,
+    viscosity: f32,
+    ambient_temperature: f32,
+    cooling_rate: f32,
+    max_speed: f32,
+    max_density: f32,
+    source_density: f32,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 @group(1) @binding(0)
 var scalar_source: texture_storage_3d<rgba16float, write>;
 
-const center: vec3<f32> = vec3<f32>(64.0, 32.0, 64.0);
-const radius: f32 = 24.0;
-const radius2: f32 = radius * radius;
 const peak: f32 = 1.0;
 
-// Units of fuel injected at the Gaussian peak.
-const INJECTION_RATE: f32 = 1.5;
+// Source falloff curves. Must match SourceFalloff in scene.rs.
+const FALLOFF_HARD: u32 = 0u;
+const FALLOFF_LINEAR: u32 = 1u;
+const FALLOFF_SMOOTHSTEP: u32 = 2u;
+const FALLOFF_GAUSSIAN: u32 = 3u;
 
 // Spatial frequency of noise features (smaller = larger blobs).
 const NOISE_SCALE: f32 = 0.08;
@@ -63,29 +84,69 @@ fn fbm(p: vec3<f32>) -> f32 {
     return v;
 }
 
-/* Adds smoke density (x) and temperature (y) to the source texture. */
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
+/* Adds fuel (z) and smoke (x) from every source sphere, and the one under the cursor, to the source
+ * texture. */
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
     let coord = vec3<i32>(gid);
+    let grid = vec3<f32>(f32(params.width), f32(params.height), f32(params.depth));
     let position = vec3<f32>(gid) + vec3<f32>(0.5);
-    let d = position - center;
-    let dist2 = dot(d, d);
-
-    if dist2 > radius2 { return; }
 
-    let sigma  = max(radius * 0.35, 1e-6);
-    let sigma2 = sigma * sigma;
+    var weight = 0.0;
+    if params.scene_sources_enabled != 0u {
+        for (var i = 0u; i < min(params.source_count, MAX_SOURCES); i++) {
+            weight += source_weight(position, params.sources[i], grid);
+        }
+    }
+    if params.cursor_source.w > 0.0 {
+        weight += source_weight(position, params.cursor_source, grid);
+    }
 
-    let gaussian = peak * exp(-dist2 / (2.0 * sigma2));
+    if weight <= 0.0 { return; }
 
     let noise_p = position * NOISE_SCALE + vec3(0.0, 0.0, params.elapsed_time * NOISE_SPEED);
     let noise = fbm(noise_p);
-    let fuel = gaussian * INJECTION_RATE * max(0.0, 1.0 + NOISE_AMPLITUDE * noise);
+    let amount = weight * max(0.0, 1.0 + NOISE_AMPLITUDE * noise);
 
     textureStore(
         scalar_source,
         coord,
-        vec4<f32>(0.0, 0.0, fuel, 0.0)
+        vec4<f32>(amount * params.source_density, 0.0, amount * params.source_fuel, 0.0)
     );
 }
+
+// Falloff of a source sphere (xyz = center and w = radius, as fractions of the grid) along the
+// params.source_falloff curve, zero outside of it. Distances are in reference voxel lengths so the
+// sphere stays round in a non-cubic box.
+fn source_weight(position: vec3<f32>, source: vec4<f32>, grid: vec3<f32>) -> f32 {
+    let center = grid * source.xyz;
+    let radius = max(grid.x * source.w * params.cell_size.x, 1e-6);
+
+    let d = (position - center) * params.cell_size;
+    // Distance from the center as a fraction of the radius.
+    let r = length(d) / radius;
+
+    if r > 1.0 { return 0.0; }
+
+    switch params.source_falloff {
+        case FALLOFF_HARD: {
+            return peak;
+        }
+        case FALLOFF_LINEAR: {
+            return peak * (1.0 - r);
+        }
+        case FALLOFF_SMOOTHSTEP: {
+            return peak * (1.0 - smoothstep(0.0, 1.0, r));
+        }
+        default: {
+            // Standard deviation of 0.35 radii.
+            return peak * exp(-r * r / (2.0 * 0.35 * 0.35));
+        }
+    }
+}
@@ -1,16 +1,32 @@
+// Must match MAX_SOURCES in scene.rs.
+const MAX_SOURCES: u32 = 4;
+
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    source_count: u32,
+    source_fuel: f32,
+    cell_size: vec3<f32>,
+    source_falloff: u32,
+    sources: array<vec4<f32>, MAX_SOURCES>,
+    buoyancy_coefficient: f32,
+    gravity: f32,
+    vorticity_strength: f32,
+    scene_sources_enabled: u32,
+    cursor_source: vec4<f32>,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 @group(1) @binding(0)
 var velocity_vector_field_read: texture_3d<f32>;
@@ -21,10 +37,13 @@ var curl: texture_3d<f32>;
 @group(1) @binding(3)
 var field_sampler: sampler;
 
-const confinement_constant: f32 = 0.100;
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
 
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main (
     @builtin(global_invocation_id) gid: vec3<u32>
 ) {
@@ -60,7 +79,7 @@ fn main (
 
     // Compute confined vorticity vector
     let curl_value = get_curl(gid);
-    let f = confinement_constant * cross(N, curl_value);
+    let f = params.vorticity_strength * cross(N, curl_value);
 
     textureStore(
         velocity_vector_field_write,
@@ -72,15 +91,15 @@ fn main (
 fn get_curl_magnitude_gradient(gid: vec3<u32>) -> vec3<f32> {
     let right_curl = get_curl_magnitude(vec3<u32>(gid.x + 1, gid.y, gid.z));
     let left_curl = get_curl_magnitude(vec3<u32>(gid.x - 1, gid.y, gid.z));
-    let x_finite_partial = (right_curl - left_curl) * 0.5;
+    let x_finite_partial = (right_curl - left_curl) / (2.0 * params.cell_size.x);
 
     let up_curl = get_curl_magnitude(vec3<u32>(gid.x, gid.y + 1, gid.z));
     let down_curl = get_curl_magnitude(vec3<u32>(gid.x, gid.y - 1, gid.z));
-    let y_finite_partial = (up_curl - down_curl) * 0.5;
+    let y_finite_partial = (up_curl - down_curl) / (2.0 * params.cell_size.y);
 
     let front_curl = get_curl_magnitude(vec3<u32>(gid.x, gid.y, gid.z + 1));
     let back_curl = get_curl_magnitude(vec3<u32>(gid.x, gid.y, gid.z - 1));
-    let z_finite_partial = (front_curl - back_curl) * 0.5;
+    let z_finite_partial = (front_curl - back_curl) / (2.0 * params.cell_size.z);
 
     return vec3<f32>(x_finite_partial, y_finite_partial, z_finite_partial);
 }
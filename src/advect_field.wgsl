@@ -0,0 +1,87 @@
+// Uniform buffers
+struct SimParams {
+    dt: f32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    _pad0: vec2<f32>,
+    cell_size: vec3<f32>,
+}
+@group(0) @binding(0)
+var<uniform> params: SimParams;
+
+// Texture bindings
+@group(1) @binding(0)
+var field_read: texture_3d<f32>;
+@group(1) @binding(1)
+var field_write: texture_storage_3d<rgba16float, write>;
+@group(1) @binding(2)
+var velocity_vector_field_texture: texture_3d<f32>;
+@group(1) @binding(3)
+var field_sampler: sampler;
+
+// 1.0 advects forward in time, -1.0 advects backward in time.
+// Set per pipeline so the same shader serves both halves of BFECC.
+override DIRECTION: f32 = 1.0;
+
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
+/**
+ * Plain semi-Lagrangian advection of every channel of a field, with no sources.
+ * Used for the intermediate forward and backward passes of BFECC.
+ */
+@compute
+@workgroup_size(WG_X, WG_Y, WG_Z)
+fn main (
+    @builtin(global_invocation_id) gid: vec3<u32>
+) {
+    // Global invocation id corresponds to the index of a voxel in the simulation grid.
+    if (gid.x >= params.width || gid.y >= params.height || gid.z >= params.depth) {
+        // In case of out of bounds.
+        return;
+    }
+
+    let uvw = voxel_center_uvw(gid);
+    let vel = textureSampleLevel(velocity_vector_field_texture, field_sampler, uvw, 0.0).xyz;
+    let uvw_back = clamp(backtrace(uvw, vel), vec3<f32>(0.0), vec3<f32>(1.0));
+
+    textureStore(
+        field_write,
+        vec3<i32>(gid),
+        textureSampleLevel(field_read, field_sampler, uvw_back, 0.0)
+    );
+}
+
+// Returns the center of the voxel indexed at gid.
+fn voxel_center_uvw(gid: vec3<u32>) -> vec3<f32> {
+    let w = f32(params.width);
+    let h = f32(params.height);
+    let d = f32(params.depth);
+    return vec3<f32>(
+        (f32(gid.x) + 0.5) / w,
+        (f32(gid.y) + 0.5) / h,
+        (f32(gid.z) + 0.5) / d
+    );
+}
+
+// Returns the uvw backtraced by the given velocity scaled by the simulation timestep.
+fn backtrace(uvw: vec3<f32>, velocity: vec3<f32>) -> vec3<f32> {
+    let w = f32(params.width);
+    let h = f32(params.height);
+    let d = f32(params.depth);
+
+    // Velocity is in reference voxel lengths per second (see cell_size), so convert to cells per second
+    // and then to texture coordinates per second, which are in range of [0, 1]
+    let vel_uvw = velocity / params.cell_size / vec3<f32>(w, h, d);
+    return uvw - DIRECTION * params.dt * vel_uvw;
+}
@@ -1,16 +1,21 @@
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    _pad0: vec2<f32>,
+    cell_size: vec3<f32>,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 // Texture bindings
 @group(1) @binding(0)
@@ -24,8 +29,13 @@ var scalar_source: texture_3d<f32>;
 @group(1) @binding(4)
 var field_sampler: sampler;
 
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main (
     @builtin(global_invocation_id) gid: vec3<u32>
 ) {
@@ -72,7 +82,8 @@ fn backtrace(uvw: vec3<f32>, velocity: vec3<f32>) -> vec3<f32> {
     let h = f32(params.height);
     let d = f32(params.depth);
 
-    // Velocity is in cells per second so convert to texture coordinates per second, which are in range of [0, 1]
-    let vel_uvw = vec3<f32>(velocity.x / w, velocity.y / h, velocity.z / d);
+    // Velocity is in reference voxel lengths per second (see cell_size), so convert to cells per second
+    // and then to texture coordinates per second, which are in range of [0, 1]
+    let vel_uvw = velocity / params.cell_size / vec3<f32>(w, h, d);
     return uvw - params.dt * vel_uvw;
 }
@@ -0,0 +1,124 @@
+// Uniform buffers
+struct SimParams {
+    dt: f32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    _pad0: vec2<f32>,
+    cell_size: vec3<f32>,
+}
+@group(0) @binding(0)
+var<uniform> params: SimParams;
+
+// Texture bindings
+@group(1) @binding(0)
+var forward_scalar_field_read: texture_3d<f32>;
+@group(1) @binding(1)
+var scalar_field_write: texture_storage_3d<rgba16float, write>;
+@group(1) @binding(2)
+var velocity_vector_field_texture: texture_3d<f32>;
+@group(1) @binding(3)
+var scalar_source: texture_3d<f32>;
+@group(1) @binding(4)
+var original_scalar_field: texture_3d<f32>;
+@group(1) @binding(5)
+var round_trip_scalar_field: texture_3d<f32>;
+@group(1) @binding(6)
+var field_sampler: sampler;
+
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
+/**
+ * Final MacCormack pass. The forward field is the original advected forward, and the round trip
+ * field is the forward field advected back. Half of the round trip's difference from the original
+ * estimates the error of one advection, and is added to the forward field instead of advecting a
+ * corrected field again like BFECC, which saves a pass.
+ * The result is clamped to the range of the original field around the backtraced point, which
+ * keeps the correction from overshooting and creating new extrema. Sources are added the same
+ * way advect_scalars.wgsl does.
+ */
+@compute
+@workgroup_size(WG_X, WG_Y, WG_Z)
+fn main (
+    @builtin(global_invocation_id) gid: vec3<u32>
+) {
+    // Global invocation id corresponds to the index of a voxel in the simulation grid.
+    if (gid.x >= params.width || gid.y >= params.height || gid.z >= params.depth) {
+        // In case of out of bounds.
+        return;
+    }
+
+    let coord = vec3<i32>(gid);
+    let forward = textureLoad(forward_scalar_field_read, coord, 0);
+    let original = textureLoad(original_scalar_field, coord, 0);
+    let round_trip = textureLoad(round_trip_scalar_field, coord, 0);
+    let corrected_scalar = forward + 0.5 * (original - round_trip);
+
+    let uvw = voxel_center_uvw(gid);
+    let vel = textureSampleLevel(velocity_vector_field_texture, field_sampler, uvw, 0.0).xyz;
+    let uvw_back = clamp(backtrace(uvw, vel), vec3<f32>(0.0), vec3<f32>(1.0));
+
+    let bounds = get_neighborhood_bounds(uvw_back);
+    let backtraced_scalar = clamp(corrected_scalar, bounds[0], bounds[1]);
+
+    let backtraced_scalar_source = textureSampleLevel(scalar_source, field_sampler, uvw_back, 0.0);
+    let total_backtraced_scalar = backtraced_scalar + backtraced_scalar_source;
+
+    textureStore(
+        scalar_field_write,
+        coord,
+        vec4<f32>(total_backtraced_scalar.x, total_backtraced_scalar.y, backtraced_scalar_source.z, total_backtraced_scalar.w)
+    );
+}
+
+// Returns the min (index 0) and max (index 1) of the original field over the 8 voxels that are
+// interpolated when sampling at uvw.
+fn get_neighborhood_bounds(uvw: vec3<f32>) -> array<vec4<f32>, 2> {
+    let dims = vec3<i32>(i32(params.width), i32(params.height), i32(params.depth));
+    let base = vec3<i32>(floor(uvw * vec3<f32>(dims) - 0.5));
+
+    var lo = vec4<f32>(3.4e38);
+    var hi = vec4<f32>(-3.4e38);
+    for (var i = 0; i < 8; i++) {
+        let offset = vec3<i32>(i & 1, (i >> 1) & 1, (i >> 2) & 1);
+        let coord = clamp(base + offset, vec3<i32>(0), dims - vec3<i32>(1));
+        let value = textureLoad(original_scalar_field, coord, 0);
+        lo = min(lo, value);
+        hi = max(hi, value);
+    }
+    return array<vec4<f32>, 2>(lo, hi);
+}
+
+// Returns the center of the voxel indexed at gid.
+fn voxel_center_uvw(gid: vec3<u32>) -> vec3<f32> {
+    let w = f32(params.width);
+    let h = f32(params.height);
+    let d = f32(params.depth);
+    return vec3<f32>(
+        (f32(gid.x) + 0.5) / w,
+        (f32(gid.y) + 0.5) / h,
+        (f32(gid.z) + 0.5) / d
+    );
+}
+
+// Returns the uvw backtraced by the given velocity scaled by the simulation timestep.
+fn backtrace(uvw: vec3<f32>, velocity: vec3<f32>) -> vec3<f32> {
+    let w = f32(params.width);
+    let h = f32(params.height);
+    let d = f32(params.depth);
+
+    // Velocity is in reference voxel lengths per second (see cell_size), so convert to cells per second
+    // and then to texture coordinates per second, which are in range of [0, 1]
+    let vel_uvw = velocity / params.cell_size / vec3<f32>(w, h, d);
+    return uvw - params.dt * vel_uvw;
+}
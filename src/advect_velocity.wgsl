@@ -1,16 +1,21 @@
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    _pad0: vec2<f32>,
+    cell_size: vec3<f32>,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 // Texture bindings
 @group(1) @binding(0)
@@ -18,10 +23,17 @@ var velocity_vector_field_read: texture_3d<f32>;
 @group(1) @binding(1)
 var velocity_vector_field_write: texture_storage_3d<rgba16float, write>;
 @group(1) @binding(2)
+var obstacles: texture_3d<f32>;
+@group(1) @binding(3)
 var field_sampler: sampler;
 
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main (
     @builtin(global_invocation_id) gid: vec3<u32>
 ) {
@@ -36,6 +48,13 @@ fn main (
 
 fn advect_velocity(gid: vec3<u32>) {
     let uvw = voxel_center_uvw(gid);
+
+    // Obstacles are static, so nothing moves inside them.
+    if (textureSampleLevel(obstacles, field_sampler, uvw, 0.0).x > 0.5) {
+        textureStore(velocity_vector_field_write, vec3<i32>(gid), vec4<f32>(0.0));
+        return;
+    }
+
     let vel = textureSampleLevel(velocity_vector_field_read, field_sampler, uvw, 0.0).xyz;
     let uvw_back = clamp(backtrace(uvw, vel), vec3<f32>(0.0), vec3<f32>(1.0));
 
@@ -67,7 +86,8 @@ fn backtrace(uvw: vec3<f32>, velocity: vec3<f32>) -> vec3<f32> {
     let h = f32(params.height);
     let d = f32(params.depth);
 
-    // Velocity is in cells per second so convert to texture coordinates per second, which are in range of [0, 1]
-    let vel_uvw = vec3<f32>(velocity.x / w, velocity.y / h, velocity.z / d);
+    // Velocity is in reference voxel lengths per second (see cell_size), so convert to cells per second
+    // and then to texture coordinates per second, which are in range of [0, 1]
+    let vel_uvw = velocity / params.cell_size / vec3<f32>(w, h, d);
     return uvw - params.dt * vel_uvw;
 }
@@ -0,0 +1,26 @@
+/// Scheme used to advect the scalar field through the velocity field.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AdvectionScheme {
+    /// First order semi-Lagrangian advection. Cheap, but numerically diffusive.
+    SemiLagrangian,
+    /// Back and Forth Error Compensation and Correction.
+    /// Advects forward, advects the result backward, corrects the original field by half of the
+    /// round trip error, then advects the corrected field. Costs three extra passes but keeps
+    /// features much sharper.
+    Bfecc,
+    /// MacCormack. Advects forward and the result backward like BFECC, but adds half of the round
+    /// trip error to the forward result instead of advecting a corrected field again. Nearly as
+    /// sharp as BFECC for one pass less.
+    MacCormack,
+}
+
+impl AdvectionScheme {
+    /// Returns the scheme after this one, wrapping around. Used to cycle schemes at runtime.
+    pub fn next(self) -> Self {
+        match self {
+            AdvectionScheme::SemiLagrangian => AdvectionScheme::Bfecc,
+            AdvectionScheme::Bfecc => AdvectionScheme::MacCormack,
+            AdvectionScheme::MacCormack => AdvectionScheme::SemiLagrangian,
+        }
+    }
+}
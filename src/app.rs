@@ -1,27 +1,66 @@
 use std::sync::Arc;
 use instant::Instant;
 use winit::application::ApplicationHandler;
+use winit::dpi::PhysicalPosition;
 use winit::event::{DeviceEvent, DeviceId, KeyEvent, MouseButton, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
-use winit::keyboard::PhysicalKey;
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::Window;
+use crate::input_recording::{InputEvent, InputRecorder, InputReplay};
 use crate::state::State;
+use crate::config::START_PAUSED;
+use crate::{CameraSettings, InitialDensity, InitialVelocityField, RenderSettings, RunOptions};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 #[cfg(target_arch = "wasm32")]
 use winit::event_loop::EventLoop;
 
+/// Number of frames between window title refreshes. Counted in frames rather than simulation
+/// steps so the title keeps up with the camera while the simulation is paused.
+const TITLE_UPDATE_INTERVAL: u64 = 30;
+
 #[derive(Default)]
 pub struct App {
     #[cfg(target_arch = "wasm32")]
     proxy: Option<winit::event_loop::EventLoopProxy<State>>,
     state: Option<State>,
     last_render_time: Option<Instant>,
+    /// Frames rendered so far.
+    frame_count: u64,
+    /// Dimensions of the simulation grid.
+    grid_dimensions: wgpu::Extent3d,
+    /// World-space size of the simulation box, or None for the simulation's default.
+    box_size: Option<[f32; 3]>,
+    /// Initial scalar field volume. Taken when the state is created.
+    density_volume: Option<Vec<u8>>,
+    /// Initial render settings. Taken when the state is created.
+    render_settings: Option<RenderSettings>,
+    /// Initial camera settings. Taken when the state is created.
+    camera_settings: Option<CameraSettings>,
+    /// Start with the simulation paused.
+    paused: bool,
+    /// Initial velocity field, instead of INITIAL_VELOCITY_FIELD. Taken when the state is created.
+    initial_velocity: Option<InitialVelocityField>,
+    /// Initial velocity speed, instead of INITIAL_VELOCITY_SPEED. Taken when the state is created.
+    velocity_scale: Option<f32>,
+    /// Initial density, instead of the scene's initial scalar field. Taken when the state is created.
+    initial_density: Option<InitialDensity>,
+    /// Background color, instead of BACKGROUND_COLOR. Taken when the state is created.
+    background: Option<wgpu::Color>,
+    /// Present mode, instead of PRESENT_MODE. Taken when the state is created.
+    present_mode: Option<wgpu::PresentMode>,
+    recorder: Option<InputRecorder>,
+    /// While set, live input is ignored and frames take their input and duration from here.
+    replay: Option<InputReplay>,
+    /// Directory recorded frames are written to, instead of FRAME_OUTPUT_DIR. Taken when the state
+    /// is created.
+    #[cfg(not(target_arch = "wasm32"))]
+    frame_output_dir: Option<std::path::PathBuf>,
 }
 
 impl App {
-    pub fn new(#[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>) -> Self {
+    pub fn new(#[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>, options: RunOptions) -> Self {
         #[cfg(target_arch = "wasm32")]
         let proxy = Some(event_loop.create_proxy());
         Self {
@@ -29,8 +68,58 @@ impl App {
             #[cfg(target_arch = "wasm32")]
             proxy,
             last_render_time: None,
+            frame_count: 0,
+            grid_dimensions: options.grid_dimensions(),
+            box_size: options.box_size(),
+            density_volume: options.density_volume,
+            render_settings: options.render_settings,
+            camera_settings: options.camera_settings,
+            paused: options.paused.unwrap_or(START_PAUSED),
+            initial_velocity: options.initial_velocity,
+            velocity_scale: options.velocity_scale,
+            initial_density: options.initial_density,
+            background: options.background,
+            present_mode: options.present_mode,
+            recorder: options.input_recorder,
+            replay: options.input_replay,
+            #[cfg(not(target_arch = "wasm32"))]
+            frame_output_dir: options.frame_output_dir,
         }
     }
+
+    /// Records live input and passes it on, unless a replay is running. Escape still quits
+    /// during a replay.
+    fn handle_live_input(&mut self, event_loop: &ActiveEventLoop, event: InputEvent) {
+        let state = match &mut self.state {
+            Some(canvas) => canvas,
+            None => return,
+        };
+
+        let is_quit = matches!(event, InputEvent::Key { code: KeyCode::Escape, .. });
+        if self.replay.is_some() && !is_quit {
+            return;
+        }
+
+        if let Some(recorder) = &mut self.recorder
+            && let Err(e) = recorder.record(&event)
+        {
+            log::error!("Failed to record input, recording stopped: {e}");
+            self.recorder = None;
+        }
+        handle_input(state, event_loop, event);
+    }
+}
+
+/// Routes an input event to the state. Live and replayed input both go through here.
+fn handle_input(state: &mut State, event_loop: &ActiveEventLoop, event: InputEvent) {
+    match event {
+        InputEvent::Key { code, state: key_state } => state.handle_key(event_loop, code, key_state),
+        InputEvent::MouseButton(mouse_state) => state.handle_mouse_click(mouse_state),
+        InputEvent::MouseMotion { dx, dy } => state.handle_mouse_motion(dx, dy),
+        InputEvent::Scroll(delta) => state.camera_controller.handle_mouse_scroll(&delta),
+        InputEvent::InjectButton(button_state) => state.handle_inject_button(button_state),
+        InputEvent::CursorMoved { x, y } => state.handle_cursor_moved(PhysicalPosition::new(x, y)),
+    }
 }
 
 impl ApplicationHandler<State> for App {
@@ -53,11 +142,43 @@ impl ApplicationHandler<State> for App {
         }
 
         let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+        let grid_dimensions = self.grid_dimensions;
+        let box_size = self.box_size;
+        let density_volume = self.density_volume.take();
+        let render_settings = self.render_settings.take().unwrap_or_default();
+        let camera_settings = self.camera_settings.take().unwrap_or_default();
+        let paused = self.paused;
 
         #[cfg(not(target_arch = "wasm32"))]
         {
             // If we are not on web we can use pollster to await the async state creation.
-            self.state = Some(pollster::block_on(State::new(window)).unwrap());
+            let mut state = match pollster::block_on(State::new(window, grid_dimensions, box_size, density_volume, render_settings, camera_settings, paused)) {
+                Ok(state) => state,
+                Err(e) => {
+                    log::error!("Failed to initialize the renderer: {e:#}");
+                    event_loop.exit();
+                    return;
+                }
+            };
+            if let Some(frame_output_dir) = self.frame_output_dir.take() {
+                state.set_frame_output_dir(frame_output_dir);
+            }
+            if let Some(initial_velocity) = self.initial_velocity.take() {
+                state.set_initial_velocity(initial_velocity);
+            }
+            if let Some(velocity_scale) = self.velocity_scale.take() {
+                state.set_initial_velocity_speed(velocity_scale);
+            }
+            if let Some(initial_density) = self.initial_density.take() {
+                state.set_initial_density(initial_density);
+            }
+            if let Some(background) = self.background.take() {
+                state.set_default_background(background);
+            }
+            if let Some(present_mode) = self.present_mode.take() {
+                state.set_present_mode(present_mode);
+            }
+            self.state = Some(state);
         }
 
         #[cfg(target_arch = "wasm32")]
@@ -68,7 +189,7 @@ impl ApplicationHandler<State> for App {
                 wasm_bindgen_futures::spawn_local(async move {
                     assert!(proxy
                         .send_event(
-                            State::new(window)
+                            State::new(window, grid_dimensions, box_size, density_volume, render_settings, camera_settings, paused)
                                 .await
                                 .expect("Unable to create canvas!!!")
                         )
@@ -89,6 +210,21 @@ impl ApplicationHandler<State> for App {
                 event.window.inner_size().height,
             );
         }
+        if let Some(initial_velocity) = self.initial_velocity.take() {
+            event.set_initial_velocity(initial_velocity);
+        }
+        if let Some(velocity_scale) = self.velocity_scale.take() {
+            event.set_initial_velocity_speed(velocity_scale);
+        }
+        if let Some(initial_density) = self.initial_density.take() {
+            event.set_initial_density(initial_density);
+        }
+        if let Some(background) = self.background.take() {
+            event.set_default_background(background);
+        }
+        if let Some(present_mode) = self.present_mode.take() {
+            event.set_present_mode(present_mode);
+        }
         self.state = Some(event);
     }
 
@@ -105,23 +241,80 @@ impl ApplicationHandler<State> for App {
 
         let last_render_time = *self.last_render_time.get_or_insert_with(Instant::now);
 
-        match event {
-            WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::Resized(size) => state.resize(size.width, size.height),
+        let input = match event {
+            WindowEvent::CloseRequested => {
+                state.shutdown();
+                event_loop.exit();
+                None
+            }
+            WindowEvent::Resized(size) => {
+                let was_hidden = state.is_hidden();
+                state.resize(size.width, size.height);
+                if was_hidden && !state.is_hidden() {
+                    state.window.request_redraw();
+                }
+                None
+            }
+            WindowEvent::Occluded(occluded) => {
+                let was_hidden = state.is_hidden();
+                state.set_occluded(occluded);
+                if was_hidden && !state.is_hidden() {
+                    state.window.request_redraw();
+                }
+                None
+            }
+            // Don't keep the cursor from other windows.
+            WindowEvent::Focused(false) => {
+                state.set_mouse_look(false);
+                None
+            }
+            // Skipped without requesting another redraw, so the event loop sleeps until the window
+            // shows again. The time it was hidden doesn't count towards the next frame.
+            WindowEvent::RedrawRequested if state.is_hidden() => {
+                self.last_render_time = None;
+                None
+            }
             WindowEvent::RedrawRequested => {
                 let now = Instant::now();
-                let dt = now - last_render_time;
+                let mut dt = now - last_render_time;
                 self.last_render_time = Some(now);
-                // TODO: Change to fixed update
+
+                if let Some(replay) = &mut self.replay {
+                    match replay.next_frame() {
+                        Some(frame) => {
+                            for event in frame.events {
+                                handle_input(state, event_loop, event);
+                            }
+                            dt = frame.dt;
+                        }
+                        None => {
+                            log::info!("Input replay finished, taking live input");
+                            self.replay = None;
+                        }
+                    }
+                }
+                if let Some(recorder) = &mut self.recorder
+                    && let Err(e) = recorder.record_frame(dt)
+                {
+                    log::error!("Failed to record input, recording stopped: {e}");
+                    self.recorder = None;
+                }
+
                 state.update(dt);
                 match state.render() {
-                    Ok(_) => {}
+                    Ok(_) => {
+                        self.frame_count += 1;
+                        if self.frame_count.is_multiple_of(TITLE_UPDATE_INTERVAL) {
+                            state.window.set_title(&state.status_line());
+                        }
+                    }
                     Err(e) => {
                         // Log the error and exit gracefully
                         log::error!("{e}");
                         event_loop.exit();
                     }
                 }
+                None
             }
             WindowEvent::KeyboardInput {
                 event:
@@ -131,35 +324,41 @@ impl ApplicationHandler<State> for App {
                     ..
                 },
                 ..
-            } => state.handle_key(event_loop, code, key_state),
+            } => Some(InputEvent::Key { code, state: key_state }),
             WindowEvent::MouseInput { button: MouseButton::Left, state: mouse_state, ..} => {
-                state.handle_mouse_click(mouse_state);
+                Some(InputEvent::MouseButton(mouse_state))
+            }
+            WindowEvent::MouseInput { button: MouseButton::Right, state: button_state, ..} => {
+                Some(InputEvent::InjectButton(button_state))
             }
-            _ => {}
+            WindowEvent::CursorMoved { position, .. } => Some(InputEvent::CursorMoved { x: position.x, y: position.y }),
+            _ => None,
+        };
+
+        if let Some(input) = input {
+            self.handle_live_input(event_loop, input);
         }
     }
 
     fn device_event(
         &mut self,
-        _event_loop: &ActiveEventLoop,
+        event_loop: &ActiveEventLoop,
         _device_id: DeviceId,
         event: DeviceEvent,
     ) {
-        let state = match &mut self.state {
-            Some(canvas) => canvas,
-            None => return,
+        let input = match event {
+            DeviceEvent::MouseMotion { delta } => InputEvent::MouseMotion { dx: delta.0, dy: delta.1 },
+            DeviceEvent::MouseWheel { delta } => InputEvent::Scroll(delta),
+            _ => return,
         };
+        self.handle_live_input(event_loop, input);
+    }
 
-        match event {
-            DeviceEvent::MouseMotion { delta } => {
-                if state.mouse_pressed {
-                    state.camera_controller.handle_mouse(delta.0, delta.1);
-                }
-            },
-            DeviceEvent::MouseWheel { delta } => {
-                state.camera_controller.handle_mouse_scroll(&delta);
-            }
-            _ => {}
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(recorder) = &mut self.recorder
+            && let Err(e) = recorder.flush()
+        {
+            log::error!("Failed to save input recording: {e}");
         }
     }
 }
@@ -0,0 +1,85 @@
+// Must match MAX_SOURCES in scene.rs.
+const MAX_SOURCES: u32 = 4;
+
+// Uniform buffers
+struct SimParams {
+    dt: f32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    source_count: u32,
+    source_fuel: f32,
+    cell_size: vec3<f32>,
+    source_falloff: u32,
+    sources: array<vec4<f32>, MAX_SOURCES>,
+    buoyancy_coefficient: f32,
+    gravity: f32,
+    vorticity_strength: f32,
+    scene_sources_enabled: u32,
+    cursor_source: vec4<f32>,
+    viscosity: f32,
+    ambient_temperature: f32,
+}
+@group(0) @binding(0)
+var<uniform> params: SimParams;
+
+// Texture bindings
+@group(1) @binding(0)
+var field_read: texture_3d<f32>;
+@group(1) @binding(1)
+var field_write: texture_storage_3d<rgba16float, write>;
+
+// 1.0 damps channel y (the temperature of the scalar field) towards the ambient temperature, 0.0
+// damps it towards zero like the other channels. Set per pipeline so the same shader drains both
+// the scalar and the velocity field.
+override AMBIENT_TEMPERATURE_TARGET: f32 = 0.0;
+
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
+/**
+ * Drains the field inside the drain sphere. Channels are damped towards zero, so the same shader
+ * removes smoke/fuel/soot from the scalar field and damps the velocity field, except for the
+ * scalar field's temperature, which is damped towards the ambient temperature (see
+ * AMBIENT_TEMPERATURE_TARGET). The damping falls off smoothly from the center to the edge of the
+ * sphere.
+ */
+@compute
+@workgroup_size(WG_X, WG_Y, WG_Z)
+fn main (
+    @builtin(global_invocation_id) gid: vec3<u32>
+) {
+    // Global invocation id corresponds to the index of a voxel in the simulation grid.
+    if (gid.x >= params.width || gid.y >= params.height || gid.z >= params.depth) {
+        // In case of out of bounds.
+        return;
+    }
+
+    let coord = vec3<i32>(gid);
+    let value = textureLoad(field_read, coord, 0);
+
+    let grid = vec3<f32>(f32(params.width), f32(params.height), f32(params.depth));
+    let position = vec3<f32>(gid) + vec3<f32>(0.5);
+    let center = grid * params.drain.xyz;
+    let radius = params.drain.w;
+    let dist = length((position - center) * params.cell_size);
+    let mask = 1.0 - smoothstep(0.0, radius, dist);
+
+    let retained = exp(-params.drain_strength * params.dt * mask);
+    let drain_target = vec4<f32>(0.0, AMBIENT_TEMPERATURE_TARGET * params.ambient_temperature, 0.0, 0.0);
+
+    textureStore(
+        field_write,
+        coord,
+        drain_target + (value - drain_target) * retained
+    );
+}
@@ -0,0 +1,48 @@
+// Uniform buffers
+struct SimParams {
+    dt: f32,
+    width: u32,
+    height: u32,
+    depth: u32,
+}
+@group(0) @binding(0)
+var<uniform> params: SimParams;
+
+// Texture bindings
+@group(1) @binding(0)
+var field_read: texture_3d<f32>;
+@group(1) @binding(1)
+var field_write: texture_storage_3d<rgba16float, write>;
+@group(1) @binding(2)
+var obstacles: texture_3d<f32>;
+
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
+/**
+ * Zeroes every channel of the field inside solid voxels and copies it everywhere else, so
+ * whatever the advection scheme carried into an obstacle doesn't stay there.
+ */
+@compute
+@workgroup_size(WG_X, WG_Y, WG_Z)
+fn main (
+    @builtin(global_invocation_id) gid: vec3<u32>
+) {
+    // Global invocation id corresponds to the index of a voxel in the simulation grid.
+    if (gid.x >= params.width || gid.y >= params.height || gid.z >= params.depth) {
+        // In case of out of bounds.
+        return;
+    }
+
+    let coord = vec3<i32>(gid);
+    let value = textureLoad(field_read, coord, 0);
+    let solid = textureLoad(obstacles, coord, 0).x > 0.5;
+
+    textureStore(
+        field_write,
+        coord,
+        select(value, vec4<f32>(0.0), solid)
+    );
+}
@@ -0,0 +1,190 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use wgpu::Device;
+
+/// Size of the [log_sum, count] pair written by compute_luminance.wgsl.
+const LUMINANCE_SUM_SIZE: wgpu::BufferAddress = 2 * size_of::<f32>() as wgpu::BufferAddress;
+
+/// Measures the average luminance of rendered frames for auto-exposure.
+///
+/// The render pass writes pre-exposure luminance to a second color target. A single workgroup
+/// reduces it to the sum of log luminance over lit pixels, which is copied to a staging buffer
+/// and mapped asynchronously so the render loop never waits on the GPU. Only one readback is in
+/// flight at a time, so frames are measured whenever the previous result has arrived.
+pub struct AutoExposure {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    sum_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Set by the map_async callback once the readback buffer can be read.
+    readback_ready: Arc<AtomicBool>,
+    /// True from the frame a measurement is encoded until its result has been read.
+    readback_in_flight: bool,
+}
+
+impl AutoExposure {
+    pub fn new(device: &Device, luminance_view: &wgpu::TextureView) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Luminance Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("compute_luminance.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compute Luminance Bind Group Layout"),
+            entries: &[
+                // 0. Luminance texture read.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                // 1. Log luminance sum write.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ]
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Luminance Pipeline Layout"),
+            bind_group_layouts: &[Some(&bind_group_layout)],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Luminance Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let sum_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Sum Buffer"),
+            size: LUMINANCE_SUM_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Readback Buffer"),
+            size: LUMINANCE_SUM_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = create_bind_group(device, &bind_group_layout, luminance_view, &sum_buffer);
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            sum_buffer,
+            readback_buffer,
+            readback_ready: Arc::new(AtomicBool::new(false)),
+            readback_in_flight: false,
+        }
+    }
+
+    /// Rebinds the luminance texture. Must be called whenever it is recreated (e.g. on resize).
+    pub fn resize(&mut self, device: &Device, luminance_view: &wgpu::TextureView) {
+        self.bind_group = create_bind_group(device, &self.bind_group_layout, luminance_view, &self.sum_buffer);
+    }
+
+    /// Encodes the luminance reduction and the copy to the readback buffer, unless a previous
+    /// measurement is still in flight. Returns true if a measurement was encoded, in which case
+    /// request_readback must be called after the encoder is submitted.
+    pub fn encode(&mut self, encoder: &mut wgpu::CommandEncoder) -> bool {
+        if self.readback_in_flight {
+            return false;
+        }
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.sum_buffer, 0, &self.readback_buffer, 0, LUMINANCE_SUM_SIZE);
+
+        self.readback_in_flight = true;
+        true
+    }
+
+    /// Whether a measurement has been encoded and its result not read yet.
+    pub fn readback_in_flight(&self) -> bool {
+        self.readback_in_flight
+    }
+
+    /// Starts mapping the readback buffer. Call after submitting a frame that encode wrote to.
+    pub fn request_readback(&self) {
+        let readback_ready = self.readback_ready.clone();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if result.is_ok() {
+                readback_ready.store(true, Ordering::Release);
+            }
+        });
+    }
+
+    /// Returns the geometric mean luminance of the last measured frame once its readback has
+    /// arrived. Returns None while waiting, or if no pixel of the frame was lit.
+    pub fn poll(&mut self, device: &Device) -> Option<f32> {
+        if !self.readback_in_flight {
+            return None;
+        }
+
+        // Never block: the result is picked up on a later frame if it is not ready yet.
+        let _ = device.poll(wgpu::PollType::Poll);
+        if !self.readback_ready.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let [log_sum, count] = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let sum: &[f32] = bytemuck::cast_slice(&data);
+            [sum[0], sum[1]]
+        };
+        self.readback_buffer.unmap();
+        self.readback_in_flight = false;
+
+        (count > 0.0).then(|| (log_sum / count).exp())
+    }
+}
+
+fn create_bind_group(
+    device: &Device,
+    layout: &wgpu::BindGroupLayout,
+    luminance_view: &wgpu::TextureView,
+    sum_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Compute Luminance Bind Group"),
+        layout,
+        entries: &[
+            // binding 0: Luminance texture
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(luminance_view),
+            },
+            // binding 1: Log luminance sum
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: sum_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
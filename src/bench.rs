@@ -0,0 +1,301 @@
+use std::time::Duration;
+use wgpu::{Device, Queue};
+
+use crate::advection_scheme::AdvectionScheme;
+use crate::compute_step::WorkgroupSize;
+use crate::config::{ADVECTION_SCHEME, WORKGROUP_SIZE};
+use crate::simulation::Simulation;
+
+/* Benchmark matrix. Every combination of grid size, workgroup size, advection scheme and projection is run. */
+const BENCH_GRID_SIZES: [u32; 3] = [32, 64, 128];
+const BENCH_WORKGROUP_SIZES: [WorkgroupSize; 3] = [
+    WorkgroupSize::new(4, 4, 4),
+    WorkgroupSize::new(8, 8, 1),
+    WorkgroupSize::new(8, 8, 4),
+];
+const BENCH_ADVECTION_SCHEMES: [AdvectionScheme; 3] =
+    [AdvectionScheme::SemiLagrangian, AdvectionScheme::Bfecc, AdvectionScheme::MacCormack];
+const BENCH_PROJECTION: [bool; 2] = [true, false];
+/* Steps run before timing starts, so pipeline compilation and first-use costs are not measured. */
+const BENCH_WARMUP_STEPS: u32 = 10;
+const BENCH_STEPS: u32 = 100;
+/* Fixed timestep so every run simulates exactly the same thing. */
+const BENCH_DT: f32 = 1.0 / 60.0;
+
+/// Header of the CSV both benchmarks print, one row per configuration.
+const BENCH_CSV_HEADER: &str =
+    "grid_size,workgroup_size,advection_scheme,projection,steps,timer,mean_step_ms,min_step_ms,max_step_ms,total_ms";
+
+/// Runs the benchmark matrix headless and prints one CSV row per configuration to stdout.
+///
+/// Step times come from GPU timestamp queries around the encoded step when the adapter supports
+/// them (timer = gpu). Otherwise each step is submitted and waited on, and the wall-clock time is
+/// used instead (timer = cpu), which also includes submission overhead.
+pub fn run_bench() -> anyhow::Result<()> {
+    env_logger::init();
+    pollster::block_on(run_bench_async())
+}
+
+/// Runs the given number of steps headless on a cubic grid with the configured workgroup size and
+/// advection scheme, projection on, and prints the header and a single CSV row as run_bench does.
+/// For tracking one configuration over time, e.g. in CI.
+pub fn run_bench_steps(grid_size: u32, steps: u32) -> anyhow::Result<()> {
+    env_logger::init();
+    pollster::block_on(async {
+        let (device, queue, mut timer) = create_bench_device().await?;
+        println!("{BENCH_CSV_HEADER}");
+        let configuration = BenchConfiguration {
+            grid_size,
+            workgroup_size: WORKGROUP_SIZE,
+            advection_scheme: ADVECTION_SCHEME,
+            projection: true,
+            steps,
+        };
+        bench_configuration(&device, &queue, &mut timer, configuration)
+    })
+}
+
+async fn run_bench_async() -> anyhow::Result<()> {
+    let (device, queue, mut timer) = create_bench_device().await?;
+    println!("{BENCH_CSV_HEADER}");
+
+    for grid_size in BENCH_GRID_SIZES {
+        for workgroup_size in BENCH_WORKGROUP_SIZES {
+            if let Err(e) = workgroup_size.validate(&device.limits()) {
+                log::warn!("Skipping workgroup size {workgroup_size}: {e}");
+                continue;
+            }
+            for advection_scheme in BENCH_ADVECTION_SCHEMES {
+                for projection in BENCH_PROJECTION {
+                    let configuration = BenchConfiguration {
+                        grid_size,
+                        workgroup_size,
+                        advection_scheme,
+                        projection,
+                        steps: BENCH_STEPS,
+                    };
+                    bench_configuration(&device, &queue, &mut timer, configuration)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a device without a surface, with timestamp queries if the adapter has them, and the
+/// timer that goes with it.
+async fn create_bench_device() -> anyhow::Result<(Device, Queue, StepTimer)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::PRIMARY,
+        flags: Default::default(),
+        memory_budget_thresholds: Default::default(),
+        backend_options: Default::default(),
+        display: None,
+    });
+
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        })
+        .await?;
+
+    let timestamp_features = wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS;
+    let use_timestamps = adapter.features().contains(timestamp_features);
+    let simulation_features = Simulation::required_features(&adapter)?;
+
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: Some("Benchmark Device"),
+            required_features: simulation_features
+                | if use_timestamps { timestamp_features } else { wgpu::Features::empty() },
+            experimental_features: wgpu::ExperimentalFeatures::disabled(),
+            required_limits: wgpu::Limits::default(),
+            memory_hints: wgpu::MemoryHints::default(),
+            trace: wgpu::Trace::Off,
+        })
+        .await?;
+
+    let timer = if use_timestamps {
+        StepTimer::Gpu(GpuTimer::new(&device, &queue))
+    } else {
+        StepTimer::Cpu
+    };
+
+    log::info!("Benchmarking on {}", adapter.get_info().name);
+    Ok((device, queue, timer))
+}
+
+/// One row of the benchmark: the simulation setup and how many steps to time it for.
+#[derive(Debug, Copy, Clone)]
+struct BenchConfiguration {
+    /// Voxels along each side of the cubic grid.
+    grid_size: u32,
+    workgroup_size: WorkgroupSize,
+    advection_scheme: AdvectionScheme,
+    /// Whether the pressure projection runs.
+    projection: bool,
+    /// Timed steps, after BENCH_WARMUP_STEPS untimed ones.
+    steps: u32,
+}
+
+/// Runs one configuration for its number of timed steps and prints its CSV row.
+fn bench_configuration(
+    device: &Device,
+    queue: &Queue,
+    timer: &mut StepTimer,
+    configuration: BenchConfiguration,
+) -> anyhow::Result<()> {
+    let BenchConfiguration { grid_size, workgroup_size, advection_scheme, projection, steps } = configuration;
+    let grid_dimensions = wgpu::Extent3d {
+        width: grid_size,
+        height: grid_size,
+        depth_or_array_layers: grid_size,
+    };
+    let mut simulation = Simulation::new(device, queue, grid_dimensions, workgroup_size, None)?;
+    simulation.set_advection_scheme(advection_scheme);
+    simulation.set_projection_enabled(projection);
+    simulation.set_pending_input(true);
+    simulation.sim_params_mut().update_dt(Duration::from_secs_f32(BENCH_DT));
+
+    for _ in 0..BENCH_WARMUP_STEPS {
+        timer.time_step(device, queue, &mut simulation)?;
+    }
+
+    let mut step_times = Vec::with_capacity(steps as usize);
+    for _ in 0..steps {
+        step_times.push(timer.time_step(device, queue, &mut simulation)?);
+    }
+
+    let total = step_times.iter().sum::<f64>();
+    let mean = total / step_times.len() as f64;
+    let min = step_times.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = step_times.iter().copied().fold(0.0, f64::max);
+    println!(
+        "{},{},{:?},{},{},{},{:.4},{:.4},{:.4},{:.4}",
+        grid_size,
+        workgroup_size,
+        advection_scheme,
+        projection,
+        steps,
+        timer.name(),
+        mean,
+        min,
+        max,
+        total,
+    );
+
+    Ok(())
+}
+
+/// Measures how long a single simulation step takes, in milliseconds.
+enum StepTimer {
+    Gpu(GpuTimer),
+    Cpu,
+}
+
+impl StepTimer {
+    fn name(&self) -> &'static str {
+        match self {
+            StepTimer::Gpu(_) => "gpu",
+            StepTimer::Cpu => "cpu",
+        }
+    }
+
+    /// Runs one simulation step and waits for it to finish.
+    fn time_step(&mut self, device: &Device, queue: &Queue, simulation: &mut Simulation) -> anyhow::Result<f64> {
+        simulation.prepare_step(queue);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Benchmark Encoder"),
+        });
+
+        match self {
+            StepTimer::Gpu(gpu_timer) => {
+                encoder.write_timestamp(&gpu_timer.query_set, 0);
+                simulation.encode_step(device, &mut encoder, None);
+                encoder.write_timestamp(&gpu_timer.query_set, 1);
+                gpu_timer.resolve(&mut encoder);
+                queue.submit(std::iter::once(encoder.finish()));
+                gpu_timer.read_ms(device)
+            }
+            StepTimer::Cpu => {
+                simulation.encode_step(device, &mut encoder, None);
+                let start = instant::Instant::now();
+                queue.submit(std::iter::once(encoder.finish()));
+                device.poll(wgpu::PollType::wait_indefinitely())?;
+                Ok(start.elapsed().as_secs_f64() * 1000.0)
+            }
+        }
+    }
+}
+
+/// A pair of timestamp queries written before and after a step, and the buffers to read them back.
+struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+}
+
+impl GpuTimer {
+    const QUERY_COUNT: u32 = 2;
+    const BUFFER_SIZE: wgpu::BufferAddress = Self::QUERY_COUNT as wgpu::BufferAddress * size_of::<u64>() as wgpu::BufferAddress;
+
+    fn new(device: &Device, queue: &Queue) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Benchmark Timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: Self::QUERY_COUNT,
+        });
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Benchmark Timestamp Resolve Buffer"),
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Benchmark Timestamp Readback Buffer"),
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+        }
+    }
+
+    fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..Self::QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, Self::BUFFER_SIZE);
+    }
+
+    /// Blocks until the submitted step has finished and returns the time between the timestamps.
+    fn read_ms(&self, device: &Device) -> anyhow::Result<f64> {
+        let slice = self.readback_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::wait_indefinitely())?;
+        receiver.recv()??;
+
+        let ticks = {
+            let data = slice.get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            timestamps[1].saturating_sub(timestamps[0])
+        };
+        self.readback_buffer.unmap();
+
+        Ok(ticks as f64 * self.period as f64 / 1_000_000.0)
+    }
+}
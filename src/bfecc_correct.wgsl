@@ -0,0 +1,58 @@
+// Uniform buffers
+struct SimParams {
+    dt: f32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    _pad0: vec2<f32>,
+}
+@group(0) @binding(0)
+var<uniform> params: SimParams;
+
+// Texture bindings
+@group(1) @binding(0)
+var field_read: texture_3d<f32>;
+@group(1) @binding(1)
+var corrected_field_write: texture_storage_3d<rgba16float, write>;
+@group(1) @binding(2)
+var round_trip_field: texture_3d<f32>;
+
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
+/**
+ * BFECC error compensation.
+ * The round trip field is the original field advected forward and then backward. With a perfect
+ * advection scheme it would equal the original, so half of the difference is an estimate of the
+ * error introduced by one advection. The original field is corrected by that amount.
+ */
+@compute
+@workgroup_size(WG_X, WG_Y, WG_Z)
+fn main (
+    @builtin(global_invocation_id) gid: vec3<u32>
+) {
+    // Global invocation id corresponds to the index of a voxel in the simulation grid.
+    if (gid.x >= params.width || gid.y >= params.height || gid.z >= params.depth) {
+        // In case of out of bounds.
+        return;
+    }
+
+    let coord = vec3<i32>(gid);
+    let original = textureLoad(field_read, coord, 0);
+    let round_trip = textureLoad(round_trip_field, coord, 0);
+
+    textureStore(
+        corrected_field_write,
+        coord,
+        original + 0.5 * (original - round_trip)
+    );
+}
@@ -0,0 +1,67 @@
+//! Headless benchmark of a single configuration, for tracking it over time:
+//! `cargo run --release --bin bench -- --grid-size 128 --steps 500`.
+
+/// Printed with any argument error.
+#[cfg(not(target_arch = "wasm32"))]
+const USAGE: &str = "\
+Usage: bench [flags]
+
+  --grid-size <voxels>  Voxels along each side of the cubic simulation grid. Defaults to 128.
+  --steps <steps>       Timed simulation steps, after a few untimed warmup ones. Defaults to 100.
+
+Prints a CSV header and one row with the mean, min, max and total step time in milliseconds.";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    let (grid_size, steps) = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = firesim::run_bench_steps(grid_size, steps) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+/// The benchmark only runs natively.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+/// Parses the grid size and step count. See USAGE for the flags.
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_args() -> anyhow::Result<(u32, u32)> {
+    let mut grid_size = 128;
+    let mut steps = 100;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--grid-size" => {
+                let size = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--grid-size expects a number of voxels"))?;
+                grid_size = size
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|size| *size > 0)
+                    .ok_or_else(|| anyhow::anyhow!("--grid-size expects a positive number of voxels, got {size}"))?;
+            }
+            "--steps" => {
+                let count = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--steps expects a number of steps"))?;
+                steps = count
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|steps| *steps > 0)
+                    .ok_or_else(|| anyhow::anyhow!("--steps expects a positive number of steps, got {count}"))?;
+            }
+            _ => anyhow::bail!("Unknown argument: {arg}"),
+        }
+    }
+
+    Ok((grid_size, steps))
+}
@@ -0,0 +1,46 @@
+/// How the ray marched volume is composited into the HDR target.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum BlendMode {
+    /// Premultiplied alpha over what is already in the target. The default.
+    Alpha,
+    /// Adds the radiance to the target, so overlapping emission builds up into a glow. Best with
+    /// pure emission fire.
+    Additive,
+    /// Replaces the target. Useful for debug views that shouldn't mix with anything underneath.
+    Opaque,
+}
+
+impl BlendMode {
+    /// Every mode, in the order State builds a render pipeline for each.
+    pub const ALL: [BlendMode; 3] = [BlendMode::Alpha, BlendMode::Additive, BlendMode::Opaque];
+
+    /// Index of the mode in ALL.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Blend state of the render pipeline targets. fs_main writes premultiplied color.
+    pub fn blend_state(self) -> Option<wgpu::BlendState> {
+        match self {
+            BlendMode::Alpha => Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            BlendMode::Additive => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            }),
+            BlendMode::Opaque => None,
+        }
+    }
+
+    /// Returns the mode after this one, wrapping around. Used to cycle modes at runtime.
+    pub fn next(self) -> Self {
+        match self {
+            BlendMode::Alpha => BlendMode::Additive,
+            BlendMode::Additive => BlendMode::Opaque,
+            BlendMode::Opaque => BlendMode::Alpha,
+        }
+    }
+}
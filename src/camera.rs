@@ -3,7 +3,10 @@ use winit::event::*;
 use winit::dpi::PhysicalPosition;
 use instant::Duration;
 use std::f32::consts::FRAC_PI_2;
+use std::sync::atomic::{AtomicBool, Ordering};
 use winit::keyboard::KeyCode;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::camera_settings::CameraSettings;
 
 /*
 The coordinate system in Wgpu is based on DirectX and Metal's coordinate systems. That means that
@@ -22,6 +25,16 @@ const _OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::from_cols(
 
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
+/// Vertical field of view range the shader gets. The tangent of half the angle blows up as it
+/// nears 180 degrees.
+const FOVY_RANGE: [Deg<f32>; 2] = [Deg(1.0), Deg(170.0)];
+/// Aspect ratio range the shader gets, for windows resized to a sliver.
+const ASPECT_RANGE: [f32; 2] = [1.0 / 64.0, 64.0];
+
+/// Clamping is logged the first time only, since a resize can hit it every frame.
+static FOVY_CLAMP_WARNED: AtomicBool = AtomicBool::new(false);
+static ASPECT_CLAMP_WARNED: AtomicBool = AtomicBool::new(false);
+
 #[derive(Debug)]
 pub struct Camera {
     position: Point3<f32>,
@@ -39,11 +52,70 @@ impl Camera {
         yaw: Y,
         pitch: P,
     ) -> Self {
-        Self {
+        let mut camera = Self {
             position: position.into(),
             yaw: yaw.into(),
-            pitch: pitch.into(),
-        }
+            pitch: Rad(0.0),
+        };
+        camera.set_pitch(pitch);
+        camera
+    }
+
+    pub fn position(&self) -> Point3<f32> {
+        self.position
+    }
+
+    pub fn yaw(&self) -> Rad<f32> {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> Rad<f32> {
+        self.pitch
+    }
+
+    pub fn set_position<V: Into<Point3<f32>>>(&mut self, position: V) {
+        self.position = position.into();
+    }
+
+    pub fn set_yaw<Y: Into<Rad<f32>>>(&mut self, yaw: Y) {
+        self.yaw = yaw.into();
+    }
+
+    /// Clamped to just short of straight up or down, so the view direction never lines up with the
+    /// up vector.
+    pub fn set_pitch<P: Into<Rad<f32>>>(&mut self, pitch: P) {
+        let pitch = pitch.into();
+        self.pitch = if pitch < -Rad(SAFE_FRAC_PI_2) {
+            -Rad(SAFE_FRAC_PI_2)
+        } else if pitch > Rad(SAFE_FRAC_PI_2) {
+            Rad(SAFE_FRAC_PI_2)
+        } else {
+            pitch
+        };
+    }
+
+    /// Saves the position, yaw and pitch to a camera preset file (see CameraSettings), so the
+    /// view can be restored with load_from_file or opened with --camera-settings.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_to_file(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let mut preset = String::from("# firesim camera pose\n");
+        preset += &format!("position = {} {} {}\n", self.position.x, self.position.y, self.position.z);
+        preset += &format!("yaw = {}\n", Deg::from(self.yaw).0);
+        preset += &format!("pitch = {}\n", Deg::from(self.pitch).0);
+        std::fs::write(path, preset)?;
+        Ok(())
+    }
+
+    /// Moves the camera to the position, yaw and pitch of a camera preset file. Missing keys fall
+    /// back to the configured camera like --camera-settings, and the lens and control settings in
+    /// the file are ignored since they aren't part of the pose.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_from_file(&mut self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let settings = CameraSettings::from_preset_str(&std::fs::read_to_string(path)?)?;
+        self.set_position(settings.position);
+        self.set_yaw(Deg(settings.yaw));
+        self.set_pitch(Deg(settings.pitch));
+        Ok(())
     }
 
     /// Calculates the view matrix for the camera.
@@ -88,8 +160,10 @@ impl Camera {
 pub struct Projection {
     aspect: f32,
     fovy: Rad<f32>,
-    _znear: f32,
-    _zfar: f32,
+    /// Distances of the near and far planes along the view direction. The ray march is clipped
+    /// to them.
+    znear: f32,
+    zfar: f32,
 }
 
 impl Projection {
@@ -97,14 +171,14 @@ impl Projection {
         width: u32,
         height: u32,
         fovy: F,
-        _znear: f32,
-        _zfar: f32,
+        znear: f32,
+        zfar: f32,
     ) -> Self {
         Self {
             aspect: width as f32 / height as f32,
             fovy: fovy.into(),
-            _znear,
-            _zfar,
+            znear,
+            zfar,
         }
     }
 
@@ -114,12 +188,12 @@ impl Projection {
 
     /// Calculates the projection matrix.
     pub fn _calc_matrix(&self) -> Matrix4<f32> {
-        _OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self._znear, self._zfar)
+        _OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
 }
 
 #[repr(C)]
-#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     // Padding is required to satisfy 16-byte alignment rules for uniform buffers.
     camera_position: [f32; 3],
@@ -136,18 +210,57 @@ pub struct CameraUniform {
 
     tan_half_fovy: f32,
     aspect: f32,
-    _pad4: [f32; 2],
+    znear: f32,
+    zfar: f32,
 }
 
 impl CameraUniform {
-    /// Updates the camera uniform given a camera and projection.
-    pub fn update(&mut self, camera: &Camera, projection: &Projection) {
+    /// Updates the camera uniform given a camera and projection. The field of view and aspect
+    /// ratio are clamped to FOVY_RANGE and ASPECT_RANGE so the shader never builds degenerate rays.
+    /// Returns true if the uniform changed and needs uploading.
+    pub fn update(&mut self, camera: &Camera, projection: &Projection) -> bool {
+        let previous = *self;
+
+        let fovy_range = FOVY_RANGE.map(Rad::from);
+        let fovy = if projection.fovy < fovy_range[0] {
+            fovy_range[0]
+        } else if projection.fovy > fovy_range[1] {
+            fovy_range[1]
+        } else {
+            projection.fovy
+        };
+        if fovy != projection.fovy && !FOVY_CLAMP_WARNED.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "Field of view of {:.1} degrees clamped to {:.1}",
+                Deg::from(projection.fovy).0,
+                Deg::from(fovy).0,
+            );
+        }
+
+        let aspect = projection.aspect.clamp(ASPECT_RANGE[0], ASPECT_RANGE[1]);
+        if aspect != projection.aspect && !ASPECT_CLAMP_WARNED.swap(true, Ordering::Relaxed) {
+            log::warn!("Aspect ratio of {} clamped to {aspect}", projection.aspect);
+        }
+
         self.camera_position = camera.position.into();
         self.camera_forward = camera.calc_forward().into();
         self.camera_right = camera.calc_right().into();
         self.camera_up = camera.calc_up().into();
-        self.tan_half_fovy = (projection.fovy.0 * 0.5).tan();
-        self.aspect = projection.aspect;
+        self.tan_half_fovy = (fovy.0 * 0.5).tan();
+        self.aspect = aspect;
+        self.znear = projection.znear;
+        self.zfar = projection.zfar;
+        *self != previous
+    }
+
+    /// Ray through a point of the viewport given in normalized device coordinates (x right and y
+    /// up, both from -1 to 1). Built like the rays fs_main marches, so it goes through whatever is
+    /// drawn at that point. Returns the origin and the normalized direction.
+    pub fn ray(&self, ndc: [f32; 2]) -> (Point3<f32>, Vector3<f32>) {
+        let direction = Vector3::from(self.camera_forward)
+            + ndc[0] * self.aspect * self.tan_half_fovy * Vector3::from(self.camera_right)
+            + ndc[1] * self.tan_half_fovy * Vector3::from(self.camera_up);
+        (Point3::from(self.camera_position), direction.normalize())
     }
 }
 
@@ -228,41 +341,37 @@ impl CameraController {
     pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
         let dt = dt.as_secs_f32();
 
+        let mut position = camera.position();
+
         // Move forward/backward and left/right
-        let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
+        let (yaw_sin, yaw_cos) = camera.yaw().0.sin_cos();
         let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
+        position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
+        position += right * (self.amount_right - self.amount_left) * self.speed * dt;
 
         // Move in/out (aka. "zoom")
         // Note: this isn't an actual zoom. The camera's position
         // changes when zooming. I've added this to make it easier
         // to get closer to an object you want to focus on.
-        let (pitch_sin, pitch_cos) = camera.pitch.0.sin_cos();
+        let (pitch_sin, pitch_cos) = camera.pitch().0.sin_cos();
         let scrollward = Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
-        camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
+        position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
         self.scroll = 0.0;
 
         // Move up/down. Since we don't use roll, we can just
         // modify the y coordinate directly.
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
+        position.y += (self.amount_up - self.amount_down) * self.speed * dt;
+        camera.set_position(position);
 
-        // Rotate
-        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        // Rotate. set_pitch keeps the camera's angle from going too high/low.
+        camera.set_yaw(camera.yaw() + Rad(self.rotate_horizontal) * self.sensitivity * dt);
+        camera.set_pitch(camera.pitch() + Rad(-self.rotate_vertical) * self.sensitivity * dt);
 
         // If process_mouse isn't called every frame, these values
         // will not get set to zero, and the camera will rotate
         // when moving in a non-cardinal direction.
         self.rotate_horizontal = 0.0;
         self.rotate_vertical = 0.0;
-
-        // Keep the camera's angle from going too high/low.
-        if camera.pitch < -Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = -Rad(SAFE_FRAC_PI_2);
-        } else if camera.pitch > Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = Rad(SAFE_FRAC_PI_2);
-        }
     }
 }
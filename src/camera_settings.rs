@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{CAMERA_FOVY, CAMERA_PITCH, CAMERA_POSITION, CAMERA_SENSITIVITY, CAMERA_SPEED, CAMERA_YAW};
+use crate::preset::{parse_floats, parse_preset};
+
+/// Initial camera pose, lens and controls.
+///
+/// Like RenderSettings, it can be loaded from a small `key = value` preset file (see
+/// from_preset_str), so a shared scene opens with the intended framing. The default is the camera
+/// configured in config.rs.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// World space position.
+    pub position: [f32; 3],
+    /// Degrees. Zero looks along +x and 90 along +z.
+    pub yaw: f32,
+    /// Degrees, positive looks up. Clamped short of straight up or down by Camera.
+    pub pitch: f32,
+    /// Vertical field of view in degrees.
+    pub fovy: f32,
+    /// Movement in world units per second.
+    pub speed: f32,
+    /// Mouse rotation per pixel of motion.
+    pub sensitivity: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            position: CAMERA_POSITION,
+            yaw: CAMERA_YAW,
+            pitch: CAMERA_PITCH,
+            fovy: CAMERA_FOVY,
+            speed: CAMERA_SPEED,
+            sensitivity: CAMERA_SENSITIVITY,
+        }
+    }
+}
+
+impl CameraSettings {
+    /// Parses a preset file with the same rules as RenderSettings::from_preset_str, then
+    /// validates the result. `position = x y z`, everything else is a single number.
+    pub fn from_preset_str(preset: &str) -> anyhow::Result<Self> {
+        let mut settings = Self::default();
+        parse_preset(preset, |key, value| settings.set(key, value))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Checks that the settings give a usable camera.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.position.iter().chain([&self.yaw, &self.pitch]).all(|v| v.is_finite()) {
+            anyhow::bail!("Camera position and angles must be finite");
+        }
+        if !(self.fovy > 0.0 && self.fovy < 180.0) {
+            anyhow::bail!("Camera field of view must be between 0 and 180 degrees, got {}", self.fovy);
+        }
+        if !(self.speed > 0.0 && self.speed.is_finite()) {
+            anyhow::bail!("Camera speed must be positive, got {}", self.speed);
+        }
+        if !(self.sensitivity > 0.0 && self.sensitivity.is_finite()) {
+            anyhow::bail!("Camera sensitivity must be positive, got {}", self.sensitivity);
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key {
+            "position" => self.position = parse_floats(value)?,
+            "yaw" => self.yaw = value.parse()?,
+            "pitch" => self.pitch = value.parse()?,
+            "fovy" => self.fovy = value.parse()?,
+            "speed" => self.speed = value.parse()?,
+            "sensitivity" => self.sensitivity = value.parse()?,
+            _ => anyhow::bail!("Unknown setting: {key}"),
+        }
+        Ok(())
+    }
+}
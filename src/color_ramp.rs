@@ -0,0 +1,29 @@
+/// How the renderer colors the smoke.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ColorRamp {
+    /// Gray smoke lit by the lights. The default.
+    Lit,
+    /// Density through black, red, orange and yellow to white.
+    Fire,
+    /// Density as a gray level, from black to white.
+    Grayscale,
+    /// Density through deep to pale blue.
+    Water,
+}
+
+impl ColorRamp {
+    /// Identifier fs_main switches on. Must match the RAMP_ constants in render_shader.wgsl.
+    pub fn shader_id(self) -> u32 {
+        self as u32
+    }
+
+    /// Returns the ramp after this one, wrapping around. Used to cycle ramps at runtime.
+    pub fn next(self) -> Self {
+        match self {
+            ColorRamp::Lit => ColorRamp::Fire,
+            ColorRamp::Fire => ColorRamp::Grayscale,
+            ColorRamp::Grayscale => ColorRamp::Water,
+            ColorRamp::Water => ColorRamp::Lit,
+        }
+    }
+}
@@ -1,16 +1,21 @@
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    _pad0: vec2<f32>,
+    cell_size: vec3<f32>,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 @group(1) @binding(0)
 var velocity_vector_field_read: texture_3d<f32>;
@@ -19,8 +24,13 @@ var curl: texture_storage_3d<rgba16float, write>;
 @group(1) @binding(2)
 var field_sampler: sampler;
 
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main (
     @builtin(global_invocation_id) gid: vec3<u32>
 ) {
@@ -59,9 +69,11 @@ fn get_curl(gid: vec3<u32>) -> vec3<f32> {
     let front_velocity = get_velocity(vec3<u32>(gid.x, gid.y, gid.z + 1));
     let back_velocity = get_velocity(vec3<u32>(gid.x, gid.y, gid.z - 1));
 
-    let curl_x = (up_velocity.z - down_velocity.z - front_velocity.y + back_velocity.y) * 0.5;
-    let curl_y = (front_velocity.x - back_velocity.x - right_velocity.z + left_velocity.z) * 0.5;
-    let curl_z = (right_velocity.y - left_velocity.y - up_velocity.x + down_velocity.x) * 0.5;
+    // Central differences over two cells along each axis.
+    let span = 2.0 * params.cell_size;
+    let curl_x = (up_velocity.z - down_velocity.z) / span.y - (front_velocity.y - back_velocity.y) / span.z;
+    let curl_y = (front_velocity.x - back_velocity.x) / span.z - (right_velocity.z - left_velocity.z) / span.x;
+    let curl_z = (right_velocity.y - left_velocity.y) / span.x - (up_velocity.x - down_velocity.x) / span.y;
 
     return vec3<f32>(curl_x, curl_y, curl_z);
 }
@@ -0,0 +1,80 @@
+// Builds a histogram of the smoke density in a single workgroup. Bins are spaced logarithmically
+// so faint and dense smoke both get resolution. Empty cells are skipped so that the empty part of
+// the grid does not swamp the histogram.
+
+// Must match DENSITY_HISTOGRAM_BINS and DENSITY_HISTOGRAM_LOG_RANGE in density_histogram.rs.
+const BINS: u32 = 64u;
+const LOG_MIN_DENSITY: f32 = -3.0;
+const LOG_MAX_DENSITY: f32 = 3.0;
+
+struct DensityHistogram {
+    min_density: f32,
+    max_density: f32,
+    count: u32,
+    _pad0: u32,
+    bins: array<u32, BINS>,
+}
+
+// Texture bindings: x = smoke density
+@group(0) @binding(0)
+var density_scalar_field: texture_3d<f32>;
+@group(0) @binding(1)
+var<storage, read_write> result: DensityHistogram;
+
+const WORKGROUP_SIZE: u32 = 256u;
+// Only every SAMPLE_STRIDE-th cell along each axis is read. Plenty for a histogram.
+const SAMPLE_STRIDE: u32 = 2u;
+
+var<workgroup> bins: array<atomic<u32>, BINS>;
+var<workgroup> count: atomic<u32>;
+// Densities are positive, so their bit patterns order the same way as the floats.
+var<workgroup> min_bits: atomic<u32>;
+var<workgroup> max_bits: atomic<u32>;
+
+@compute @workgroup_size(256)
+fn main(@builtin(local_invocation_index) index: u32) {
+    if (index < BINS) {
+        atomicStore(&bins[index], 0u);
+    }
+    if (index == 0u) {
+        atomicStore(&count, 0u);
+        atomicStore(&min_bits, 0xffffffffu);
+        atomicStore(&max_bits, 0u);
+    }
+    workgroupBarrier();
+
+    let size = textureDimensions(density_scalar_field);
+    let samples = (size + vec3<u32>(SAMPLE_STRIDE - 1u)) / SAMPLE_STRIDE;
+    let min_density = pow(10.0, LOG_MIN_DENSITY);
+
+    for (var i = index; i < samples.x * samples.y * samples.z; i = i + WORKGROUP_SIZE) {
+        let cell = vec3<u32>(i % samples.x, (i / samples.x) % samples.y, i / (samples.x * samples.y));
+        let density = textureLoad(density_scalar_field, cell * SAMPLE_STRIDE, 0).x;
+        if (density < min_density) {
+            continue;
+        }
+
+        let t = (log(density) / log(10.0) - LOG_MIN_DENSITY) / (LOG_MAX_DENSITY - LOG_MIN_DENSITY);
+        let bin = min(u32(t * f32(BINS)), BINS - 1u);
+        atomicAdd(&bins[bin], 1u);
+        atomicAdd(&count, 1u);
+        atomicMin(&min_bits, bitcast<u32>(density));
+        atomicMax(&max_bits, bitcast<u32>(density));
+    }
+    workgroupBarrier();
+
+    if (index < BINS) {
+        result.bins[index] = atomicLoad(&bins[index]);
+    }
+    if (index == 0u) {
+        let total = atomicLoad(&count);
+        result.count = total;
+        if (total > 0u) {
+            result.min_density = bitcast<f32>(atomicLoad(&min_bits));
+            result.max_density = bitcast<f32>(atomicLoad(&max_bits));
+        } else {
+            result.min_density = 0.0;
+            result.max_density = 0.0;
+        }
+    }
+}
@@ -1,26 +1,38 @@
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    _pad0: vec2<f32>,
+    cell_size: vec3<f32>,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 @group(1) @binding(0)
 var velocity_vector_field: texture_3d<f32>;
 @group(1) @binding(1)
 var divergence: texture_storage_3d<rgba16float, write>;
 @group(1) @binding(2)
+var obstacles: texture_3d<f32>;
+@group(1) @binding(3)
 var field_sampler: sampler;
 
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main (
     @builtin(global_invocation_id) gid: vec3<u32>
 ) {
@@ -40,7 +52,11 @@ fn main (
         return;
     }
 
-    let divergence_value = get_divergence(gid);
+    // Nothing flows in or out of a solid voxel.
+    var divergence_value = 0.0;
+    if (!is_solid(gid)) {
+        divergence_value = get_divergence(gid);
+    }
     textureStore(
         divergence,
         vec3<i32>(gid),
@@ -63,20 +79,29 @@ fn voxel_center_uvw(gid: vec3<u32>) -> vec3<f32> {
 fn get_divergence(gid: vec3<u32>) -> f32 {
     let right_velocity = get_velocity(vec3<u32>(gid.x + 1, gid.y, gid.z));
     let left_velocity = get_velocity(vec3<u32>(gid.x - 1, gid.y, gid.z));
-    let x_finite_partial = (right_velocity.x - left_velocity.x) * 0.5;
+    let x_finite_partial = (right_velocity.x - left_velocity.x) / (2.0 * params.cell_size.x);
 
     let up_velocity = get_velocity(vec3<u32>(gid.x, gid.y + 1, gid.z));
     let down_velocity = get_velocity(vec3<u32>(gid.x, gid.y - 1, gid.z));
-    let y_finite_partial = (up_velocity.y - down_velocity.y) * 0.5;
+    let y_finite_partial = (up_velocity.y - down_velocity.y) / (2.0 * params.cell_size.y);
 
     let front_velocity = get_velocity(vec3<u32>(gid.x, gid.y, gid.z + 1));
     let back_velocity = get_velocity(vec3<u32>(gid.x, gid.y, gid.z - 1));
-    let z_finite_partial = (front_velocity.z - back_velocity.z) * 0.5;
+    let z_finite_partial = (front_velocity.z - back_velocity.z) / (2.0 * params.cell_size.z);
 
     return x_finite_partial + y_finite_partial + z_finite_partial;
 }
 
+// Solid neighbours are static, so they contribute zero velocity.
 fn get_velocity(index: vec3<u32>) -> vec3<f32> {
+    if (is_solid(index)) {
+        return vec3<f32>(0.0);
+    }
     let uvw = voxel_center_uvw(index);
     return textureSampleLevel(velocity_vector_field, field_sampler, uvw, 0.0).xyz;
 }
+
+fn is_solid(index: vec3<u32>) -> bool {
+    let uvw = voxel_center_uvw(index);
+    return textureSampleLevel(obstacles, field_sampler, uvw, 0.0).x > 0.5;
+}
@@ -0,0 +1,55 @@
+// Sums the log luminance of the rendered frame in a single workgroup. Pixels that the ray march
+// left (nearly) black are skipped so that the empty background does not drag the average down.
+
+struct LuminanceSum {
+    log_sum: f32,
+    count: f32,
+}
+
+// Texture bindings
+@group(0) @binding(0)
+var luminance_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var<storage, read_write> result: LuminanceSum;
+
+const WORKGROUP_SIZE: u32 = 256u;
+// Only every SAMPLE_STRIDE-th pixel along each axis is read. Plenty for an average.
+const SAMPLE_STRIDE: u32 = 4u;
+const MIN_LUMINANCE: f32 = 1e-4;
+
+var<workgroup> log_sums: array<f32, WORKGROUP_SIZE>;
+var<workgroup> counts: array<f32, WORKGROUP_SIZE>;
+
+@compute @workgroup_size(256)
+fn main(@builtin(local_invocation_index) index: u32) {
+    let size = textureDimensions(luminance_texture);
+    let columns = (size.x + SAMPLE_STRIDE - 1u) / SAMPLE_STRIDE;
+    let rows = (size.y + SAMPLE_STRIDE - 1u) / SAMPLE_STRIDE;
+
+    var log_sum = 0.0;
+    var count = 0.0;
+    for (var i = index; i < columns * rows; i = i + WORKGROUP_SIZE) {
+        let pixel = vec2<u32>(i % columns, i / columns) * SAMPLE_STRIDE;
+        let luminance = textureLoad(luminance_texture, pixel, 0).x;
+        if (luminance > MIN_LUMINANCE) {
+            log_sum += log(luminance);
+            count += 1.0;
+        }
+    }
+    log_sums[index] = log_sum;
+    counts[index] = count;
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride = stride / 2u) {
+        if (index < stride) {
+            log_sums[index] += log_sums[index + stride];
+            counts[index] += counts[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (index == 0u) {
+        result.log_sum = log_sums[0];
+        result.count = counts[0];
+    }
+}
@@ -1,57 +0,0 @@
-use std::time::Duration;
-use crate::config::GRID_DIMENSION_LENGTH;
-
-/// Struct to contain read-only params for the compute pipeline.
-/// Should be passed to the shader via a uniform buffer.
-/// TODO: Rename.
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct ComputeParams {
-    // TODO: Make a grid struct to contain all the information about the grid.
-    // It shouldn't be stored here. This is only the means of transportation.
-    dt: f32,
-    /// Number of voxels along the X axis of the simulation grid.
-    width: u32,
-    /// Number of voxels along the Y axis of the simulation grid.
-    height: u32,
-    /// Number of voxels along the Z axis of the simulation grid.
-    depth: u32,
-    /// Minimum point in world space for the simulation grid.
-    /// xyz + padding.
-    box_min: [f32; 4],
-    /// Maximum point in world space for the simulation grid.
-    /// xyz + padding.
-    box_max: [f32; 4],
-    /// Number of pixels [width, height]
-    viewport: [f32; 2],
-    elapsed_time: f32,
-    _pad0: f32,
-}
-
-impl ComputeParams {
-    pub fn new(box_min: [f32; 4], box_max: [f32; 4], config: &wgpu::SurfaceConfiguration) -> Self {
-        Self {
-            dt: Duration::new(0, 0).as_secs_f32(),
-            width: GRID_DIMENSION_LENGTH,
-            height: GRID_DIMENSION_LENGTH,
-            depth: GRID_DIMENSION_LENGTH,
-            box_min,
-            box_max,
-            viewport: [config.width as f32, config.height as f32],
-            elapsed_time: 0.0,
-            _pad0: 0.0,
-        }
-    }
-
-    pub fn update_dt(&mut self, dt: Duration, ) {
-        self.dt = dt.as_secs_f32();
-    }
-
-    pub fn update_elapsed_time(&mut self, elapsed: f32) {
-        self.elapsed_time = elapsed;
-    }
-
-    pub fn update_viewport(&mut self, config: &wgpu::SurfaceConfiguration) {
-        self.viewport = [config.width as f32, config.height as f32];
-    }
-}
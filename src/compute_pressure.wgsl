@@ -1,16 +1,21 @@
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    _pad0: vec2<f32>,
+    cell_size: vec3<f32>,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 @group(1) @binding(0)
 var pressure_read: texture_3d<f32>;
@@ -19,10 +24,17 @@ var pressure_write: texture_storage_3d<rgba16float, write>;
 @group(1) @binding(2)
 var divergence: texture_3d<f32>;
 @group(1) @binding(3)
+var obstacles: texture_3d<f32>;
+@group(1) @binding(4)
 var field_sampler: sampler;
 
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main (
     @builtin(global_invocation_id) gid: vec3<u32>
 ) {
@@ -42,7 +54,10 @@ fn main (
         return;
     }
 
-    let pressure_estimate = get_pressure_estimate(gid);
+    var pressure_estimate = 0.0;
+    if (!is_solid(gid)) {
+        pressure_estimate = get_pressure_estimate(gid);
+    }
     textureStore(
         pressure_write,
         vec3<i32>(gid),
@@ -64,21 +79,27 @@ fn voxel_center_uvw(gid: vec3<u32>) -> vec3<f32> {
 
 // Computes the pressure estimate at the given gid for this iteration of the jacobi method.
 // This is solving the Poisson-pressure equation.
-// This assumes that the cells are one unit apart.
+// Neighbours along each axis are cell_size apart, so each pair is weighted by 1 / cell_size².
+// Solid neighbours take the center's pressure, so the gradient across an obstacle's surface is
+// zero and the solve pushes no fluid into it.
 fn get_pressure_estimate(gid: vec3<u32>) -> f32 {
-    let right_pressure = get_pressure(vec3<u32>(gid.x + 1, gid.y, gid.z));
-    let left_pressure = get_pressure(vec3<u32>(gid.x - 1, gid.y, gid.z));
+    let center_pressure = get_pressure(gid);
+
+    let right_pressure = get_neighbour_pressure(vec3<u32>(gid.x + 1, gid.y, gid.z), center_pressure);
+    let left_pressure = get_neighbour_pressure(vec3<u32>(gid.x - 1, gid.y, gid.z), center_pressure);
 
-    let up_pressure = get_pressure(vec3<u32>(gid.x, gid.y + 1, gid.z));
-    let down_pressure = get_pressure(vec3<u32>(gid.x, gid.y - 1, gid.z));
+    let up_pressure = get_neighbour_pressure(vec3<u32>(gid.x, gid.y + 1, gid.z), center_pressure);
+    let down_pressure = get_neighbour_pressure(vec3<u32>(gid.x, gid.y - 1, gid.z), center_pressure);
 
-    let front_pressure = get_pressure(vec3<u32>(gid.x, gid.y, gid.z + 1));
-    let back_pressure = get_pressure(vec3<u32>(gid.x, gid.y, gid.z - 1));
+    let front_pressure = get_neighbour_pressure(vec3<u32>(gid.x, gid.y, gid.z + 1), center_pressure);
+    let back_pressure = get_neighbour_pressure(vec3<u32>(gid.x, gid.y, gid.z - 1), center_pressure);
 
     let divergence = get_divergence(gid);
 
-    return (right_pressure + left_pressure + up_pressure +
-            down_pressure + front_pressure + back_pressure - divergence) / 6;
+    let weights = 1.0 / (params.cell_size * params.cell_size);
+    return (weights.x * (right_pressure + left_pressure) +
+            weights.y * (up_pressure + down_pressure) +
+            weights.z * (front_pressure + back_pressure) - divergence) / (2.0 * (weights.x + weights.y + weights.z));
 }
 
 fn get_pressure(index: vec3<u32>) -> f32 {
@@ -86,6 +107,18 @@ fn get_pressure(index: vec3<u32>) -> f32 {
     return textureSampleLevel(pressure_read, field_sampler, uvw, 0.0).x;
 }
 
+fn get_neighbour_pressure(index: vec3<u32>, center_pressure: f32) -> f32 {
+    if (is_solid(index)) {
+        return center_pressure;
+    }
+    return get_pressure(index);
+}
+
+fn is_solid(index: vec3<u32>) -> bool {
+    let uvw = voxel_center_uvw(index);
+    return textureSampleLevel(obstacles, field_sampler, uvw, 0.0).x > 0.5;
+}
+
 fn get_divergence(gid: vec3<u32>) -> f32 {
     let uvw = voxel_center_uvw(gid);
     return textureSampleLevel(divergence, field_sampler, uvw, 0.0).x;
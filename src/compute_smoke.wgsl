@@ -1,16 +1,37 @@
+// Must match MAX_SOURCES in scene.rs.
+const MAX_SOURCES: u32 = 4;
+
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    source_count: u32,
+    source_fuel: f32,
+    cell_size: vec3<f32>,
+    source_falloff: u32,
+    sources: array<vec4<f32>, MAX_SOURCES>,
+    buoyancy_coefficient: f32,
+    gravity: f32,
+    vorticity_strength: f32,
+    scene_sources_enabled: u32,
+    cursor_source: vec4<f32>,
+    viscosity: f32,
+    ambient_temperature: f32,
+    cooling_rate: f32,
+    max_speed: f32,
+    max_density: f32,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 // Texture bindings
 @group(1) @binding(0)
@@ -27,13 +48,21 @@ const gamma_smoke: f32 = 0.5;
 // 0.5 = only the dense core generates smoke
 // Tune empirically — higher values pull smoke inward.
 const SMOKE_FUEL_THRESHOLD: f32 = 0.3;
+// Fraction of soot that dissipates per second. Soot lingers longer than smoke.
+const gamma_soot: f32 = 0.2;
+
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
 
 /**
  * Smoke density is stored in the first (x) channel.
+ * Soot density is stored in the fourth (w) channel. Soot is produced wherever fuel is burning,
+ * scaled by params.soot_yield.
  */
-
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main (
     @builtin(global_invocation_id) gid: vec3<u32>
 ) {
@@ -51,10 +80,13 @@ fn main (
 
     let new_smoke = current_smoke * decay + smoke_from_fuel;
 
+    let soot_decay = pow(1.0 - gamma_soot, params.dt);
+    let new_soot = get_soot(gid) * soot_decay + get_soot_from_combustion(gid, fuel);
+
     textureStore(
         scalar_field_write,
         vec3<i32>(gid),
-        vec4<f32>(new_smoke, get_temperature(gid), get_fuel(gid), 0.0)
+        vec4<f32>(min(new_smoke, params.max_density), get_temperature(gid), fuel, min(new_soot, params.max_density))
     );
 }
 
@@ -84,3 +116,14 @@ fn get_fuel(index: vec3<u32>) -> f32 {
     let uvw = voxel_center_uvw(index);
     return textureSampleLevel(scalar_field_read, field_sampler, uvw, 0.0).z;
 }
+
+fn get_soot(index: vec3<u32>) -> f32 {
+    let uvw = voxel_center_uvw(index);
+    return textureSampleLevel(scalar_field_read, field_sampler, uvw, 0.0).w;
+}
+
+// Soot produced this step. Combustion is taken as fuel that has reached ignition temperature.
+fn get_soot_from_combustion(index: vec3<u32>, fuel: f32) -> f32 {
+    let burning = step(params.ignition_temperature, get_temperature(index));
+    return params.soot_yield * fuel * burning * params.dt;
+}
@@ -22,22 +22,14 @@ impl ComputeStep {
 
     /// Dispatches workers and completes the compute shader that this struct represents.
     ///
-    /// The read texture will be binded at index 0.
-    /// The write texture will be binded at index 1.
-    /// The read only textures will be binded in the order they were given.
-    /// The sampler, if provided, will be binded at the last index.
-    ///
     /// The bind group layout created on this struct's creation needs to have the layout that will
-    /// be binded base on this functions input.
+    /// be binded base on the given textures (see DispatchTextures for their order).
     pub fn dispatch(
         &self,
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
-        compute_params_bind_group: &wgpu::BindGroup,
-        texture_read: &wgpu::TextureView,
-        texture_write: &wgpu::TextureView,
-        textures_read_only: &[&wgpu::TextureView],
-        sampler: Option<&wgpu::Sampler>,
+        sim_params_bind_group: &wgpu::BindGroup,
+        textures: DispatchTextures,
         workgroups: (u32, u32, u32),
     ) {
         // Build entries in the order WGSL expects:
@@ -45,23 +37,23 @@ impl ComputeStep {
 
         entries.push(wgpu::BindGroupEntry {
             binding: 0,
-            resource: wgpu::BindingResource::TextureView(texture_read),
+            resource: wgpu::BindingResource::TextureView(textures.read),
         });
         entries.push(wgpu::BindGroupEntry {
             binding: 1,
-            resource: wgpu::BindingResource::TextureView(texture_write),
+            resource: wgpu::BindingResource::TextureView(textures.write),
         });
 
-        for (i, v) in textures_read_only.iter().enumerate() {
+        for (i, v) in textures.read_only.iter().enumerate() {
             entries.push(wgpu::BindGroupEntry {
                 binding: 2 + i as u32,
                 resource: wgpu::BindingResource::TextureView(v),
             });
         }
 
-        if let Some(s) = sampler {
+        if let Some(s) = textures.sampler {
             entries.push(wgpu::BindGroupEntry {
-                binding: 2 + textures_read_only.len() as u32,
+                binding: 2 + textures.read_only.len() as u32,
                 resource: wgpu::BindingResource::Sampler(s),
             });
         }
@@ -74,8 +66,113 @@ impl ComputeStep {
 
         let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
         pass.set_pipeline(&self.compute_pipeline);
-        pass.set_bind_group(0, compute_params_bind_group, &[]);
+        pass.set_bind_group(0, sim_params_bind_group, &[]);
         pass.set_bind_group(1, &bind_group, &[]);
         pass.dispatch_workgroups(workgroups.0, workgroups.1, workgroups.2);
     }
-}
\ No newline at end of file
+}
+/// Textures and sampler a ComputeStep binds at bind group 1 for one dispatch.
+///
+/// The read texture will be binded at index 0.
+/// The write texture will be binded at index 1.
+/// The read only textures will be binded in the order they were given.
+/// The sampler, if provided, will be binded at the last index.
+pub struct DispatchTextures<'a> {
+    pub read: &'a wgpu::TextureView,
+    pub write: &'a wgpu::TextureView,
+    pub read_only: &'a [&'a wgpu::TextureView],
+    pub sampler: Option<&'a wgpu::Sampler>,
+}
+
+/// Dimensions of the compute workgroups.
+///
+/// The compute shaders declare their @workgroup_size through the WG_X, WG_Y and WG_Z override
+/// constants, so the size is picked when the pipelines are created rather than baked into the WGSL.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WorkgroupSize {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl WorkgroupSize {
+    pub const fn new(x: u32, y: u32, z: u32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Pipeline-overridable constants that set this size in the compute shaders.
+    pub fn constants(&self) -> [(&'static str, f64); 3] {
+        [
+            ("WG_X", self.x as f64),
+            ("WG_Y", self.y as f64),
+            ("WG_Z", self.z as f64),
+        ]
+    }
+
+    /// Number of workgroups needed along each axis to cover a grid of the given dimensions.
+    ///
+    /// Rounds up, so a grid that isn't a multiple of the size gets a partial workgroup at the far
+    /// edge whose extra invocations the shaders skip. Debug builds log a warning when that happens,
+    /// since rounding down instead would silently leave the last slab of voxels unsimulated.
+    pub fn workgroups_for(&self, grid_dimensions: wgpu::Extent3d) -> (u32, u32, u32) {
+        let workgroups = (
+            grid_dimensions.width.div_ceil(self.x),
+            grid_dimensions.height.div_ceil(self.y),
+            grid_dimensions.depth_or_array_layers.div_ceil(self.z),
+        );
+
+        if cfg!(debug_assertions)
+            && (!grid_dimensions.width.is_multiple_of(self.x)
+                || !grid_dimensions.height.is_multiple_of(self.y)
+                || !grid_dimensions.depth_or_array_layers.is_multiple_of(self.z))
+        {
+            log::warn!(
+                "Grid {}x{}x{} is not a multiple of workgroup size {self}. Dispatching {}x{}x{} workgroups \
+                 to cover {}x{}x{} voxels, with partial workgroups at the far edges",
+                grid_dimensions.width,
+                grid_dimensions.height,
+                grid_dimensions.depth_or_array_layers,
+                workgroups.0,
+                workgroups.1,
+                workgroups.2,
+                workgroups.0 * self.x,
+                workgroups.1 * self.y,
+                workgroups.2 * self.z,
+            );
+        }
+
+        workgroups
+    }
+
+    /// Fails if the device can't run workgroups of this size.
+    pub fn validate(&self, limits: &wgpu::Limits) -> anyhow::Result<()> {
+        if self.x == 0 || self.y == 0 || self.z == 0 {
+            anyhow::bail!("Workgroup size {self} has a zero dimension");
+        }
+        if self.x > limits.max_compute_workgroup_size_x
+            || self.y > limits.max_compute_workgroup_size_y
+            || self.z > limits.max_compute_workgroup_size_z
+        {
+            anyhow::bail!(
+                "Workgroup size {self} exceeds the device limit of {}x{}x{}",
+                limits.max_compute_workgroup_size_x,
+                limits.max_compute_workgroup_size_y,
+                limits.max_compute_workgroup_size_z,
+            );
+        }
+        let invocations = self.x * self.y * self.z;
+        if invocations > limits.max_compute_invocations_per_workgroup {
+            anyhow::bail!(
+                "Workgroup size {self} has {invocations} invocations but the device allows {}",
+                limits.max_compute_invocations_per_workgroup,
+            );
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for WorkgroupSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}x{}", self.x, self.y, self.z)
+    }
+}
@@ -1,16 +1,35 @@
+// Must match MAX_SOURCES in scene.rs.
+const MAX_SOURCES: u32 = 4;
+
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    source_count: u32,
+    source_fuel: f32,
+    cell_size: vec3<f32>,
+    source_falloff: u32,
+    sources: array<vec4<f32>, MAX_SOURCES>,
+    buoyancy_coefficient: f32,
+    gravity: f32,
+    vorticity_strength: f32,
+    scene_sources_enabled: u32,
+    cursor_source: vec4<f32>,
+    viscosity: f32,
+    ambient_temperature: f32,
+    cooling_rate: f32,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 // Texture bindings
 @group(1) @binding(0)
@@ -20,18 +39,22 @@ var scalar_field_write: texture_storage_3d<rgba16float, write>;
 @group(1) @binding(2)
 var field_sampler: sampler;
 
-const COOLING: f32 = 800.0;
 const BURN_TEMPERATURE: f32 = 2000.0;
 const BURN_RATE: f32 = 1.0;
 
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
 /**
  * Temperature is stored in the second (y) channel.
  * Temperature is set by source injection (add_source.wgsl) and decays via
- * Stefan-Boltzmann radiative cooling. There is no per-frame heating from smoke.
+ * Stefan-Boltzmann radiative cooling towards the ambient temperature, which it never drops below.
+ * There is no per-frame heating from smoke.
  */
-
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main (
     @builtin(global_invocation_id) gid: vec3<u32>
 ) {
@@ -46,12 +69,12 @@ fn main (
     let fuel = get_fuel(gid);
     let fuel_temperature = fuel * BURN_TEMPERATURE;
 
-    let new_temperature = clamp(max(cooled_temperature, fuel_temperature), 0.0, BURN_TEMPERATURE);
+    let new_temperature = clamp(max(cooled_temperature, fuel_temperature), params.ambient_temperature, BURN_TEMPERATURE);
 
     textureStore(
         scalar_field_write,
         vec3<i32>(gid),
-        vec4<f32>(get_smoke(gid), new_temperature, fuel, 0.0)
+        vec4<f32>(get_smoke(gid), new_temperature, fuel, get_soot(gid))
     );
 }
 
@@ -77,9 +100,16 @@ fn get_smoke(index: vec3<u32>) -> f32 {
     return textureSampleLevel(scalar_field_read, field_sampler, uvw, 0.0).x;
 }
 
-// Stefan-Boltzmann radiative coolin (aproximation).
+// Stefan-Boltzmann radiative coolin (aproximation). The gas radiates as T⁴ and absorbs from the
+// surrounding air as ambient⁴, so it settles at the ambient temperature.
 fn get_cooling(T: f32) -> f32 {
-    return -params.dt * COOLING * pow(T / BURN_TEMPERATURE, 4.0);
+    let radiated = pow(T / BURN_TEMPERATURE, 4.0) - pow(params.ambient_temperature / BURN_TEMPERATURE, 4.0);
+    return -params.dt * params.cooling_rate * radiated;
+}
+
+fn get_soot(index: vec3<u32>) -> f32 {
+    let uvw = voxel_center_uvw(index);
+    return textureSampleLevel(scalar_field_read, field_sampler, uvw, 0.0).w;
 }
 
 fn get_fuel(index: vec3<u32>) -> f32 {
@@ -1,9 +1,223 @@
-pub const GRID_DIMENSION_LENGTH: u32 = 128;
-/* Grid will be a cube and have GRID_SIZE x GRID_SIZE x GRID_SIZE voxels. */
+use crate::advection_scheme::AdvectionScheme;
+use crate::blend_mode::BlendMode;
+use crate::color_ramp::ColorRamp;
+use crate::compute_step::WorkgroupSize;
+use crate::emission::EmissionPreset;
+use crate::initial_velocity::InitialVelocityField;
+use crate::light::{Light, MAX_LIGHTS};
+use crate::obstacle::Obstacle;
+use crate::scene::{ScenePreset, SourceFalloff};
+
+/* Voxels along x, y and z. The axes are independent, e.g. 64 x 128 x 64 for a tall domain a plume
+can rise through. */
 pub const GRID_DIMENSIONS: wgpu::Extent3d = wgpu::Extent3d {
-    width: GRID_DIMENSION_LENGTH,
-    height: GRID_DIMENSION_LENGTH,
-    depth_or_array_layers: GRID_DIMENSION_LENGTH,
+    width: 128,
+    height: 128,
+    depth_or_array_layers: 128,
 };
+/* World-space side of a voxel unless BOX_SIZE or --voxel-length says otherwise, so the box is
+GRID_DIMENSIONS times this along each axis. Also the unit velocities are measured in. */
 pub const GRID_VOXEL_SIDE_LENGTH: f32 = 0.0125;
-pub const _VELOCITY_SCALE: f32 = 15.0;
+/* Cube grid sides F6, F7 and F8 switch to at runtime, over the same world-space box. */
+pub const RUNTIME_GRID_DIMENSION_LENGTHS: [u32; 3] = [32, 64, 128];
+/* World-space size of the simulation box. None gives cubic voxels of GRID_VOXEL_SIDE_LENGTH, and a
+size with other proportions than the grid stretches the voxels, e.g. [1.6, 3.2, 1.6] for a tall
+chimney on the same grid. */
+pub const BOX_SIZE: Option<[f32; 3]> = None;
+/* Most memory, in MiB, the simulation's field textures may take. Creating a bigger grid fails with
+the estimate instead of an allocation error. */
+pub const FIELD_MEMORY_BUDGET_MIB: u64 = 2048;
+/* Compute workgroup dimensions. Tune per GPU with the --bench mode; no shader edits needed. */
+pub const WORKGROUP_SIZE: WorkgroupSize = WorkgroupSize::new(4, 4, 4);
+/* Camera on startup, unless other camera settings are passed in. Angles are in degrees: a yaw of
+zero looks along +x and 90 along +z, and a positive pitch looks up. */
+pub const CAMERA_POSITION: [f32; 3] = [0.6125, 1.25, 2.5];
+pub const CAMERA_YAW: f32 = -90.0;
+pub const CAMERA_PITCH: f32 = -20.0;
+pub const CAMERA_FOVY: f32 = 45.0;
+/* Camera movement in world units per second, and mouse rotation per pixel of motion. */
+pub const CAMERA_SPEED: f32 = 1.0;
+pub const CAMERA_SENSITIVITY: f32 = 0.2;
+/* Near and far planes, in world units along the view direction. The ray march is clipped to them,
+and the box's faces closer than the near plane aren't drawn. */
+pub const CAMERA_ZNEAR: f32 = 0.01;
+pub const CAMERA_ZFAR: f32 = 100.0;
+/* Longest distance the ray march covers from where it enters the box, in world units. It runs
+RAYMARCH_STEPS steps, so a cap keeps them short on huge boxes. Early opacity termination can
+still end a ray sooner. None uses the box diagonal, which no ray through the box exceeds. */
+pub const MAX_RAY_DISTANCE: Option<f32> = None;
+/* Steps the ray march takes through the box on startup. Fewer render faster on big grids, more
+show less banding. Page Up and Page Down raise and lower it at runtime. */
+pub const RAYMARCH_STEPS: u32 = 64;
+pub const RAYMARCH_STEPS_STEP: u32 = 16;
+/* Accumulated opacity at which a ray stops marching. Lower ends rays sooner through dense smoke,
+at the cost of whatever is left behind it. */
+pub const OPACITY_THRESHOLD: f32 = 0.99;
+/* Faces of the simulation box the volume render pipeline culls. The ray march runs for the back
+faces, which cover every pixel the box does from outside and inside it alike. None runs it twice
+per pixel from outside, and Back leaves nothing to draw from inside the box. */
+pub const VOLUME_CULL_MODE: Option<wgpu::Face> = Some(wgpu::Face::Front);
+/* Samples per pixel of the render pass the box edges and the volume are drawn in: 1 (no MSAA), 4
+or 8. Smooths the edge lines and the outline of the box. The volume is still ray marched once per
+pixel. A count the adapter can't render with falls back to the next lower one. */
+pub const MSAA_SAMPLE_COUNT: u32 = 4;
+/* Relative distance the box edges (F2) are pushed away from the camera by, so the edges on the
+box's back faces don't z-fight with the faces the volume is drawn on. Lines can't take a pipeline
+depth bias, so the edge shader offsets their depth instead; raise it if the edges still flicker.
+The volume pass itself is never biased. */
+pub const BOX_EDGE_DEPTH_OFFSET: f32 = 1e-3;
+/* Color behind the volume, unless --background gives another. Written to the surface as is, like
+a clear color, and shown through the volume wherever it isn't opaque. */
+pub const BACKGROUND_COLOR: wgpu::Color = wgpu::Color::BLACK;
+/* Simulated seconds per simulation step. Frames run as many whole steps as their time covers. */
+pub const FIXED_TIMESTEP: f32 = 1.0 / 60.0;
+/* Most steps a single frame may run. A frame that needs more drops the rest, and the simulation
+falls behind real time instead of slowing every following frame down. */
+pub const MAX_SUBSTEPS_PER_FRAME: u32 = 4;
+/* Simulated seconds per real second. */
+pub const TIME_SCALE: f32 = 1.0;
+/* How frames are presented, unless --present-mode gives another: Fifo waits for vsync, Mailbox and
+Immediate don't (for benchmarking). A mode the surface doesn't support falls back to Fifo, which
+every surface has. F1 cycles through the supported ones. */
+pub const PRESENT_MODE: wgpu::PresentMode = wgpu::PresentMode::Fifo;
+/* Start with the simulation paused, so the initial condition can be looked at before anything
+moves. Tab resumes it and Enter runs single steps. */
+pub const START_PAUSED: bool = false;
+/* Scene loaded on startup. Number keys 1-5 load the others. */
+pub const SCENE_PRESET: ScenePreset = ScenePreset::Campfire;
+/* Velocity field the simulation starts from, and restarts from on every scene load, unless
+--initial-velocity gives another. INITIAL_VELOCITY_SPEED is the speed of the generated fields, in
+reference voxel lengths per second, unless --velocity-scale gives another. */
+pub const INITIAL_VELOCITY_FIELD: InitialVelocityField = InitialVelocityField::Zero;
+pub const INITIAL_VELOCITY_SPEED: f32 = 15.0;
+/* Solid the fluid flows around on startup. Kept through scene loads, and I cycles it at runtime. */
+pub const OBSTACLE: Obstacle = Obstacle::None;
+/* Fuel injected at the peak of each source. Comma and period lower and raise it at runtime. */
+pub const SOURCE_FUEL: f32 = 1.5;
+pub const SOURCE_FUEL_STEP: f32 = 0.25;
+/* Smoke injected at the peak of each source alongside its fuel. Zero, the default, leaves all of
+the smoke to the burning fuel. Numpad slash and asterisk lower and raise it at runtime. */
+pub const SOURCE_DENSITY: f32 = 0.0;
+pub const SOURCE_DENSITY_STEP: f32 = 0.25;
+/* The numpad moves the scene's sources by SOURCE_NUDGE_STEP, as a fraction of the grid, per press:
+4 and 6 along x, 2 and 8 along y, 3 and 9 along z. Numpad minus and plus scale their radii by
+SOURCE_RADIUS_SCALE. */
+pub const SOURCE_NUDGE_STEP: f32 = 0.02;
+pub const SOURCE_RADIUS_SCALE: f32 = 1.25;
+/* Profile of the fuel injected across each source sphere. Gaussian is the softest onset, Hard a
+sharp-edged column. */
+pub const SOURCE_FALLOFF: SourceFalloff = SourceFalloff::Gaussian;
+/* Radius, as a fraction of the grid width, of the source the right mouse button places under the
+cursor. */
+pub const CURSOR_SOURCE_RADIUS: f32 = 0.08;
+/* Upward acceleration per kelvin (buoyancy) and downward acceleration per unit of smoke and soot
+density (gravity), in reference voxel lengths per second squared. */
+pub const BUOYANCY_COEFFICIENT: f32 = 0.01;
+pub const GRAVITY: f32 = 0.0;
+/* Temperature (K) of the surrounding air, which gas cools towards and buoyancy is measured from,
+and the radiative cooling rate (K per second) at the burn temperature. */
+pub const AMBIENT_TEMPERATURE: f32 = 0.0;
+pub const COOLING_RATE: f32 = 800.0;
+/* Strength of the vorticity confinement that restores swirls smeared out by advection. Zero turns
+it off. 9 and 0 lower and raise it at runtime. */
+pub const VORTICITY_STRENGTH: f32 = 0.1;
+pub const VORTICITY_STRENGTH_STEP: f32 = 0.05;
+/* Kinematic viscosity of the velocity field in reference voxel lengths squared per second, and the
+Jacobi iterations solving its implicit diffusion per step. Zero skips the diffusion; raise it for
+thick, syrupy flow. */
+pub const VISCOSITY: f32 = 0.0;
+pub const DIFFUSION_ITERATIONS: u32 = 20;
+/* Bounds on the speed (reference voxel lengths per second) forces can accelerate the velocity to,
+and on the smoke and soot densities. Far above anything a stable simulation reaches, they only keep
+a blowup (e.g. forces piling up with the projection off) from overflowing the 16-bit float fields
+to infinity and blacking out the render. F4 counts the voxels that went non-finite anyway. */
+pub const MAX_SPEED: f32 = 1000.0;
+pub const MAX_DENSITY: f32 = 1000.0;
+/* Default strength and spatial frequency of the curl-noise turbulence injected near the source. */
+pub const TURBULENCE_STRENGTH: f32 = 40.0;
+pub const TURBULENCE_SCALE: f32 = 0.06;
+/* Default drain sphere. The center is a fraction of the grid, like a source's, so it stays in place
+when the grid is resized, and the radius is in reference voxel lengths. A radius of zero disables
+the drain. */
+pub const DRAIN_CENTER: [f32; 3] = [0.5, 0.9375, 0.5];
+pub const DRAIN_RADIUS: f32 = 0.0;
+pub const DRAIN_STRENGTH: f32 = 5.0;
+/* Jacobi iterations of the pressure solve per step on startup. Semicolon and apostrophe lower and
+raise it at runtime. */
+pub const PRESSURE_ITERATIONS: u32 = 20;
+pub const PRESSURE_ITERATIONS_STEP: u32 = 10;
+/* Formats of the pressure and divergence fields. The pressure solve starts from the last step's
+pressure, so the rounding of a 16-bit field builds up over long runs; R32Float avoids it. The solver
+samples them with linear filtering, which 32-bit floats only allow with the FLOAT32_FILTERABLE
+feature, so an adapter without it falls back to Rgba16Float with a warning. */
+pub const PRESSURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+pub const DIVERGENCE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+/* Scheme used to advect the scalar field on startup. Can be cycled at runtime. */
+pub const ADVECTION_SCHEME: AdvectionScheme = AdvectionScheme::Bfecc;
+/* Flame emission bands used on startup. Can be cycled at runtime. */
+pub const EMISSION_PRESET: EmissionPreset = EmissionPreset::Campfire;
+/* How the volume is composited into the frame on startup. Can be cycled at runtime. */
+pub const BLEND_MODE: BlendMode = BlendMode::Alpha;
+/* How the smoke is colored on startup: lit, or through a density color ramp. Can be cycled at
+runtime. */
+pub const COLOR_RAMP: ColorRamp = ColorRamp::Lit;
+/* Soot produced per unit of burning fuel per second. Zero gives a clean flame. */
+pub const SOOT_YIELD: f32 = 0.0;
+/* Smoke density below which voxels are left out of exported OpenVDB grids. */
+pub const VDB_DENSITY_THRESHOLD: f32 = 1e-3;
+/* Directory R records frames to, unless --frame-dir gives another. Every
+FRAME_RECORDING_INTERVAL-th rendered frame is recorded, through a ring of FRAME_RECORDING_BUFFERS
+staging buffers (more buffers wait on the GPU less often at the cost of memory). */
+pub const FRAME_OUTPUT_DIR: &str = "frames";
+pub const FRAME_RECORDING_INTERVAL: u64 = 1;
+pub const FRAME_RECORDING_BUFFERS: usize = 3;
+/* The stats overlay (F3) shows the frames per second and frame time, averaged over the update
+interval in seconds. */
+pub const STATS_OVERLAY: bool = false;
+pub const STATS_UPDATE_INTERVAL: f32 = 1.0;
+/* Values at the bottom and top of the slice view's color ramp (Backquote), for each field it
+cycles through: smoke density, temperature (K), fuel, soot density and speed (reference voxel
+lengths per second). */
+pub const SLICE_VIEW_RANGES: [[f32; 2]; 5] = [[0.0, 10.0], [0.0, 2000.0], [0.0, 1.5], [0.0, 1.0], [0.0, 30.0]];
+/* GPU profiling logs the average GPU time per frame of each simulation and render stage every
+report interval (seconds). It needs timestamp queries and is left off without them. G toggles it
+at runtime. Each stage of each substep takes a timestamp, so frames with more than the maximum are
+not measured. */
+pub const GPU_PROFILING: bool = false;
+pub const GPU_PROFILER_REPORT_INTERVAL: f32 = 1.0;
+pub const GPU_PROFILER_MAX_TIMESTAMPS: u32 = 256;
+/* File F5 saves the camera pose to and F9 restores it from. */
+pub const CAMERA_POSE_PATH: &str = "camera.txt";
+/* File F10 saves a snapshot of the whole simulation to and F11 restores it from. */
+pub const SNAPSHOT_PATH: &str = "snapshot.bin";
+/* File the current render settings are saved to. */
+pub const RENDER_SETTINGS_PATH: &str = "render_settings.txt";
+/* Lights that scatter off the smoke (world space). A cool key light from above and a warm fill
+ * light at the base of the fire. Unused slots are Light::DISABLED. */
+pub const LIGHTS: [Light; MAX_LIGHTS] = [
+    Light::directional([0.6, 1.0, 0.4], [1.0, 0.97, 0.92], 0.8),
+    Light::point([0.8, 0.45, 0.8], [1.0, 0.55, 0.2], 0.05),
+    Light::DISABLED,
+    Light::DISABLED,
+];
+/* Angle (radians) Y and U turn the directional lights by around the vertical axis, and Home and End
+ * raise and lower them by. */
+pub const LIGHT_ROTATION_STEP: f32 = std::f32::consts::PI / 12.0;
+/* Exposure applied before tone mapping. Used as is when auto-exposure is off and as the starting
+ * point when it is on. */
+pub const EXPOSURE: f32 = 1.0;
+/* Auto-exposure drives the exposure so that the average (log) luminance of the lit pixels maps
+ * to the key value, adapting at the given rate (per second) within the exposure range. */
+pub const AUTO_EXPOSURE: bool = false;
+pub const AUTO_EXPOSURE_KEY: f32 = 0.18;
+pub const AUTO_EXPOSURE_ADAPTATION_RATE: f32 = 1.5;
+pub const AUTO_EXPOSURE_RANGE: [f32; 2] = [0.05, 20.0];
+/* Auto density range scales the smoke density the renderer sees so that the upper of the two
+ * percentiles (fractions of the non-empty cells) maps to the reference density, adapting at the
+ * given rate (per second) within the scale range. Faint smoke is brought up and dense smoke
+ * brought down to where the extinction reads well. */
+pub const AUTO_DENSITY_RANGE: bool = false;
+pub const AUTO_DENSITY_PERCENTILES: [f32; 2] = [0.05, 0.99];
+pub const AUTO_DENSITY_REFERENCE: f32 = 10.0;
+pub const AUTO_DENSITY_ADAPTATION_RATE: f32 = 1.5;
+pub const AUTO_DENSITY_SCALE_RANGE: [f32; 2] = [0.01, 100.0];
@@ -0,0 +1,66 @@
+/// CPU version of advect_scalars.wgsl, the semi-Lagrangian scalar advection, for checking the
+/// shader against. Fields are one entry per voxel, x-major then y then z, as read_to_vec_f32
+/// returns them.
+///
+/// Follows the shader step by step: the velocity is sampled at each voxel's center, the center is
+/// traced back by dt times the velocity (converted from reference voxel lengths to the grid with
+/// cell_size) and clamped to the grid, and the field plus the source is sampled there. Fuel (z)
+/// isn't carried along and comes from the source alone, as in the shader.
+pub fn advect_scalars_semi_lagrangian(
+    field: &[[f32; 4]],
+    source: &[[f32; 4]],
+    velocity: &[[f32; 4]],
+    dimensions: [u32; 3],
+    dt: f32,
+    cell_size: [f32; 3],
+) -> Vec<[f32; 4]> {
+    let [width, height, depth] = dimensions;
+    let grid = dimensions.map(|n| n as f32);
+
+    let mut advected = Vec::with_capacity(field.len());
+    for z in 0..depth {
+        for y in 0..height {
+            for x in 0..width {
+                let uvw: [f32; 3] = std::array::from_fn(|i| ([x, y, z][i] as f32 + 0.5) / grid[i]);
+                let vel = sample_trilinear(velocity, dimensions, uvw);
+                let uvw_back: [f32; 3] =
+                    std::array::from_fn(|i| (uvw[i] - dt * vel[i] / cell_size[i] / grid[i]).clamp(0.0, 1.0));
+
+                let scalar = sample_trilinear(field, dimensions, uvw_back);
+                let scalar_source = sample_trilinear(source, dimensions, uvw_back);
+                advected.push([
+                    scalar[0] + scalar_source[0],
+                    scalar[1] + scalar_source[1],
+                    scalar_source[2],
+                    scalar[3] + scalar_source[3],
+                ]);
+            }
+        }
+    }
+    advected
+}
+
+/// Samples a field at normalized texture coordinates like the fields' linear sampler: trilinear
+/// between the four channels of the eight nearest voxel centers, repeating the edge voxels outside
+/// the grid (clamp to edge).
+pub fn sample_trilinear(field: &[[f32; 4]], dimensions: [u32; 3], uvw: [f32; 3]) -> [f32; 4] {
+    // Texel space, where voxel i's center is at i.
+    let texel: [f32; 3] = std::array::from_fn(|i| uvw[i] * dimensions[i] as f32 - 0.5);
+    let base = texel.map(f32::floor);
+    let weight: [f32; 3] = std::array::from_fn(|i| texel[i] - base[i]);
+    let index = |axis: usize, offset: i64| (base[axis] as i64 + offset).clamp(0, dimensions[axis] as i64 - 1) as usize;
+
+    let mut sample = [0.0; 4];
+    for corner in 0..8 {
+        let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+        let corner_weight: f32 = (0..3)
+            .map(|i| if offset[i] == 1 { weight[i] } else { 1.0 - weight[i] })
+            .product();
+        let (x, y, z) = (index(0, offset[0]), index(1, offset[1]), index(2, offset[2]));
+        let voxel = field[x + dimensions[0] as usize * (y + dimensions[1] as usize * z)];
+        for channel in 0..4 {
+            sample[channel] += corner_weight * voxel[channel];
+        }
+    }
+    sample
+}
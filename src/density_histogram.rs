@@ -0,0 +1,219 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use wgpu::Device;
+
+/// Number of bins written by compute_density_histogram.wgsl.
+const DENSITY_HISTOGRAM_BINS: usize = 64;
+/// The bins evenly cover this range of log10 density. Lower densities are left out, higher ones
+/// land in the last bin.
+const DENSITY_HISTOGRAM_LOG_RANGE: [f32; 2] = [-3.0, 3.0];
+/// Size of the [min, max, count, pad] header and the bins written by compute_density_histogram.wgsl.
+const DENSITY_HISTOGRAM_SIZE: wgpu::BufferAddress = (4 + DENSITY_HISTOGRAM_BINS as wgpu::BufferAddress) * 4;
+
+/// Smoke densities measured from one frame's scalar field. Empty cells are left out.
+#[derive(Debug, Copy, Clone)]
+pub struct DensityRange {
+    pub min: f32,
+    pub max: f32,
+    /// Density below which the lower percentile of the cells lie.
+    pub low: f32,
+    /// Density below which the upper percentile of the cells lie.
+    pub high: f32,
+}
+
+/// Measures the distribution of smoke density for auto-ranging the renderer.
+///
+/// A single workgroup bins the smoke channel of the scalar field into a log-spaced histogram,
+/// which is copied to a staging buffer and mapped asynchronously like AutoExposure's luminance
+/// sum. Only one readback is in flight at a time.
+pub struct DensityHistogram {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    histogram_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Set by the map_async callback once the readback buffer can be read.
+    readback_ready: Arc<AtomicBool>,
+    /// True from the frame a histogram is encoded until its result has been read.
+    readback_in_flight: bool,
+}
+
+impl DensityHistogram {
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Density Histogram Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("compute_density_histogram.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compute Density Histogram Bind Group Layout"),
+            entries: &[
+                // 0. Scalar field read.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                // 1. Histogram write.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ]
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Density Histogram Pipeline Layout"),
+            bind_group_layouts: &[Some(&bind_group_layout)],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Density Histogram Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Density Histogram Buffer"),
+            size: DENSITY_HISTOGRAM_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Density Histogram Readback Buffer"),
+            size: DENSITY_HISTOGRAM_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            histogram_buffer,
+            readback_buffer,
+            readback_ready: Arc::new(AtomicBool::new(false)),
+            readback_in_flight: false,
+        }
+    }
+
+    /// Encodes the histogram of the given scalar field view and the copy to the readback buffer,
+    /// unless a previous histogram is still in flight. Returns true if one was encoded, in which
+    /// case request_readback must be called after the encoder is submitted.
+    pub fn encode(
+        &mut self,
+        device: &Device,
+        encoder: &mut wgpu::CommandEncoder,
+        scalar_field_view: &wgpu::TextureView,
+    ) -> bool {
+        if self.readback_in_flight {
+            return false;
+        }
+
+        // The scalar field ping-pongs, so the bind group is made for the current read view.
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compute Density Histogram Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                // binding 0: Scalar field
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(scalar_field_view),
+                },
+                // binding 1: Histogram
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.histogram_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.histogram_buffer, 0, &self.readback_buffer, 0, DENSITY_HISTOGRAM_SIZE);
+
+        self.readback_in_flight = true;
+        true
+    }
+
+    /// Whether a histogram has been encoded and its result not read yet.
+    pub fn readback_in_flight(&self) -> bool {
+        self.readback_in_flight
+    }
+
+    /// Starts mapping the readback buffer. Call after submitting a frame that encode wrote to.
+    pub fn request_readback(&self) {
+        let readback_ready = self.readback_ready.clone();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if result.is_ok() {
+                readback_ready.store(true, Ordering::Release);
+            }
+        });
+    }
+
+    /// Returns the density range of the last measured field once its readback has arrived, with
+    /// low and high at the given percentiles (as fractions). Returns None while waiting, or if
+    /// the field held no smoke.
+    pub fn poll(&mut self, device: &Device, percentiles: [f32; 2]) -> Option<DensityRange> {
+        if !self.readback_in_flight {
+            return None;
+        }
+
+        // Never block: the result is picked up on a later frame if it is not ready yet.
+        let _ = device.poll(wgpu::PollType::Poll);
+        if !self.readback_ready.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let (min, max, count, bins) = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let words: &[u32] = bytemuck::cast_slice(&data);
+            let bins: [u32; DENSITY_HISTOGRAM_BINS] = std::array::from_fn(|i| words[4 + i]);
+            (f32::from_bits(words[0]), f32::from_bits(words[1]), words[2], bins)
+        };
+        self.readback_buffer.unmap();
+        self.readback_in_flight = false;
+
+        (count > 0).then(|| DensityRange {
+            min,
+            max,
+            low: percentile(&bins, count, percentiles[0]).clamp(min, max),
+            high: percentile(&bins, count, percentiles[1]).clamp(min, max),
+        })
+    }
+}
+
+/// Upper edge of the bin the given fraction of the counted cells falls in.
+fn percentile(bins: &[u32; DENSITY_HISTOGRAM_BINS], count: u32, fraction: f32) -> f32 {
+    let target = (fraction * count as f32).ceil().max(1.0) as u32;
+    let mut cumulative = 0;
+    let bin = bins
+        .iter()
+        .position(|&cells| {
+            cumulative += cells;
+            cumulative >= target
+        })
+        .unwrap_or(DENSITY_HISTOGRAM_BINS - 1);
+
+    let [log_min, log_max] = DENSITY_HISTOGRAM_LOG_RANGE;
+    let bin_width = (log_max - log_min) / DENSITY_HISTOGRAM_BINS as f32;
+    10f32.powf(log_min + (bin + 1) as f32 * bin_width)
+}
@@ -0,0 +1,80 @@
+// Must match MAX_SOURCES in scene.rs.
+const MAX_SOURCES: u32 = 4;
+
+// Uniform buffers
+struct SimParams {
+    dt: f32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    source_count: u32,
+    source_fuel: f32,
+    cell_size: vec3<f32>,
+    source_falloff: u32,
+    sources: array<vec4<f32>, MAX_SOURCES>,
+    buoyancy_coefficient: f32,
+    gravity: f32,
+    vorticity_strength: f32,
+    scene_sources_enabled: u32,
+    cursor_source: vec4<f32>,
+    viscosity: f32,
+}
+@group(0) @binding(0)
+var<uniform> params: SimParams;
+
+// Texture bindings
+@group(1) @binding(0)
+var velocity_estimate_read: texture_3d<f32>;
+@group(1) @binding(1)
+var velocity_estimate_write: texture_storage_3d<rgba16float, write>;
+@group(1) @binding(2)
+var undiffused_velocity: texture_3d<f32>;
+
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
+/**
+ * One Jacobi iteration of the implicit viscosity solve (I - viscosity * dt * laplacian) u = u0,
+ * where u0 is the velocity before diffusion. Implicit, so any viscosity is stable however large
+ * the time step; more iterations only bring the estimate closer to the exact solution.
+ * Neighbours past the edges of the grid repeat the edge voxel, so no momentum diffuses out of it.
+ */
+@compute
+@workgroup_size(WG_X, WG_Y, WG_Z)
+fn main (
+    @builtin(global_invocation_id) gid: vec3<u32>
+) {
+    // Global invocation id corresponds to the index of a voxel in the simulation grid.
+    if (gid.x >= params.width || gid.y >= params.height || gid.z >= params.depth) {
+        // In case of out of bounds.
+        return;
+    }
+
+    let coord = vec3<i32>(gid);
+
+    // Neighbours along each axis are cell_size apart, so each pair is weighted by 1 / cell_size².
+    let weights = params.viscosity * params.dt / (params.cell_size * params.cell_size);
+    let neighbours =
+        weights.x * (get_velocity(coord + vec3<i32>(1, 0, 0)) + get_velocity(coord - vec3<i32>(1, 0, 0))) +
+        weights.y * (get_velocity(coord + vec3<i32>(0, 1, 0)) + get_velocity(coord - vec3<i32>(0, 1, 0))) +
+        weights.z * (get_velocity(coord + vec3<i32>(0, 0, 1)) + get_velocity(coord - vec3<i32>(0, 0, 1)));
+    let undiffused = textureLoad(undiffused_velocity, coord, 0).xyz;
+
+    let velocity = (undiffused + neighbours) / (1.0 + 2.0 * (weights.x + weights.y + weights.z));
+    textureStore(velocity_estimate_write, coord, vec4<f32>(velocity, 0.0));
+}
+
+// Returns the current estimate of the velocity at the given voxel, clamped to the grid.
+fn get_velocity(coord: vec3<i32>) -> vec3<f32> {
+    let dims = vec3<i32>(i32(params.width), i32(params.height), i32(params.depth));
+    return textureLoad(velocity_estimate_read, clamp(coord, vec3<i32>(0), dims - vec3<i32>(1)), 0).xyz;
+}
@@ -0,0 +1,45 @@
+/// Temperature thresholds (Kelvin) for the two-band flame emission model used by the renderer.
+///
+/// Below `ignition_temperature` nothing is emitted. Emission fades in as orange between
+/// `ignition_temperature` and `cool_band_temperature`, then shifts towards a blue-white core
+/// between `cool_band_temperature` and `hot_band_temperature`.
+#[derive(Debug, Copy, Clone)]
+pub struct EmissionBands {
+    pub ignition_temperature: f32,
+    pub cool_band_temperature: f32,
+    pub hot_band_temperature: f32,
+}
+
+/// Named sets of emission bands.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum EmissionPreset {
+    /// Mostly orange flame with a small blue base at the hottest voxels.
+    Campfire,
+    /// Clean burning flame that is blue for most of its height with orange tips.
+    GasFlame,
+}
+
+impl EmissionPreset {
+    pub fn bands(self) -> EmissionBands {
+        match self {
+            EmissionPreset::Campfire => EmissionBands {
+                ignition_temperature: 500.0,
+                cool_band_temperature: 1100.0,
+                hot_band_temperature: 1950.0,
+            },
+            EmissionPreset::GasFlame => EmissionBands {
+                ignition_temperature: 400.0,
+                cool_band_temperature: 700.0,
+                hot_band_temperature: 1200.0,
+            },
+        }
+    }
+
+    /// Returns the preset after this one, wrapping around. Used to cycle presets at runtime.
+    pub fn next(self) -> Self {
+        match self {
+            EmissionPreset::Campfire => EmissionPreset::GasFlame,
+            EmissionPreset::GasFlame => EmissionPreset::Campfire,
+        }
+    }
+}
@@ -0,0 +1,638 @@
+use std::io::Write;
+use std::path::Path;
+
+/// Writes a vector field to a legacy VTK structured points file (binary), which ParaView opens
+/// directly.
+///
+/// The vectors are expected x-major, then y, then z, one per voxel, which matches the layout of
+/// the simulation textures. Points are placed at voxel centers, so the origin is half a voxel in
+/// from box_min and the spacing is the voxel size along each axis.
+pub fn write_vtk_vector_field(
+    path: &Path,
+    name: &str,
+    vectors: &[[f32; 3]],
+    dimensions: [u32; 3],
+    box_min: [f32; 3],
+    box_max: [f32; 3],
+) -> anyhow::Result<()> {
+    let point_count = dimensions.iter().map(|&n| n as usize).product::<usize>();
+    if vectors.len() != point_count {
+        anyhow::bail!(
+            "Vector field has {} points but a {}x{}x{} grid needs {}",
+            vectors.len(),
+            dimensions[0],
+            dimensions[1],
+            dimensions[2],
+            point_count,
+        );
+    }
+
+    let spacing: [f32; 3] = std::array::from_fn(|i| (box_max[i] - box_min[i]) / dimensions[i] as f32);
+    let origin: [f32; 3] = std::array::from_fn(|i| box_min[i] + 0.5 * spacing[i]);
+
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write!(
+        writer,
+        "# vtk DataFile Version 3.0\n\
+         firesim {name}\n\
+         BINARY\n\
+         DATASET STRUCTURED_POINTS\n\
+         DIMENSIONS {} {} {}\n\
+         ORIGIN {} {} {}\n\
+         SPACING {} {} {}\n\
+         POINT_DATA {point_count}\n\
+         VECTORS {name} float\n",
+        dimensions[0], dimensions[1], dimensions[2],
+        origin[0], origin[1], origin[2],
+        spacing[0], spacing[1], spacing[2],
+    )?;
+
+    // Legacy VTK binary data is big-endian.
+    for vector in vectors {
+        for component in vector {
+            writer.write_all(&component.to_be_bytes())?;
+        }
+    }
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/* Raw volumes */
+
+/// Size of the header of a raw .f32 volume: the dimensions as three u32.
+const RAW_VOLUME_HEADER_SIZE: usize = 3 * size_of::<u32>();
+
+/// Writes a scalar field to a raw .f32 volume, the simplest thing to load in NumPy or a custom
+/// importer. The layout, all little-endian:
+///
+/// - bytes 0..12: width, height and depth as u32
+/// - then width * height * depth f32 values, x-major, then y, then z, like the simulation
+///   textures
+///
+/// In NumPy:
+///
+/// ```text
+/// width, height, depth = np.fromfile(path, dtype="<u4", count=3)
+/// volume = np.fromfile(path, dtype="<f4", offset=12).reshape(depth, height, width)
+/// ```
+///
+/// so volume[z, y, x] is voxel (x, y, z).
+pub fn write_raw_f32_volume(path: &Path, values: &[f32], dimensions: [u32; 3]) -> anyhow::Result<()> {
+    let point_count = dimensions.iter().map(|&n| n as usize).product::<usize>();
+    if values.len() != point_count {
+        anyhow::bail!(
+            "Scalar field has {} voxels but a {}x{}x{} grid needs {}",
+            values.len(),
+            dimensions[0],
+            dimensions[1],
+            dimensions[2],
+            point_count,
+        );
+    }
+
+    let mut out = Vec::with_capacity(RAW_VOLUME_HEADER_SIZE + size_of_val(values));
+    for dimension in dimensions {
+        out.extend_from_slice(&dimension.to_le_bytes());
+    }
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    std::fs::write(path, out)?;
+
+    Ok(())
+}
+
+/* Minimal OpenVDB writer */
+
+/// Magic number at the start of every .vdb file ("VDB " as a little-endian int64).
+const VDB_MAGIC: i64 = 0x5644_4220;
+/// File format version this writer produces.
+const VDB_FILE_VERSION: u32 = 224;
+/// Library version recorded in the header. Only informational for readers.
+const VDB_LIBRARY_VERSION: (u32, u32) = (10, 0);
+/// Per-value metadata flag meaning "no mask, every value follows".
+const VDB_NO_MASK_AND_ALL_VALS: u8 = 6;
+
+/// log2 of the side length of a leaf node (8³ voxels).
+const LEAF_LOG2_DIM: u32 = 3;
+/// log2 of the side length, in children, of a lower internal node (16³ leaves).
+const LOWER_LOG2_DIM: u32 = 4;
+/// log2 of the side length, in children, of an upper internal node (32³ lower nodes).
+const UPPER_LOG2_DIM: u32 = 5;
+
+/// Writes a scalar field to an OpenVDB file as a single float fog volume grid, so it can be
+/// loaded by offline renderers such as Blender and Houdini.
+///
+/// Only voxels whose value is above the threshold are stored (as active voxels); everything else
+/// is left as the zero background. Values are expected x-major, then y, then z. Index space is
+/// mapped to world space so that voxel (i, j, k) sits at the center of the matching simulation
+/// voxel inside [box_min, box_max].
+///
+/// The file is written uncompressed with a Tree_float_5_4_3 tree, which is the layout
+/// openvdb::FloatGrid uses.
+pub fn write_vdb_scalar_field(
+    path: &Path,
+    name: &str,
+    values: &[f32],
+    dimensions: [u32; 3],
+    box_min: [f32; 3],
+    box_max: [f32; 3],
+    threshold: f32,
+) -> anyhow::Result<()> {
+    let point_count = dimensions.iter().map(|&n| n as usize).product::<usize>();
+    if values.len() != point_count {
+        anyhow::bail!(
+            "Scalar field has {} voxels but a {}x{}x{} grid needs {}",
+            values.len(),
+            dimensions[0],
+            dimensions[1],
+            dimensions[2],
+            point_count,
+        );
+    }
+
+    let tree = VdbTree::build(values, dimensions, threshold);
+
+    let voxel_size: [f64; 3] = std::array::from_fn(|i| ((box_max[i] - box_min[i]) / dimensions[i] as f32) as f64);
+    let translation: [f64; 3] = std::array::from_fn(|i| box_min[i] as f64 + 0.5 * voxel_size[i]);
+
+    let mut out: Vec<u8> = Vec::new();
+
+    // File header.
+    out.extend_from_slice(&VDB_MAGIC.to_le_bytes());
+    out.extend_from_slice(&VDB_FILE_VERSION.to_le_bytes());
+    out.extend_from_slice(&VDB_LIBRARY_VERSION.0.to_le_bytes());
+    out.extend_from_slice(&VDB_LIBRARY_VERSION.1.to_le_bytes());
+    // Has grid offsets.
+    out.push(1);
+    out.extend_from_slice(pseudo_uuid().as_bytes());
+    // File level metadata (none).
+    out.extend_from_slice(&0u32.to_le_bytes());
+    // Grid count.
+    out.extend_from_slice(&1i32.to_le_bytes());
+
+    // Grid descriptor.
+    write_vdb_string(&mut out, name);
+    write_vdb_string(&mut out, "Tree_float_5_4_3");
+    // Instance parent name (not an instance).
+    write_vdb_string(&mut out, "");
+    let offsets_position = out.len();
+    // Grid, block and end offsets. Patched once they are known.
+    out.extend_from_slice(&[0u8; 24]);
+    let grid_position = out.len() as i64;
+
+    // Grid compression flags (none).
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    // Grid metadata.
+    out.extend_from_slice(&2u32.to_le_bytes());
+    write_vdb_string_metadata(&mut out, "class", "fog volume");
+    write_vdb_string_metadata(&mut out, "name", name);
+
+    // Transform: index space -> world space.
+    write_vdb_string(&mut out, "ScaleTranslateMap");
+    for vector in [
+        translation,
+        voxel_size,
+        voxel_size,
+        voxel_size.map(|s| 1.0 / s),
+        voxel_size.map(|s| 1.0 / (s * s)),
+        voxel_size.map(|s| 0.5 / s),
+    ] {
+        for component in vector {
+            out.extend_from_slice(&component.to_le_bytes());
+        }
+    }
+
+    tree.write_topology(&mut out);
+    let block_position = out.len() as i64;
+    tree.write_buffers(&mut out);
+    let end_position = out.len() as i64;
+
+    for (i, position) in [grid_position, block_position, end_position].iter().enumerate() {
+        let start = offsets_position + i * 8;
+        out[start..start + 8].copy_from_slice(&position.to_le_bytes());
+    }
+
+    std::fs::write(path, out)?;
+
+    Ok(())
+}
+
+/// Sparse root -> upper -> lower -> leaf tree holding the active voxels of a scalar field.
+struct VdbTree {
+    /// Upper internal nodes keyed by origin. Sorted, as OpenVDB writes root children in
+    /// coordinate order.
+    upper_nodes: std::collections::BTreeMap<[i32; 3], VdbInternalNode<VdbInternalNode<VdbLeafNode>>>,
+}
+
+struct VdbInternalNode<Child> {
+    log2_dim: u32,
+    /// Children keyed by their linear offset within this node, in offset order.
+    children: std::collections::BTreeMap<u32, Child>,
+}
+
+struct VdbLeafNode {
+    values: [f32; 1 << (3 * LEAF_LOG2_DIM)],
+    active: Vec<u64>,
+}
+
+impl VdbTree {
+    fn build(values: &[f32], dimensions: [u32; 3], threshold: f32) -> Self {
+        let mut upper_nodes = std::collections::BTreeMap::new();
+        let lower_total = LOWER_LOG2_DIM + LEAF_LOG2_DIM;
+        let upper_total = UPPER_LOG2_DIM + lower_total;
+
+        for z in 0..dimensions[2] {
+            for y in 0..dimensions[1] {
+                for x in 0..dimensions[0] {
+                    let i = x as usize
+                        + dimensions[0] as usize * (y as usize + dimensions[1] as usize * z as usize);
+                    let value = values[i];
+                    if value.is_nan() || value <= threshold {
+                        continue;
+                    }
+
+                    let coord = [x, y, z];
+                    let upper_origin = coord.map(|c| ((c >> upper_total) << upper_total) as i32);
+                    let upper = upper_nodes
+                        .entry(upper_origin)
+                        .or_insert_with(|| VdbInternalNode::new(UPPER_LOG2_DIM));
+                    let lower = upper
+                        .children
+                        .entry(child_offset(coord, UPPER_LOG2_DIM, lower_total))
+                        .or_insert_with(|| VdbInternalNode::new(LOWER_LOG2_DIM));
+                    let leaf = lower
+                        .children
+                        .entry(child_offset(coord, LOWER_LOG2_DIM, LEAF_LOG2_DIM))
+                        .or_insert_with(VdbLeafNode::new);
+
+                    let n = child_offset(coord, LEAF_LOG2_DIM, 0);
+                    leaf.values[n as usize] = value;
+                    leaf.active[(n >> 6) as usize] |= 1 << (n & 63);
+                }
+            }
+        }
+
+        Self { upper_nodes }
+    }
+
+    fn write_topology(&self, out: &mut Vec<u8>) {
+        // Buffer count.
+        out.extend_from_slice(&1i32.to_le_bytes());
+        // Root node: background, tile count, child count, then children.
+        out.extend_from_slice(&0.0f32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&(self.upper_nodes.len() as u32).to_le_bytes());
+        for (origin, upper) in &self.upper_nodes {
+            for c in origin {
+                out.extend_from_slice(&c.to_le_bytes());
+            }
+            upper.write_topology(out, |lower, out| lower.write_topology(out, VdbLeafNode::write_topology));
+        }
+    }
+
+    fn write_buffers(&self, out: &mut Vec<u8>) {
+        for upper in self.upper_nodes.values() {
+            for lower in upper.children.values() {
+                for leaf in lower.children.values() {
+                    leaf.write_buffers(out);
+                }
+            }
+        }
+    }
+}
+
+impl<Child> VdbInternalNode<Child> {
+    fn new(log2_dim: u32) -> Self {
+        Self { log2_dim, children: std::collections::BTreeMap::new() }
+    }
+
+    fn write_topology(&self, out: &mut Vec<u8>, write_child: impl Fn(&Child, &mut Vec<u8>)) {
+        let value_count = 1usize << (3 * self.log2_dim);
+
+        // Child mask.
+        let mut child_mask = vec![0u64; value_count / 64];
+        for offset in self.children.keys() {
+            child_mask[(offset >> 6) as usize] |= 1 << (offset & 63);
+        }
+        write_mask(out, &child_mask);
+        // Value mask. There are no active tiles.
+        write_mask(out, &vec![0u64; value_count / 64]);
+        // Tile values, all background.
+        out.push(VDB_NO_MASK_AND_ALL_VALS);
+        out.extend(std::iter::repeat_n(0u8, value_count * 4));
+
+        for child in self.children.values() {
+            write_child(child, out);
+        }
+    }
+}
+
+impl VdbLeafNode {
+    fn new() -> Self {
+        Self {
+            values: [0.0; 1 << (3 * LEAF_LOG2_DIM)],
+            active: vec![0u64; (1 << (3 * LEAF_LOG2_DIM)) / 64],
+        }
+    }
+
+    fn write_topology(&self, out: &mut Vec<u8>) {
+        write_mask(out, &self.active);
+    }
+
+    fn write_buffers(&self, out: &mut Vec<u8>) {
+        write_mask(out, &self.active);
+        out.push(VDB_NO_MASK_AND_ALL_VALS);
+        for value in &self.values {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+/// Returns the linear offset of the child containing coord within a node.
+/// OpenVDB orders offsets with x as the slowest varying axis and z as the fastest.
+fn child_offset(coord: [u32; 3], log2_dim: u32, child_total: u32) -> u32 {
+    let mask = (1 << log2_dim) - 1;
+    let [x, y, z] = coord.map(|c| (c >> child_total) & mask);
+    (x << (2 * log2_dim)) | (y << log2_dim) | z
+}
+
+fn write_mask(out: &mut Vec<u8>, words: &[u64]) {
+    for word in words {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+}
+
+fn write_vdb_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_vdb_string_metadata(out: &mut Vec<u8>, name: &str, value: &str) {
+    write_vdb_string(out, name);
+    write_vdb_string(out, "string");
+    write_vdb_string(out, value);
+}
+
+/// Returns a 36 character UUID-formatted string. OpenVDB only uses it to tell files apart, so it
+/// is derived from the current time rather than a random source.
+fn pseudo_uuid() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let hex = format!("{:032x}", nanos.wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835));
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+/// Largest block of a stored (uncompressed) deflate stream.
+const DEFLATE_MAX_STORED_BLOCK: usize = 65535;
+
+/// Writes an 8-bit RGBA image to a PNG file. Pixels are expected row by row from the top left.
+///
+/// The image data is stored without compression, which every PNG reader handles, so screenshots
+/// are large but need no compression library.
+pub fn write_png_rgba8(path: &Path, pixels: &[[u8; 4]], width: u32, height: u32) -> anyhow::Result<()> {
+    if width == 0 || height == 0 {
+        anyhow::bail!("PNG images can't be empty, got {width}x{height}");
+    }
+    if pixels.len() != width as usize * height as usize {
+        anyhow::bail!("Image has {} pixels but a {width}x{height} image needs {}", pixels.len(), width * height);
+    }
+
+    // Every row starts with its filter type, 0 (none).
+    let row_len = 1 + 4 * width as usize;
+    let mut raw = Vec::with_capacity(row_len * height as usize);
+    for row in pixels.chunks_exact(width as usize) {
+        raw.push(0);
+        raw.extend(row.iter().flatten());
+    }
+
+    // zlib stream: header (deflate, 32K window, no preset dictionary, fastest), stored blocks, and
+    // the Adler-32 of the uncompressed data.
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(DEFLATE_MAX_STORED_BLOCK).peekable();
+    while let Some(block) = blocks.next() {
+        // The last block has BFINAL set. BTYPE 00 is stored.
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type 6 (RGBA), default compression and filtering, not interlaced.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut out = Vec::with_capacity(zlib.len() + 64);
+    out.extend_from_slice(&PNG_SIGNATURE);
+    write_png_chunk(&mut out, b"IHDR", &header);
+    write_png_chunk(&mut out, b"IDAT", &zlib);
+    write_png_chunk(&mut out, b"IEND", &[]);
+
+    std::fs::write(path, out)?;
+
+    Ok(())
+}
+
+fn write_png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    // The CRC covers the chunk type and data, not the length.
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// CRC-32 lookup table for the polynomial PNG uses (0xedb88320, reflected).
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |c, &byte| CRC32_TABLE[((c ^ byte as u32) & 0xff) as usize] ^ (c >> 8))
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums stay below 2^32 for 5552 bytes between reductions.
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::Texture;
+
+    /// Path in the temporary directory that is unique to this process and test.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("firesim-export-{}-{name}", std::process::id()))
+    }
+
+    /// Writes a file with the given writer and returns its bytes, removing it again.
+    fn written_bytes(name: &str, write: impl FnOnce(&Path) -> anyhow::Result<()>) -> Vec<u8> {
+        let path = temp_path(name);
+        write(&path).expect("failed to write the file");
+        let bytes = std::fs::read(&path).expect("failed to read the file back");
+        std::fs::remove_file(&path).expect("failed to remove the file");
+        bytes
+    }
+
+    #[test]
+    fn vtk_header_and_big_endian_data() {
+        let mut vectors = vec![[0.0; 3]; 8];
+        vectors[0] = [1.0, 2.0, 3.0];
+        let bytes = written_bytes("field.vtk", |path| {
+            write_vtk_vector_field(path, "velocity", &vectors, [2, 2, 2], [0.0; 3], [2.0, 4.0, 6.0])
+        });
+
+        let header = "# vtk DataFile Version 3.0\n\
+                      firesim velocity\n\
+                      BINARY\n\
+                      DATASET STRUCTURED_POINTS\n\
+                      DIMENSIONS 2 2 2\n\
+                      ORIGIN 0.5 1 1.5\n\
+                      SPACING 1 2 3\n\
+                      POINT_DATA 8\n\
+                      VECTORS velocity float\n";
+        assert_eq!(std::str::from_utf8(&bytes[..header.len()]), Ok(header));
+        let data = &bytes[header.len()..];
+        assert_eq!(data.len(), 8 * 3 * 4 + 1);
+        assert_eq!(data[..12], [0x3f, 0x80, 0, 0, 0x40, 0, 0, 0, 0x40, 0x40, 0, 0]);
+        assert_eq!(data.last(), Some(&b'\n'));
+    }
+
+    #[test]
+    fn raw_volume_round_trips_through_density_import() {
+        let dimensions = [2, 3, 4];
+        let values: Vec<f32> = (0..24).map(|i| i as f32 * 0.25).collect();
+        let bytes = written_bytes("density.f32", |path| write_raw_f32_volume(path, &values, dimensions));
+
+        assert_eq!(bytes[..RAW_VOLUME_HEADER_SIZE], [2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0]);
+        assert_eq!(bytes.len(), RAW_VOLUME_HEADER_SIZE + 24 * 4);
+
+        let extent = wgpu::Extent3d { width: 2, height: 3, depth_or_array_layers: 4 };
+        let volume = Texture::raw_volume_from_f32_density(&bytes, extent).expect("failed to import the volume");
+        assert_eq!(volume.len(), Texture::raw_volume_len(extent));
+        for (voxel, value) in volume.chunks_exact(8).zip(&values) {
+            assert_eq!(half::f16::from_le_bytes([voxel[0], voxel[1]]).to_f32(), *value);
+            assert!(voxel[2..].iter().all(|&byte| byte == 0), "non-zero channel besides smoke in {voxel:?}");
+        }
+
+        let other_grid = wgpu::Extent3d { width: 4, height: 3, depth_or_array_layers: 2 };
+        assert!(Texture::raw_volume_from_f32_density(&bytes, other_grid).is_err());
+    }
+
+    #[test]
+    fn vdb_golden_header() {
+        let mut values = vec![0.0; 8];
+        // Voxel (1, 0, 1).
+        values[5] = 0.75;
+        let bytes = written_bytes("density.vdb", |path| {
+            write_vdb_scalar_field(path, "density", &values, [2, 2, 2], [0.0; 3], [2.0; 3], 0.0)
+        });
+
+        // Magic, file version 224, library version 10.0 and "has grid offsets".
+        assert_eq!(bytes[..21], *b"\x20\x42\x44\x56\0\0\0\0\xe0\0\0\0\x0a\0\0\0\0\0\0\0\x01");
+        let uuid = std::str::from_utf8(&bytes[21..57]).expect("the UUID isn't text");
+        for (i, c) in uuid.char_indices() {
+            if [8, 13, 18, 23].contains(&i) {
+                assert_eq!(c, '-', "UUID {uuid}");
+            } else {
+                assert!(c.is_ascii_hexdigit(), "UUID {uuid}");
+            }
+        }
+        // No file metadata, one grid, its name, tree type and empty instance parent.
+        assert_eq!(
+            bytes[57..100],
+            *b"\0\0\0\0\x01\0\0\0\x07\0\0\0density\x10\0\0\0Tree_float_5_4_3\0\0\0\0",
+        );
+
+        let offset = |i: usize| i64::from_le_bytes(bytes[100 + i * 8..108 + i * 8].try_into().unwrap()) as usize;
+        let (grid, block, end) = (offset(0), offset(1), offset(2));
+        assert_eq!(grid, 124);
+        assert!(grid < block && block < end);
+        assert_eq!(end, bytes.len());
+
+        // No compression, then the class and name metadata and the transform type.
+        let metadata: &[u8] = b"\0\0\0\0\x02\0\0\0\
+            \x05\0\0\0class\x06\0\0\0string\x0a\0\0\0fog volume\
+            \x04\0\0\0name\x06\0\0\0string\x07\0\0\0density\
+            \x11\0\0\0ScaleTranslateMap";
+        assert_eq!(bytes[grid..grid + metadata.len()], *metadata);
+        // Translation to the first voxel center, then the voxel size and the derived scales.
+        let transform: Vec<f64> = bytes[grid + metadata.len()..]
+            .chunks_exact(8)
+            .take(18)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(transform[..3], [0.5; 3]);
+        assert_eq!(transform[3..15], [1.0; 12]);
+        assert_eq!(transform[15..], [0.5; 3]);
+
+        // The one leaf: its value mask, the metadata flag and all 512 values.
+        assert_eq!(end - block, 64 + 1 + 512 * 4);
+        let leaf_values = &bytes[block + 64 + 1..end];
+        // OpenVDB offsets run z fastest, so voxel (1, 0, 1) is at 1 * 64 + 0 * 8 + 1.
+        assert_eq!(leaf_values[65 * 4..66 * 4], 0.75f32.to_le_bytes());
+    }
+
+    #[test]
+    fn png_chunks_and_checksums() {
+        let pixels = [[255, 0, 0, 255], [0, 255, 0, 128]];
+        let bytes = written_bytes("image.png", |path| write_png_rgba8(path, &pixels, 2, 1));
+
+        assert_eq!(bytes[..8], PNG_SIGNATURE);
+        // Length, type, data and CRC of each chunk, with CRCs from a reference implementation
+        // (Python's zlib.crc32).
+        let ihdr = &bytes[8..33];
+        assert_eq!(ihdr[..8], *b"\0\0\0\x0dIHDR");
+        // 2x1, bit depth 8, RGBA, default compression and filtering, not interlaced.
+        assert_eq!(ihdr[8..21], [0, 0, 0, 2, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        assert_eq!(ihdr[21..], 0xf422_7f8au32.to_be_bytes());
+
+        let idat = &bytes[33..65];
+        assert_eq!(idat[..8], *b"\0\0\0\x14IDAT");
+        // zlib header, then a single final stored block of the 9 bytes of filtered rows.
+        assert_eq!(idat[8..15], [0x78, 0x01, 0x01, 9, 0, !9, 0xff]);
+        assert_eq!(idat[15..24], [0, 255, 0, 0, 255, 0, 255, 0, 128]);
+        // Adler-32 of the filtered rows.
+        assert_eq!(idat[24..28], 0x1079_037eu32.to_be_bytes());
+        assert_eq!(idat[28..], 0x36a0_6860u32.to_be_bytes());
+
+        assert_eq!(bytes[65..], *b"\0\0\0\0IEND\xae\x42\x60\x82");
+
+        // The check values of both checksums.
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::texture::Texture;
+
+/// Storage format the compute shaders declare for the field they write. Replaced with the field's
+/// own format when the shader is created.
+const SHADER_STORAGE_FORMAT: &str = "rgba16float";
+
+/// How a simulation field is stored: how many channels it uses and the texture format holding
+/// them.
+///
+/// Drives the creation of the field's textures, the storage texture bindings that write it and
+/// the storage format the writing shaders declare, so a field can trade memory for channels in
+/// one place. Shaders read every field as vec4 whatever its format (missing channels read as
+/// zero, and alpha as one), so a narrower format only needs the shaders to leave the dropped
+/// channels alone.
+///
+/// The fields are sampled with linear filtering for advection. 16-bit float formats are always
+/// filterable, 32-bit float ones only with the FLOAT32_FILTERABLE feature.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FieldDescriptor {
+    pub label: &'static str,
+    /// Channels the simulation uses, starting from x.
+    pub channels: u32,
+    pub format: wgpu::TextureFormat,
+}
+
+impl FieldDescriptor {
+    /// x = smoke, y = temperature, z = fuel, w = soot. Also used for the source and the BFECC
+    /// scratch textures.
+    pub const SCALARS: Self = Self::new("Scalar Field", 4, wgpu::TextureFormat::Rgba16Float);
+    /// xyz = velocity. Shares the drain pass with SCALARS, so the two need the same format.
+    pub const VELOCITY: Self = Self::new("Velocity Field", 3, wgpu::TextureFormat::Rgba16Float);
+    /// x = pressure.
+    pub const PRESSURE: Self = Self::new("Pressure", 1, wgpu::TextureFormat::Rgba16Float);
+    /// x = divergence of the velocity.
+    pub const DIVERGENCE: Self = Self::new("Divergence", 1, wgpu::TextureFormat::Rgba16Float);
+    /// xyz = curl of the velocity.
+    pub const CURL: Self = Self::new("Curl", 3, wgpu::TextureFormat::Rgba16Float);
+    /// x = 1 in solid voxels, 0 in fluid ones. Written from the CPU (see Texture::write_obstacle_sphere),
+    /// only ever read by the shaders.
+    pub const OBSTACLES: Self = Self::new("Obstacles", 1, wgpu::TextureFormat::Rgba16Float);
+
+    pub const fn new(label: &'static str, channels: u32, format: wgpu::TextureFormat) -> Self {
+        Self { label, channels, format }
+    }
+
+    /// The same field stored in another format.
+    pub const fn with_format(self, format: wgpu::TextureFormat) -> Self {
+        Self { format, ..self }
+    }
+
+    /// Creates a texture for this field covering the grid. Panics if the format has fewer
+    /// channels than the field uses.
+    pub fn create_texture(&self, device: &wgpu::Device, dimensions: wgpu::Extent3d, label: &str) -> Texture {
+        assert!(
+            self.channels <= self.format.components() as u32,
+            "{} uses {} channels but {:?} only has {}",
+            self.label,
+            self.channels,
+            self.format,
+            self.format.components(),
+        );
+        Texture::create_compute_texture(device, self.format, dimensions, Some(label))
+    }
+
+    /// Size in bytes of one texture of this field covering the grid.
+    pub fn texture_size(&self, dimensions: wgpu::Extent3d) -> u64 {
+        let texel_size = self.format.block_copy_size(None).expect("field formats are color formats") as u64;
+        dimensions.width as u64 * dimensions.height as u64 * dimensions.depth_or_array_layers as u64 * texel_size
+    }
+
+    /// Binding type of a compute pass writing this field.
+    pub fn storage_binding_type(&self) -> wgpu::BindingType {
+        wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: self.format,
+            view_dimension: wgpu::TextureViewDimension::D3,
+        }
+    }
+
+    /// What the simulation does with this field that the given features of its format don't
+    /// allow, e.g. the ones an adapter reports for it. Empty if they allow everything.
+    pub fn missing_format_features(&self, features: wgpu::TextureFormatFeatures) -> Vec<&'static str> {
+        let usages = [
+            (wgpu::TextureUsages::TEXTURE_BINDING, "sampling"),
+            (wgpu::TextureUsages::STORAGE_BINDING, "storage binding"),
+            (wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST, "copies"),
+        ];
+        let flags = [
+            (wgpu::TextureFormatFeatureFlags::STORAGE_WRITE_ONLY, "write-only storage access"),
+            (wgpu::TextureFormatFeatureFlags::FILTERABLE, "linear filtering"),
+        ];
+        usages
+            .into_iter()
+            .filter(|(usage, _)| !features.allowed_usages.contains(*usage))
+            .map(|(_, name)| name)
+            .chain(flags.into_iter().filter(|(flag, _)| !features.flags.contains(*flag)).map(|(_, name)| name))
+            .collect()
+    }
+
+    /// WGSL source of a shader that writes this field, with its storage texture declared in this
+    /// field's format.
+    pub fn shader_source(&self, source: &str) -> String {
+        source.replace(SHADER_STORAGE_FORMAT, self.wgsl_storage_format())
+    }
+
+    /// Texel format name WGSL uses for this field's format. Formats beyond the ones WebGPU can
+    /// always write to (e.g. r16float) need the TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES feature.
+    pub fn wgsl_storage_format(&self) -> &'static str {
+        match self.format {
+            wgpu::TextureFormat::R16Float => "r16float",
+            wgpu::TextureFormat::Rg16Float => "rg16float",
+            wgpu::TextureFormat::Rgba16Float => "rgba16float",
+            wgpu::TextureFormat::R32Float => "r32float",
+            wgpu::TextureFormat::Rg32Float => "rg32float",
+            wgpu::TextureFormat::Rgba32Float => "rgba32float",
+            format => panic!("{} can't be stored as {format:?}", self.label),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::thread::JoinHandle;
+
+use wgpu::Device;
+
+use crate::config::{FRAME_RECORDING_BUFFERS, FRAME_RECORDING_INTERVAL};
+use crate::texture::Texture;
+
+/// Frames waiting to be written that the writer thread holds before recording waits on it.
+const WRITER_QUEUE_LEN: usize = 4;
+
+/// Records rendered frames to frame_00000.png, frame_00001.png, ... in an output directory, for
+/// encoding a video offline.
+///
+/// A recorded frame is tone mapped a second time into a capture texture, which is cheap next to
+/// the ray march, and copied into one of a ring of staging buffers. The buffers are mapped
+/// asynchronously like the auto-exposure readback, and a writer thread turns them into PNGs, so
+/// the render loop only waits when every buffer is still in flight. Frames are never dropped:
+/// the recording slows the render loop down rather than skip one.
+pub struct FrameRecorder {
+    output_dir: PathBuf,
+    /// Surface sized target recorded frames are tone mapped into. None while not recording.
+    capture_texture: Option<Texture>,
+    staging_buffers: Vec<StagingBuffer>,
+    /// Staging buffer the next recorded frame is copied to.
+    next_buffer: usize,
+    /// Frames offered to begin_frame since recording started. Every FRAME_RECORDING_INTERVAL-th
+    /// one is recorded.
+    frames_seen: u64,
+    /// Number of the next recorded frame file. Carries on across recordings so a second one
+    /// doesn't overwrite the first.
+    next_frame_number: u32,
+    writer: Option<FrameWriter>,
+}
+
+struct StagingBuffer {
+    buffer: wgpu::Buffer,
+    /// Set by the map_async callback once the buffer can be read.
+    ready: Arc<AtomicBool>,
+    /// File number of the frame copied into the buffer, until it has been handed to the writer.
+    frame_number: Option<u32>,
+}
+
+/// Texels of a recorded frame, tightly packed in the capture texture's format.
+struct CapturedFrame {
+    path: PathBuf,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    bytes: Vec<u8>,
+}
+
+struct FrameWriter {
+    sender: SyncSender<CapturedFrame>,
+    thread: JoinHandle<()>,
+}
+
+impl FrameRecorder {
+    pub fn new(output_dir: PathBuf) -> Self {
+        Self {
+            output_dir,
+            capture_texture: None,
+            staging_buffers: Vec::new(),
+            next_buffer: 0,
+            frames_seen: 0,
+            next_frame_number: 0,
+            writer: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// Creates the output directory and the capture resources, and starts the writer thread.
+    pub fn start(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration) -> anyhow::Result<()> {
+        if self.is_recording() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.output_dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {e}", self.output_dir.display()))?;
+
+        let (sender, receiver) = std::sync::mpsc::sync_channel::<CapturedFrame>(WRITER_QUEUE_LEN);
+        let thread = std::thread::Builder::new()
+            .name("frame writer".into())
+            .spawn(move || {
+                for frame in receiver {
+                    let written = Texture::decode_rgba8(frame.format, &frame.bytes).and_then(|pixels| {
+                        // The surface is shown opaque, whatever alpha the tone mapping leaves in it.
+                        let pixels: Vec<[u8; 4]> = pixels.into_iter().map(|[r, g, b, _]| [r, g, b, u8::MAX]).collect();
+                        crate::export::write_png_rgba8(&frame.path, &pixels, frame.width, frame.height)
+                    });
+                    if let Err(e) = written {
+                        log::error!("Failed to write {}: {e}", frame.path.display());
+                    }
+                }
+            })?;
+
+        self.writer = Some(FrameWriter { sender, thread });
+        self.frames_seen = 0;
+        self.create_capture_resources(device, config);
+        log::info!("Recording frames to {}", self.output_dir.display());
+
+        Ok(())
+    }
+
+    /// Waits for the frames still in flight, writes them and stops the writer thread once it has
+    /// written everything it was sent.
+    pub fn stop(&mut self, device: &Device) {
+        if !self.is_recording() {
+            return;
+        }
+        self.flush(device);
+        self.capture_texture = None;
+        self.staging_buffers.clear();
+
+        if let Some(writer) = self.writer.take() {
+            drop(writer.sender);
+            if writer.thread.join().is_err() {
+                log::error!("Frame writer thread panicked");
+            }
+        }
+        log::info!("Stopped recording frames, {} so far", self.next_frame_number);
+    }
+
+    /// Recreates the capture resources at the new surface size. Frames in flight are written at
+    /// the size they were recorded at first.
+    pub fn resize(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration) {
+        if self.is_recording() {
+            self.flush(device);
+            self.create_capture_resources(device, config);
+        }
+    }
+
+    /// Returns true if this frame is one to record, after making sure a staging buffer is free to
+    /// copy it to. It then needs to be drawn to capture_view before encode_copy.
+    pub fn begin_frame(&mut self, device: &Device) -> bool {
+        if !self.is_recording() {
+            return false;
+        }
+        let recorded = self.frames_seen.is_multiple_of(FRAME_RECORDING_INTERVAL);
+        self.frames_seen += 1;
+        if !recorded {
+            return false;
+        }
+
+        self.collect(device);
+        if self.staging_buffers[self.next_buffer].frame_number.is_some() {
+            // Every buffer is in flight. Waiting beats dropping the frame.
+            log::debug!("Frame recording is waiting on the GPU");
+            if let Err(e) = device.poll(wgpu::PollType::wait_indefinitely()) {
+                log::error!("Failed to wait for recorded frames: {e}");
+            }
+            self.collect(device);
+        }
+
+        true
+    }
+
+    /// View of the capture texture, in the surface format. None while not recording.
+    pub fn capture_view(&self) -> Option<&wgpu::TextureView> {
+        self.capture_texture.as_ref().map(|texture| &texture.view)
+    }
+
+    /// Encodes the copy of the capture texture into the next staging buffer. request_readback
+    /// must be called after the encoder is submitted.
+    pub fn encode_copy(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(capture_texture) = &self.capture_texture else {
+            return;
+        };
+        let size = capture_texture.texture.size();
+        let staging = &mut self.staging_buffers[self.next_buffer];
+
+        encoder.copy_texture_to_buffer(
+            capture_texture.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &staging.buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row(size.width)),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            size,
+        );
+        staging.frame_number = Some(self.next_frame_number);
+        self.next_frame_number += 1;
+    }
+
+    /// Starts mapping the staging buffer encode_copy wrote to, and moves on to the next one.
+    pub fn request_readback(&mut self) {
+        let staging = &self.staging_buffers[self.next_buffer];
+        let ready = staging.ready.clone();
+        staging.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if result.is_ok() {
+                ready.store(true, Ordering::Release);
+            }
+        });
+        self.next_buffer = (self.next_buffer + 1) % self.staging_buffers.len();
+    }
+
+    /// Hands every staging buffer whose mapping has arrived to the writer thread. Never blocks on
+    /// the GPU, only on the writer if it is behind.
+    fn collect(&mut self, device: &Device) {
+        let _ = device.poll(wgpu::PollType::Poll);
+        let (Some(capture_texture), Some(writer)) = (&self.capture_texture, &self.writer) else {
+            return;
+        };
+        let size = capture_texture.texture.size();
+        let format = capture_texture.texture.format();
+        let unpadded_bytes_per_row = 4 * size.width as usize;
+
+        for staging in &mut self.staging_buffers {
+            let Some(frame_number) = staging.frame_number else {
+                continue;
+            };
+            if !staging.ready.swap(false, Ordering::Acquire) {
+                continue;
+            }
+
+            let mut bytes = Vec::with_capacity(unpadded_bytes_per_row * size.height as usize);
+            {
+                let mapped = staging.buffer.slice(..).get_mapped_range();
+                for row in mapped.chunks_exact(padded_bytes_per_row(size.width) as usize) {
+                    bytes.extend_from_slice(&row[..unpadded_bytes_per_row]);
+                }
+            }
+            staging.buffer.unmap();
+            staging.frame_number = None;
+
+            let frame = CapturedFrame {
+                path: self.output_dir.join(format!("frame_{frame_number:05}.png")),
+                format,
+                width: size.width,
+                height: size.height,
+                bytes,
+            };
+            if writer.sender.send(frame).is_err() {
+                log::error!("Frame writer thread stopped, frame {frame_number} is lost");
+            }
+        }
+    }
+
+    /// Waits for every frame in flight and hands it to the writer.
+    fn flush(&mut self, device: &Device) {
+        if self.staging_buffers.iter().any(|staging| staging.frame_number.is_some()) {
+            if let Err(e) = device.poll(wgpu::PollType::wait_indefinitely()) {
+                log::error!("Failed to wait for recorded frames: {e}");
+            }
+            self.collect(device);
+        }
+    }
+
+    fn create_capture_resources(&mut self, device: &Device, config: &wgpu::SurfaceConfiguration) {
+        let capture_texture = Texture::create_capture_texture(device, config, "frame_recording_texture");
+        let size = capture_texture.texture.size();
+        self.staging_buffers = (0..FRAME_RECORDING_BUFFERS)
+            .map(|_| StagingBuffer {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Frame Recording Buffer"),
+                    size: padded_bytes_per_row(size.width) as u64 * size.height as u64,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                ready: Arc::new(AtomicBool::new(false)),
+                frame_number: None,
+            })
+            .collect();
+        self.next_buffer = 0;
+        self.capture_texture = Some(capture_texture);
+    }
+}
+
+/// Bytes per row of a capture copy, aligned as copies to buffers need.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    (4 * width).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+}
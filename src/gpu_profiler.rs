@@ -0,0 +1,233 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use instant::Instant;
+use wgpu::Device;
+
+use crate::config::{GPU_PROFILER_MAX_TIMESTAMPS, GPU_PROFILER_REPORT_INTERVAL};
+
+/// Size of one resolved timestamp.
+const TIMESTAMP_SIZE: wgpu::BufferAddress = size_of::<u64>() as wgpu::BufferAddress;
+
+/// Measures how long the GPU spends in each stage of a frame with timestamp queries, and logs
+/// the average per frame every GPU_PROFILER_REPORT_INTERVAL seconds.
+///
+/// A timestamp is written at the start of every stage by an empty compute pass with
+/// timestamp_writes, so stages made of many passes (like the Jacobi iterations) are timed as a
+/// whole and only TIMESTAMP_QUERY is needed. A stage runs until the next mark, or until end,
+/// which leaves the gaps between submissions untimed. Stages seen more than once in a frame
+/// (every substep) are added up.
+///
+/// The timestamps are resolved at the end of the frame and mapped asynchronously like the
+/// auto-exposure readback, so only one frame is measured at a time and the render loop never
+/// waits on the GPU.
+pub struct GpuProfiler {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+    /// Stage started by each timestamp written this frame. None for end.
+    labels: Vec<Option<&'static str>>,
+    /// Whether this frame is measured: false while the previous frame's readback is in flight.
+    measuring: bool,
+    /// Set when a frame has more marks than GPU_PROFILER_MAX_TIMESTAMPS. The frame is dropped.
+    overflowed: bool,
+    /// Labels of the frame being read back.
+    readback_labels: Vec<Option<&'static str>>,
+    /// Set by the map_async callback once the readback buffer can be read.
+    readback_ready: Arc<AtomicBool>,
+    /// True from the frame the timestamps are resolved until they have been read.
+    readback_in_flight: bool,
+    /// Nanoseconds spent in each stage over the frames measured since the last report, in the
+    /// order the stages first ran.
+    totals: Vec<(&'static str, f64)>,
+    measured_frames: u32,
+    last_report: Instant,
+}
+
+impl GpuProfiler {
+    /// Returns None if the device was created without TIMESTAMP_QUERY, in which case nothing
+    /// is profiled.
+    pub fn new(device: &Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU Profiler Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: GPU_PROFILER_MAX_TIMESTAMPS,
+        });
+        let size = GPU_PROFILER_MAX_TIMESTAMPS as wgpu::BufferAddress * TIMESTAMP_SIZE;
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Profiler Resolve Buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Profiler Readback Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            labels: Vec::new(),
+            measuring: false,
+            overflowed: false,
+            readback_labels: Vec::new(),
+            readback_ready: Arc::new(AtomicBool::new(false)),
+            readback_in_flight: false,
+            totals: Vec::new(),
+            measured_frames: 0,
+            last_report: Instant::now(),
+        })
+    }
+
+    /// Picks up the last measured frame if its readback has arrived, logs the report when it is
+    /// due, and measures this frame unless a readback is still in flight. Call before anything of
+    /// the frame is encoded.
+    pub fn begin_frame(&mut self, device: &Device) {
+        self.collect(device);
+        self.measuring = !self.readback_in_flight;
+        self.overflowed = false;
+        self.labels.clear();
+
+        let elapsed = self.last_report.elapsed().as_secs_f32();
+        if elapsed >= GPU_PROFILER_REPORT_INTERVAL {
+            self.report();
+            self.last_report = Instant::now();
+        }
+    }
+
+    /// Starts timing the given stage, ending the one before it.
+    pub fn mark(&mut self, encoder: &mut wgpu::CommandEncoder, label: &'static str) {
+        self.write_timestamp(encoder, Some(label));
+    }
+
+    /// Ends the current stage without starting another, e.g. at the end of a submission.
+    pub fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.write_timestamp(encoder, None);
+    }
+
+    /// Encodes the resolve of this frame's timestamps and their copy to the readback buffer, if
+    /// the frame was measured. Returns true if it was, in which case request_readback must be
+    /// called after the encoder is submitted. Call after the frame's last end.
+    pub fn encode_resolve(&mut self, encoder: &mut wgpu::CommandEncoder) -> bool {
+        if !self.measuring || self.labels.is_empty() {
+            return false;
+        }
+        if self.overflowed {
+            log::warn!("Frame has more than {GPU_PROFILER_MAX_TIMESTAMPS} GPU profiler marks, not measured");
+            return false;
+        }
+
+        let count = self.labels.len() as u32;
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            count as wgpu::BufferAddress * TIMESTAMP_SIZE,
+        );
+
+        self.readback_labels = std::mem::take(&mut self.labels);
+        self.readback_in_flight = true;
+        true
+    }
+
+    /// Whether a frame has been resolved and its timestamps not read yet.
+    pub fn readback_in_flight(&self) -> bool {
+        self.readback_in_flight
+    }
+
+    /// Starts mapping the readback buffer. Call after submitting a frame that encode_resolve
+    /// wrote to.
+    pub fn request_readback(&self) {
+        let readback_ready = self.readback_ready.clone();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if result.is_ok() {
+                readback_ready.store(true, Ordering::Release);
+            }
+        });
+    }
+
+    fn write_timestamp(&mut self, encoder: &mut wgpu::CommandEncoder, label: Option<&'static str>) {
+        if !self.measuring || self.overflowed {
+            return;
+        }
+        if self.labels.len() as u32 >= GPU_PROFILER_MAX_TIMESTAMPS {
+            self.overflowed = true;
+            return;
+        }
+
+        let index = self.labels.len() as u32;
+        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("GPU Profiler Timestamp Pass"),
+            timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: None,
+            }),
+        });
+        self.labels.push(label);
+    }
+
+    /// Adds the stage times of the frame in flight to the totals once its readback has arrived.
+    /// Never blocks.
+    fn collect(&mut self, device: &Device) {
+        if !self.readback_in_flight {
+            return;
+        }
+        let _ = device.poll(wgpu::PollType::Poll);
+        if !self.readback_ready.swap(false, Ordering::Acquire) {
+            return;
+        }
+
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            for (i, label) in self.readback_labels.iter().enumerate() {
+                let (Some(label), Some(next)) = (label, timestamps.get(i + 1)) else {
+                    continue;
+                };
+                let nanoseconds = next.saturating_sub(timestamps[i]) as f64 * self.period as f64;
+                match self.totals.iter_mut().find(|(stage, _)| stage == label) {
+                    Some((_, total)) => *total += nanoseconds,
+                    None => self.totals.push((label, nanoseconds)),
+                }
+            }
+        }
+        self.readback_buffer.unmap();
+        self.readback_in_flight = false;
+        self.measured_frames += 1;
+    }
+
+    /// Logs the average milliseconds per frame of every stage, and starts over.
+    fn report(&mut self) {
+        if self.measured_frames == 0 {
+            return;
+        }
+        let frames = self.measured_frames as f64;
+        let total: f64 = self.totals.iter().map(|(_, nanoseconds)| nanoseconds).sum();
+        let stages: Vec<String> = self
+            .totals
+            .iter()
+            .map(|(stage, nanoseconds)| format!("{stage} {:.3}", nanoseconds / frames / 1e6))
+            .collect();
+        log::info!(
+            "GPU ms per frame over {} frames: {} | total {:.3}",
+            self.measured_frames,
+            stages.join(", "),
+            total / frames / 1e6,
+        );
+        self.totals.clear();
+        self.measured_frames = 0;
+    }
+}
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+
+use cgmath::Vector3;
+
+/// Radius of the solid-body core of the Vortex field, as a fraction of the half-width of the grid.
+const VORTEX_CORE_RADIUS: f32 = 0.25;
+/// Thickness of the ShearLayer field's transition, as a fraction of the half-height of the grid.
+const SHEAR_LAYER_THICKNESS: f32 = 0.1;
+/// Vertical kick that seeds the ShearLayer's roll-up, as a fraction of the field's speed.
+const SHEAR_LAYER_PERTURBATION: f32 = 0.05;
+
+/// Velocity field the simulation starts from, and restarts from when a scene is loaded.
+///
+/// The generated fields move at the speed velocity_at is given, INITIAL_VELOCITY_SPEED unless
+/// --velocity-scale gives another, in reference voxel lengths per second like every velocity in
+/// the simulation. Uniform moves at its own velocity.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InitialVelocityField {
+    /// Constant speed around the vertical axis through the center of the grid.
+    Tornado,
+    /// Rankine vortex around the vertical axis: spins as a solid body inside the core and falls
+    /// off with distance outside of it.
+    Vortex,
+    /// Horizontal flow along +x above mid-height and -x below it, with a small vertical kick so
+    /// the layer rolls up into Kelvin-Helmholtz billows.
+    ShearLayer,
+    /// Still. Nothing moves unless forces act, which makes advection bugs easy to spot.
+    Zero,
+    /// The same velocity everywhere.
+    Uniform(Vector3<f32>),
+}
+
+impl InitialVelocityField {
+    /// Velocity at a point given in grid coordinates mapped to [-1, 1] along each axis, for the
+    /// generated fields moving at the given speed.
+    pub fn velocity_at(&self, p: [f32; 3], speed: f32) -> [f32; 3] {
+        let [px, py, pz] = p;
+        match self {
+            InitialVelocityField::Tornado => {
+                // Tangent around the Y axis: (pz, 0, -px) normalized. Undefined on the axis.
+                let r = (px * px + pz * pz).sqrt();
+                if r < 1e-3 {
+                    return [0.0; 3];
+                }
+                [pz / r * speed, 0.0, -px / r * speed]
+            }
+            InitialVelocityField::Vortex => {
+                let r = (px * px + pz * pz).sqrt();
+                if r < 1e-3 {
+                    return [0.0; 3];
+                }
+                let speed = if r < VORTEX_CORE_RADIUS {
+                    r / VORTEX_CORE_RADIUS
+                } else {
+                    VORTEX_CORE_RADIUS / r
+                } * speed;
+                [pz / r * speed, 0.0, -px / r * speed]
+            }
+            InitialVelocityField::ShearLayer => {
+                let y = py / SHEAR_LAYER_THICKNESS;
+                let kick = SHEAR_LAYER_PERTURBATION * (std::f32::consts::PI * px).sin() * (-y * y).exp();
+                [y.tanh() * speed, kick * speed, 0.0]
+            }
+            InitialVelocityField::Zero => [0.0; 3],
+            InitialVelocityField::Uniform(velocity) => (*velocity).into(),
+        }
+    }
+}
+
+impl FromStr for InitialVelocityField {
+    type Err = anyhow::Error;
+
+    /// Parses `tornado`, `vortex`, `shear-layer`, `zero` or `uniform:x,y,z`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "tornado" => InitialVelocityField::Tornado,
+            "vortex" => InitialVelocityField::Vortex,
+            "shear-layer" => InitialVelocityField::ShearLayer,
+            "zero" => InitialVelocityField::Zero,
+            _ => {
+                let components = s
+                    .strip_prefix("uniform:")
+                    .ok_or_else(|| anyhow::anyhow!("Unknown initial velocity field: {s}"))?
+                    .split(',')
+                    .map(|component| component.trim().parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let [x, y, z] = components[..] else {
+                    anyhow::bail!("uniform expects three components, got {}", components.len());
+                };
+                if ![x, y, z].iter().all(|v| v.is_finite()) {
+                    anyhow::bail!("uniform expects finite components, got {x},{y},{z}");
+                }
+                InitialVelocityField::Uniform(Vector3::new(x, y, z))
+            }
+        })
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use instant::{Duration, Instant};
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, MouseScrollDelta};
+use winit::keyboard::KeyCode;
+
+/// Input the app reacts to, in the form it is recorded and replayed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InputEvent {
+    Key { code: KeyCode, state: ElementState },
+    /// Left mouse button.
+    MouseButton(ElementState),
+    /// Raw mouse motion. Only moves the camera while the button is held.
+    MouseMotion { dx: f64, dy: f64 },
+    Scroll(MouseScrollDelta),
+    /// Right mouse button. Injects fuel under the cursor while held.
+    InjectButton(ElementState),
+    /// Cursor position in the window, in physical pixels.
+    CursorMoved { x: f64, y: f64 },
+}
+
+/// Writes every input event and frame time of a session to a file, one per line.
+///
+/// Each line starts with the seconds since recording began, followed by the event:
+///
+/// ```text
+/// 1.250000 key KeyF pressed
+/// 1.262000 button released
+/// 1.270000 motion -3 1.5
+/// 1.280000 scroll lines 0 1
+/// 1.281000 cursor 640 360
+/// 1.282000 inject pressed
+/// 1.283000 frame 16666667
+/// ```
+///
+/// A `frame` line ends a frame and holds its duration in nanoseconds. The events above it are
+/// the ones handled before that frame was updated, which is what lets InputReplay reproduce the
+/// session.
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl InputRecorder {
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "# firesim input recording")?;
+        Ok(Self { writer, start: Instant::now() })
+    }
+
+    pub fn record(&mut self, event: &InputEvent) -> anyhow::Result<()> {
+        let line = match event {
+            InputEvent::Key { code, state } => format!("key {code:?} {}", element_state_str(*state)),
+            InputEvent::MouseButton(state) => format!("button {}", element_state_str(*state)),
+            InputEvent::MouseMotion { dx, dy } => format!("motion {dx} {dy}"),
+            InputEvent::Scroll(MouseScrollDelta::LineDelta(x, y)) => format!("scroll lines {x} {y}"),
+            InputEvent::Scroll(MouseScrollDelta::PixelDelta(position)) => {
+                format!("scroll pixels {} {}", position.x, position.y)
+            }
+            InputEvent::InjectButton(state) => format!("inject {}", element_state_str(*state)),
+            InputEvent::CursorMoved { x, y } => format!("cursor {x} {y}"),
+        };
+        self.write_line(&line)
+    }
+
+    pub fn record_frame(&mut self, dt: Duration) -> anyhow::Result<()> {
+        self.write_line(&format!("frame {}", dt.as_nanos()))
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    fn write_line(&mut self, line: &str) -> anyhow::Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        writeln!(self.writer, "{time:.6} {line}")?;
+        Ok(())
+    }
+}
+
+/// One recorded frame: the events handled before it, then its duration.
+pub struct RecordedFrame {
+    pub events: Vec<InputEvent>,
+    pub dt: Duration,
+}
+
+/// Frames read back from a file written by InputRecorder.
+pub struct InputReplay {
+    frames: VecDeque<RecordedFrame>,
+}
+
+impl InputReplay {
+    /// Parses a recording. Blank lines and lines starting with # are ignored, and events after
+    /// the last frame line are dropped since they never reached a frame.
+    pub fn from_recording_str(recording: &str) -> anyhow::Result<Self> {
+        let mut frames = VecDeque::new();
+        let mut events = Vec::new();
+
+        for (line_number, line) in recording.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match fields[..] {
+                // The timestamp is informational; replay is driven by the frame lines.
+                [_, "frame", nanos] => nanos.parse::<u64>().map(|nanos| {
+                    frames.push_back(RecordedFrame {
+                        events: std::mem::take(&mut events),
+                        dt: Duration::from_nanos(nanos),
+                    });
+                }).map_err(anyhow::Error::from),
+                [_, ref event @ ..] => parse_event(event).map(|event| events.push(event)),
+                [] => unreachable!("blank lines are skipped"),
+            };
+            parsed.map_err(|e| anyhow::anyhow!("Line {}: {e}", line_number + 1))?;
+        }
+
+        Ok(Self { frames })
+    }
+
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        self.frames.pop_front()
+    }
+}
+
+fn parse_event(fields: &[&str]) -> anyhow::Result<InputEvent> {
+    Ok(match fields {
+        ["key", code, state] => InputEvent::Key {
+            code: KeyCode::deserialize(code.into_deserializer())
+                .map_err(|_: serde::de::value::Error| anyhow::anyhow!("Unknown key code: {code}"))?,
+            state: parse_element_state(state)?,
+        },
+        ["button", state] => InputEvent::MouseButton(parse_element_state(state)?),
+        ["motion", dx, dy] => InputEvent::MouseMotion { dx: dx.parse()?, dy: dy.parse()? },
+        ["scroll", "lines", x, y] => InputEvent::Scroll(MouseScrollDelta::LineDelta(x.parse()?, y.parse()?)),
+        ["scroll", "pixels", x, y] => {
+            InputEvent::Scroll(MouseScrollDelta::PixelDelta(PhysicalPosition::new(x.parse()?, y.parse()?)))
+        }
+        ["inject", state] => InputEvent::InjectButton(parse_element_state(state)?),
+        ["cursor", x, y] => InputEvent::CursorMoved { x: x.parse()?, y: y.parse()? },
+        _ => anyhow::bail!("Unknown event: {}", fields.join(" ")),
+    })
+}
+
+fn element_state_str(state: ElementState) -> &'static str {
+    match state {
+        ElementState::Pressed => "pressed",
+        ElementState::Released => "released",
+    }
+}
+
+fn parse_element_state(state: &str) -> anyhow::Result<ElementState> {
+    match state {
+        "pressed" => Ok(ElementState::Pressed),
+        "released" => Ok(ElementState::Released),
+        _ => anyhow::bail!("Expected pressed or released, got {state}"),
+    }
+}
@@ -1,22 +1,156 @@
+//! Real-time GPU fire and smoke simulation.
+//!
+//! The binary is a thin wrapper around [run_with_options], which opens a window and runs the
+//! interactive viewer. The simulation itself doesn't need a window: [Simulation] only takes a
+//! wgpu device and queue, so other programs can embed it, step it themselves and read the fields
+//! back.
+//!
+//! ```no_run
+//! # async fn example(device: wgpu::Device, queue: wgpu::Queue) -> anyhow::Result<()> {
+//! use firesim::{Simulation, WorkgroupSize};
+//!
+//! let grid = wgpu::Extent3d { width: 64, height: 64, depth_or_array_layers: 64 };
+//! let mut simulation = Simulation::new(&device, &queue, grid, WorkgroupSize::new(4, 4, 4), None)?;
+//! simulation.set_pending_input(true);
+//! for _ in 0..60 {
+//!     simulation.step(&device, &queue);
+//! }
+//! // x = smoke, y = temperature, z = fuel, w = soot, one entry per voxel.
+//! let voxels = simulation.scalar_field().get_read_texture().read_to_vec_f32(&device, &queue)?;
+//! # Ok(())
+//! # }
+//! ```
+
 mod app;
 mod state;
 mod camera;
 mod texture;
-mod compute_params;
+mod sim_params;
+mod render_params;
 mod config;
 mod compute_step;
+mod field;
 mod ping_pong;
+mod advection_scheme;
+mod cpu_advection;
+mod emission;
+mod blend_mode;
+mod color_ramp;
+mod initial_velocity;
+mod obstacle;
+mod scene;
+mod simulation;
+mod light;
+mod render_settings;
+mod slice_view;
+mod camera_settings;
+mod preset;
+mod auto_exposure;
+mod density_histogram;
+mod gpu_profiler;
+mod timestep;
+#[cfg(not(target_arch = "wasm32"))]
+mod export;
+#[cfg(not(target_arch = "wasm32"))]
+mod bench;
+#[cfg(not(target_arch = "wasm32"))]
+mod frame_recorder;
+mod input_recording;
 
 use winit::event_loop::EventLoop;
 
 use crate::app::App;
+use crate::config::{BOX_SIZE, GRID_DIMENSIONS};
+
+pub use crate::advection_scheme::AdvectionScheme;
+pub use crate::camera::{Camera, CameraController, CameraUniform, Projection};
+pub use crate::camera_settings::CameraSettings;
+pub use crate::compute_step::{ComputeStep, DispatchTextures, WorkgroupSize};
+pub use crate::cpu_advection::{advect_scalars_semi_lagrangian, sample_trilinear};
+pub use crate::field::FieldDescriptor;
+pub use crate::gpu_profiler::GpuProfiler;
+pub use crate::initial_velocity::InitialVelocityField;
+pub use crate::obstacle::Obstacle;
+pub use crate::ping_pong::PingPong;
+pub use crate::render_settings::RenderSettings;
+pub use crate::scene::{InitialDensity, ScenePreset, Source, SourceFalloff, SourcePath};
+pub use crate::sim_params::SimParams;
+pub use crate::simulation::Simulation;
+pub use crate::texture::{SamplerConfig, Texture};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::bench::{run_bench, run_bench_steps};
+pub use crate::input_recording::{InputRecorder, InputReplay};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 #[cfg(target_arch = "wasm32")]
 use winit::platform::web::EventLoopExtWebSys;
 
+/// Options used to start the simulation.
+#[derive(Default)]
+pub struct RunOptions {
+    /// Voxels along x, y and z of the simulation grid. Defaults to the one configured in
+    /// config.rs.
+    pub grid_size: Option<[u32; 3]>,
+    /// World-space length of a voxel along x, y and z. Defaults to the box size configured in
+    /// config.rs.
+    pub voxel_length: Option<[f32; 3]>,
+    /// Raw RGBA16F volume, covering the whole grid, used as the initial scalar field.
+    pub density_volume: Option<Vec<u8>>,
+    /// Look to start with. Defaults to the one configured in config.rs.
+    pub render_settings: Option<RenderSettings>,
+    /// Camera to start with. Defaults to the one configured in config.rs.
+    pub camera_settings: Option<CameraSettings>,
+    /// Start with the simulation paused. Defaults to the one configured in config.rs.
+    pub paused: Option<bool>,
+    /// Records every input event and frame time of the session.
+    pub input_recorder: Option<InputRecorder>,
+    /// Replays a recorded session instead of taking live input, then hands control back.
+    pub input_replay: Option<InputReplay>,
+    /// Velocity field the simulation starts from. Defaults to the one configured in config.rs.
+    pub initial_velocity: Option<InitialVelocityField>,
+    /// Speed of the generated initial velocity fields, instead of INITIAL_VELOCITY_SPEED.
+    pub velocity_scale: Option<f32>,
+    /// Smoke the simulation starts from instead of the scene's initial scalar field.
+    pub initial_density: Option<InitialDensity>,
+    /// Color behind the volume. Defaults to the one configured in config.rs.
+    pub background: Option<wgpu::Color>,
+    /// How frames are presented. Defaults to the one configured in config.rs.
+    pub present_mode: Option<wgpu::PresentMode>,
+    /// Directory recorded frames are written to. Defaults to the one configured in config.rs.
+    pub frame_output_dir: Option<std::path::PathBuf>,
+}
+
+impl RunOptions {
+    /// Dimensions of the simulation grid these options ask for.
+    pub fn grid_dimensions(&self) -> wgpu::Extent3d {
+        match self.grid_size {
+            Some([width, height, depth]) => wgpu::Extent3d { width, height, depth_or_array_layers: depth },
+            None => GRID_DIMENSIONS,
+        }
+    }
+
+    /// World-space size of the simulation box these options ask for, or None for the simulation's
+    /// default of cubic voxels of GRID_VOXEL_SIDE_LENGTH.
+    pub fn box_size(&self) -> Option<[f32; 3]> {
+        let dimensions = self.grid_dimensions();
+        match self.voxel_length {
+            Some(length) => Some([
+                dimensions.width as f32 * length[0],
+                dimensions.height as f32 * length[1],
+                dimensions.depth_or_array_layers as f32 * length[2],
+            ]),
+            None => BOX_SIZE,
+        }
+    }
+}
+
 pub fn run() -> anyhow::Result<()> {
+    run_with_options(RunOptions::default())
+}
+
+pub fn run_with_options(options: RunOptions) -> anyhow::Result<()> {
     #[cfg(not(target_arch = "wasm32"))]
     {
         env_logger::init();
@@ -26,15 +160,30 @@ pub fn run() -> anyhow::Result<()> {
         console_log::init_with_level(log::Level::Info).unwrap_throw();
     }
 
+    // Fail before opening a window if the provided volume can't be uploaded.
+    if let Some(density_volume) = &options.density_volume {
+        let expected_len = Texture::raw_volume_len(options.grid_dimensions());
+        if density_volume.len() != expected_len {
+            anyhow::bail!(
+                "Density volume is {} bytes but the simulation grid needs {} bytes of RGBA16F data",
+                density_volume.len(),
+                expected_len,
+            );
+        }
+    }
+
     let event_loop = EventLoop::with_user_event().build()?;
+    // Frames are driven by the redraw every render requests, not by polling, so the loop sleeps
+    // whenever the window is hidden and stops asking for them (see State::is_hidden).
+    event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
     #[cfg(not(target_arch = "wasm32"))]
     {
-        let mut app = App::new();
+        let mut app = App::new(options);
         event_loop.run_app(&mut app)?;
     }
     #[cfg(target_arch = "wasm32")]
     {
-        let app = App::new(&event_loop);
+        let app = App::new(&event_loop, options);
         event_loop.spawn_app(app);
     }
 
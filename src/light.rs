@@ -0,0 +1,80 @@
+/// Maximum number of lights the renderer evaluates. Must match MAX_LIGHTS in render_shader.wgsl.
+pub const MAX_LIGHTS: usize = 4;
+/// Steepest elevation Light::rotated turns a directional light to, short of straight up or down
+/// where turning about the vertical axis does nothing.
+const MAX_ELEVATION: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+/// A light that scatters off the smoke. Each enabled light costs one shadow ray per ray march
+/// step, so keep the number of lights small.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable, serde::Serialize, serde::Deserialize)]
+pub struct Light {
+    /// w = 0: xyz is the direction towards a directional light.
+    /// w = 1: xyz is the world space position of a point light.
+    position: [f32; 4],
+    /// rgb = color scaled by intensity. A black light is skipped by the renderer.
+    /// a is padding.
+    color: [f32; 4],
+}
+
+impl Light {
+    /// Unused light slot.
+    pub const DISABLED: Light = Light { position: [0.0; 4], color: [0.0; 4] };
+
+    /// Light infinitely far away shining along -direction. The direction does not need to be
+    /// normalized.
+    pub const fn directional(direction: [f32; 3], color: [f32; 3], intensity: f32) -> Self {
+        Self {
+            position: [direction[0], direction[1], direction[2], 0.0],
+            color: [color[0] * intensity, color[1] * intensity, color[2] * intensity, 0.0],
+        }
+    }
+
+    /// Light at a world space position whose intensity falls off with the square of distance.
+    pub const fn point(position: [f32; 3], color: [f32; 3], intensity: f32) -> Self {
+        Self {
+            position: [position[0], position[1], position[2], 1.0],
+            color: [color[0] * intensity, color[1] * intensity, color[2] * intensity, 0.0],
+        }
+    }
+
+    /// Light from its raw uniform layout, as returned by position and color.
+    pub const fn from_raw(position: [f32; 4], color: [f32; 4]) -> Self {
+        Self { position, color }
+    }
+
+    /// Returns the light with its direction turned by yaw radians about the world up (y) axis and
+    /// its elevation raised by pitch radians, within MAX_ELEVATION. Point lights are returned
+    /// unchanged.
+    pub fn rotated(&self, yaw: f32, pitch: f32) -> Self {
+        if self.position[3] != 0.0 {
+            return *self;
+        }
+
+        let [x, y, z, _] = self.position;
+        let length = (x * x + y * y + z * z).sqrt();
+        if length == 0.0 {
+            return *self;
+        }
+        let azimuth = z.atan2(x) + yaw;
+        let elevation = (y.atan2((x * x + z * z).sqrt()) + pitch).clamp(-MAX_ELEVATION, MAX_ELEVATION);
+
+        Self {
+            position: [
+                length * elevation.cos() * azimuth.cos(),
+                length * elevation.sin(),
+                length * elevation.cos() * azimuth.sin(),
+                0.0,
+            ],
+            color: self.color,
+        }
+    }
+
+    pub fn position(&self) -> [f32; 4] {
+        self.position
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        self.color
+    }
+}
@@ -1,3 +1,264 @@
+use firesim::{CameraSettings, InitialDensity, InitialVelocityField, InputRecorder, InputReplay, RenderSettings, RunOptions, Texture};
+
+/// What the binary was asked to do.
+enum Mode {
+    Run(Box<RunOptions>),
+    Bench,
+}
+
+/// Printed with any argument error. Flags that aren't given keep the defaults from config.rs.
+const USAGE: &str = "\
+Usage: firesim [flags]
+
+  --grid-size <voxels>          Voxels along each side of the simulation grid, or x,y,z for a
+                                grid that isn't a cube, e.g. 64,128,64.
+  --voxel-length <length>       World-space side length of a voxel, or x,y,z per axis.
+  --density-volume <path.raw>   Raw RGBA16F volume used as the initial scalar field.
+  --density-file <path.f32>     Raw f32 density volume, e.g. one exported with O, used as the
+                                initial smoke density. Its size must match the grid.
+  --render-settings <path.txt>  Render settings preset, e.g. one saved with P.
+  --camera-settings <path.txt>  Initial camera position, angles, field of view and controls.
+  --initial-velocity <field>    Velocity to start from: tornado, vortex, shear-layer, zero or
+                                uniform:x,y,z (voxel lengths per second).
+  --velocity-scale <speed>      Speed of the tornado, vortex and shear-layer fields, in voxel
+                                lengths per second.
+  --initial <density>           Smoke to start from instead of the scene's: shell, a thin hollow
+                                sphere that shows numerical diffusion as it fills in.
+  --background <r,g,b>          Color behind the volume, each component from 0 to 1.
+  --present-mode <mode>         fifo (vsync), fifo-relaxed, mailbox or immediate. Falls back to
+                                fifo if the surface doesn't support it.
+  --paused                      Start with the simulation paused (Tab resumes, Enter steps).
+  --record-input <path.txt>     Record every input event and frame time to a file.
+  --replay-input <path.txt>     Replay a recording made with --record-input.
+  --frame-dir <dir>             Directory R records numbered PNG frames to.
+  --bench                       Run the headless benchmark matrix and print CSV step times.";
+
 fn main() {
-    firesim::run().unwrap();
+    let mode = match parse_args() {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    match mode {
+        Mode::Run(options) => firesim::run_with_options(*options).unwrap(),
+        Mode::Bench => {
+            if let Err(e) = firesim::run_bench() {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Parses the command line arguments into the mode to start in. See USAGE for the flags.
+fn parse_args() -> anyhow::Result<Mode> {
+    let mut options = RunOptions::default();
+    let mut bench = false;
+    let mut density_file = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--voxel-length" => {
+                let length = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--voxel-length expects a length"))?;
+                let length = parse_per_axis::<f32>(&length)
+                    .filter(|length| length.iter().all(|length| *length > 0.0 && length.is_finite()))
+                    .ok_or_else(|| anyhow::anyhow!("--voxel-length expects a positive length or x,y,z lengths, got {length}"))?;
+                options.voxel_length = Some(length);
+            }
+            "--density-volume" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--density-volume expects a path"))?;
+                let data = std::fs::read(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read density volume {path}: {e}"))?;
+                options.density_volume = Some(data);
+            }
+            "--density-file" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--density-file expects a path"))?;
+                density_file = Some(path);
+            }
+            "--render-settings" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--render-settings expects a path"))?;
+                let preset = std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read render settings {path}: {e}"))?;
+                let render_settings = RenderSettings::from_preset_str(&preset)
+                    .map_err(|e| anyhow::anyhow!("Invalid render settings {path}: {e}"))?;
+                options.render_settings = Some(render_settings);
+            }
+            "--camera-settings" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--camera-settings expects a path"))?;
+                let preset = std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read camera settings {path}: {e}"))?;
+                let camera_settings = CameraSettings::from_preset_str(&preset)
+                    .map_err(|e| anyhow::anyhow!("Invalid camera settings {path}: {e}"))?;
+                options.camera_settings = Some(camera_settings);
+            }
+            "--record-input" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--record-input expects a path"))?;
+                let recorder = InputRecorder::create(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to create input recording {path}: {e}"))?;
+                options.input_recorder = Some(recorder);
+            }
+            "--replay-input" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--replay-input expects a path"))?;
+                let recording = std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read input recording {path}: {e}"))?;
+                let replay = InputReplay::from_recording_str(&recording)
+                    .map_err(|e| anyhow::anyhow!("Invalid input recording {path}: {e}"))?;
+                options.input_replay = Some(replay);
+            }
+            "--grid-size" => {
+                let size = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--grid-size expects a number of voxels"))?;
+                let size = parse_per_axis::<u32>(&size)
+                    .filter(|size| size.iter().all(|size| *size > 0))
+                    .ok_or_else(|| anyhow::anyhow!("--grid-size expects a positive number of voxels or x,y,z numbers, got {size}"))?;
+                options.grid_size = Some(size);
+            }
+            "--frame-dir" => {
+                let dir = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--frame-dir expects a directory"))?;
+                options.frame_output_dir = Some(dir.into());
+            }
+            "--initial-velocity" => {
+                let field = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--initial-velocity expects a field"))?;
+                let field = field
+                    .parse::<InitialVelocityField>()
+                    .map_err(|e| anyhow::anyhow!("Invalid --initial-velocity {field}: {e}"))?;
+                options.initial_velocity = Some(field);
+            }
+            "--velocity-scale" => {
+                let speed = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--velocity-scale expects a speed"))?;
+                let speed = speed
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|speed| *speed >= 0.0 && speed.is_finite())
+                    .ok_or_else(|| anyhow::anyhow!("--velocity-scale expects a speed of at least zero, got {speed}"))?;
+                options.velocity_scale = Some(speed);
+            }
+            "--initial" => {
+                let density = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--initial expects a density"))?;
+                let density = density
+                    .parse::<InitialDensity>()
+                    .map_err(|e| anyhow::anyhow!("Invalid --initial {density}: {e}"))?;
+                options.initial_density = Some(density);
+            }
+            "--background" => {
+                let color = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--background expects a color"))?;
+                options.background = Some(parse_color(&color)?);
+            }
+            "--present-mode" => {
+                let mode = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--present-mode expects a mode"))?;
+                options.present_mode = Some(match mode.as_str() {
+                    "fifo" => wgpu::PresentMode::Fifo,
+                    "fifo-relaxed" => wgpu::PresentMode::FifoRelaxed,
+                    "mailbox" => wgpu::PresentMode::Mailbox,
+                    "immediate" => wgpu::PresentMode::Immediate,
+                    _ => anyhow::bail!("--present-mode expects fifo, fifo-relaxed, mailbox or immediate, got {mode}"),
+                });
+            }
+            "--paused" => options.paused = Some(true),
+            "--bench" => bench = true,
+            _ => anyhow::bail!("Unknown argument: {arg}"),
+        }
+    }
+
+    // Converted once every flag is in, since the grid size it has to match can come after it.
+    if let Some(path) = density_file {
+        if options.density_volume.is_some() {
+            anyhow::bail!("--density-file can't be combined with --density-volume");
+        }
+        let bytes = std::fs::read(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to read density file {path}: {e}"))?;
+        let volume = Texture::raw_volume_from_f32_density(&bytes, options.grid_dimensions())
+            .map_err(|e| anyhow::anyhow!("Invalid density file {path}: {e}"))?;
+        options.density_volume = Some(volume);
+    }
+
+    if options.initial_density.is_some() && options.density_volume.is_some() {
+        anyhow::bail!("--initial can't be combined with --density-volume or --density-file");
+    }
+
+    if bench {
+        if options.grid_size.is_some()
+            || options.voxel_length.is_some()
+            || options.density_volume.is_some()
+            || options.render_settings.is_some()
+            || options.camera_settings.is_some()
+            || options.initial_velocity.is_some()
+            || options.velocity_scale.is_some()
+            || options.initial_density.is_some()
+            || options.background.is_some()
+            || options.present_mode.is_some()
+            || options.paused.is_some()
+            || options.input_recorder.is_some()
+            || options.input_replay.is_some()
+            || options.frame_output_dir.is_some()
+        {
+            anyhow::bail!("--bench only runs the benchmark and can't be combined with other flags");
+        }
+        return Ok(Mode::Bench);
+    }
+    if options.input_recorder.is_some() && options.input_replay.is_some() {
+        anyhow::bail!("--record-input can't be combined with --replay-input");
+    }
+
+    Ok(Mode::Run(Box::new(options)))
+}
+
+/// Parses a single value for all three axes, or x,y,z.
+fn parse_per_axis<T: std::str::FromStr + Copy>(s: &str) -> Option<[T; 3]> {
+    let values = s
+        .split(',')
+        .map(|value| value.trim().parse::<T>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    match values[..] {
+        [value] => Some([value; 3]),
+        [x, y, z] => Some([x, y, z]),
+        _ => None,
+    }
+}
+
+/// Parses an `r,g,b` color with components from 0 to 1.
+fn parse_color(s: &str) -> anyhow::Result<wgpu::Color> {
+    let components = s
+        .split(',')
+        .map(|component| component.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("Invalid --background {s}: {e}"))?;
+    let [r, g, b] = components[..] else {
+        anyhow::bail!("--background expects three components r,g,b, got {s}");
+    };
+    if ![r, g, b].iter().all(|c| (0.0..=1.0).contains(c)) {
+        anyhow::bail!("--background expects components from 0 to 1, got {s}");
+    }
+    Ok(wgpu::Color { r, g, b, a: 1.0 })
 }
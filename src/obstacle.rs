@@ -0,0 +1,27 @@
+/// Static solid the fluid flows around. Positions are in grid coordinates mapped to [0, 1] along
+/// each axis, and the sphere's radius is a fraction of the grid width, like Source.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Obstacle {
+    /// Nothing in the way.
+    None,
+    Sphere { center: [f32; 3], radius: f32 },
+    /// Axis aligned box between two opposite corners.
+    Box { min: [f32; 3], max: [f32; 3] },
+}
+
+impl Obstacle {
+    /// Sphere above the default scene's source, so its plume has to split around it.
+    pub const SPHERE: Self = Obstacle::Sphere { center: [0.5, 0.55, 0.5], radius: 0.12 };
+    /// Slab above the default scene's source, so its plume spreads out under it.
+    pub const BOX: Self = Obstacle::Box { min: [0.3, 0.5, 0.3], max: [0.7, 0.58, 0.7] };
+
+    /// Returns the obstacle after this one in None, SPHERE, BOX, wrapping around. Used to cycle
+    /// obstacles at runtime.
+    pub fn next(self) -> Self {
+        match self {
+            Obstacle::None => Obstacle::SPHERE,
+            Obstacle::Sphere { .. } => Obstacle::BOX,
+            Obstacle::Box { .. } => Obstacle::None,
+        }
+    }
+}
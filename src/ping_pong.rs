@@ -16,25 +16,56 @@ impl PingPong {
     }
 
     pub fn get_read_and_write(&self) -> (&wgpu::TextureView, &wgpu::TextureView) {
-        let read_and_write = if self.a_to_b {
+        if self.a_to_b {
             (&self.texture_a.view, &self.texture_b.view)
         } else {
             (&self.texture_b.view, &self.texture_a.view)
-        };
-
-        read_and_write
+        }
     }
 
     pub fn get_read(&self) -> &wgpu::TextureView {
-        let read = if self.a_to_b {
+        if self.a_to_b {
             // If we were to read from a and write to b, then a has the most up-to-date data.
             &self.texture_a.view
         } else {
             // If we were to read from b and write to a, then b has the most up-to-date data.
             &self.texture_b.view
-        };
+        }
+    }
+
+    /// Returns the texture holding the most up-to-date data. See get_read.
+    pub fn get_read_texture(&self) -> &Texture {
+        if self.a_to_b {
+            &self.texture_a
+        } else {
+            &self.texture_b
+        }
+    }
+
+    /// Clears both textures to zero.
+    pub fn clear(&self, queue: &wgpu::Queue) {
+        self.texture_a.clear(queue);
+        self.texture_b.clear(queue);
+    }
+
+    /// Clears both textures and reads from A again, as after new.
+    pub fn reset(&mut self, queue: &wgpu::Queue) {
+        self.clear(queue);
+        self.a_to_b = true;
+    }
+
+    /// Whether A is the read side. Saved with the textures by Simulation::save_snapshot.
+    pub fn a_to_b(&self) -> bool {
+        self.a_to_b
+    }
+
+    pub fn set_a_to_b(&mut self, a_to_b: bool) {
+        self.a_to_b = a_to_b;
+    }
 
-        read
+    /// Both textures, A then B, whichever is the read side.
+    pub fn textures(&self) -> [&Texture; 2] {
+        [&self.texture_a, &self.texture_b]
     }
 
     pub fn swap(&mut self) {
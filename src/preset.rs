@@ -0,0 +1,31 @@
+/// Parses a `key = value` preset file, passing every setting to `set` in order. Blank lines and
+/// lines starting with # are ignored. Errors are prefixed with the line they came from.
+pub fn parse_preset(
+    preset: &str,
+    mut set: impl FnMut(&str, &str) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    for (line_number, line) in preset.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .ok_or_else(|| anyhow::anyhow!("Line {}: expected `key = value`", line_number + 1))?;
+
+        set(key, value).map_err(|e| anyhow::anyhow!("Line {}: {e}", line_number + 1))?;
+    }
+    Ok(())
+}
+
+/// Parses exactly N whitespace separated floats.
+pub fn parse_floats<const N: usize>(value: &str) -> anyhow::Result<[f32; N]> {
+    let components = value
+        .split_whitespace()
+        .map(str::parse::<f32>)
+        .collect::<Result<Vec<_>, _>>()?;
+    components
+        .try_into()
+        .map_err(|components: Vec<f32>| anyhow::anyhow!("Expected {N} values, got {}", components.len()))
+}
@@ -0,0 +1,141 @@
+use crate::config::{MAX_RAY_DISTANCE, OPACITY_THRESHOLD, RAYMARCH_STEPS};
+use crate::light::{Light, MAX_LIGHTS};
+use crate::render_settings::RenderSettings;
+use crate::slice_view::SliceView;
+
+/// Struct to contain read-only params for the render pipeline.
+/// Should be passed to the shader via a uniform buffer, at bind group 1.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RenderParams {
+    /// Minimum point in world space for the simulation grid.
+    /// xyz + padding.
+    box_min: [f32; 4],
+    /// Maximum point in world space for the simulation grid.
+    /// xyz + padding.
+    box_max: [f32; 4],
+    /// Number of pixels [width, height]
+    viewport: [f32; 2],
+    /// Multiplier applied to the linear radiance of the ray march before tone mapping.
+    exposure: f32,
+    /// Number of entries of lights the renderer evaluates. Zero lights the smoke with ambient
+    /// light only.
+    light_count: u32,
+    /// Temperature (K) below which the renderer emits no light.
+    ignition_temperature: f32,
+    /// Temperature (K) at which the orange band is fully on and the shift to blue begins.
+    cool_band_temperature: f32,
+    /// Temperature (K) at which the emission is fully blue-white.
+    hot_band_temperature: f32,
+    /// Multiplier applied to the smoke density before extinction. 1 unless the density range is
+    /// auto-ranged.
+    smoke_density_scale: f32,
+    /// Longest distance, in world units, the ray march covers from where it enters the box.
+    max_ray_distance: f32,
+    /// How the smoke is colored. See ColorRamp::shader_id.
+    color_ramp: u32,
+    /// x = frames per second, y = average frame time in milliseconds, shown by the stats overlay.
+    frame_stats: [f32; 2],
+    /// Color the volume is composited over after tone mapping. rgb + padding.
+    background: [f32; 4],
+    /// Steps of the ray march through the box.
+    raymarch_steps: u32,
+    /// Accumulated opacity at which a ray stops marching.
+    opacity_threshold: f32,
+    /// Channel the slice view shows. See SliceField::shader_id.
+    slice_field: u32,
+    /// Axis the slice view's plane is perpendicular to. See SliceAxis::shader_id.
+    slice_axis: u32,
+    /// Voxel index of the slice view's plane along slice_axis.
+    slice_index: u32,
+    _pad0: f32,
+    /// Values of the slice view's field at the bottom and top of its color ramp.
+    slice_range: [f32; 2],
+    lights: [Light; MAX_LIGHTS],
+}
+
+impl RenderParams {
+    pub const RAYMARCH_STEPS_RANGE: [u32; 2] = [8, 512];
+
+    /// The viewport starts at 1x1. Call update_viewport once there is a surface to render to.
+    /// The ray march covers at most MAX_RAY_DISTANCE, or the box diagonal if that isn't set. No
+    /// ray through the box is longer than its diagonal, so the default never cuts a ray short.
+    pub fn new(box_min: [f32; 3], box_max: [f32; 3], settings: &RenderSettings) -> Self {
+        let box_diagonal = (0..3).map(|i| (box_max[i] - box_min[i]).powi(2)).sum::<f32>().sqrt();
+        let mut params = Self {
+            box_min: [box_min[0], box_min[1], box_min[2], 0.0],
+            box_max: [box_max[0], box_max[1], box_max[2], 0.0],
+            viewport: [1.0, 1.0],
+            exposure: 1.0,
+            light_count: 0,
+            ignition_temperature: 0.0,
+            cool_band_temperature: 0.0,
+            hot_band_temperature: 0.0,
+            smoke_density_scale: 1.0,
+            max_ray_distance: MAX_RAY_DISTANCE.unwrap_or(box_diagonal),
+            color_ramp: 0,
+            frame_stats: [0.0; 2],
+            background: [0.0, 0.0, 0.0, 0.0],
+            raymarch_steps: RAYMARCH_STEPS,
+            opacity_threshold: OPACITY_THRESHOLD,
+            slice_field: 0,
+            slice_axis: 0,
+            slice_index: 0,
+            _pad0: 0.0,
+            slice_range: [0.0, 1.0],
+            lights: [Light::DISABLED; MAX_LIGHTS],
+        };
+        params.update_render_settings(settings);
+        params
+    }
+
+    /// Copies the part of the render settings the shader reads. The exposure is overwritten every
+    /// frame by update_exposure when auto-exposure is on.
+    pub fn update_render_settings(&mut self, settings: &RenderSettings) {
+        let bands = settings.emission_preset.bands();
+        self.ignition_temperature = bands.ignition_temperature;
+        self.cool_band_temperature = bands.cool_band_temperature;
+        self.hot_band_temperature = bands.hot_band_temperature;
+        self.light_count = settings.light_count();
+        self.exposure = settings.exposure;
+        self.lights = settings.lights;
+        self.color_ramp = settings.color_ramp.shader_id();
+    }
+
+    pub fn update_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure;
+    }
+
+    pub fn update_smoke_density_scale(&mut self, smoke_density_scale: f32) {
+        self.smoke_density_scale = smoke_density_scale;
+    }
+
+    pub fn raymarch_steps(&self) -> u32 {
+        self.raymarch_steps
+    }
+
+    /// Clamped to RAYMARCH_STEPS_RANGE.
+    pub fn update_raymarch_steps(&mut self, steps: u32) {
+        self.raymarch_steps = steps.clamp(Self::RAYMARCH_STEPS_RANGE[0], Self::RAYMARCH_STEPS_RANGE[1]);
+    }
+
+    pub fn update_background(&mut self, color: wgpu::Color) {
+        self.background = [color.r as f32, color.g as f32, color.b as f32, 0.0];
+    }
+
+    pub fn update_slice_view(&mut self, slice_view: &SliceView) {
+        self.slice_field = slice_view.field.shader_id();
+        self.slice_axis = slice_view.axis.shader_id();
+        self.slice_index = slice_view.index;
+        self.slice_range = slice_view.field.range();
+    }
+
+    pub fn update_frame_stats(&mut self, frames_per_second: f32, frame_time_ms: f32) {
+        self.frame_stats = [frames_per_second, frame_time_ms];
+    }
+
+    pub fn update_viewport(&mut self, config: &wgpu::SurfaceConfiguration) {
+        // A zero viewport would divide by zero when generating rays.
+        self.viewport = [config.width.max(1) as f32, config.height.max(1) as f32];
+    }
+}
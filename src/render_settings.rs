@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+use crate::blend_mode::BlendMode;
+use crate::color_ramp::ColorRamp;
+use crate::config::{AUTO_DENSITY_RANGE, AUTO_EXPOSURE, BLEND_MODE, COLOR_RAMP, EMISSION_PRESET, EXPOSURE, LIGHTS};
+use crate::emission::EmissionPreset;
+use crate::light::{Light, MAX_LIGHTS};
+use crate::preset::{parse_floats, parse_preset};
+
+/// Everything that changes how the fields are rendered but not how they are simulated.
+///
+/// Implements serde's traits, and can also be saved to and loaded from a small `key = value`
+/// preset file (see to_preset_string and from_preset_str) so a look can be shared. The default
+/// is the look configured in config.rs.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    /// Flame emission bands.
+    pub emission_preset: EmissionPreset,
+    /// Multiplier applied to the linear radiance before tone mapping. Used as is when
+    /// auto-exposure is off, and as the starting point when it is on.
+    pub exposure: f32,
+    /// Adapt the exposure to the average luminance of the frame.
+    pub auto_exposure: bool,
+    /// Scale the smoke density to the range measured from the field.
+    pub auto_density_range: bool,
+    /// Light the smoke with lights, or with the ambient term only when false.
+    pub lights_enabled: bool,
+    pub lights: [Light; MAX_LIGHTS],
+    /// Sample the fields with nearest neighbor filtering to see raw voxels.
+    pub nearest_sampling: bool,
+    /// Draw a color legend of the emission bands over the image.
+    pub legend_enabled: bool,
+    /// Draw the edges of the simulation box.
+    pub box_edges_enabled: bool,
+    /// How the volume is composited into the frame.
+    pub blend_mode: BlendMode,
+    /// How the smoke is colored.
+    pub color_ramp: ColorRamp,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            emission_preset: EMISSION_PRESET,
+            exposure: EXPOSURE,
+            auto_exposure: AUTO_EXPOSURE,
+            auto_density_range: AUTO_DENSITY_RANGE,
+            lights_enabled: true,
+            lights: LIGHTS,
+            nearest_sampling: false,
+            legend_enabled: false,
+            box_edges_enabled: false,
+            blend_mode: BLEND_MODE,
+            color_ramp: COLOR_RAMP,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Number of lights the renderer should evaluate.
+    pub fn light_count(&self) -> u32 {
+        if self.lights_enabled { MAX_LIGHTS as u32 } else { 0 }
+    }
+
+    /// Writes the settings as a preset file. Every setting is written, so the file doesn't depend
+    /// on the defaults of the build that loads it.
+    pub fn to_preset_string(&self) -> String {
+        let mut preset = String::from("# firesim render settings\n");
+        preset += &format!("emission_preset = {:?}\n", self.emission_preset);
+        preset += &format!("exposure = {}\n", self.exposure);
+        preset += &format!("auto_exposure = {}\n", self.auto_exposure);
+        preset += &format!("auto_density_range = {}\n", self.auto_density_range);
+        preset += &format!("lights_enabled = {}\n", self.lights_enabled);
+        for (i, light) in self.lights.iter().enumerate() {
+            let [x, y, z, w] = light.position();
+            let [r, g, b, _] = light.color();
+            preset += &format!("light{i} = {x} {y} {z} {w} {r} {g} {b}\n");
+        }
+        preset += &format!("nearest_sampling = {}\n", self.nearest_sampling);
+        preset += &format!("legend_enabled = {}\n", self.legend_enabled);
+        preset += &format!("box_edges_enabled = {}\n", self.box_edges_enabled);
+        preset += &format!("blend_mode = {:?}\n", self.blend_mode);
+        preset += &format!("color_ramp = {:?}\n", self.color_ramp);
+        preset
+    }
+
+    /// Parses a preset file. Blank lines and lines starting with # are ignored, and settings that
+    /// are missing keep their default.
+    pub fn from_preset_str(preset: &str) -> anyhow::Result<Self> {
+        let mut settings = Self::default();
+        parse_preset(preset, |key, value| settings.set(key, value))?;
+        Ok(settings)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key {
+            "emission_preset" => {
+                self.emission_preset = match value {
+                    "Campfire" => EmissionPreset::Campfire,
+                    "GasFlame" => EmissionPreset::GasFlame,
+                    _ => anyhow::bail!("Unknown emission preset: {value}"),
+                }
+            }
+            "exposure" => {
+                self.exposure = value.parse()?;
+                if !(self.exposure > 0.0 && self.exposure.is_finite()) {
+                    anyhow::bail!("Exposure must be positive, got {value}");
+                }
+            }
+            "auto_exposure" => self.auto_exposure = value.parse()?,
+            "auto_density_range" => self.auto_density_range = value.parse()?,
+            "lights_enabled" => self.lights_enabled = value.parse()?,
+            "nearest_sampling" => self.nearest_sampling = value.parse()?,
+            "legend_enabled" => self.legend_enabled = value.parse()?,
+            "box_edges_enabled" => self.box_edges_enabled = value.parse()?,
+            "blend_mode" => {
+                self.blend_mode = match value {
+                    "Alpha" => BlendMode::Alpha,
+                    "Additive" => BlendMode::Additive,
+                    "Opaque" => BlendMode::Opaque,
+                    _ => anyhow::bail!("Unknown blend mode: {value}"),
+                }
+            }
+            "color_ramp" => {
+                self.color_ramp = match value {
+                    "Lit" => ColorRamp::Lit,
+                    "Fire" => ColorRamp::Fire,
+                    "Grayscale" => ColorRamp::Grayscale,
+                    "Water" => ColorRamp::Water,
+                    _ => anyhow::bail!("Unknown color ramp: {value}"),
+                }
+            }
+            _ => {
+                let index = key
+                    .strip_prefix("light")
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .filter(|index| *index < MAX_LIGHTS)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown setting: {key}"))?;
+                self.lights[index] = parse_light(value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses `x y z w r g b`, the position (w = 0 directional, 1 point) and the color scaled by
+/// intensity.
+fn parse_light(value: &str) -> anyhow::Result<Light> {
+    let [x, y, z, w, r, g, b] = parse_floats::<7>(value)
+        .map_err(|_| anyhow::anyhow!("A light needs 7 values: x y z w r g b"))?;
+    if w != 0.0 && w != 1.0 {
+        anyhow::bail!("A light's w must be 0 (directional) or 1 (point), got {w}");
+    }
+    Ok(Light::from_raw([x, y, z, w], [r, g, b, 0.0]))
+}
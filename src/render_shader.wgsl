@@ -14,23 +14,47 @@ struct CameraUniform {
 
     tan_half_fovy: f32,
     aspect: f32,
-    _pad4: vec2<f32>,
+    // Near and far planes, as distances along camera_forward.
+    znear: f32,
+    zfar: f32,
 };
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
-struct Params {
-    dt: f32,
-    width: u32,
-    height: u32,
-    depth: u32,
+const MAX_LIGHTS: u32 = 4u;
+
+// w = 0: xyz is the direction towards a directional light. w = 1: xyz is a point light position.
+// color.rgb is the color scaled by intensity.
+struct Light {
+    position: vec4<f32>,
+    color: vec4<f32>,
+}
+
+struct RenderParams {
     box_min: vec4<f32>,
     box_max: vec4<f32>,
     viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    exposure: f32,
+    light_count: u32,
+    ignition_temperature: f32,
+    cool_band_temperature: f32,
+    hot_band_temperature: f32,
+    smoke_density_scale: f32,
+    max_ray_distance: f32,
+    color_ramp: u32,
+    frame_stats: vec2<f32>,
+    background: vec4<f32>,
+    raymarch_steps: u32,
+    opacity_threshold: f32,
+    slice_field: u32,
+    slice_axis: u32,
+    slice_index: u32,
+    _pad0: f32,
+    slice_range: vec2<f32>,
+    lights: array<Light, MAX_LIGHTS>,
 }
 @group(1) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: RenderParams;
 
 @vertex
 fn vs_main(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
@@ -43,7 +67,80 @@ fn vs_main(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
     return vec4<f32>(pos[vid], 0.0, 1.0);
 }
 
-// Texture bindings: x = smoke density, y = temperature (normalized [0,1])
+// Draws the back faces of the simulation box, so fs_main only runs for pixels whose ray hits the
+// box. Back faces rather than front faces because they stay in front of the camera when it is
+// inside the box, and every pixel the box covers gets exactly one.
+@vertex
+fn vs_box(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    // Corners are indexed x + 2y + 4z. Triangles are wound counter-clockwise seen from outside.
+    var indices = array<u32, 36>(
+        4u, 6u, 2u, 4u, 2u, 0u,
+        1u, 3u, 7u, 1u, 7u, 5u,
+        1u, 5u, 4u, 1u, 4u, 0u,
+        2u, 6u, 7u, 2u, 7u, 3u,
+        2u, 3u, 1u, 2u, 1u, 0u,
+        4u, 5u, 7u, 4u, 7u, 6u,
+    );
+    let corner = indices[vid];
+    let weights = vec3<f32>((vec3<u32>(corner) >> vec3<u32>(0u, 1u, 2u)) & vec3<u32>(1u));
+    let p = mix(params.box_min.xyz, params.box_max.xyz, weights);
+    return project(p);
+}
+
+// Clip position of the world space point p, for the camera fs_main casts rays from.
+fn project(p: vec3<f32>) -> vec4<f32> {
+    // Inverse of the ray construction in fs_main: solve
+    // p - camera_pos = s * (forward + a * right + b * up)
+    // camera_up is world up rather than perpendicular to forward, so the basis is inverted with
+    // cross products instead of dot products.
+    let f = camera.camera_forward;
+    let r = camera.camera_right;
+    let u = camera.camera_up;
+    let d = p - camera.camera_pos;
+    let det = dot(f, cross(r, u));
+    let s = dot(cross(r, u), d) / det;
+    let s_a = dot(cross(u, f), d) / det;
+    let s_b = dot(cross(f, r), d) / det;
+
+    // fs_main scales a by aspect * tan_half_fovy and b by tan_half_fovy to get to NDC.
+    return vec4<f32>(
+        s_a / (camera.aspect * camera.tan_half_fovy),
+        s_b / camera.tan_half_fovy,
+        s - camera.znear,
+        s,
+    );
+}
+
+// Color of the box edges after exposure, before tone mapping.
+const BOX_EDGE_COLOR: vec3<f32> = vec3<f32>(0.5, 0.5, 0.5);
+// Relative distance the box edges are pushed away from the camera by. Line pipelines can't take a
+// depth bias, and without it the edges on the box's back faces z-fight with vs_box. Overridden from
+// Rust (see BOX_EDGE_DEPTH_OFFSET in config.rs).
+override BOX_EDGE_DEPTH_OFFSET: f32 = 1e-3;
+
+// Draws the box's 12 edges as a line list, from the 24 corner positions in the vertex buffer. Drawn
+// before the volume with depth writes, so edges in front of the back faces hide the smoke behind
+// them and the volume is blended over the edges on the back faces, covering them where the smoke is
+// dense.
+@vertex
+fn vs_box_edges(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    let clip = project(position);
+    // Same depth as a point BOX_EDGE_DEPTH_OFFSET further along the ray, keeping x, y and w.
+    let pushed = clip.w * (1.0 + BOX_EDGE_DEPTH_OFFSET);
+    return vec4<f32>(clip.xy, clip.w - camera.znear * clip.w / pushed, clip.w);
+}
+
+// The luminance target is masked out in the pipeline, so the edges don't change the auto-exposure.
+@fragment
+fn fs_box_edges() -> FragmentOutput {
+    var out: FragmentOutput;
+    // Undoes the exposure fs_tonemap applies, so the edges look the same at any exposure.
+    out.color = vec4<f32>(BOX_EDGE_COLOR / params.exposure, 1.0);
+    out.luminance = vec4<f32>(0.0);
+    return out;
+}
+
+// Texture bindings: x = smoke density, y = temperature (Kelvin), z = fuel, w = soot density
 @group(2) @binding(0)
 var density_scalar_field: texture_3d<f32>;
 @group(2) @binding(1)
@@ -63,13 +160,19 @@ const C2: f32 = 14387769.0;   // nm · K
 // ~0.5 pre-tone-mapping. Tune: too dark → increase; blown out → decrease.
 const EXPOSURE: f32 = 1e-13;
 
-// Grey color for cool smoke scattering ambient light.
+// Grey color for cool smoke scattering light.
 // Increase for brighter/lighter smoke; decrease for darker smoke.
 const SMOKE_COLOR: vec3<f32> = vec3<f32>(0.35, 0.35, 0.35);
 
+// Unshadowed light reaching every point of the smoke, on top of the lights in params.
+const AMBIENT_LIGHT: f32 = 0.5;
+
 // Extinction coefficient for smoke. Higher = denser/more opaque smoke.
 const SIGMA_SMOKE: f32 = 0.1;
 
+// Extinction coefficient for soot. Soot only absorbs, so it darkens whatever is behind it.
+const SIGMA_SOOT: f32 = 0.6;
+
 // Planck spectral radiance B(λ, T) = c₁ / (λ⁵ · (exp(c₂/λT) − 1))
 // Returns 0 when the exponent would overflow f32 (very low T or short λ).
 fn planck(lambda_nm: f32, T_K: f32) -> f32 {
@@ -129,15 +232,151 @@ fn tone_map(c: vec3<f32>) -> vec3<f32> {
     return pow(max(mapped, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.2));
 }
 
-// Convert simulation temperature (Kelvin) to a display RGB color.
-fn blackbody_color(temperature: f32) -> vec3<f32> {
-    if temperature < 300.0 { return vec3<f32>(0.0); }
-    let xyz = blackbody_xyz(temperature);
-    return tone_map(xyz_to_linear_srgb(xyz));
+/* Density color ramps */
+
+// Must match ColorRamp in color_ramp.rs.
+const RAMP_LIT: u32 = 0u;
+const RAMP_FIRE: u32 = 1u;
+const RAMP_GRAYSCALE: u32 = 2u;
+const RAMP_WATER: u32 = 3u;
+
+// Scaled smoke density at the top of the ramps. The auto density range brings the dense end of
+// the field to about here (AUTO_DENSITY_REFERENCE in config.rs).
+const RAMP_FULL_DENSITY: f32 = 10.0;
+
+// Color (linear sRGB) of a ramp at t in 0..1, through evenly spaced stops.
+fn ramp_color(ramp: u32, t: f32) -> vec3<f32> {
+    var stops: array<vec3<f32>, 5>;
+    switch ramp {
+        case RAMP_FIRE: {
+            stops = array<vec3<f32>, 5>(
+                vec3<f32>(0.0), vec3<f32>(0.6, 0.02, 0.0), vec3<f32>(1.0, 0.3, 0.0),
+                vec3<f32>(1.0, 0.75, 0.1), vec3<f32>(1.0),
+            );
+        }
+        case RAMP_WATER: {
+            stops = array<vec3<f32>, 5>(
+                vec3<f32>(0.0, 0.01, 0.05), vec3<f32>(0.0, 0.05, 0.25), vec3<f32>(0.02, 0.2, 0.6),
+                vec3<f32>(0.2, 0.5, 0.85), vec3<f32>(0.7, 0.9, 1.0),
+            );
+        }
+        default: {
+            return vec3<f32>(clamp(t, 0.0, 1.0));
+        }
+    }
+    let x = clamp(t, 0.0, 1.0) * 4.0;
+    let i = min(u32(x), 3u);
+    return mix(stops[i], stops[i + 1u], x - f32(i));
+}
+
+/* Two-band flame emission */
+
+// Color of the hot core of the flame (linear sRGB).
+const HOT_BAND_COLOR: vec3<f32> = vec3<f32>(0.45, 0.6, 1.0);
+// Linear brightness of a fully ignited voxel before tone mapping.
+const BAND_BRIGHTNESS: f32 = 1.5;
+// Lowest temperature used to derive the hue of the cool band. Below this the blackbody hue is
+// too dark and red to read as flame.
+const MIN_COOL_BAND_HUE_TEMPERATURE: f32 = 1400.0;
+
+// Hue of the cool (orange) band, taken from the blackbody spectrum at the given temperature and
+// normalized so that its brightest channel is 1.
+fn cool_band_color(temperature: f32) -> vec3<f32> {
+    let hue_temperature = max(temperature, MIN_COOL_BAND_HUE_TEMPERATURE);
+    let rgb = max(xyz_to_linear_srgb(blackbody_xyz(hue_temperature)), vec3<f32>(0.0));
+    let peak = max(max(rgb.r, rgb.g), rgb.b);
+    if peak <= 0.0 { return vec3<f32>(0.0); }
+    return rgb / peak;
+}
+
+// Convert simulation temperature (Kelvin) to linear RGB emission.
+// Below the ignition temperature nothing is emitted. Emission fades in as orange up to the cool
+// band temperature and then shifts to blue-white up to the hot band temperature.
+fn flame_color(temperature: f32) -> vec3<f32> {
+    if temperature < params.ignition_temperature { return vec3<f32>(0.0); }
+
+    let ignition = smoothstep(params.ignition_temperature, params.cool_band_temperature, temperature);
+    let hot = smoothstep(params.cool_band_temperature, params.hot_band_temperature, temperature);
+
+    let color = mix(cool_band_color(temperature), HOT_BAND_COLOR, hot);
+    return color * ignition * BAND_BRIGHTNESS;
+}
+
+/* Volume lighting */
+
+// Number of samples taken along each shadow ray. Cost scales with this times the number of lights.
+const SHADOW_STEPS: u32 = 16u;
+// Henyey-Greenstein asymmetry. Positive values scatter forward, so smoke glows when backlit.
+const PHASE_G: f32 = 0.3;
+// Closest distance used for point light falloff, to avoid blowing out voxels right on the light.
+const MIN_POINT_LIGHT_DISTANCE: f32 = 0.05;
+// Steps that scatter less than this skip the lighting entirely.
+const MIN_SCATTERING_ALPHA: f32 = 1e-3;
+
+// Henyey-Greenstein phase function, scaled so that isotropic scattering (g = 0) is 1.
+fn phase(cos_theta: f32) -> f32 {
+    let g2 = PHASE_G * PHASE_G;
+    let denom = 1.0 + g2 - 2.0 * PHASE_G * cos_theta;
+    return (1.0 - g2) / (denom * sqrt(denom));
+}
+
+// Fraction of light that makes it from p to max_distance along dir through smoke and soot.
+fn light_transmittance(p: vec3<f32>, dir: vec3<f32>, max_distance: f32, bmin: vec3<f32>, bmax: vec3<f32>) -> f32 {
+    let t_exit = min(intersect_aabb(p, dir, bmin, bmax).y, max_distance);
+    if (t_exit <= 0.0) { return 1.0; }
+
+    let ds = t_exit / f32(SHADOW_STEPS);
+    var optical_depth = 0.0;
+    for (var i: u32 = 0u; i < SHADOW_STEPS; i = i + 1u) {
+        let q = p + dir * ((f32(i) + 0.5) * ds);
+        let s = textureSampleLevel(density_scalar_field, field_sampler, (q - bmin) / (bmax - bmin), 0.0);
+        optical_depth += (s.x * params.smoke_density_scale * SIGMA_SMOKE + s.w * SIGMA_SOOT) * ds;
+    }
+    return exp(-optical_depth);
+}
+
+// Light scattered towards the camera at p, for a view ray travelling along rd.
+fn in_scattered_light(p: vec3<f32>, rd: vec3<f32>, bmin: vec3<f32>, bmax: vec3<f32>) -> vec3<f32> {
+    var light = vec3<f32>(AMBIENT_LIGHT);
+
+    for (var i: u32 = 0u; i < min(params.light_count, MAX_LIGHTS); i = i + 1u) {
+        let l = params.lights[i];
+        var intensity = l.color.rgb;
+        if (max(max(intensity.r, intensity.g), intensity.b) <= 0.0) { continue; }
+
+        var dir: vec3<f32>;
+        var max_distance: f32;
+        if (l.position.w == 0.0) {
+            dir = normalize(l.position.xyz);
+            max_distance = 1e6;
+        } else {
+            let to_light = l.position.xyz - p;
+            max_distance = length(to_light);
+            dir = to_light / max(max_distance, 1e-6);
+            intensity /= max(max_distance * max_distance, MIN_POINT_LIGHT_DISTANCE * MIN_POINT_LIGHT_DISTANCE);
+        }
+
+        // Light travels along -dir and leaves along -rd, so the scattering angle is between dir and rd.
+        light += intensity * phase(dot(dir, rd)) * light_transmittance(p, dir, max_distance, bmin, bmax);
+    }
+
+    return light;
+}
+
+struct FragmentOutput {
+    // Linear radiance before exposure and tone mapping, resolved to the screen by fs_tonemap.
+    @location(0) color: vec4<f32>,
+    // Luminance before exposure and tone mapping, read by the auto-exposure reduction.
+    @location(1) luminance: vec4<f32>,
+}
+
+// Relative luminance of linear sRGB.
+fn luminance(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
 }
 
 @fragment
-fn fs_main(@builtin(position) frag_clip_position: vec4<f32>) -> @location(0) vec4<f32> {
+fn fs_main(@builtin(position) frag_clip_position: vec4<f32>) -> FragmentOutput {
     // Frag (pixel) coordinates normalized to 0..1
     let uv = frag_clip_position.xy / params.viewport;
 
@@ -158,15 +397,23 @@ fn fs_main(@builtin(position) frag_clip_position: vec4<f32>) -> @location(0) vec
     let bmin = params.box_min.xyz;
     let bmax = params.box_max.xyz;
 
+    // The march covers the part of the ray inside the box and between the near and far planes
+    // (distances along the view direction, so they are converted to distances along the ray), for
+    // at most max_ray_distance. The step count is fixed, so capping the distance keeps the steps
+    // short enough to resolve the field when the box is huge.
     let hit = intersect_aabb(ro, rd, bmin, bmax);
-    let t_enter = max(hit.x, 0.0);
-    let t_exit = hit.y;
+    let view_cos = dot(rd, camera.camera_forward);
+    let t_enter = max(hit.x, camera.znear / view_cos);
+    let t_exit = min(min(hit.y, camera.zfar / view_cos), t_enter + params.max_ray_distance);
 
+    var out: FragmentOutput;
     if (t_exit <= t_enter) {
-        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        out.color = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        out.luminance = vec4<f32>(0.0);
+        return out;
     }
 
-    let steps: u32 = 64u;
+    let steps = params.raymarch_steps;
     let len = t_exit - t_enter;
     let ds = len / f32(steps);
 
@@ -179,25 +426,45 @@ fn fs_main(@builtin(position) frag_clip_position: vec4<f32>) -> @location(0) vec
         let uvw = (p - bmin) / (bmax - bmin);
 
         let s = textureSampleLevel(density_scalar_field, field_sampler, uvw, 0.0);
-        let smoke = s.x;
+        let smoke = s.x * params.smoke_density_scale;
         let temp = s.y;
+        let soot = s.w;
 
-        // Beer-Lambert extinction: alpha contribution from smoke density this step
+        // Beer-Lambert extinction: alpha contribution from smoke and soot density this step
         let smoke_alpha = 1.0 - exp(-smoke * SIGMA_SMOKE * ds);
-
-        // Emission from blackbody radiation at this temperature
-        let emit_color = blackbody_color(temp);
+        let soot_alpha = 1.0 - exp(-soot * SIGMA_SOOT * ds);
+        let step_alpha = 1.0 - (1.0 - smoke_alpha) * (1.0 - soot_alpha);
+
+        // Emission from the flame at this temperature
+        let emit_color = flame_color(temp);
+
+        // Light scattered by the smoke (soot absorbs without scattering), or the color ramp's
+        // color of its density, unlit
+        let scattering_alpha = smoke_alpha * (1.0 - soot_alpha);
+        var scattered = vec3<f32>(0.0);
+        if (params.color_ramp != RAMP_LIT) {
+            scattered = ramp_color(params.color_ramp, smoke / RAMP_FULL_DENSITY) * scattering_alpha;
+        } else if (scattering_alpha > MIN_SCATTERING_ALPHA) {
+            scattered = SMOKE_COLOR * scattering_alpha * in_scattered_light(p, rd, bmin, bmax);
+        }
 
         // Front-to-back compositing: smoke scattering + fire emission
-        accum_color += (1.0 - accum_alpha) * (SMOKE_COLOR * smoke_alpha + emit_color);
-        accum_alpha += (1.0 - accum_alpha) * smoke_alpha;
+        accum_color += (1.0 - accum_alpha) * (scattered + emit_color);
+        accum_alpha += (1.0 - accum_alpha) * step_alpha;
 
-        if (accum_alpha > 0.99) { break; }
+        // Early opacity termination. It can only end the march sooner than t_exit, so the
+        // distance cap never makes a pixel more expensive.
+        if (accum_alpha > params.opacity_threshold) { break; }
 
         t = t + ds;
     }
 
-    return vec4<f32>(accum_color, 1.0);
+    // Exposure and tone mapping are applied once per pixel by fs_tonemap, so bright emission
+    // isn't clipped here. Front-to-back compositing leaves the color premultiplied by its
+    // coverage, which is what every BlendMode expects.
+    out.color = vec4<f32>(accum_color, accum_alpha);
+    out.luminance = vec4<f32>(luminance(accum_color), 0.0, 0.0, accum_alpha);
+    return out;
 }
 
 fn intersect_aabb(ro: vec3<f32>, rd: vec3<f32>, bmin: vec3<f32>, bmax: vec3<f32>) -> vec2<f32> {
@@ -212,3 +479,313 @@ fn intersect_aabb(ro: vec3<f32>, rd: vec3<f32>, bmin: vec3<f32>, bmax: vec3<f32>
     let t_exit  = min(min(tmax3.x, tmax3.y), tmax3.z);
     return vec2<f32>(t_enter, t_exit);
 }
+
+/* Tone mapping */
+
+// Linear radiance written by fs_main.
+@group(3) @binding(0)
+var hdr_color: texture_2d<f32>;
+
+// Drawn with vs_main's full screen triangle. Reads the HDR target texel for texel. The volume is
+// composited over the background after tone mapping, so the exposure doesn't change the
+// background. The HDR color is premultiplied by its coverage in alpha, and so is its tone mapped
+// color, since the tone map goes through zero.
+@fragment
+fn fs_tonemap(@builtin(position) frag_clip_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let hdr = textureLoad(hdr_color, vec2<i32>(frag_clip_position.xy), 0);
+    let coverage = clamp(hdr.a, 0.0, 1.0);
+    let color = tone_map(hdr.rgb * params.exposure) + params.background.rgb * (1.0 - coverage);
+    return vec4<f32>(color, 1.0);
+}
+
+/* Slice view */
+
+// Must match SliceField and SliceAxis in slice_view.rs.
+const SLICE_FIELD_DENSITY: u32 = 0u;
+const SLICE_FIELD_TEMPERATURE: u32 = 1u;
+const SLICE_FIELD_FUEL: u32 = 2u;
+const SLICE_FIELD_SOOT: u32 = 3u;
+const SLICE_FIELD_SPEED: u32 = 4u;
+const SLICE_AXIS_X: u32 = 0u;
+const SLICE_AXIS_Y: u32 = 1u;
+
+// Drawn with vs_main's full screen triangle instead of the ray march and tone mapping. Group 2
+// holds the field being sliced, the scalar or the velocity field. The slice keeps the proportions
+// of the box, centered over the background, with the grid's y (or z, for a slice along y) pointing
+// up. Texels are loaded as they are, without filtering, and colored by the fire ramp over
+// slice_range.
+@fragment
+fn fs_slice(@builtin(position) frag_clip_position: vec4<f32>) -> @location(0) vec4<f32> {
+    // Grid axes along the screen's x and y.
+    var screen_axes = vec2<u32>(0u, 1u);
+    switch params.slice_axis {
+        case SLICE_AXIS_X: {
+            screen_axes = vec2<u32>(2u, 1u);
+        }
+        case SLICE_AXIS_Y: {
+            screen_axes = vec2<u32>(0u, 2u);
+        }
+        default: {}
+    }
+
+    let box_size = params.box_max.xyz - params.box_min.xyz;
+    let slice_size = vec2<f32>(box_size[screen_axes.x], box_size[screen_axes.y]);
+    let pixels_per_unit = min(params.viewport.x / slice_size.x, params.viewport.y / slice_size.y);
+    let origin = 0.5 * (params.viewport - slice_size * pixels_per_unit);
+    let uv = (frag_clip_position.xy - origin) / (slice_size * pixels_per_unit);
+    if (any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0))) {
+        return vec4<f32>(params.background.rgb, 1.0);
+    }
+
+    let dimensions = vec3<i32>(textureDimensions(density_scalar_field));
+    var texel: vec3<i32>;
+    texel[screen_axes.x] = i32(uv.x * f32(dimensions[screen_axes.x]));
+    texel[screen_axes.y] = i32((1.0 - uv.y) * f32(dimensions[screen_axes.y]));
+    texel[params.slice_axis] = i32(params.slice_index);
+    // The grid may have shrunk since the slice index was set.
+    texel = clamp(texel, vec3<i32>(0), dimensions - vec3<i32>(1));
+
+    let voxel = textureLoad(density_scalar_field, texel, 0);
+    var value: f32;
+    switch params.slice_field {
+        case SLICE_FIELD_DENSITY: {
+            value = voxel.x;
+        }
+        case SLICE_FIELD_TEMPERATURE: {
+            value = voxel.y;
+        }
+        case SLICE_FIELD_FUEL: {
+            value = voxel.z;
+        }
+        case SLICE_FIELD_SOOT: {
+            value = voxel.w;
+        }
+        case SLICE_FIELD_SPEED, default: {
+            value = length(voxel.xyz);
+        }
+    }
+    let t = (value - params.slice_range.x) / (params.slice_range.y - params.slice_range.x);
+    return vec4<f32>(ramp_color(RAMP_FIRE, t), 1.0);
+}
+
+/* Bitmap font */
+
+// Side of one font pixel. Glyphs are 3x5 font pixels and advance by 4.
+const FONT_SCALE: f32 = 3.0;
+const GLYPH_ADVANCE: f32 = 4.0 * FONT_SCALE;
+const GLYPH_HEIGHT: f32 = 5.0 * FONT_SCALE;
+// Glyphs past the digits 0-9. S is drawn with the 5.
+const GLYPH_K: u32 = 10u;
+const GLYPH_F: u32 = 11u;
+const GLYPH_P: u32 = 12u;
+const GLYPH_M: u32 = 13u;
+const GLYPH_DOT: u32 = 14u;
+const GLYPH_SPACE: u32 = 15u;
+const GLYPH_S: u32 = 5u;
+
+// 3x5 bitmap of a glyph: the digits 0-9, then K, F, P, M, a dot and a space. Bit 14 is the top
+// left pixel and the rows follow from left to right, top to bottom.
+fn font_glyph(index: u32) -> u32 {
+    var glyphs = array<u32, 16>(
+        0x7b6fu, 0x2c97u, 0x73e7u, 0x73cfu, 0x5bc9u, 0x79cfu, 0x79efu, 0x7249u, 0x7befu, 0x7bcfu,
+        0x5badu, 0x79a4u, 0x7be4u, 0x5fedu, 0x0002u, 0x0000u,
+    );
+    return glyphs[index];
+}
+
+// Number of decimal digits of value, at least one and at most max_digits.
+fn digit_count(value: u32, max_digits: u32) -> u32 {
+    var digits = 1u;
+    var power = 10u;
+    while (value >= power && digits < max_digits) {
+        digits += 1u;
+        power *= 10u;
+    }
+    return digits;
+}
+
+// Digit at index, counted from the left, of value written with the given number of digits.
+fn digit_at(value: u32, digits: u32, index: u32) -> u32 {
+    var divisor = 1u;
+    for (var i = index + 1u; i < digits; i = i + 1u) {
+        divisor *= 10u;
+    }
+    return (value / divisor) % 10u;
+}
+
+// Index of the glyph that pixel p falls on, in a line of text starting at origin (its top left
+// corner), and the font pixel within it as x = column, y = row. The index is past any line when
+// p is outside of it or between glyphs.
+fn glyph_cell(p: vec2<f32>, origin: vec2<f32>) -> vec3<u32> {
+    let font_pixel = (p - origin) / FONT_SCALE;
+    if (font_pixel.x < 0.0 || font_pixel.y < 0.0 || font_pixel.y >= 5.0) { return vec3<u32>(0xffffffffu, 0u, 0u); }
+
+    let index = u32(font_pixel.x / 4.0);
+    let column = u32(font_pixel.x) - index * 4u;
+    if (column >= 3u) { return vec3<u32>(0xffffffffu, 0u, 0u); }
+    return vec3<u32>(index, column, u32(font_pixel.y));
+}
+
+// Whether the font pixel at the given column and row of glyph is lit.
+fn glyph_lit(glyph: u32, column: u32, row: u32) -> bool {
+    return ((font_glyph(glyph) >> (14u - row * 3u - column)) & 1u) == 1u;
+}
+
+/* Color legend */
+
+// Layout of the legend in pixels. The bar sits in the bottom right corner of the window with the
+// temperature labels to its left.
+const LEGEND_MARGIN: f32 = 16.0;
+const LEGEND_BAR_WIDTH: f32 = 16.0;
+const LEGEND_MAX_BAR_HEIGHT: f32 = 256.0;
+const LEGEND_LABEL_GAP: f32 = 6.0;
+// Labels are at most five digits followed by K.
+const LEGEND_MAX_DIGITS: u32 = 5u;
+const LEGEND_BORDER_COLOR: vec3<f32> = vec3<f32>(0.5);
+const LEGEND_LABEL_COLOR: vec3<f32> = vec3<f32>(1.0);
+
+// Bar as (left, top, right, bottom) in pixels, y down. Bottom is the ignition temperature and top
+// the hot band temperature.
+fn legend_bar() -> vec4<f32> {
+    let right = params.viewport.x - LEGEND_MARGIN;
+    let bottom = params.viewport.y - LEGEND_MARGIN - 0.5 * GLYPH_HEIGHT;
+    let height = clamp(params.viewport.y - 2.0 * LEGEND_MARGIN - GLYPH_HEIGHT, 0.0, LEGEND_MAX_BAR_HEIGHT);
+    return vec4<f32>(right - LEGEND_BAR_WIDTH, bottom - height, right, bottom);
+}
+
+// Whether pixel p is lit by the label "<temperature>K", drawn left of the bar at the height the
+// temperature has on it.
+fn legend_label(p: vec2<f32>, bar: vec4<f32>, temperature: f32) -> bool {
+    let range = max(params.hot_band_temperature - params.ignition_temperature, 1.0);
+    let y = bar.w - (temperature - params.ignition_temperature) / range * (bar.w - bar.y);
+
+    let value = min(u32(round(max(temperature, 0.0))), 99999u);
+    let digits = digit_count(value, LEGEND_MAX_DIGITS);
+    let glyph_count = digits + 1u;
+
+    let origin = vec2<f32>(
+        bar.x - LEGEND_LABEL_GAP - f32(glyph_count) * GLYPH_ADVANCE,
+        y - 0.5 * GLYPH_HEIGHT,
+    );
+    let cell = glyph_cell(p, origin);
+    if (cell.x >= glyph_count) { return false; }
+
+    var glyph = GLYPH_K;
+    if (cell.x < digits) {
+        glyph = digit_at(value, digits, cell.x);
+    }
+    return glyph_lit(glyph, cell.y, cell.z);
+}
+
+@vertex
+fn vs_legend(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    // Quad over the bar, its border and the widest labels, which overhang the bar by half a glyph.
+    let bar = legend_bar();
+    let min_pixel = vec2<f32>(
+        bar.x - LEGEND_LABEL_GAP - f32(LEGEND_MAX_DIGITS + 1u) * GLYPH_ADVANCE,
+        bar.y - 0.5 * GLYPH_HEIGHT,
+    );
+    let max_pixel = vec2<f32>(bar.z + 1.0, bar.w + 0.5 * GLYPH_HEIGHT);
+
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let pixel = mix(min_pixel, max_pixel, corners[vid]);
+    let ndc = vec2<f32>(pixel.x / params.viewport.x * 2.0 - 1.0, 1.0 - pixel.y / params.viewport.y * 2.0);
+    return vec4<f32>(ndc, 0.0, 1.0);
+}
+
+// Draws the emission colors over the ignition to hot band range, as they appear on screen, with
+// the band temperatures as labels.
+@fragment
+fn fs_legend(@builtin(position) frag_clip_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let p = frag_clip_position.xy;
+    let bar = legend_bar();
+
+    if (p.x >= bar.x - 1.0 && p.y >= bar.y - 1.0 && p.y <= bar.w + 1.0) {
+        if (p.x < bar.x || p.x > bar.z || p.y < bar.y || p.y > bar.w) {
+            return vec4<f32>(LEGEND_BORDER_COLOR, 1.0);
+        }
+        // Same mapping as the ray march: emission, exposure, then tone mapping.
+        let t = (bar.w - p.y) / max(bar.w - bar.y, 1.0);
+        let temperature = mix(params.ignition_temperature, params.hot_band_temperature, t);
+        return vec4<f32>(tone_map(flame_color(temperature) * params.exposure), 1.0);
+    }
+
+    if (legend_label(p, bar, params.ignition_temperature)
+        || legend_label(p, bar, params.cool_band_temperature)
+        || legend_label(p, bar, params.hot_band_temperature)) {
+        return vec4<f32>(LEGEND_LABEL_COLOR, 1.0);
+    }
+    discard;
+}
+
+/* Frame stats overlay */
+
+// The stats sit in the top left corner of the window as "<fps> FPS <frame time> MS", with the
+// frame time in tenths of a millisecond.
+const STATS_MARGIN: f32 = 16.0;
+// Longest text: "9999 FPS 9999.9 MS".
+const STATS_MAX_DIGITS: u32 = 4u;
+const STATS_MAX_GLYPHS: u32 = 18u;
+const STATS_BACKGROUND_COLOR: vec3<f32> = vec3<f32>(0.0);
+const STATS_TEXT_COLOR: vec3<f32> = vec3<f32>(1.0);
+
+// Glyph at index of the stats text, or a space past its end.
+fn stats_glyph(index: u32) -> u32 {
+    let fps = min(u32(round(params.frame_stats.x)), 9999u);
+    let fps_digits = digit_count(fps, STATS_MAX_DIGITS);
+    let tenths = min(u32(round(params.frame_stats.y * 10.0)), 99999u);
+    let milliseconds = tenths / 10u;
+    let milliseconds_digits = digit_count(milliseconds, STATS_MAX_DIGITS);
+
+    var i = index;
+    if (i < fps_digits) { return digit_at(fps, fps_digits, i); }
+    i -= fps_digits;
+    var fps_suffix = array<u32, 5>(GLYPH_SPACE, GLYPH_F, GLYPH_P, GLYPH_S, GLYPH_SPACE);
+    if (i < 5u) { return fps_suffix[i]; }
+    i -= 5u;
+    if (i < milliseconds_digits) { return digit_at(milliseconds, milliseconds_digits, i); }
+    i -= milliseconds_digits;
+    var milliseconds_suffix = array<u32, 5>(GLYPH_DOT, tenths % 10u, GLYPH_SPACE, GLYPH_M, GLYPH_S);
+    if (i < 5u) { return milliseconds_suffix[i]; }
+    return GLYPH_SPACE;
+}
+
+@vertex
+fn vs_stats(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    // Quad over the longest text, with a glyph's advance of background around it.
+    let min_pixel = vec2<f32>(STATS_MARGIN - FONT_SCALE);
+    let max_pixel = vec2<f32>(
+        STATS_MARGIN + f32(STATS_MAX_GLYPHS) * GLYPH_ADVANCE,
+        STATS_MARGIN + GLYPH_HEIGHT + FONT_SCALE,
+    );
+
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let pixel = mix(min_pixel, max_pixel, corners[vid]);
+    let ndc = vec2<f32>(pixel.x / params.viewport.x * 2.0 - 1.0, 1.0 - pixel.y / params.viewport.y * 2.0);
+    return vec4<f32>(ndc, 0.0, 1.0);
+}
+
+// Draws the frames per second and the average frame time on a dark background, so they read
+// over bright flames.
+@fragment
+fn fs_stats(@builtin(position) frag_clip_position: vec4<f32>) -> @location(0) vec4<f32> {
+    let cell = glyph_cell(frag_clip_position.xy, vec2<f32>(STATS_MARGIN));
+    if (cell.x < STATS_MAX_GLYPHS && glyph_lit(stats_glyph(cell.x), cell.y, cell.z)) {
+        return vec4<f32>(STATS_TEXT_COLOR, 1.0);
+    }
+    return vec4<f32>(STATS_BACKGROUND_COLOR, 1.0);
+}
@@ -0,0 +1,255 @@
+use std::str::FromStr;
+
+use crate::config::{TURBULENCE_SCALE, TURBULENCE_STRENGTH};
+use crate::texture::Texture;
+
+/// Maximum number of fuel sources. Must match MAX_SOURCES in add_source.wgsl.
+pub const MAX_SOURCES: usize = 4;
+
+/// Sphere that injects fuel while the source is on.
+#[derive(Debug, Copy, Clone)]
+pub struct Source {
+    /// Center as a fraction of the grid along each axis.
+    pub center: [f32; 3],
+    /// Radius as a fraction of the grid width.
+    pub radius: f32,
+}
+
+impl Source {
+    /// xyz = center, w = radius, as the shader reads it.
+    pub fn to_uniform(self) -> [f32; 4] {
+        [self.center[0], self.center[1], self.center[2], self.radius]
+    }
+}
+
+/// Profile of the fuel a source injects, from its center to its radius. Must match
+/// source_weight in add_source.wgsl.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SourceFalloff {
+    /// Full strength all the way to the edge, so the plume starts as a sharp-edged column.
+    Hard,
+    /// Falls off linearly to zero at the edge.
+    Linear,
+    /// Smoothstep from the center to zero at the edge. Softer onset than Linear.
+    Smoothstep,
+    /// Gaussian with a standard deviation of 0.35 radii, cut off at the edge. The default.
+    Gaussian,
+}
+
+impl SourceFalloff {
+    /// Value of the source_falloff field of SimParams.
+    pub fn to_uniform(self) -> u32 {
+        self as u32
+    }
+
+    /// Weight at the given distance from the center, as a fraction of the radius. Zero outside.
+    pub fn weight(self, distance: f32) -> f32 {
+        if distance > 1.0 {
+            return 0.0;
+        }
+        match self {
+            SourceFalloff::Hard => 1.0,
+            SourceFalloff::Linear => 1.0 - distance,
+            SourceFalloff::Smoothstep => 1.0 - distance * distance * (3.0 - 2.0 * distance),
+            SourceFalloff::Gaussian => (-distance * distance / (2.0 * 0.35 * 0.35)).exp(),
+        }
+    }
+}
+
+/// Polyline the first source of a scene follows. Points are fractions of the grid along each
+/// axis, like Source::center.
+#[derive(Debug, Copy, Clone)]
+pub struct SourcePath {
+    pub points: &'static [[f32; 3]],
+    /// Distance travelled along the path per simulated second, in grid fractions.
+    pub speed: f32,
+    /// Whether the path closes back to its first point and repeats. Otherwise the source stops at
+    /// the last point.
+    pub looping: bool,
+}
+
+impl SourcePath {
+    /// Position after the given simulated time, moving at constant speed from the first point.
+    pub fn position_at(&self, time: f32) -> [f32; 3] {
+        let segment_count = if self.looping { self.points.len() } else { self.points.len() - 1 };
+        let segment = |i: usize| (self.points[i], self.points[(i + 1) % self.points.len()]);
+        let total_length: f32 = (0..segment_count).map(|i| distance(segment(i).0, segment(i).1)).sum();
+        if total_length <= 0.0 {
+            return self.points[0];
+        }
+
+        let mut travelled = (time * self.speed).max(0.0);
+        travelled = if self.looping { travelled % total_length } else { travelled.min(total_length) };
+        for i in 0..segment_count {
+            let (start, end) = segment(i);
+            let length = distance(start, end);
+            if travelled <= length && length > 0.0 {
+                let t = travelled / length;
+                return std::array::from_fn(|axis| start[axis] + (end[axis] - start[axis]) * t);
+            }
+            travelled -= length;
+        }
+        segment(segment_count - 1).1
+    }
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2) + (b[2] - a[2]).powi(2)).sqrt()
+}
+
+/// Demo scenes. Each one sets the sources, the turbulence and the initial fields.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScenePreset {
+    /// One turbulent source near the floor. The default scene.
+    Campfire,
+    /// Same source with much stronger, finer curl-noise turbulence.
+    Turbulent,
+    /// Small source with no turbulence, giving a clean rising plume.
+    HotPlume,
+    /// Two sources side by side whose plumes are drawn together as they rise.
+    TwinPlumes,
+    /// A sphere of smoke with no sources or forces. It should stay put, so any drift or blurring
+    /// is numerical.
+    StillBlob,
+    /// A small source carried around a square near the floor, leaving a trail of flame.
+    Torch,
+}
+
+impl ScenePreset {
+    /// Presets in the order of the number keys that load them.
+    pub const ALL: [ScenePreset; 6] = [
+        ScenePreset::Campfire,
+        ScenePreset::Turbulent,
+        ScenePreset::HotPlume,
+        ScenePreset::TwinPlumes,
+        ScenePreset::StillBlob,
+        ScenePreset::Torch,
+    ];
+
+    pub fn sources(self) -> &'static [Source] {
+        match self {
+            ScenePreset::Campfire | ScenePreset::Turbulent => &[
+                Source { center: [0.5, 0.25, 0.5], radius: 0.1875 },
+            ],
+            ScenePreset::HotPlume => &[
+                Source { center: [0.5, 0.1, 0.5], radius: 0.1 },
+            ],
+            ScenePreset::TwinPlumes => &[
+                Source { center: [0.3, 0.15, 0.5], radius: 0.12 },
+                Source { center: [0.7, 0.15, 0.5], radius: 0.12 },
+            ],
+            ScenePreset::StillBlob => &[],
+            ScenePreset::Torch => &[
+                Source { center: [0.3, 0.15, 0.3], radius: 0.08 },
+            ],
+        }
+    }
+
+    /// Path the first source follows, or None if the sources stay put.
+    pub fn source_path(self) -> Option<SourcePath> {
+        match self {
+            ScenePreset::Torch => Some(SourcePath {
+                points: &[[0.3, 0.15, 0.3], [0.7, 0.15, 0.3], [0.7, 0.15, 0.7], [0.3, 0.15, 0.7]],
+                speed: 0.2,
+                looping: true,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Curl-noise turbulence (strength, scale). See SimParams.
+    pub fn turbulence(self) -> (f32, f32) {
+        match self {
+            ScenePreset::Campfire | ScenePreset::TwinPlumes | ScenePreset::Torch => (TURBULENCE_STRENGTH, TURBULENCE_SCALE),
+            ScenePreset::Turbulent => (TURBULENCE_STRENGTH * 3.0, TURBULENCE_SCALE * 2.0),
+            ScenePreset::HotPlume | ScenePreset::StillBlob => (0.0, TURBULENCE_SCALE),
+        }
+    }
+
+    /// Whether the sources start on.
+    pub fn injects(self) -> bool {
+        !self.sources().is_empty()
+    }
+
+    /// Initial scalar field as raw RGBA16F (see Texture::write_from_raw_f16), or None to start
+    /// empty.
+    pub fn initial_scalar_field(self, grid_dimensions: wgpu::Extent3d) -> Option<Vec<u8>> {
+        match self {
+            ScenePreset::StillBlob => Some(smoke_sphere(grid_dimensions, [0.5, 0.5, 0.5], 0.2)),
+            _ => None,
+        }
+    }
+}
+
+/// Sphere of smoke with a smooth edge. Temperature, fuel and soot are zero, so it doesn't rise.
+fn smoke_sphere(grid_dimensions: wgpu::Extent3d, center: [f32; 3], radius: f32) -> Vec<u8> {
+    let (width, height, depth) = (
+        grid_dimensions.width,
+        grid_dimensions.height,
+        grid_dimensions.depth_or_array_layers,
+    );
+    let center = [center[0] * width as f32, center[1] * height as f32, center[2] * depth as f32];
+    let radius = radius * width as f32;
+    let zero = half::f16::ZERO.to_bits().to_le_bytes();
+
+    let mut data = Vec::with_capacity((width * height * depth) as usize * 8);
+    for z in 0..depth {
+        for y in 0..height {
+            for x in 0..width {
+                let d = [
+                    x as f32 + 0.5 - center[0],
+                    y as f32 + 0.5 - center[1],
+                    z as f32 + 0.5 - center[2],
+                ];
+                let distance = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+                // Fades out over the last two voxels so the edge isn't aliased.
+                let smoke = ((radius - distance) / 2.0).clamp(0.0, 1.0);
+
+                data.extend_from_slice(&half::f16::from_f32(smoke).to_bits().to_le_bytes());
+                data.extend_from_slice(&zero);
+                data.extend_from_slice(&zero);
+                data.extend_from_slice(&zero);
+            }
+        }
+    }
+    data
+}
+
+/// Smoke the simulation starts from instead of the scene's initial scalar field, given with
+/// --initial. Kept through resets and grid changes, and dropped by the next scene load.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InitialDensity {
+    /// Thin hollow sphere of smoke around the center of the grid, with no temperature, fuel or
+    /// soot. Advected through a swirling initial velocity (e.g. --initial-velocity tornado), the
+    /// hollow filling in shows the numerical diffusion of the advection scheme.
+    SphereShell,
+}
+
+impl InitialDensity {
+    /// Writes the initial smoke into a scalar field texture, overwriting all of it.
+    pub fn write(self, queue: &wgpu::Queue, texture: &Texture) {
+        let size = texture.texture.size();
+        let grid = [size.width as f32, size.height as f32, size.depth_or_array_layers as f32];
+        match self {
+            InitialDensity::SphereShell => texture.write_density_sphere_shell(
+                queue,
+                grid.map(|n| n * 0.5),
+                0.2 * grid[0],
+                0.25 * grid[0],
+                1.0,
+            ),
+        }
+    }
+}
+
+impl FromStr for InitialDensity {
+    type Err = anyhow::Error;
+
+    /// Parses `shell`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "shell" => Ok(InitialDensity::SphereShell),
+            _ => anyhow::bail!("Unknown initial density: {s}"),
+        }
+    }
+}
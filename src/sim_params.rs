@@ -0,0 +1,319 @@
+use std::time::Duration;
+use crate::config::{
+    AMBIENT_TEMPERATURE, BUOYANCY_COEFFICIENT, COOLING_RATE, CURSOR_SOURCE_RADIUS, DRAIN_CENTER, DRAIN_RADIUS,
+    DRAIN_STRENGTH, GRAVITY, MAX_DENSITY, MAX_SPEED, SCENE_PRESET, SOOT_YIELD, SOURCE_DENSITY, SOURCE_FALLOFF, SOURCE_FUEL,
+    VISCOSITY, VORTICITY_STRENGTH,
+};
+use crate::emission::EmissionBands;
+use crate::scene::{ScenePreset, SourceFalloff, MAX_SOURCES};
+
+/// Struct to contain read-only params for the simulation's compute pipelines.
+/// Should be passed to the shaders via a uniform buffer, at bind group 0.
+///
+/// Anything only the renderer reads belongs in RenderParams instead, so render tweaks don't
+/// touch this buffer.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SimParams {
+    dt: f32,
+    /// Number of voxels along the X axis of the simulation grid.
+    width: u32,
+    /// Number of voxels along the Y axis of the simulation grid.
+    height: u32,
+    /// Number of voxels along the Z axis of the simulation grid.
+    depth: u32,
+    elapsed_time: f32,
+    /// Magnitude of the curl-noise force injected near the source, in cells per second squared.
+    /// Zero disables turbulence injection.
+    turbulence_strength: f32,
+    /// Spatial frequency of the curl-noise (smaller = larger eddies).
+    turbulence_scale: f32,
+    /// Rate (per second) at which the drain removes scalars and damps velocity at its center.
+    drain_strength: f32,
+    /// Drain sphere. xyz = center as a fraction of the grid, w = radius in reference voxel lengths
+    /// (see cell_size). A radius of zero disables the drain.
+    drain: [f32; 4],
+    /// Temperature (K) above which fuel burns into soot.
+    ignition_temperature: f32,
+    /// Soot produced per unit of burning fuel per second. Zero gives a clean flame.
+    soot_yield: f32,
+    /// Number of entries of sources the add source pass evaluates.
+    source_count: u32,
+    /// Units of fuel a source injects at its peak.
+    source_fuel: f32,
+    /// World-space size of a cell along each axis, in units of GRID_VOXEL_SIDE_LENGTH (the
+    /// reference voxel length). Velocities are in reference voxel lengths per second, which is
+    /// cells per second when the voxels are cubes of that size, and the shaders scale their
+    /// backtraces and finite differences by this per axis.
+    cell_size: [f32; 3],
+    /// SourceFalloff of the sources, as SourceFalloff::to_uniform.
+    source_falloff: u32,
+    /// Fuel source spheres. xyz = center as a fraction of the grid, w = radius as a fraction of
+    /// the grid width.
+    sources: [[f32; 4]; MAX_SOURCES],
+    /// Upward acceleration per kelvin of temperature, in reference voxel lengths per second
+    /// squared. Hot gas rises.
+    buoyancy_coefficient: f32,
+    /// Downward acceleration per unit of smoke and soot density, in reference voxel lengths per
+    /// second squared. Dense smoke sinks. A uniform pull would only be cancelled by the pressure
+    /// projection, so gravity is always weighted by density.
+    gravity: f32,
+    /// Scale of the vorticity confinement force. Zero turns the confinement off.
+    vorticity_strength: f32,
+    /// Whether the add source pass evaluates sources, as 0 or 1. Simulation::set_pending_input
+    /// turns the scene's sources on and off through it, while the cursor source is always
+    /// evaluated.
+    scene_sources_enabled: u32,
+    /// Source sphere under the mouse cursor, in the same form as sources. A radius of zero means
+    /// there is none.
+    cursor_source: [f32; 4],
+    /// Kinematic viscosity of the velocity diffusion, in reference voxel lengths squared per
+    /// second. Zero skips the diffusion passes.
+    viscosity: f32,
+    /// Temperature (K) of the surrounding air. Gas cools towards it, never below it, and only
+    /// the excess over it gives buoyancy.
+    ambient_temperature: f32,
+    /// Rate (K per second) of radiative cooling at the burn temperature. Cooling scales with the
+    /// fourth power of temperature (Stefan-Boltzmann), so hot gas cools much faster than warm.
+    cooling_rate: f32,
+    /// Speed, in reference voxel lengths per second, the forces pass clamps the velocity to.
+    max_speed: f32,
+    /// Density the smoke pass clamps smoke and soot to.
+    max_density: f32,
+    /// Units of smoke a source injects at its peak, on top of its fuel. Zero leaves all of the
+    /// smoke to the burning fuel.
+    source_density: f32,
+    _pad0: [f32; 2],
+}
+
+impl SimParams {
+    pub const SOURCE_FUEL_RANGE: [f32; 2] = [0.25, 6.0];
+    pub const SOURCE_DENSITY_RANGE: [f32; 2] = [0.0, 4.0];
+    /// Radii a source can be scaled to, as a fraction of the grid width.
+    pub const SOURCE_RADIUS_RANGE: [f32; 2] = [0.02, 0.5];
+
+    /// Sources and turbulence start from the configured scene preset.
+    pub fn new(grid_dimensions: wgpu::Extent3d, bands: EmissionBands) -> Self {
+        let mut params = Self {
+            dt: Duration::new(0, 0).as_secs_f32(),
+            width: grid_dimensions.width,
+            height: grid_dimensions.height,
+            depth: grid_dimensions.depth_or_array_layers,
+            elapsed_time: 0.0,
+            turbulence_strength: 0.0,
+            turbulence_scale: 0.0,
+            drain_strength: DRAIN_STRENGTH,
+            drain: [DRAIN_CENTER[0], DRAIN_CENTER[1], DRAIN_CENTER[2], DRAIN_RADIUS],
+            ignition_temperature: bands.ignition_temperature,
+            soot_yield: SOOT_YIELD,
+            source_count: 0,
+            source_fuel: SOURCE_FUEL,
+            cell_size: [1.0; 3],
+            source_falloff: SOURCE_FALLOFF.to_uniform(),
+            sources: [[0.0; 4]; MAX_SOURCES],
+            buoyancy_coefficient: BUOYANCY_COEFFICIENT,
+            gravity: GRAVITY,
+            vorticity_strength: VORTICITY_STRENGTH,
+            scene_sources_enabled: 0,
+            cursor_source: [0.0; 4],
+            viscosity: VISCOSITY,
+            ambient_temperature: AMBIENT_TEMPERATURE,
+            cooling_rate: COOLING_RATE,
+            max_speed: MAX_SPEED,
+            max_density: MAX_DENSITY,
+            source_density: SOURCE_DENSITY,
+            _pad0: [0.0; 2],
+        };
+        params.update_scene(SCENE_PRESET);
+        params
+    }
+
+    /// Returns true if the drain has a non-zero size and should be applied.
+    pub fn is_drain_enabled(&self) -> bool {
+        self.drain[3] > 0.0 && self.drain_strength > 0.0
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    pub fn update_dt(&mut self, dt: Duration, ) {
+        self.dt = dt.as_secs_f32();
+    }
+
+    pub fn update_elapsed_time(&mut self, elapsed: f32) {
+        self.elapsed_time = elapsed;
+    }
+
+    /// Sets the sources and turbulence of a scene preset.
+    pub fn update_scene(&mut self, preset: ScenePreset) {
+        let sources = preset.sources();
+        assert!(sources.len() <= MAX_SOURCES, "{preset:?} has more than {MAX_SOURCES} sources");
+        self.sources = [[0.0; 4]; MAX_SOURCES];
+        for (slot, source) in self.sources.iter_mut().zip(sources) {
+            *slot = source.to_uniform();
+        }
+        self.source_count = sources.len() as u32;
+        (self.turbulence_strength, self.turbulence_scale) = preset.turbulence();
+    }
+
+    /// Moves one of the scene's sources. The center is a fraction of the grid, like Source::center.
+    pub fn update_source_center(&mut self, index: usize, center: [f32; 3]) {
+        self.sources[index][..3].copy_from_slice(&center);
+    }
+
+    /// Returns the scene's sources, xyz = center and w = radius as in Source.
+    pub fn sources(&self) -> &[[f32; 4]] {
+        &self.sources[..self.source_count as usize]
+    }
+
+    /// Moves every scene source by the given offset, a fraction of the grid like Source::center.
+    /// Centers are kept inside the grid.
+    pub fn offset_sources(&mut self, offset: [f32; 3]) {
+        for source in &mut self.sources[..self.source_count as usize] {
+            for axis in 0..3 {
+                source[axis] = (source[axis] + offset[axis]).clamp(0.0, 1.0);
+            }
+        }
+    }
+
+    /// Scales the radius of every scene source by the given factor, clamped to
+    /// SOURCE_RADIUS_RANGE.
+    pub fn scale_source_radii(&mut self, factor: f32) {
+        for source in &mut self.sources[..self.source_count as usize] {
+            source[3] = (source[3] * factor).clamp(Self::SOURCE_RADIUS_RANGE[0], Self::SOURCE_RADIUS_RANGE[1]);
+        }
+    }
+
+    pub fn update_scene_sources_enabled(&mut self, enabled: bool) {
+        self.scene_sources_enabled = enabled as u32;
+    }
+
+    /// Returns the center of the cursor source, if there is one.
+    pub fn cursor_source(&self) -> Option<[f32; 3]> {
+        (self.cursor_source[3] > 0.0).then(|| [self.cursor_source[0], self.cursor_source[1], self.cursor_source[2]])
+    }
+
+    /// Places the cursor source at a center given as a fraction of the grid, like Source::center,
+    /// with a radius of CURSOR_SOURCE_RADIUS. None removes it.
+    pub fn update_cursor_source(&mut self, center: Option<[f32; 3]>) {
+        self.cursor_source = match center {
+            Some(center) => [center[0], center[1], center[2], CURSOR_SOURCE_RADIUS],
+            None => [0.0; 4],
+        };
+    }
+
+    pub fn source_fuel(&self) -> f32 {
+        self.source_fuel
+    }
+
+    /// Clamped to SOURCE_FUEL_RANGE.
+    pub fn update_source_fuel(&mut self, source_fuel: f32) {
+        self.source_fuel = source_fuel.clamp(Self::SOURCE_FUEL_RANGE[0], Self::SOURCE_FUEL_RANGE[1]);
+    }
+
+    pub fn source_density(&self) -> f32 {
+        self.source_density
+    }
+
+    /// Clamped to SOURCE_DENSITY_RANGE.
+    pub fn update_source_density(&mut self, source_density: f32) {
+        self.source_density =
+            source_density.clamp(Self::SOURCE_DENSITY_RANGE[0], Self::SOURCE_DENSITY_RANGE[1]);
+    }
+
+    pub fn buoyancy_coefficient(&self) -> f32 {
+        self.buoyancy_coefficient
+    }
+
+    /// Clamped to at least zero.
+    pub fn update_buoyancy_coefficient(&mut self, buoyancy_coefficient: f32) {
+        self.buoyancy_coefficient = buoyancy_coefficient.max(0.0);
+    }
+
+    pub fn gravity(&self) -> f32 {
+        self.gravity
+    }
+
+    /// Clamped to at least zero.
+    pub fn update_gravity(&mut self, gravity: f32) {
+        self.gravity = gravity.max(0.0);
+    }
+
+    pub fn vorticity_strength(&self) -> f32 {
+        self.vorticity_strength
+    }
+
+    /// Clamped to at least zero.
+    pub fn update_vorticity_strength(&mut self, vorticity_strength: f32) {
+        self.vorticity_strength = vorticity_strength.max(0.0);
+    }
+
+    pub fn viscosity(&self) -> f32 {
+        self.viscosity
+    }
+
+    /// Clamped to at least zero.
+    pub fn update_viscosity(&mut self, viscosity: f32) {
+        self.viscosity = viscosity.max(0.0);
+    }
+
+    pub fn ambient_temperature(&self) -> f32 {
+        self.ambient_temperature
+    }
+
+    /// Clamped to at least zero.
+    pub fn update_ambient_temperature(&mut self, ambient_temperature: f32) {
+        self.ambient_temperature = ambient_temperature.max(0.0);
+    }
+
+    pub fn cooling_rate(&self) -> f32 {
+        self.cooling_rate
+    }
+
+    /// Clamped to at least zero.
+    pub fn update_cooling_rate(&mut self, cooling_rate: f32) {
+        self.cooling_rate = cooling_rate.max(0.0);
+    }
+
+    pub fn max_speed(&self) -> f32 {
+        self.max_speed
+    }
+
+    /// Clamped to above zero.
+    pub fn update_max_speed(&mut self, max_speed: f32) {
+        self.max_speed = max_speed.max(f32::MIN_POSITIVE);
+    }
+
+    pub fn max_density(&self) -> f32 {
+        self.max_density
+    }
+
+    /// Clamped to above zero.
+    pub fn update_max_density(&mut self, max_density: f32) {
+        self.max_density = max_density.max(f32::MIN_POSITIVE);
+    }
+
+    pub fn update_source_falloff(&mut self, falloff: SourceFalloff) {
+        self.source_falloff = falloff.to_uniform();
+    }
+
+    pub fn update_grid_dimensions(&mut self, grid_dimensions: wgpu::Extent3d) {
+        self.width = grid_dimensions.width;
+        self.height = grid_dimensions.height;
+        self.depth = grid_dimensions.depth_or_array_layers;
+    }
+
+    pub fn cell_size(&self) -> [f32; 3] {
+        self.cell_size
+    }
+
+    pub fn update_cell_size(&mut self, cell_size: [f32; 3]) {
+        self.cell_size = cell_size;
+    }
+
+    /// The renderer reads the same bands from RenderParams, so keep the two in sync.
+    pub fn update_emission_bands(&mut self, bands: EmissionBands) {
+        self.ignition_temperature = bands.ignition_temperature;
+    }
+}
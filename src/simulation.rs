@@ -0,0 +1,3051 @@
+use wgpu::{Device, Queue};
+use wgpu::util::DeviceExt;
+
+use crate::texture::Texture;
+use crate::sim_params::SimParams;
+use crate::compute_step::{ComputeStep, DispatchTextures, WorkgroupSize};
+use crate::field::FieldDescriptor;
+use crate::config::{
+    ADVECTION_SCHEME, DIFFUSION_ITERATIONS, DIVERGENCE_FORMAT, EMISSION_PRESET, FIELD_MEMORY_BUDGET_MIB,
+    GRID_VOXEL_SIDE_LENGTH, INITIAL_VELOCITY_FIELD, INITIAL_VELOCITY_SPEED, OBSTACLE, PRESSURE_FORMAT, PRESSURE_ITERATIONS, SCENE_PRESET,
+};
+use crate::advection_scheme::AdvectionScheme;
+use crate::ping_pong::PingPong;
+use crate::scene::{InitialDensity, ScenePreset, SourcePath};
+use crate::initial_velocity::InitialVelocityField;
+use crate::obstacle::Obstacle;
+use crate::gpu_profiler::GpuProfiler;
+
+/// Textures Simulation::new creates for each field on a device with the given features, for
+/// estimating the memory it needs.
+fn field_textures(device_features: wgpu::Features) -> [(FieldDescriptor, u64); 6] {
+    [
+        // Ping pong, source and the two BFECC and MacCormack scratch textures.
+        (FieldDescriptor::SCALARS, 5),
+        // Ping pong and the undiffused velocity the viscosity solve starts from.
+        (FieldDescriptor::VELOCITY, 3),
+        (select_field_format(FieldDescriptor::PRESSURE, PRESSURE_FORMAT, device_features), 2),
+        (select_field_format(FieldDescriptor::DIVERGENCE, DIVERGENCE_FORMAT, device_features), 1),
+        (FieldDescriptor::CURL, 1),
+        (FieldDescriptor::OBSTACLES, 1),
+    ]
+}
+
+/// The field in the given format if the WebGPU guarantees for a device with the given features
+/// cover everything the simulation does with it, otherwise the field as it is.
+fn select_field_format(field: FieldDescriptor, format: wgpu::TextureFormat, device_features: wgpu::Features) -> FieldDescriptor {
+    let selected = field.with_format(format);
+    if selected.missing_format_features(format.guaranteed_format_features(device_features)).is_empty() {
+        selected
+    } else {
+        field
+    }
+}
+
+/// Starts every file written by Simulation::save_snapshot.
+const SNAPSHOT_MAGIC: &[u8; 8] = b"FIRESNAP";
+/// Bumped whenever the snapshot layout changes. SimParams changing size is caught separately.
+const SNAPSHOT_VERSION: u32 = 2;
+
+/// GPU resources and compute passes of the fluid simulation.
+///
+/// Knows nothing about windows or surfaces, so it can be stepped headless (e.g. by the
+/// benchmark) as well as by State, which renders the fields it produces.
+pub struct Simulation {
+    grid_dimensions: wgpu::Extent3d,
+    /// World space bounds of the grid.
+    box_min: [f32; 3],
+    box_max: [f32; 3],
+    /// Size the compute pipelines were built for.
+    workgroup_size: WorkgroupSize,
+    /// Number of workgroups dispatched along each axis to cover the grid.
+    workgroups: (u32, u32, u32),
+    sim_params: SimParams,
+    sim_params_bind_group: wgpu::BindGroup,
+    sim_params_buffer: wgpu::Buffer,
+    add_source_pipeline: wgpu::ComputePipeline,
+    remove_source_pipeline: wgpu::ComputePipeline,
+    add_source_bind_group_layout: wgpu::BindGroupLayout,
+    add_source_bind_group: wgpu::BindGroup,
+    scalar_field_ping_pong: PingPong,
+    velocity_vector_field_ping_pong: PingPong,
+    /// Injected scalars for the current step. Kept out of the ping pong on purpose: the add source
+    /// pass writes here, and scalar advection samples it and adds it to what it writes into the
+    /// write side of the scalar ping pong. The swap that follows makes that the read side, so the
+    /// injection always lands in the texture the next pass reads, whatever the ping pong order.
+    scalar_source_texture: Texture,
+    advect_scalars_compute_step: ComputeStep,
+    advection_scheme: AdvectionScheme,
+    advect_field_forward_compute_step: ComputeStep,
+    advect_field_backward_compute_step: ComputeStep,
+    bfecc_correct_compute_step: ComputeStep,
+    advect_scalars_bfecc_compute_step: ComputeStep,
+    advect_scalars_maccormack_compute_step: ComputeStep,
+    bfecc_scratch_texture_a: Texture,
+    bfecc_scratch_texture_b: Texture,
+    advect_velocity_compute_step: ComputeStep,
+    add_forces_to_velocity_compute_step: ComputeStep,
+    compute_divergence_bind_group_layout: wgpu::BindGroupLayout,
+    compute_divergence_pipeline: wgpu::ComputePipeline,
+    divergence_texture: Texture,
+    pressure_ping_pong: PingPong,
+    /// PRESSURE and DIVERGENCE in PRESSURE_FORMAT and DIVERGENCE_FORMAT, or in their own format
+    /// where the device can't filter those (see select_field_format).
+    pressure_field: FieldDescriptor,
+    divergence_field: FieldDescriptor,
+    compute_pressure_compute_step: ComputeStep,
+    subtract_pressure_gradient_compute_step: ComputeStep,
+    compute_curl_bind_group_layout: wgpu::BindGroupLayout,
+    compute_curl_pipeline: wgpu::ComputePipeline,
+    curl_texture: Texture,
+    add_vorticity_confinement_force_compute_step: ComputeStep,
+    compute_temperature_compute_step: ComputeStep,
+    compute_smoke_compute_step: ComputeStep,
+    apply_drain_scalars_compute_step: ComputeStep,
+    apply_drain_velocity_compute_step: ComputeStep,
+    apply_obstacles_compute_step: ComputeStep,
+    /// Solid voxels, which advection and the projection keep the fluid out of.
+    obstacle_texture: Texture,
+    /// Obstacle written into obstacle_texture. Kept through scene loads and resets.
+    obstacle: Obstacle,
+    /// Whether the pressure projection (divergence, Jacobi iterations and gradient subtraction)
+    /// runs. Only worth turning off to measure what it costs.
+    projection_enabled: bool,
+    /// Jacobi iterations of the pressure solve per step. More iterations leave less divergence
+    /// behind, at the cost of two texture reads per neighbour per iteration.
+    pressure_iterations: u32,
+    diffuse_velocity_compute_step: ComputeStep,
+    /// Copy of the velocity before diffusion, the right hand side of the viscosity solve.
+    undiffused_velocity_texture: Texture,
+    /// Jacobi iterations of the viscosity solve per step, when the viscosity is above zero.
+    diffusion_iterations: u32,
+    /// Whether the scene's sources inject fuel. Together with the cursor source (see
+    /// SimParams::update_cursor_source) this is the only injection path, and both are sampled once
+    /// per step by encode_step:
+    ///
+    /// - Off, with no cursor source: the source texture stays zero and nothing is injected.
+    /// - On: the add source pass writes the source at the start of the step, advection reads it,
+    ///   and the remove source pass clears it at the end of the same step.
+    ///
+    /// So the source texture is always zero between steps, and a change made between two steps
+    /// takes effect on the next one, never halfway through.
+    pending_input: bool,
+    /// Velocity field the simulation restarts from on every scene load.
+    initial_velocity: InitialVelocityField,
+    /// Speed the initial velocity field is generated at, in reference voxel lengths per second.
+    initial_velocity_speed: f32,
+    /// Scene loaded last, which reset starts over.
+    scene: ScenePreset,
+    /// Scalar field volume the simulation was created with, kept for reset until another scene
+    /// is loaded.
+    density_volume: Option<Vec<u8>>,
+    /// Smoke the simulation starts from instead of the scene's initial scalar field, kept for reset
+    /// until another scene is loaded.
+    initial_density: Option<InitialDensity>,
+    /// Path the first source follows through the scene, sampled at sim_time every step.
+    source_path: Option<SourcePath>,
+    /// Number of simulation steps that have been run.
+    step_count: u64,
+    /// Total simulated time in seconds. Advanced by the timestep of every simulation step, so it is
+    /// independent of wall-clock time.
+    sim_time: f32,
+}
+
+impl Simulation {
+    pub const PRESSURE_ITERATIONS_RANGE: [u32; 2] = [1, 200];
+    pub const DIFFUSION_ITERATIONS_RANGE: [u32; 2] = [1, 200];
+
+    /// Creates the simulation fields for a grid of the given dimensions, with compute pipelines
+    /// built for the given workgroup size. The scalar field starts from density_volume if given
+    /// (see Texture::write_from_raw_f16), otherwise from the configured scene preset.
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        grid_dimensions: wgpu::Extent3d,
+        workgroup_size: WorkgroupSize,
+        density_volume: Option<&[u8]>,
+    ) -> anyhow::Result<Self> {
+        workgroup_size.validate(&device.limits())?;
+        Self::validate_grid(grid_dimensions, &device.limits(), device.features())?;
+        let workgroups = workgroup_size.workgroups_for(grid_dimensions);
+
+        let [.., (pressure_field, _), (divergence_field, _), _, _] = field_textures(device.features());
+        for (field, format) in [(pressure_field, PRESSURE_FORMAT), (divergence_field, DIVERGENCE_FORMAT)] {
+            if field.format != format {
+                log::warn!(
+                    "{} can't use {format:?} on this device (it needs FLOAT32_FILTERABLE to be filtered), \
+                     falling back to {:?}",
+                    field.label,
+                    field.format,
+                );
+            }
+        }
+
+        let box_min = [0.0, 0.0, 0.0];
+        let box_max = [
+            grid_dimensions.width as f32 * GRID_VOXEL_SIDE_LENGTH,
+            grid_dimensions.height as f32 * GRID_VOXEL_SIDE_LENGTH,
+            grid_dimensions.depth_or_array_layers as f32 * GRID_VOXEL_SIDE_LENGTH,
+        ];
+
+        // Cubic voxels of GRID_VOXEL_SIDE_LENGTH until set_box_size says otherwise.
+        let sim_params = SimParams::new(grid_dimensions, EMISSION_PRESET.bands());
+
+        let sim_params_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Simulation Parameters Buffer"),
+                contents: bytemuck::cast_slice(&[sim_params]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let sim_params_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Simulation Params Bind Group Layout"),
+            entries: &[
+                // 0. Uniform buffer for compute params
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ]
+        });
+
+        let sim_params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Simulation Params Bind Group"),
+            layout: &sim_params_bind_group_layout,
+            entries: &[
+                // binding 0: Compute params
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sim_params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+
+        // TODO: Add note on why we're using a texture here instead of a buffer.
+        let scalar_field_texture_a = FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "Scalar Field Texture A");
+
+        let scalar_field_texture_b = FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "Scalar Field Texture B");
+
+        // The ping pong reads from texture A first, so the initial volume goes there.
+        if let Some(density_volume) = density_volume {
+            scalar_field_texture_a.write_from_raw_f16(queue, density_volume, grid_dimensions)?;
+        } else if let Some(initial_scalar_field) = SCENE_PRESET.initial_scalar_field(grid_dimensions) {
+            scalar_field_texture_a.write_from_raw_f16(queue, &initial_scalar_field, grid_dimensions)?;
+        }
+
+        let scalar_field_ping_pong = PingPong::new(
+            scalar_field_texture_a,
+            scalar_field_texture_b,
+        );
+
+        let scalar_source_texture = FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "Scalar Source Texture");
+
+        let velocity_vector_field_texture_a = FieldDescriptor::VELOCITY.create_texture(device, grid_dimensions, "Velocity Field Texture A");
+
+        let velocity_vector_field_texture_b = FieldDescriptor::VELOCITY.create_texture(device, grid_dimensions, "Velocity Field Texture B");
+
+        let velocity_vector_field_ping_pong = PingPong::new(
+            velocity_vector_field_texture_a,
+            velocity_vector_field_texture_b,
+        );
+
+
+        let add_source_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Add Source Bind Group Layout"),
+            entries: &[
+                //  Source texture write.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                    count: None,
+                },
+            ]
+        });
+
+        let add_source_bind_group =
+            create_add_source_bind_group(device, &add_source_bind_group_layout, &scalar_source_texture);
+
+        let add_source_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Add Source Pipeline Layout"),
+                bind_group_layouts: &[
+                    Some(&sim_params_bind_group_layout),
+                    Some(&add_source_bind_group_layout),
+                ],
+                immediate_size: 0,
+            });
+
+        let remove_source_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Remove Source Pipeline Layout"),
+                bind_group_layouts: &[
+                    Some(&add_source_bind_group_layout),
+                ],
+                immediate_size: 0,
+            });
+
+        let add_source_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Add Source Shader"),
+            source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("add_source.wgsl")).into()),
+        });
+
+        let add_source_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Source Pipeline"),
+            layout: Some(&add_source_pipeline_layout),
+            module: &add_source_shader,
+            // Will default to @compute
+            entry_point: None,
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &workgroup_size.constants(),
+                ..Default::default()
+            },
+            cache: None,
+        });
+
+        let remove_source_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Remove Source Shader"),
+            source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("remove_source.wgsl")).into()),
+        });
+
+        let remove_source_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Remove Source Pipeline"),
+            layout: Some(&remove_source_pipeline_layout),
+            module: &remove_source_shader,
+            // Will default to @compute
+            entry_point: None,
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &workgroup_size.constants(),
+                ..Default::default()
+            },
+            cache: None,
+        });
+
+        // Create advect scalars compute step
+        let advect_scalars_compute_step = create_advect_scalars_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        // BFECC and MacCormack advection steps and the scratch textures for their intermediate fields.
+        let advect_field_forward_compute_step = create_advect_field_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            1.0,
+            workgroup_size
+        );
+
+        let advect_field_backward_compute_step = create_advect_field_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            -1.0,
+            workgroup_size
+        );
+
+        let bfecc_correct_compute_step = create_bfecc_correct_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let advect_scalars_bfecc_compute_step = create_advect_scalars_bfecc_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let advect_scalars_maccormack_compute_step = create_advect_scalars_maccormack_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let bfecc_scratch_texture_a = FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "BFECC Scratch Texture A");
+
+        let bfecc_scratch_texture_b = FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "BFECC Scratch Texture B");
+
+        let advect_velocity_compute_step = create_advect_velocity_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let add_forces_to_velocity_compute_step = create_add_forces_to_velocity_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let compute_divergence_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compute Divergence Bind Group Layout"),
+            entries: &[
+                // 0. Velocity vector field texture read.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // 1. Divergence texture write.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: divergence_field.storage_binding_type(),
+                    count: None,
+                },
+                // 2. Obstacle texture read.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // 3. Sampler.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+
+        let compute_divergence_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Compute Divergence Pipeline Layout"),
+                bind_group_layouts: &[
+                    Some(&sim_params_bind_group_layout),
+                    Some(&compute_divergence_bind_group_layout),
+                ],
+                immediate_size: 0,
+            });
+
+        let compute_divergence_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Divergence Shader"),
+            source: wgpu::ShaderSource::Wgsl(divergence_field.shader_source(include_str!("compute_divergence.wgsl")).into()),
+        });
+
+        let compute_divergence_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Divergence Pipeline"),
+            layout: Some(&compute_divergence_pipeline_layout),
+            module: &compute_divergence_shader,
+            // Will default to @compute
+            entry_point: None,
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &workgroup_size.constants(),
+                ..Default::default()
+            },
+            cache: None,
+        });
+
+        let divergence_texture = divergence_field.create_texture(device, grid_dimensions, "Divergence Texture");
+
+        let pressure_texture_a = pressure_field.create_texture(device, grid_dimensions, "Pressure Texture A");
+
+        let pressure_texture_b = pressure_field.create_texture(device, grid_dimensions, "Pressure Texture B");
+
+        let pressure_ping_pong = PingPong::new(
+            pressure_texture_a,
+            pressure_texture_b,
+        );
+
+        let diffuse_velocity_compute_step = create_diffuse_velocity_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let undiffused_velocity_texture =
+            FieldDescriptor::VELOCITY.create_texture(device, grid_dimensions, "Undiffused Velocity Texture");
+
+        let compute_pressure_compute_step = create_compute_pressure_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            pressure_field,
+            workgroup_size
+        );
+
+        let subtract_pressure_gradient_compute_step = create_subtract_pressure_gradient_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let compute_curl_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compute Curl Bind Group Layout"),
+            entries: &[
+                // 0. Velocity vector field texture read.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // 1. Curl texture write.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: FieldDescriptor::CURL.storage_binding_type(),
+                    count: None,
+                },
+                // 2. Sampler.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+
+        let compute_curl_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Compute Curl Pipeline Layout"),
+                bind_group_layouts: &[
+                    Some(&sim_params_bind_group_layout),
+                    Some(&compute_curl_bind_group_layout),
+                ],
+                immediate_size: 0,
+            });
+
+        let compute_curl_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Curl Shader"),
+            source: wgpu::ShaderSource::Wgsl(FieldDescriptor::CURL.shader_source(include_str!("compute_curl.wgsl")).into()),
+        });
+
+        let compute_curl_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Compute Curl Pipeline"),
+            layout: Some(&compute_curl_pipeline_layout),
+            module: &compute_curl_shader,
+            // Will default to @compute
+            entry_point: None,
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &workgroup_size.constants(),
+                ..Default::default()
+            },
+            cache: None,
+        });
+
+        let curl_texture = FieldDescriptor::CURL.create_texture(device, grid_dimensions, "Curl Texture");
+
+        let add_vorticity_confinement_force_compute_step = create_add_vorticity_confinement_force_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let compute_temperature_compute_step = create_compute_temperature_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let compute_smoke_compute_step = create_compute_smoke_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let apply_drain_scalars_compute_step = create_apply_drain_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            1.0,
+            workgroup_size
+        );
+
+        let apply_drain_velocity_compute_step = create_apply_drain_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            0.0,
+            workgroup_size
+        );
+
+        let apply_obstacles_compute_step = create_apply_obstacles_compute_step(
+            device,
+            &sim_params_bind_group_layout,
+            workgroup_size
+        );
+
+        let obstacle_texture = FieldDescriptor::OBSTACLES.create_texture(device, grid_dimensions, "Obstacle Texture");
+
+        let mut simulation = Self {
+            grid_dimensions,
+            box_min,
+            box_max,
+            workgroup_size,
+            workgroups,
+            sim_params,
+            sim_params_bind_group,
+            sim_params_buffer,
+            add_source_pipeline,
+            remove_source_pipeline,
+            add_source_bind_group_layout,
+            add_source_bind_group,
+            scalar_field_ping_pong,
+            velocity_vector_field_ping_pong,
+            scalar_source_texture,
+            advect_scalars_compute_step,
+            advection_scheme: ADVECTION_SCHEME,
+            advect_field_forward_compute_step,
+            advect_field_backward_compute_step,
+            bfecc_correct_compute_step,
+            advect_scalars_bfecc_compute_step,
+            advect_scalars_maccormack_compute_step,
+            bfecc_scratch_texture_a,
+            bfecc_scratch_texture_b,
+            advect_velocity_compute_step,
+            add_forces_to_velocity_compute_step,
+            compute_divergence_bind_group_layout,
+            compute_divergence_pipeline,
+            divergence_texture,
+            compute_pressure_compute_step,
+            pressure_ping_pong,
+            pressure_field,
+            divergence_field,
+            subtract_pressure_gradient_compute_step,
+            compute_curl_bind_group_layout,
+            compute_curl_pipeline,
+            curl_texture,
+            add_vorticity_confinement_force_compute_step,
+            compute_temperature_compute_step,
+            compute_smoke_compute_step,
+            apply_drain_scalars_compute_step,
+            apply_drain_velocity_compute_step,
+            apply_obstacles_compute_step,
+            obstacle_texture,
+            obstacle: Obstacle::None,
+            projection_enabled: true,
+            pressure_iterations: PRESSURE_ITERATIONS,
+            diffuse_velocity_compute_step,
+            undiffused_velocity_texture,
+            diffusion_iterations: DIFFUSION_ITERATIONS,
+            pending_input: false,
+            initial_velocity: INITIAL_VELOCITY_FIELD,
+            initial_velocity_speed: INITIAL_VELOCITY_SPEED,
+            scene: SCENE_PRESET,
+            density_volume: density_volume.map(<[u8]>::to_vec),
+            initial_density: None,
+            source_path: SCENE_PRESET.source_path(),
+            step_count: 0,
+            sim_time: 0.0,
+        };
+        simulation.write_initial_velocity(queue);
+        simulation.set_obstacle(queue, OBSTACLE);
+
+        Ok(simulation)
+    }
+
+    /// Clears every field and starts the given scene preset: its initial scalar field, sources and
+    /// turbulence, with the velocity back at the initial velocity field. The source is turned on
+    /// if the scene has any, and the step count and simulated time start over.
+    pub fn load_scene(&mut self, queue: &Queue, preset: ScenePreset) -> anyhow::Result<()> {
+        self.initial_density = None;
+        self.restart(queue, preset.initial_scalar_field(self.grid_dimensions).as_deref())?;
+        self.scene = preset;
+        self.density_volume = None;
+
+        self.sim_params.update_scene(preset);
+        self.source_path = preset.source_path();
+        self.set_pending_input(preset.injects());
+
+        Ok(())
+    }
+
+    /// Starts the last loaded scene over the way the simulation started: from its initial scalar
+    /// field, the density volume the simulation was created with, or the initial density, and the
+    /// initial velocity, with the sources off. The scene's parameters keep any changes made since it
+    /// was loaded.
+    pub fn reset(&mut self, queue: &Queue) -> anyhow::Result<()> {
+        let initial_scalar_field = match &self.density_volume {
+            Some(density_volume) => Some(density_volume.clone()),
+            None => self.scene.initial_scalar_field(self.grid_dimensions),
+        };
+        self.restart(queue, initial_scalar_field.as_deref())?;
+        self.source_path = self.scene.source_path();
+        self.set_pending_input(false);
+
+        Ok(())
+    }
+
+    /// Clears every field texture, including both sides of each ping pong and the scratch
+    /// textures, so nothing of the previous run is left to read. Then writes the initial fields,
+    /// with the initial density over the given scalar field if set, and starts the step count and
+    /// simulated time over.
+    fn restart(&mut self, queue: &Queue, initial_scalar_field: Option<&[u8]>) -> anyhow::Result<()> {
+        self.scalar_field_ping_pong.reset(queue);
+        self.velocity_vector_field_ping_pong.reset(queue);
+        self.pressure_ping_pong.reset(queue);
+        for texture in [
+            &self.scalar_source_texture,
+            &self.bfecc_scratch_texture_a,
+            &self.bfecc_scratch_texture_b,
+            &self.divergence_texture,
+            &self.curl_texture,
+            &self.undiffused_velocity_texture,
+        ] {
+            texture.clear(queue);
+        }
+
+        self.write_initial_velocity(queue);
+        if let Some(initial_scalar_field) = initial_scalar_field {
+            self.scalar_field_ping_pong
+                .get_read_texture()
+                .write_from_raw_f16(queue, initial_scalar_field, self.grid_dimensions)?;
+        }
+        if let Some(initial_density) = self.initial_density {
+            initial_density.write(queue, self.scalar_field_ping_pong.get_read_texture());
+        }
+        self.step_count = 0;
+        self.sim_time = 0.0;
+
+        Ok(())
+    }
+
+    pub fn initial_velocity(&self) -> InitialVelocityField {
+        self.initial_velocity
+    }
+
+    /// Replaces the velocity field with the given initial one, which later scene loads restart
+    /// from too.
+    pub fn set_initial_velocity(&mut self, queue: &Queue, initial_velocity: InitialVelocityField) {
+        self.initial_velocity = initial_velocity;
+        self.velocity_vector_field_ping_pong.clear(queue);
+        self.write_initial_velocity(queue);
+    }
+
+    pub fn initial_velocity_speed(&self) -> f32 {
+        self.initial_velocity_speed
+    }
+
+    /// Replaces the velocity field with the initial one generated at the given speed, which later
+    /// scene loads restart from too.
+    pub fn set_initial_velocity_speed(&mut self, queue: &Queue, initial_velocity_speed: f32) {
+        self.initial_velocity_speed = initial_velocity_speed;
+        self.velocity_vector_field_ping_pong.clear(queue);
+        self.write_initial_velocity(queue);
+    }
+
+    pub fn initial_density(&self) -> Option<InitialDensity> {
+        self.initial_density
+    }
+
+    /// Replaces the scalar field with the given initial density, which reset starts over from until
+    /// another scene is loaded.
+    pub fn set_initial_density(&mut self, queue: &Queue, initial_density: InitialDensity) {
+        self.initial_density = Some(initial_density);
+        self.density_volume = None;
+        self.scalar_field_ping_pong.clear(queue);
+        initial_density.write(queue, self.scalar_field_ping_pong.get_read_texture());
+    }
+
+    pub fn obstacle(&self) -> Obstacle {
+        self.obstacle
+    }
+
+    /// Replaces the solid obstacle in the grid. Fluid already inside it is cleared by the next step.
+    pub fn set_obstacle(&mut self, queue: &Queue, obstacle: Obstacle) {
+        self.obstacle = obstacle;
+
+        let grid = [
+            self.grid_dimensions.width as f32,
+            self.grid_dimensions.height as f32,
+            self.grid_dimensions.depth_or_array_layers as f32,
+        ];
+        let to_voxels = |p: [f32; 3]| [p[0] * grid[0], p[1] * grid[1], p[2] * grid[2]];
+        match obstacle {
+            Obstacle::None => self.obstacle_texture.clear(queue),
+            Obstacle::Sphere { center, radius } => {
+                self.obstacle_texture.write_obstacle_sphere(queue, to_voxels(center), radius * grid[0]);
+            }
+            Obstacle::Box { min, max } => {
+                self.obstacle_texture.write_obstacle_box(queue, to_voxels(min), to_voxels(max));
+            }
+        }
+    }
+
+    /// Writes the initial velocity into the read side of the velocity field, which has just been
+    /// cleared. Zero needs no upload.
+    fn write_initial_velocity(&self, queue: &Queue) {
+        if self.initial_velocity != InitialVelocityField::Zero {
+            self.velocity_vector_field_ping_pong
+                .get_read_texture()
+                .write_initial_velocity_rgba16f(queue, self.initial_velocity, self.initial_velocity_speed);
+        }
+    }
+
+    /// Device features the fields need on the given adapter. Fails with everything missing if the
+    /// adapter can't sample, write or filter one of the field formats, rather than at texture or
+    /// pipeline creation.
+    ///
+    /// WebGPU guarantees all of it for the 16-bit float formats the fields use. FLOAT32_FILTERABLE
+    /// is asked for if the adapter has it, to store pressure and divergence in PRESSURE_FORMAT and
+    /// DIVERGENCE_FORMAT. Any other format the guarantees don't cover needs the adapter's own
+    /// features, which the device only allows with TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES.
+    pub fn required_features(adapter: &wgpu::Adapter) -> anyhow::Result<wgpu::Features> {
+        let float32_filterable = adapter.features() & wgpu::Features::FLOAT32_FILTERABLE;
+        let mut features = wgpu::Features::empty();
+        if field_textures(float32_filterable) != field_textures(wgpu::Features::empty()) {
+            features |= float32_filterable;
+        }
+
+        let mut missing = Vec::new();
+        for (field, _) in field_textures(features) {
+            let adapter_missing = field.missing_format_features(adapter.get_texture_format_features(field.format));
+            if !adapter_missing.is_empty() {
+                missing.push(format!("{} ({:?}): {}", field.label, field.format, adapter_missing.join(", ")));
+                continue;
+            }
+
+            let guaranteed = field.format.guaranteed_format_features(features);
+            if !field.missing_format_features(guaranteed).is_empty() {
+                if !adapter.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+                    missing.push(format!(
+                        "{} ({:?}): TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES, to use more than the WebGPU guarantees",
+                        field.label,
+                        field.format,
+                    ));
+                }
+                features |= wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+            }
+        }
+
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "{} doesn't support what the simulation fields need:\n  {}",
+                adapter.get_info().name,
+                missing.join("\n  "),
+            );
+        }
+        Ok(features)
+    }
+
+    /// Bytes of texture memory the fields of a grid of the given dimensions take on a device with
+    /// the given features.
+    pub fn field_memory(grid_dimensions: wgpu::Extent3d, device_features: wgpu::Features) -> u64 {
+        field_textures(device_features)
+            .iter()
+            .map(|(field, count)| field.texture_size(grid_dimensions) * count)
+            .sum()
+    }
+
+    /// Logs the field memory of the grid, and fails with it if it is over FIELD_MEMORY_BUDGET_MIB
+    /// or the grid is bigger than the device's 3D textures can be.
+    fn validate_grid(
+        grid_dimensions: wgpu::Extent3d,
+        limits: &wgpu::Limits,
+        device_features: wgpu::Features,
+    ) -> anyhow::Result<()> {
+        let (width, height, depth) = (
+            grid_dimensions.width,
+            grid_dimensions.height,
+            grid_dimensions.depth_or_array_layers,
+        );
+        if width == 0 || height == 0 || depth == 0 {
+            anyhow::bail!("Grid {width}x{height}x{depth} has a zero dimension");
+        }
+        let max_dimension = limits.max_texture_dimension_3d;
+        if width.max(height).max(depth) > max_dimension {
+            anyhow::bail!(
+                "Grid {width}x{height}x{depth} exceeds the device's 3D texture limit of {max_dimension} per side",
+            );
+        }
+
+        let memory_mib = Self::field_memory(grid_dimensions, device_features) as f64 / (1024.0 * 1024.0);
+        log::info!("Grid {width}x{height}x{depth} needs {memory_mib:.1} MiB of field textures");
+        if memory_mib > FIELD_MEMORY_BUDGET_MIB as f64 {
+            anyhow::bail!(
+                "Grid {width}x{height}x{depth} needs {memory_mib:.1} MiB of field textures, over the budget of \
+                 {FIELD_MEMORY_BUDGET_MIB} MiB (FIELD_MEMORY_BUDGET_MIB in config.rs). Use a smaller grid or raise \
+                 the budget",
+            );
+        }
+        Ok(())
+    }
+
+    pub fn grid_dimensions(&self) -> wgpu::Extent3d {
+        self.grid_dimensions
+    }
+
+    /// Moves the simulation to a grid of the given dimensions over the same world-space box, so
+    /// the voxels change size instead. Recreates the field textures and the bind group on one of
+    /// them, keeping the pipelines, and starts the current scene over on the new grid with the
+    /// sources left as they were. A density volume the simulation was created with doesn't fit
+    /// the new grid, so resets start from the scene's initial field from then on.
+    ///
+    /// Fails without touching the current grid if the new one is over the device's 3D texture
+    /// limit or FIELD_MEMORY_BUDGET_MIB.
+    pub fn set_grid_dimensions(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        grid_dimensions: wgpu::Extent3d,
+    ) -> anyhow::Result<()> {
+        Self::validate_grid(grid_dimensions, &device.limits(), device.features())?;
+        let box_size: [f32; 3] = std::array::from_fn(|i| self.box_max[i] - self.box_min[i]);
+        self.grid_dimensions = grid_dimensions;
+        self.workgroups = self.workgroup_size.workgroups_for(grid_dimensions);
+        self.sim_params.update_grid_dimensions(grid_dimensions);
+        self.set_box_size(box_size)?;
+
+        // Each texture is dropped as it is replaced, so the old and new grids are never both
+        // allocated in full.
+        self.scalar_field_ping_pong = PingPong::new(
+            FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "Scalar Field Texture A"),
+            FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "Scalar Field Texture B"),
+        );
+        self.scalar_source_texture =
+            FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "Scalar Source Texture");
+        self.add_source_bind_group =
+            create_add_source_bind_group(device, &self.add_source_bind_group_layout, &self.scalar_source_texture);
+        self.velocity_vector_field_ping_pong = PingPong::new(
+            FieldDescriptor::VELOCITY.create_texture(device, grid_dimensions, "Velocity Field Texture A"),
+            FieldDescriptor::VELOCITY.create_texture(device, grid_dimensions, "Velocity Field Texture B"),
+        );
+        self.bfecc_scratch_texture_a =
+            FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "BFECC Scratch Texture A");
+        self.bfecc_scratch_texture_b =
+            FieldDescriptor::SCALARS.create_texture(device, grid_dimensions, "BFECC Scratch Texture B");
+        self.divergence_texture = self.divergence_field.create_texture(device, grid_dimensions, "Divergence Texture");
+        self.pressure_ping_pong = PingPong::new(
+            self.pressure_field.create_texture(device, grid_dimensions, "Pressure Texture A"),
+            self.pressure_field.create_texture(device, grid_dimensions, "Pressure Texture B"),
+        );
+        self.undiffused_velocity_texture =
+            FieldDescriptor::VELOCITY.create_texture(device, grid_dimensions, "Undiffused Velocity Texture");
+        self.curl_texture = FieldDescriptor::CURL.create_texture(device, grid_dimensions, "Curl Texture");
+        self.obstacle_texture = FieldDescriptor::OBSTACLES.create_texture(device, grid_dimensions, "Obstacle Texture");
+
+        self.density_volume = None;
+        self.restart(queue, self.scene.initial_scalar_field(grid_dimensions).as_deref())?;
+        self.source_path = self.scene.source_path();
+        self.set_obstacle(queue, self.obstacle);
+
+        Ok(())
+    }
+
+    pub fn sim_params_mut(&mut self) -> &mut SimParams {
+        &mut self.sim_params
+    }
+
+    /// Uploads the compute params to the GPU. Call after changing them and before the next step.
+    pub fn write_sim_params(&self, queue: &Queue) {
+        queue.write_buffer(&self.sim_params_buffer, 0, bytemuck::cast_slice(&[self.sim_params]));
+    }
+
+    /// Minimum point in world space of the grid.
+    pub fn box_min(&self) -> [f32; 3] {
+        self.box_min
+    }
+
+    /// Maximum point in world space of the grid.
+    pub fn box_max(&self) -> [f32; 3] {
+        self.box_max
+    }
+
+    /// Where a world-space ray passes through the box, as a fraction of the grid like
+    /// Source::center. Takes the middle of the part of the ray inside the box, so the point sits
+    /// halfway through the volume rather than on its surface. None if the ray misses the box or
+    /// the box is behind the origin.
+    pub fn pick(&self, origin: [f32; 3], direction: [f32; 3]) -> Option<[f32; 3]> {
+        // Slab test, as intersect_aabb in the render shader. Starting at zero clips the part
+        // behind the origin, e.g. when the camera is inside the box.
+        let mut t_enter = 0.0_f32;
+        let mut t_exit = f32::INFINITY;
+        for i in 0..3 {
+            let t0 = (self.box_min[i] - origin[i]) / direction[i];
+            let t1 = (self.box_max[i] - origin[i]) / direction[i];
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+        }
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let t = 0.5 * (t_enter + t_exit);
+        Some(std::array::from_fn(|i| {
+            (origin[i] + t * direction[i] - self.box_min[i]) / (self.box_max[i] - self.box_min[i])
+        }))
+    }
+
+    /// Stretches the grid over a box of the given world-space size, keeping box_min. The voxel
+    /// counts don't change, so a box whose proportions differ from the grid's gets non-cubic
+    /// voxels, e.g. a tall chimney on a cubic grid. Takes effect from the next step.
+    pub fn set_box_size(&mut self, box_size: [f32; 3]) -> anyhow::Result<()> {
+        if !box_size.iter().all(|size| *size > 0.0 && size.is_finite()) {
+            anyhow::bail!("Box size must be positive along every axis, got {box_size:?}");
+        }
+
+        let dimensions = [
+            self.grid_dimensions.width,
+            self.grid_dimensions.height,
+            self.grid_dimensions.depth_or_array_layers,
+        ];
+        self.box_max = std::array::from_fn(|i| self.box_min[i] + box_size[i]);
+        self.sim_params.update_cell_size(std::array::from_fn(|i| {
+            box_size[i] / dimensions[i] as f32 / GRID_VOXEL_SIDE_LENGTH
+        }));
+
+        Ok(())
+    }
+
+    /// x = smoke density, y = temperature (Kelvin), z = fuel, w = soot density.
+    pub fn scalar_field(&self) -> &PingPong {
+        &self.scalar_field_ping_pong
+    }
+
+    /// Velocity in GRID_VOXEL_SIDE_LENGTH units per second, which is cells per second unless
+    /// set_box_size made the voxels a different size.
+    pub fn velocity_field(&self) -> &PingPong {
+        &self.velocity_vector_field_ping_pong
+    }
+
+    /// x = pressure, in the format select_field_format chose for the device.
+    pub fn pressure_field(&self) -> &PingPong {
+        &self.pressure_ping_pong
+    }
+
+    /// Reads back everything the next step depends on, so load_snapshot can continue exactly from
+    /// here: both textures of the scalar, velocity and pressure ping pongs (the pressure solve
+    /// starts from the last pressure, and passes that skip boundary voxels leave the write sides'
+    /// edges in place) with the side each reads from, the params, the step count and the
+    /// simulated time. Scratch textures are rebuilt within every step and aren't saved.
+    ///
+    /// Settings kept outside SimParams, such as the scene's source path, the obstacle and the
+    /// advection scheme, aren't saved either; the loaded state runs with the current ones.
+    ///
+    /// Little-endian layout: SNAPSHOT_MAGIC, the version, the grid's width, height and depth as
+    /// u32, the step count as u64, the simulated time as f32, one byte each for the sources being
+    /// on and the scalar, velocity and pressure ping pongs reading from A, the size of SimParams as
+    /// u32 and its bytes, the WGSL format name of each ping pong's textures (e.g. r32float) as a
+    /// u8 length and its bytes, then the six raw volumes in those formats, A before B (see
+    /// Texture::read_raw).
+    ///
+    /// Blocks until the GPU is done, like the exporters.
+    pub fn save_snapshot(&self, device: &Device, queue: &Queue) -> anyhow::Result<Vec<u8>> {
+        let ping_pongs = [&self.scalar_field_ping_pong, &self.velocity_vector_field_ping_pong, &self.pressure_ping_pong];
+        let params = bytemuck::bytes_of(&self.sim_params);
+
+        let mut snapshot = Vec::new();
+        snapshot.extend_from_slice(SNAPSHOT_MAGIC);
+        snapshot.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        for dimension in [
+            self.grid_dimensions.width,
+            self.grid_dimensions.height,
+            self.grid_dimensions.depth_or_array_layers,
+        ] {
+            snapshot.extend_from_slice(&dimension.to_le_bytes());
+        }
+        snapshot.extend_from_slice(&self.step_count.to_le_bytes());
+        snapshot.extend_from_slice(&self.sim_time.to_le_bytes());
+        snapshot.push(self.pending_input as u8);
+        snapshot.extend(ping_pongs.map(|ping_pong| ping_pong.a_to_b() as u8));
+        snapshot.extend_from_slice(&(params.len() as u32).to_le_bytes());
+        snapshot.extend_from_slice(params);
+        for field in self.snapshot_fields() {
+            let format = field.wgsl_storage_format();
+            snapshot.push(format.len() as u8);
+            snapshot.extend_from_slice(format.as_bytes());
+        }
+        for ping_pong in ping_pongs {
+            for texture in ping_pong.textures() {
+                snapshot.extend_from_slice(&texture.read_raw(device, queue)?);
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Restores a snapshot written by save_snapshot, switching to its grid first if it was saved
+    /// on another one (see set_grid_dimensions). The next step continues where the saved
+    /// simulation left off. Fails without changing anything if the snapshot is malformed, was
+    /// written by a build with a different SimParams or stores a field in another format than this
+    /// device does (e.g. pressure saved as r32float on a device that falls back to rgba16float).
+    pub fn load_snapshot(&mut self, device: &Device, queue: &Queue, snapshot: &[u8]) -> anyhow::Result<()> {
+        let mut reader = SnapshotReader { rest: snapshot };
+        if reader.take(SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            anyhow::bail!("Not a firesim snapshot");
+        }
+        let version = reader.u32()?;
+        if version != SNAPSHOT_VERSION {
+            anyhow::bail!("Snapshot version {version} isn't supported, expected {SNAPSHOT_VERSION}");
+        }
+        let grid_dimensions = wgpu::Extent3d {
+            width: reader.u32()?,
+            height: reader.u32()?,
+            depth_or_array_layers: reader.u32()?,
+        };
+        let step_count = u64::from_le_bytes(reader.take(8)?.try_into()?);
+        let sim_time = f32::from_le_bytes(reader.take(4)?.try_into()?);
+        let flags = reader.take(4)?;
+        let (pending_input, a_to_b) = (flags[0] != 0, [flags[1] != 0, flags[2] != 0, flags[3] != 0]);
+        let params_len = reader.u32()? as usize;
+        if params_len != std::mem::size_of::<SimParams>() {
+            anyhow::bail!(
+                "Snapshot params are {params_len} bytes but this build's are {}",
+                std::mem::size_of::<SimParams>(),
+            );
+        }
+        let sim_params: SimParams = bytemuck::pod_read_unaligned(reader.take(params_len)?);
+        let fields = self.snapshot_fields();
+        for field in fields {
+            let format_len = reader.take(1)?[0] as usize;
+            let format = reader.take(format_len)?;
+            if format != field.wgsl_storage_format().as_bytes() {
+                anyhow::bail!(
+                    "Snapshot stores {} as {} but this device uses {}",
+                    field.label,
+                    String::from_utf8_lossy(format),
+                    field.wgsl_storage_format(),
+                );
+            }
+        }
+        let mut volumes = Vec::with_capacity(6);
+        for field in fields {
+            for _ in 0..2 {
+                volumes.push(reader.take(field.texture_size(grid_dimensions) as usize)?);
+            }
+        }
+        if !reader.rest.is_empty() {
+            anyhow::bail!("Snapshot has {} bytes past its end", reader.rest.len());
+        }
+
+        if grid_dimensions != self.grid_dimensions {
+            self.set_grid_dimensions(device, queue, grid_dimensions)?;
+        }
+        let ping_pongs = [
+            &mut self.scalar_field_ping_pong,
+            &mut self.velocity_vector_field_ping_pong,
+            &mut self.pressure_ping_pong,
+        ];
+        for ((ping_pong, a_to_b), volumes) in ping_pongs.into_iter().zip(a_to_b).zip(volumes.chunks_exact(2)) {
+            ping_pong.set_a_to_b(a_to_b);
+            for (texture, volume) in ping_pong.textures().into_iter().zip(volumes) {
+                texture.write_raw(queue, volume)?;
+            }
+        }
+        self.sim_params = sim_params;
+        self.pending_input = pending_input;
+        self.step_count = step_count;
+        self.sim_time = sim_time;
+        self.write_sim_params(queue);
+
+        Ok(())
+    }
+
+    /// Fields of the ping pongs save_snapshot writes, in its order.
+    fn snapshot_fields(&self) -> [FieldDescriptor; 3] {
+        [FieldDescriptor::SCALARS, FieldDescriptor::VELOCITY, self.pressure_field]
+    }
+
+    /// Reads the scalar and velocity fields back and counts the voxels of each with a NaN or
+    /// infinite channel, as (scalar, velocity). Blocks until the GPU is done, so this is for
+    /// diagnosing a blowup rather than per-frame use.
+    pub fn non_finite_voxels(&self, device: &Device, queue: &Queue) -> anyhow::Result<(usize, usize)> {
+        let count = |field: &PingPong| -> anyhow::Result<usize> {
+            let voxels = field.get_read_texture().read_to_vec_f32(device, queue)?;
+            Ok(voxels.iter().filter(|voxel| !voxel.iter().all(|channel| channel.is_finite())).count())
+        };
+        Ok((count(&self.scalar_field_ping_pong)?, count(&self.velocity_vector_field_ping_pong)?))
+    }
+
+    pub fn advection_scheme(&self) -> AdvectionScheme {
+        self.advection_scheme
+    }
+
+    pub fn set_advection_scheme(&mut self, advection_scheme: AdvectionScheme) {
+        self.advection_scheme = advection_scheme;
+    }
+
+    pub fn set_projection_enabled(&mut self, projection_enabled: bool) {
+        self.projection_enabled = projection_enabled;
+    }
+
+    pub fn pressure_iterations(&self) -> u32 {
+        self.pressure_iterations
+    }
+
+    /// Clamped to PRESSURE_ITERATIONS_RANGE.
+    pub fn set_pressure_iterations(&mut self, pressure_iterations: u32) {
+        self.pressure_iterations =
+            pressure_iterations.clamp(Self::PRESSURE_ITERATIONS_RANGE[0], Self::PRESSURE_ITERATIONS_RANGE[1]);
+    }
+
+    pub fn diffusion_iterations(&self) -> u32 {
+        self.diffusion_iterations
+    }
+
+    /// Clamped to DIFFUSION_ITERATIONS_RANGE.
+    pub fn set_diffusion_iterations(&mut self, diffusion_iterations: u32) {
+        self.diffusion_iterations =
+            diffusion_iterations.clamp(Self::DIFFUSION_ITERATIONS_RANGE[0], Self::DIFFUSION_ITERATIONS_RANGE[1]);
+    }
+
+    pub fn pending_input(&self) -> bool {
+        self.pending_input
+    }
+
+    /// Turns the source on or off from the next encoded step on. See the pending_input field.
+    pub fn set_pending_input(&mut self, pending_input: bool) {
+        self.pending_input = pending_input;
+        self.sim_params.update_scene_sources_enabled(pending_input);
+    }
+
+    /// Returns the number of simulation steps that have been run.
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    /// Returns the total simulated time in seconds.
+    pub fn sim_time(&self) -> f32 {
+        self.sim_time
+    }
+
+    /// Runs one simulation step: uploads the parameters with this step's elapsed time and source
+    /// position, then encodes and submits the step on its own, so consecutive steps each see their
+    /// own time.
+    pub fn step(&mut self, device: &Device, queue: &Queue) {
+        self.step_with_profiler(device, queue, None);
+    }
+
+    /// Same as step, with the stages of the step timed by the given profiler.
+    pub fn step_with_profiler(&mut self, device: &Device, queue: &Queue, profiler: Option<&mut GpuProfiler>) {
+        self.prepare_step(queue);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Simulation Step Encoder"),
+        });
+        self.encode_step(device, &mut encoder, profiler);
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Runs scalar advection alone, with the current scheme and without sources, and makes its
+    /// result the read side. Nothing else in a step touches the fields, so the shader can be
+    /// checked on its own, e.g. against advect_scalars_semi_lagrangian.
+    pub fn advect_scalars(&mut self, device: &Device, queue: &Queue) {
+        self.write_sim_params(queue);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Scalar Advection Encoder"),
+        });
+        match self.advection_scheme {
+            AdvectionScheme::SemiLagrangian => self.encode_advect_scalars_semi_lagrangian(device, &mut encoder),
+            AdvectionScheme::Bfecc => self.encode_advect_scalars_bfecc(device, &mut encoder),
+            AdvectionScheme::MacCormack => self.encode_advect_scalars_maccormack(device, &mut encoder),
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        self.scalar_field_ping_pong.swap();
+    }
+
+    /// Uploads the parameters with the next step's elapsed time and source position. Call before
+    /// encoding a step with encode_step, and submit each step on its own afterwards, as step does.
+    pub fn prepare_step(&mut self, queue: &Queue) {
+        self.sim_params.update_elapsed_time(self.sim_time);
+        if let Some(path) = &self.source_path {
+            self.sim_params.update_source_center(0, path.position_at(self.sim_time));
+        }
+        self.write_sim_params(queue);
+    }
+
+    /// Encodes one simulation step: sources, scalar advection and reactions, velocity advection,
+    /// forces, vorticity confinement and the pressure projection. The fields are ready to be
+    /// rendered (or read back) once the encoder is submitted. Each stage is marked on the profiler
+    /// if one is given.
+    pub fn encode_step(
+        &mut self,
+        device: &Device,
+        encoder: &mut wgpu::CommandEncoder,
+        mut profiler: Option<&mut GpuProfiler>,
+    ) {
+        // Starts timing a stage, or stops timing with None.
+        let mut mark = |encoder: &mut wgpu::CommandEncoder, stage: Option<&'static str>| {
+            if let Some(profiler) = profiler.as_deref_mut() {
+                match stage {
+                    Some(stage) => profiler.mark(encoder, stage),
+                    None => profiler.end(encoder),
+                }
+            }
+        };
+
+        /* Add Sources if Present */
+        // Sampled once so the add and remove passes below always pair up.
+        let inject = self.pending_input || self.sim_params.cursor_source().is_some();
+        if inject {
+            mark(encoder, Some("add sources"));
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                compute_pass.set_pipeline(&self.add_source_pipeline);
+
+                compute_pass.set_bind_group(0, &self.sim_params_bind_group, &[]);
+                compute_pass.set_bind_group(1, &self.add_source_bind_group, &[]);
+
+                compute_pass.dispatch_workgroups(
+                    self.workgroups.0,
+                    self.workgroups.1,
+                    self.workgroups.2
+                );
+            }
+        }
+
+        /* Simulation Steps */
+
+        // Advect scalars
+        mark(encoder, Some("advect scalars"));
+        match self.advection_scheme {
+            AdvectionScheme::SemiLagrangian => self.encode_advect_scalars_semi_lagrangian(device, encoder),
+            AdvectionScheme::Bfecc => self.encode_advect_scalars_bfecc(device, encoder),
+            AdvectionScheme::MacCormack => self.encode_advect_scalars_maccormack(device, encoder),
+        }
+
+        // The advected field, sources included, becomes the read side from here on.
+        self.scalar_field_ping_pong.swap();
+
+        // Clear whatever was advected into the obstacle.
+        if self.obstacle != Obstacle::None {
+            let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
+            let textures_read_only: [&wgpu::TextureView; 1] = [&self.obstacle_texture.view];
+
+            self.apply_obstacles_compute_step.dispatch(
+                device,
+                encoder,
+                &self.sim_params_bind_group,
+                DispatchTextures {
+                    read: read_texture,
+                    write: write_texture,
+                    read_only: &textures_read_only,
+                    sampler: None,
+                },
+                self.workgroups
+            );
+
+            self.scalar_field_ping_pong.swap();
+        }
+
+        // Compute temperature
+        mark(encoder, Some("reactions"));
+        let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
+
+        self.compute_temperature_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: read_texture,
+                write: write_texture,
+                read_only: &[],
+                sampler: Some(self.scalar_field_ping_pong.get_sampler()),
+            },
+            self.workgroups
+        );
+
+        self.scalar_field_ping_pong.swap();
+
+        // Decay smoke density
+        let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
+
+        self.compute_smoke_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: read_texture,
+                write: write_texture,
+                read_only: &[],
+                sampler: Some(self.scalar_field_ping_pong.get_sampler()),
+            },
+            self.workgroups
+        );
+
+        self.scalar_field_ping_pong.swap();
+
+        // Drain scalars
+        if self.sim_params.is_drain_enabled() {
+            let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
+
+            self.apply_drain_scalars_compute_step.dispatch(
+                device,
+                encoder,
+                &self.sim_params_bind_group,
+                DispatchTextures {
+                    read: read_texture,
+                    write: write_texture,
+                    read_only: &[],
+                    sampler: None,
+                },
+                self.workgroups
+            );
+
+            self.scalar_field_ping_pong.swap();
+        }
+
+        // Advect velocity
+        mark(encoder, Some("advect velocity"));
+        let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
+        let textures_read_only: [&wgpu::TextureView; 1] = [&self.obstacle_texture.view];
+
+        self.advect_velocity_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: read_texture,
+                write: write_texture,
+                read_only: &textures_read_only,
+                sampler: Some(self.velocity_vector_field_ping_pong.get_sampler()),
+            },
+            self.workgroups
+        );
+
+        self.velocity_vector_field_ping_pong.swap();
+
+        // Add forces to velocity
+        mark(encoder, Some("forces"));
+        let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
+        let textures_read_only: [&wgpu::TextureView; 1] = [self.scalar_field_ping_pong.get_read()];
+
+        self.add_forces_to_velocity_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: read_texture,
+                write: write_texture,
+                read_only: &textures_read_only,
+                sampler: None,
+            },
+            self.workgroups
+        );
+
+        self.velocity_vector_field_ping_pong.swap();
+
+        // Vorticity Confinement
+        // Skipped entirely when off, so a zero strength costs nothing.
+        if self.sim_params.vorticity_strength() > 0.0 {
+            mark(encoder, Some("vorticity confinement"));
+            // Compute curl
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                compute_pass.set_pipeline(&self.compute_curl_pipeline);
+
+                let compute_curl_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Compute Curl Group"),
+                    layout: &self.compute_curl_bind_group_layout,
+                    entries: &[
+                        // binding 0: Velocity vector field read
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(self.velocity_vector_field_ping_pong.get_read())
+                        },
+                        // binding 1: Divergence scalar field write
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&self.curl_texture.view)
+                        },
+                        // binding 2: Sample
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::Sampler(self.velocity_vector_field_ping_pong.get_sampler())
+                        }
+                    ],
+                });
+
+                compute_pass.set_bind_group(0, &self.sim_params_bind_group, &[]);
+                compute_pass.set_bind_group(1, &compute_curl_bind_group, &[]);
+
+                compute_pass.dispatch_workgroups(
+                    self.workgroups.0,
+                    self.workgroups.1,
+                    self.workgroups.2
+                );
+            }
+
+            // Add vorticity confinement force
+            let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
+            let textures_read_only: [&wgpu::TextureView; 1] = [&self.curl_texture.view];
+
+            self.add_vorticity_confinement_force_compute_step.dispatch(
+                device,
+                encoder,
+                &self.sim_params_bind_group,
+                DispatchTextures {
+                    read: read_texture,
+                    write: write_texture,
+                    read_only: &textures_read_only,
+                    sampler: Some(self.velocity_vector_field_ping_pong.get_sampler()),
+                },
+                self.workgroups
+            );
+
+            self.velocity_vector_field_ping_pong.swap();
+        }
+
+        // Drain velocity
+        if self.sim_params.is_drain_enabled() {
+            mark(encoder, Some("drain"));
+            let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
+
+            self.apply_drain_velocity_compute_step.dispatch(
+                device,
+                encoder,
+                &self.sim_params_bind_group,
+                DispatchTextures {
+                    read: read_texture,
+                    write: write_texture,
+                    read_only: &[],
+                    sampler: None,
+                },
+                self.workgroups
+            );
+
+            self.velocity_vector_field_ping_pong.swap();
+        }
+
+        // Diffuse velocity via Jacobi method, starting from the undiffused velocity.
+        if self.sim_params.viscosity() > 0.0 {
+            mark(encoder, Some("diffusion"));
+            encoder.copy_texture_to_texture(
+                self.velocity_vector_field_ping_pong.get_read_texture().texture.as_image_copy(),
+                self.undiffused_velocity_texture.texture.as_image_copy(),
+                self.undiffused_velocity_texture.texture.size(),
+            );
+
+            for _ in 0..self.diffusion_iterations {
+                let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
+                let textures_read_only: [&wgpu::TextureView; 1] = [&self.undiffused_velocity_texture.view];
+
+                self.diffuse_velocity_compute_step.dispatch(
+                    device,
+                    encoder,
+                    &self.sim_params_bind_group,
+                    DispatchTextures {
+                        read: read_texture,
+                        write: write_texture,
+                        read_only: &textures_read_only,
+                        sampler: None,
+                    },
+                    self.workgroups
+                );
+
+                self.velocity_vector_field_ping_pong.swap();
+            }
+        }
+
+        // Projection
+        if self.projection_enabled {
+            mark(encoder, Some("projection"));
+
+            // Compute divergence
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                compute_pass.set_pipeline(&self.compute_divergence_pipeline);
+
+                let compute_divergence_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Compute Divergence Group"),
+                    layout: &self.compute_divergence_bind_group_layout,
+                    entries: &[
+                        // binding 0: Velocity vector field read
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(self.velocity_vector_field_ping_pong.get_read())
+                        },
+                        // binding 1: Divergence scalar field write
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&self.divergence_texture.view)
+                        },
+                        // binding 2: Obstacles read
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&self.obstacle_texture.view)
+                        },
+                        // binding 3: Sample
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::Sampler(self.velocity_vector_field_ping_pong.get_sampler())
+                        }
+                    ],
+                });
+
+                compute_pass.set_bind_group(0, &self.sim_params_bind_group, &[]);
+                compute_pass.set_bind_group(1, &compute_divergence_bind_group, &[]);
+
+                compute_pass.dispatch_workgroups(
+                    self.workgroups.0,
+                    self.workgroups.1,
+                    self.workgroups.2
+                );
+            }
+
+            // Compute pressure via Jacobi method
+            for _ in 0..self.pressure_iterations {
+                let (read_texture, write_texture) = self.pressure_ping_pong.get_read_and_write();
+                let textures_read_only: [&wgpu::TextureView; 2] =
+                    [&self.divergence_texture.view, &self.obstacle_texture.view];
+
+                self.compute_pressure_compute_step.dispatch(
+                    device,
+                    encoder,
+                    &self.sim_params_bind_group,
+                    DispatchTextures {
+                        read: read_texture,
+                        write: write_texture,
+                        read_only: &textures_read_only,
+                        sampler: Some(self.pressure_ping_pong.get_sampler()),
+                    },
+                    self.workgroups
+                );
+
+                self.pressure_ping_pong.swap();
+            }
+
+            // Subtract pressure gradient from the velocity field.
+            let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
+            let textures_read_only: [&wgpu::TextureView; 2] =
+                [self.pressure_ping_pong.get_read(), &self.obstacle_texture.view];
+
+            self.subtract_pressure_gradient_compute_step.dispatch(
+                device,
+                encoder,
+                &self.sim_params_bind_group,
+                DispatchTextures {
+                    read: read_texture,
+                    write: write_texture,
+                    read_only: &textures_read_only,
+                    sampler: Some(self.velocity_vector_field_ping_pong.get_sampler()),
+                },
+                self.workgroups
+            );
+
+            self.velocity_vector_field_ping_pong.swap();
+        }
+
+        /* Remove Sources if Present */
+
+        if inject {
+            mark(encoder, Some("remove sources"));
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                compute_pass.set_pipeline(&self.remove_source_pipeline);
+
+                compute_pass.set_bind_group(0, &self.add_source_bind_group, &[]);
+
+                compute_pass.dispatch_workgroups(
+                    self.workgroups.0,
+                    self.workgroups.1,
+                    self.workgroups.2
+                );
+            }
+        }
+
+        mark(encoder, None);
+
+        self.step_count += 1;
+        self.sim_time += self.sim_params.dt();
+    }
+
+    /// Encodes one semi-Lagrangian advection of the scalar field, adding sources.
+    /// Writes to the write texture of the scalar field ping pong.
+    fn encode_advect_scalars_semi_lagrangian(&self, device: &Device, encoder: &mut wgpu::CommandEncoder) {
+        let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
+        let textures_read_only: [&wgpu::TextureView; 2] = [
+            self.velocity_vector_field_ping_pong.get_read(),
+            &self.scalar_source_texture.view
+        ];
+
+        self.advect_scalars_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: read_texture,
+                write: write_texture,
+                read_only: &textures_read_only,
+                sampler: Some(self.scalar_field_ping_pong.get_sampler()),
+            },
+            self.workgroups
+        );
+    }
+
+    /// Encodes one BFECC advection of the scalar field, adding sources.
+    /// Writes to the write texture of the scalar field ping pong.
+    fn encode_advect_scalars_bfecc(&self, device: &Device, encoder: &mut wgpu::CommandEncoder) {
+        let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
+        let velocity = self.velocity_vector_field_ping_pong.get_read();
+        let sampler = self.scalar_field_ping_pong.get_sampler();
+
+        // Forward advection: phi -> scratch a
+        self.advect_field_forward_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: read_texture,
+                write: &self.bfecc_scratch_texture_a.view,
+                read_only: &[velocity],
+                sampler: Some(sampler),
+            },
+            self.workgroups
+        );
+
+        // Backward advection: scratch a -> scratch b
+        self.advect_field_backward_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: &self.bfecc_scratch_texture_a.view,
+                write: &self.bfecc_scratch_texture_b.view,
+                read_only: &[velocity],
+                sampler: Some(sampler),
+            },
+            self.workgroups
+        );
+
+        // Error correction: phi + (phi - scratch b) / 2 -> scratch a
+        self.bfecc_correct_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: read_texture,
+                write: &self.bfecc_scratch_texture_a.view,
+                read_only: &[&self.bfecc_scratch_texture_b.view],
+                sampler: None,
+            },
+            self.workgroups
+        );
+
+        // Final clamped advection of the corrected field, adding sources.
+        let textures_read_only: [&wgpu::TextureView; 3] = [
+            velocity,
+            &self.scalar_source_texture.view,
+            read_texture,
+        ];
+
+        self.advect_scalars_bfecc_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: &self.bfecc_scratch_texture_a.view,
+                write: write_texture,
+                read_only: &textures_read_only,
+                sampler: Some(sampler),
+            },
+            self.workgroups
+        );
+    }
+
+    /// Encodes one MacCormack advection of the scalar field, adding sources.
+    /// Writes to the write texture of the scalar field ping pong.
+    fn encode_advect_scalars_maccormack(&self, device: &Device, encoder: &mut wgpu::CommandEncoder) {
+        let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
+        let velocity = self.velocity_vector_field_ping_pong.get_read();
+        let sampler = self.scalar_field_ping_pong.get_sampler();
+
+        // Forward advection: phi -> scratch a
+        self.advect_field_forward_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: read_texture,
+                write: &self.bfecc_scratch_texture_a.view,
+                read_only: &[velocity],
+                sampler: Some(sampler),
+            },
+            self.workgroups
+        );
+
+        // Backward advection: scratch a -> scratch b
+        self.advect_field_backward_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: &self.bfecc_scratch_texture_a.view,
+                write: &self.bfecc_scratch_texture_b.view,
+                read_only: &[velocity],
+                sampler: Some(sampler),
+            },
+            self.workgroups
+        );
+
+        // Clamped correction: scratch a + (phi - scratch b) / 2, adding sources.
+        let textures_read_only: [&wgpu::TextureView; 4] = [
+            velocity,
+            &self.scalar_source_texture.view,
+            read_texture,
+            &self.bfecc_scratch_texture_b.view,
+        ];
+
+        self.advect_scalars_maccormack_compute_step.dispatch(
+            device,
+            encoder,
+            &self.sim_params_bind_group,
+            DispatchTextures {
+                read: &self.bfecc_scratch_texture_a.view,
+                write: write_texture,
+                read_only: &textures_read_only,
+                sampler: Some(sampler),
+            },
+            self.workgroups
+        );
+    }
+}
+
+// TODO: Find a better way to organize this code.
+/* Helper functions to create each compute step */
+
+fn create_advect_scalars_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let advect_scalars_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Advect Scalars Bind Group Layout"),
+        entries: &[
+            // 0. Scalar field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Scalar field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                count: None,
+            },
+            // 2. Velocity vector field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 3. Density source texture input.
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 4. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let advect_scalars_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Advect Scalars Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&advect_scalars_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let advect_scalars_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Advect Scalars Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("advect_scalars.wgsl")).into()),
+    });
+
+    let advect_scalars_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Advect Scalars Pipeline"),
+        layout: Some(&advect_scalars_pipeline_layout),
+        module: &advect_scalars_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Advect Scalars Compute Step",
+        advect_scalars_pipeline,
+        advect_scalars_bind_group_layout,
+    )
+}
+
+fn create_advect_field_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, direction: f64, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let advect_field_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Advect Field Bind Group Layout"),
+        entries: &[
+            // 0. Field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                count: None,
+            },
+            // 2. Velocity vector field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 3. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let advect_field_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Advect Field Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&advect_field_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let advect_field_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Advect Field Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("advect_field.wgsl")).into()),
+    });
+
+    let [wg_x, wg_y, wg_z] = workgroup_size.constants();
+    let advect_field_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Advect Field Pipeline"),
+        layout: Some(&advect_field_pipeline_layout),
+        module: &advect_field_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            // Forward (1.0) or backward (-1.0) in time.
+            constants: &[("DIRECTION", direction), wg_x, wg_y, wg_z],
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Advect Field Compute Step",
+        advect_field_pipeline,
+        advect_field_bind_group_layout,
+    )
+}
+
+fn create_bfecc_correct_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let bfecc_correct_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("BFECC Correct Bind Group Layout"),
+        entries: &[
+            // 0. Original field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Corrected field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                count: None,
+            },
+            // 2. Round trip (forward then backward advected) field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+        ]
+    });
+
+    let bfecc_correct_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("BFECC Correct Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&bfecc_correct_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let bfecc_correct_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("BFECC Correct Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("bfecc_correct.wgsl")).into()),
+    });
+
+    let bfecc_correct_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("BFECC Correct Pipeline"),
+        layout: Some(&bfecc_correct_pipeline_layout),
+        module: &bfecc_correct_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "BFECC Correct Compute Step",
+        bfecc_correct_pipeline,
+        bfecc_correct_bind_group_layout,
+    )
+}
+
+fn create_advect_scalars_bfecc_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let advect_scalars_bfecc_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Advect Scalars BFECC Bind Group Layout"),
+        entries: &[
+            // 0. Corrected scalar field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Scalar field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                count: None,
+            },
+            // 2. Velocity vector field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 3. Density source texture input.
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 4. Original scalar field texture read, used for clamping.
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 5. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let advect_scalars_bfecc_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Advect Scalars BFECC Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&advect_scalars_bfecc_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let advect_scalars_bfecc_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Advect Scalars BFECC Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("advect_scalars_bfecc.wgsl")).into()),
+    });
+
+    let advect_scalars_bfecc_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Advect Scalars BFECC Pipeline"),
+        layout: Some(&advect_scalars_bfecc_pipeline_layout),
+        module: &advect_scalars_bfecc_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Advect Scalars BFECC Compute Step",
+        advect_scalars_bfecc_pipeline,
+        advect_scalars_bfecc_bind_group_layout,
+    )
+}
+
+fn create_advect_scalars_maccormack_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let advect_scalars_maccormack_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Advect Scalars MacCormack Bind Group Layout"),
+        entries: &[
+            // 0. Forward advected scalar field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Scalar field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                count: None,
+            },
+            // 2. Velocity vector field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 3. Density source texture input.
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 4. Original scalar field texture read, used for clamping.
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 5. Round trip scalar field texture read, forward advected and then backward.
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 6. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let advect_scalars_maccormack_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Advect Scalars MacCormack Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&advect_scalars_maccormack_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let advect_scalars_maccormack_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Advect Scalars MacCormack Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("advect_scalars_maccormack.wgsl")).into()),
+    });
+
+    let advect_scalars_maccormack_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Advect Scalars MacCormack Pipeline"),
+        layout: Some(&advect_scalars_maccormack_pipeline_layout),
+        module: &advect_scalars_maccormack_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Advect Scalars MacCormack Compute Step",
+        advect_scalars_maccormack_pipeline,
+        advect_scalars_maccormack_bind_group_layout,
+    )
+}
+
+fn create_advect_velocity_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let advect_velocity_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Advect Velocity Bind Group Layout"),
+        entries: &[
+            // 0. Velocity vector field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Vector velocity field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::VELOCITY.storage_binding_type(),
+                count: None,
+            },
+            // 2. Obstacle texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 3. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let advect_velocity_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Advect Velocity Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&advect_velocity_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let advect_velocity_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Advect Velocity Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::VELOCITY.shader_source(include_str!("advect_velocity.wgsl")).into()),
+    });
+
+    let advect_velocity_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Advect Velocity Pipeline"),
+        layout: Some(&advect_velocity_pipeline_layout),
+        module: &advect_velocity_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Advect Velocity Compute Step",
+        advect_velocity_pipeline,
+        advect_velocity_bind_group_layout,
+    )
+}
+
+fn create_add_forces_to_velocity_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let add_forces_to_velocity_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Add Forces to Velocity Bind Group Layout"),
+        entries: &[
+            // 0. Velocity vector field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Vector velocity field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::VELOCITY.storage_binding_type(),
+                count: None,
+            },
+            // 2. Scalar texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+        ]
+    });
+
+    let add_forces_to_velocity_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Add Forces to Velocity Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&add_forces_to_velocity_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let add_forces_to_velocity_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Add Forces to Velocity Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::VELOCITY.shader_source(include_str!("add_forces_to_velocity.wgsl")).into()),
+    });
+
+    let add_forces_to_velocity_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Add Forces to Velocity Pipeline"),
+        layout: Some(&add_forces_to_velocity_pipeline_layout),
+        module: &add_forces_to_velocity_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Add Forces to Velocity Compute Step",
+        add_forces_to_velocity_pipeline,
+        add_forces_to_velocity_bind_group_layout,
+    )
+}
+
+/// Reads a snapshot front to back. See Simulation::save_snapshot for the layout.
+struct SnapshotReader<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> SnapshotReader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if self.rest.len() < len {
+            anyhow::bail!("Snapshot is truncated");
+        }
+        let (bytes, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+}
+
+fn create_add_source_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, scalar_source_texture: &Texture) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Add Source Bind Group"),
+        layout,
+        entries: &[
+            // binding 0: Scalar field
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&scalar_source_texture.view)
+            },
+        ],
+    })
+}
+
+fn create_compute_pressure_compute_step(
+    device: &Device,
+    sim_params_bind_group_layout: &wgpu::BindGroupLayout,
+    pressure_field: FieldDescriptor,
+    workgroup_size: WorkgroupSize,
+) -> ComputeStep {
+    let compute_pressure_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Compute Pressure Bind Group Layout"),
+        entries: &[
+            // 0. Pressure texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Pressure texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: pressure_field.storage_binding_type(),
+                count: None,
+            },
+            // 2. Divergence texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 3. Obstacle texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 4. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let compute_pressure_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Pressure Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&compute_pressure_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let compute_pressure_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Compute Pressure Shader"),
+        source: wgpu::ShaderSource::Wgsl(pressure_field.shader_source(include_str!("compute_pressure.wgsl")).into()),
+    });
+
+    let compute_pressure_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Add Forces to Velocity Pipeline"),
+        layout: Some(&compute_pressure_pipeline_layout),
+        module: &compute_pressure_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Compute Pressure Compute Step",
+        compute_pressure_pipeline,
+        compute_pressure_bind_group_layout,
+    )
+}
+
+fn create_subtract_pressure_gradient_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let subtract_pressure_gradient_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Subtract Pressure Gradient Bind Group Layout"),
+        entries: &[
+            // 0. Velocity vector field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Vector velocity field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::VELOCITY.storage_binding_type(),
+                count: None,
+            },
+            // 2. Pressure texture,
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 3. Obstacle texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 4. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let subtract_pressure_gradient_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Subtract Gradient Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&subtract_pressure_gradient_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let subtract_pressure_gradient_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Subtract Pressure Gradient Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::VELOCITY.shader_source(include_str!("subtract_pressure_gradient.wgsl")).into()),
+    });
+
+    let subtract_pressure_gradient_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Subtract Pressure Gradient Pipeline"),
+        layout: Some(&subtract_pressure_gradient_pipeline_layout),
+        module: &subtract_pressure_gradient_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Subtract Pressure Gradient Compute Step",
+        subtract_pressure_gradient_pipeline,
+        subtract_pressure_gradient_bind_group_layout,
+    )
+}
+
+fn create_add_vorticity_confinement_force_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let add_vorticity_confinement_force_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Add Vorticity Confinement Force Bind Group Layout"),
+        entries: &[
+            // 0. Velocity vector field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Vector velocity field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::VELOCITY.storage_binding_type(),
+                count: None,
+            },
+            // 2. Curl texture,
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 3. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let add_vorticity_confinement_force_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Add Vorticity Confinement Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&add_vorticity_confinement_force_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let add_vorticity_confinement_force_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Add Vorticity Confinement Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::VELOCITY.shader_source(include_str!("add_vorticity_confinement_force.wgsl")).into()),
+    });
+
+    let subtract_pressure_gradient_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Subtract Pressure Gradient Pipeline"),
+        layout: Some(&add_vorticity_confinement_force_pipeline_layout),
+        module: &add_vorticity_confinement_force_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Subtract Pressure Gradient Compute Step",
+        subtract_pressure_gradient_pipeline,
+        add_vorticity_confinement_force_bind_group_layout,
+    )
+}
+
+fn create_compute_temperature_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let compute_temperature_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Compute Temperature Bind Group Layout"),
+        entries: &[
+            // 0. Scalar field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Scalar field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                count: None,
+            },
+            // 2. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let compute_temperature_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Temperature Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&compute_temperature_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let compute_temperature_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Compute Temperature Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("compute_temperature.wgsl")).into()),
+    });
+
+    let compute_temperature_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Compute Temperature Pipeline"),
+        layout: Some(&compute_temperature_pipeline_layout),
+        module: &compute_temperature_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Advect Scalars Compute Step",
+        compute_temperature_pipeline,
+        compute_temperature_bind_group_layout,
+    )
+}
+
+fn create_compute_smoke_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let compute_smoke_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Compute Smoke Bind Group Layout"),
+        entries: &[
+            // 0. Scalar field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Scalar field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                count: None,
+            },
+            // 2. Sampler.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            }
+        ]
+    });
+
+    let compute_smoke_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compute Smoke Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&compute_smoke_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let compute_smoke_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Compute Smoke Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("compute_smoke.wgsl")).into()),
+    });
+
+    let compute_smoke_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Compute Smoke Pipeline"),
+        layout: Some(&compute_smoke_pipeline_layout),
+        module: &compute_smoke_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Compute Smoke Compute Step",
+        compute_smoke_pipeline,
+        compute_smoke_bind_group_layout,
+    )
+}
+
+fn create_apply_drain_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, ambient_temperature_target: f64, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let apply_drain_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Apply Drain Bind Group Layout"),
+        entries: &[
+            // 0. Field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                count: None,
+            },
+        ]
+    });
+
+    let apply_drain_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Apply Drain Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&apply_drain_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let apply_drain_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Apply Drain Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("apply_drain.wgsl")).into()),
+    });
+
+    let [wg_x, wg_y, wg_z] = workgroup_size.constants();
+    let apply_drain_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Apply Drain Pipeline"),
+        layout: Some(&apply_drain_pipeline_layout),
+        module: &apply_drain_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            // Temperature towards the ambient temperature (1.0, scalar field) or zero (0.0).
+            constants: &[("AMBIENT_TEMPERATURE_TARGET", ambient_temperature_target), wg_x, wg_y, wg_z],
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Apply Drain Compute Step",
+        apply_drain_pipeline,
+        apply_drain_bind_group_layout,
+    )
+}
+
+fn create_apply_obstacles_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let apply_obstacles_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Apply Obstacles Bind Group Layout"),
+        entries: &[
+            // 0. Field texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Field texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::SCALARS.storage_binding_type(),
+                count: None,
+            },
+            // 2. Obstacle texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+        ]
+    });
+
+    let apply_obstacles_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Apply Obstacles Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&apply_obstacles_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let apply_obstacles_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Apply Obstacles Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::SCALARS.shader_source(include_str!("apply_obstacles.wgsl")).into()),
+    });
+
+    let apply_obstacles_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Apply Obstacles Pipeline"),
+        layout: Some(&apply_obstacles_pipeline_layout),
+        module: &apply_obstacles_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Apply Obstacles Compute Step",
+        apply_obstacles_pipeline,
+        apply_obstacles_bind_group_layout,
+    )
+}
+
+fn create_diffuse_velocity_compute_step(device: &Device, sim_params_bind_group_layout: &wgpu::BindGroupLayout, workgroup_size: WorkgroupSize) -> ComputeStep {
+    let diffuse_velocity_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Diffuse Velocity Bind Group Layout"),
+        entries: &[
+            // 0. Velocity estimate texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // 1. Velocity estimate texture write.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: FieldDescriptor::VELOCITY.storage_binding_type(),
+                count: None,
+            },
+            // 2. Undiffused velocity texture read.
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+        ]
+    });
+
+    let diffuse_velocity_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Diffuse Velocity Pipeline Layout"),
+            bind_group_layouts: &[
+                Some(sim_params_bind_group_layout),
+                Some(&diffuse_velocity_bind_group_layout),
+            ],
+            immediate_size: 0,
+        });
+
+    let diffuse_velocity_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Diffuse Velocity Shader"),
+        source: wgpu::ShaderSource::Wgsl(FieldDescriptor::VELOCITY.shader_source(include_str!("diffuse_velocity.wgsl")).into()),
+    });
+
+    let diffuse_velocity_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Diffuse Velocity Pipeline"),
+        layout: Some(&diffuse_velocity_pipeline_layout),
+        module: &diffuse_velocity_shader,
+        // Will default to @compute
+        entry_point: None,
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &workgroup_size.constants(),
+            ..Default::default()
+        },
+        cache: None,
+    });
+
+    ComputeStep::new(
+        "Diffuse Velocity Compute Step",
+        diffuse_velocity_pipeline,
+        diffuse_velocity_bind_group_layout,
+    )
+}
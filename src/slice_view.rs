@@ -0,0 +1,119 @@
+use crate::config::SLICE_VIEW_RANGES;
+
+/// Channel of the fields the slice view shows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SliceField {
+    /// Smoke density, x of the scalar field.
+    Density,
+    /// Temperature (K), y of the scalar field.
+    Temperature,
+    /// Fuel, z of the scalar field.
+    Fuel,
+    /// Soot density, w of the scalar field.
+    Soot,
+    /// Length of the velocity, in reference voxel lengths per second.
+    Speed,
+}
+
+impl SliceField {
+    /// Identifier fs_slice switches on. Must match the SLICE_FIELD_ constants in
+    /// render_shader.wgsl.
+    pub fn shader_id(self) -> u32 {
+        self as u32
+    }
+
+    /// Returns the field after this one, wrapping around. Used to cycle fields at runtime.
+    pub fn next(self) -> Self {
+        match self {
+            SliceField::Density => SliceField::Temperature,
+            SliceField::Temperature => SliceField::Fuel,
+            SliceField::Fuel => SliceField::Soot,
+            SliceField::Soot => SliceField::Speed,
+            SliceField::Speed => SliceField::Density,
+        }
+    }
+
+    /// Values at the bottom and top of the color ramp, from SLICE_VIEW_RANGES.
+    pub fn range(self) -> [f32; 2] {
+        SLICE_VIEW_RANGES[self as usize]
+    }
+
+    /// Whether the channel is read from the velocity field rather than the scalar field.
+    pub fn reads_velocity(self) -> bool {
+        self == SliceField::Speed
+    }
+}
+
+/// Grid axis the slice view's plane is perpendicular to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SliceAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl SliceAxis {
+    /// Identifier fs_slice switches on. Must match the SLICE_AXIS_ constants in
+    /// render_shader.wgsl.
+    pub fn shader_id(self) -> u32 {
+        self as u32
+    }
+
+    /// Returns the axis after this one, wrapping around. Used to cycle axes at runtime.
+    pub fn next(self) -> Self {
+        match self {
+            SliceAxis::X => SliceAxis::Y,
+            SliceAxis::Y => SliceAxis::Z,
+            SliceAxis::Z => SliceAxis::X,
+        }
+    }
+
+    /// Number of voxels of the grid along the axis.
+    fn length(self, grid_dimensions: wgpu::Extent3d) -> u32 {
+        match self {
+            SliceAxis::X => grid_dimensions.width,
+            SliceAxis::Y => grid_dimensions.height,
+            SliceAxis::Z => grid_dimensions.depth_or_array_layers,
+        }
+    }
+}
+
+/// One axis aligned slice of a field channel, drawn flat over the whole screen in place of the
+/// volume to look at the raw voxel values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SliceView {
+    pub field: SliceField,
+    pub axis: SliceAxis,
+    /// Voxel index of the slice along axis.
+    pub index: u32,
+}
+
+impl SliceView {
+    /// The smoke density in the middle slice along z.
+    pub fn new(grid_dimensions: wgpu::Extent3d) -> Self {
+        let axis = SliceAxis::Z;
+        Self {
+            field: SliceField::Density,
+            axis,
+            index: axis.length(grid_dimensions) / 2,
+        }
+    }
+
+    /// Moves the slice by delta voxels along its axis, staying inside the grid. The grid may have
+    /// shrunk since the slice was last moved.
+    pub fn offset(&mut self, delta: i32, grid_dimensions: wgpu::Extent3d) {
+        let last = self.axis.length(grid_dimensions).saturating_sub(1) as i64;
+        self.index = (self.index.min(last as u32) as i64 + delta as i64).clamp(0, last) as u32;
+    }
+
+    /// Switches to the next axis, starting from its middle slice.
+    pub fn next_axis(&mut self, grid_dimensions: wgpu::Extent3d) {
+        self.axis = self.axis.next();
+        self.index = self.axis.length(grid_dimensions) / 2;
+    }
+
+    /// Number of slices along the current axis.
+    pub fn slice_count(&self, grid_dimensions: wgpu::Extent3d) -> u32 {
+        self.axis.length(grid_dimensions)
+    }
+}
@@ -1,33 +1,38 @@
 use std::sync::Arc;
+use std::time::Duration;
 use wgpu::{Device, Queue, Surface, SurfaceConfiguration};
 use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalPosition;
 use winit::event::ElementState;
 use winit::event_loop::ActiveEventLoop;
 use winit::keyboard::KeyCode;
-use winit::window::Window;
+use winit::window::{CursorGrabMode, Window};
 
+use crate::blend_mode::BlendMode;
 use crate::camera::{Camera, CameraController, CameraUniform, Projection};
-use crate::texture::Texture;
-use crate::compute_params::ComputeParams;
-use crate::compute_step::ComputeStep;
-use crate::config::{GRID_DIMENSION_LENGTH, GRID_VOXEL_SIDE_LENGTH};
-use crate::ping_pong::PingPong;
-
-/**
-Each channel (RBGA) in the texture will be a 16-bit float.
-The 16-bit float channel is filterable (needed for interpolation) but the 32-bit float channel
-is not.
-TODO: My current machine allows this will the texture usages I need, but add check for this.
-TODO: Make just one format.
-*/
-const CHANNEL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
-const NUMBER_DISPATCHES_PER_DIMENSION: u32 = GRID_DIMENSION_LENGTH / 4;
-const WORKGROUPS: (u32, u32, u32) = (
-    NUMBER_DISPATCHES_PER_DIMENSION,
-    NUMBER_DISPATCHES_PER_DIMENSION,
-    NUMBER_DISPATCHES_PER_DIMENSION
-);
-const JACOBI_ITERATIONS: u32 = 20;
+use crate::texture::{SamplerConfig, Texture};
+use crate::config::{
+    AUTO_DENSITY_ADAPTATION_RATE, AUTO_DENSITY_PERCENTILES, AUTO_DENSITY_REFERENCE, AUTO_DENSITY_SCALE_RANGE,
+    AUTO_EXPOSURE_ADAPTATION_RATE, AUTO_EXPOSURE_KEY, AUTO_EXPOSURE_RANGE, BACKGROUND_COLOR, BOX_EDGE_DEPTH_OFFSET, CAMERA_ZFAR, CAMERA_ZNEAR,
+    FIXED_TIMESTEP, GPU_PROFILING, LIGHT_ROTATION_STEP, MAX_SUBSTEPS_PER_FRAME, MSAA_SAMPLE_COUNT, PRESENT_MODE, PRESSURE_ITERATIONS_STEP, RAYMARCH_STEPS_STEP, SOURCE_DENSITY_STEP, SOURCE_FUEL_STEP, SOURCE_NUDGE_STEP,
+    RUNTIME_GRID_DIMENSION_LENGTHS, SOURCE_RADIUS_SCALE, STATS_OVERLAY,
+    STATS_UPDATE_INTERVAL, TIME_SCALE, VOLUME_CULL_MODE, VORTICITY_STRENGTH_STEP, WORKGROUP_SIZE,
+};
+use crate::render_settings::RenderSettings;
+use crate::camera_settings::CameraSettings;
+use crate::render_params::RenderParams;
+use crate::slice_view::SliceView;
+use crate::scene::{InitialDensity, ScenePreset};
+use crate::timestep::FixedTimestep;
+use crate::auto_exposure::AutoExposure;
+use crate::density_histogram::{DensityHistogram, DensityRange};
+use crate::gpu_profiler::GpuProfiler;
+use crate::simulation::Simulation;
+use crate::initial_velocity::InitialVelocityField;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::config::FRAME_OUTPUT_DIR;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::frame_recorder::FrameRecorder;
 
 pub struct State {
     surface: Surface<'static>,
@@ -35,51 +40,129 @@ pub struct State {
     queue: Queue,
     config: SurfaceConfiguration,
     is_surface_configured: bool,
+    /// The window is covered by others or off screen, as reported by the windowing system.
+    occluded: bool,
+    /// The window was resized to zero, as when it is minimized on some platforms.
+    minimized: bool,
+    /// Present modes the surface supports, in the order it reports them.
+    present_modes: Vec<wgpu::PresentMode>,
+    /// Samples per pixel of the render pass, MSAA_SAMPLE_COUNT or the closest lower count the
+    /// adapter supports.
+    msaa_sample_count: u32,
     depth_texture: Texture,
+    /// Linear radiance of the volume, resolved to the surface by the tone mapping pass.
+    hdr_texture: Texture,
+    /// Multisampled targets the render pass draws to and resolves into hdr_texture and
+    /// luminance_texture. None without MSAA, when it draws to those directly.
+    hdr_msaa_texture: Option<Texture>,
+    luminance_msaa_texture: Option<Texture>,
+    hdr_bind_group_layout: wgpu::BindGroupLayout,
+    hdr_bind_group: wgpu::BindGroup,
+    luminance_texture: Texture,
+    auto_exposure: AutoExposure,
+    /// Exposure currently fed to the tone mapper.
+    exposure: f32,
+    /// Exposure that auto-exposure is adapting towards, from the last measured frame.
+    target_exposure: f32,
+    density_histogram: DensityHistogram,
+    /// Smoke density scale currently fed to the renderer.
+    smoke_density_scale: f32,
+    /// Smoke density scale that auto density range is adapting towards.
+    target_smoke_density_scale: f32,
+    /// Density range of the last measured field, while auto density range is on.
+    density_range: Option<DensityRange>,
+    /// None if the device has no timestamp queries.
+    gpu_profiler: Option<GpuProfiler>,
+    /// Time the stages of every frame on the GPU and log them periodically.
+    gpu_profiling: bool,
+    /// Keep the current smoke density scale instead of measuring new ranges.
+    density_range_locked: bool,
     camera: Camera,
     pub camera_controller: CameraController,
     projection: Projection,
     camera_uniform: CameraUniform,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
-    render_pipeline: wgpu::RenderPipeline,
+    render_params: RenderParams,
+    render_params_buffer: wgpu::Buffer,
+    render_params_bind_group: wgpu::BindGroup,
+    /// Set whenever render_params changes. update uploads them once and clears it, so frames
+    /// where nothing changed skip the upload.
+    render_params_dirty: bool,
+    /// Ray marches the volume. One per BlendMode, indexed by BlendMode::index.
+    render_pipelines: [wgpu::RenderPipeline; BlendMode::ALL.len()],
+    /// Draws the edges of the simulation box as lines, before the volume.
+    box_edge_pipeline: wgpu::RenderPipeline,
+    /// Both corners of each of the box's 12 edges. The box doesn't change after new, so neither
+    /// does this.
+    box_edge_vertex_buffer: wgpu::Buffer,
+    /// Applies exposure and tone mapping to hdr_texture and writes the result to the surface.
+    tonemap_pipeline: wgpu::RenderPipeline,
+    /// Draws the color legend over the rendered image.
+    legend_pipeline: wgpu::RenderPipeline,
+    /// Draws the frame stats over the rendered image.
+    stats_pipeline: wgpu::RenderPipeline,
+    /// Draws a slice of a field over the whole screen, in place of the volume.
+    slice_pipeline: wgpu::RenderPipeline,
     density_texture_bind_group_layout: wgpu::BindGroupLayout,
-    compute_params: ComputeParams,
-    compute_params_bind_group: wgpu::BindGroup,
-    compute_params_buffer: wgpu::Buffer,
-    add_source_pipeline: wgpu::ComputePipeline,
-    remove_source_pipeline: wgpu::ComputePipeline,
-    add_source_bind_group: wgpu::BindGroup,
-    scalar_field_ping_pong: PingPong,
-    velocity_vector_field_ping_pong: PingPong,
-    scalar_source_texture: Texture,
-    advect_scalars_compute_step: ComputeStep,
-    advect_velocity_compute_step: ComputeStep,
-    add_forces_to_velocity_compute_step: ComputeStep,
-    compute_divergence_bind_group_layout: wgpu::BindGroupLayout,
-    compute_divergence_pipeline: wgpu::ComputePipeline,
-    divergence_texture: Texture,
-    pressure_ping_pong: PingPong,
-    compute_pressure_compute_step: ComputeStep,
-    subtract_pressure_gradient_compute_step: ComputeStep,
-    compute_curl_bind_group_layout: wgpu::BindGroupLayout,
-    compute_curl_pipeline: wgpu::ComputePipeline,
-    curl_texture: Texture,
-    add_vorticity_confinement_force_compute_step: ComputeStep,
-    compute_temperature_compute_step: ComputeStep,
-    compute_smoke_compute_step: ComputeStep,
-    elapsed_time: f32,
-    pending_input: bool,
+    /// Sampler the renderer uses instead of the scalar field's own when nearest sampling is on.
+    nearest_sampler: wgpu::Sampler,
+    render_settings: RenderSettings,
+    simulation: Simulation,
+    timestep: FixedTimestep,
+    /// Simulation steps the next render runs, from the last update.
+    pending_substeps: u32,
+    /// No simulation steps run while paused. Rendering and the camera keep working.
+    paused: bool,
+    /// Run a single step on the next update while paused.
+    single_step_requested: bool,
     pub mouse_pressed: bool,
+    /// The cursor is hidden and grabbed, and every mouse motion rotates the camera, without a
+    /// button held.
+    mouse_look: bool,
+    /// Last cursor position in the window, in physical pixels. None until the cursor first moves
+    /// over the window.
+    cursor_position: Option<PhysicalPosition<f64>>,
+    /// Right mouse button held: a source follows the cursor.
+    injecting_at_cursor: bool,
+    /// Show the camera position and orientation in the window title.
+    camera_hud_enabled: bool,
+    /// Color behind the volume, and the one the resolve pass clears to.
+    clear_color: wgpu::Color,
+    /// Background color the background key cycles back to: BACKGROUND_COLOR or --background.
+    default_clear_color: wgpu::Color,
+    /// Show the frames per second and average frame time in the top left corner.
+    stats_overlay_enabled: bool,
+    /// Frames and seconds since the frame stats were last updated. Only counted while the stats
+    /// overlay is on.
+    stats_frames: u32,
+    stats_elapsed: f32,
+    /// Show slice_view instead of ray marching the volume.
+    slice_view_enabled: bool,
+    slice_view: SliceView,
+    #[cfg(not(target_arch = "wasm32"))]
+    frame_recorder: FrameRecorder,
     pub window: Arc<Window>,
 }
 
 impl State {
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
+    pub async fn new(
+        window: Arc<Window>,
+        grid_dimensions: wgpu::Extent3d,
+        box_size: Option<[f32; 3]>,
+        density_volume: Option<Vec<u8>>,
+        render_settings: RenderSettings,
+        camera_settings: CameraSettings,
+        paused: bool,
+    ) -> anyhow::Result<Self> {
+        camera_settings.validate()?;
+
         let size = window.inner_size();
 
+        // WGPU_BACKEND (e.g. vulkan, metal, dx12, gl) replaces the primary backends.
+        let backends = wgpu::Backends::PRIMARY.with_env();
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
+            backends,
             flags: Default::default(),
             memory_budget_thresholds: Default::default(),
             backend_options: Default::default(),
@@ -88,42 +171,60 @@ impl State {
 
         let surface = instance.create_surface(window.clone())?;
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await?;
+        let adapter = request_adapter(&instance, &surface, backends).await?;
+        log::info!("Using {} ({:?})", adapter.get_info().name, adapter.get_info().backend);
+        let simulation_features = Simulation::required_features(&adapter)?;
+        let (msaa_sample_count, msaa_features) = select_msaa_sample_count(MSAA_SAMPLE_COUNT, &adapter);
 
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::empty(),
+                // Timestamps are only needed for GPU profiling, which is left off without them.
+                required_features: simulation_features
+                    | msaa_features
+                    | (adapter.features() & wgpu::Features::TIMESTAMP_QUERY),
                 experimental_features: wgpu::ExperimentalFeatures::disabled(),
                 required_limits: wgpu::Limits::default(),
                 memory_hints: wgpu::MemoryHints::default(),
                 trace: wgpu::Trace::Off,
             })
-            .await?;
+            .await
+            .map_err(|e| {
+                let info = adapter.get_info();
+                anyhow::anyhow!(
+                    "Failed to create a device on {} ({:?}): {e}. Try another backend with WGPU_BACKEND, e.g. WGPU_BACKEND=gl",
+                    info.name,
+                    info.backend,
+                )
+            })?;
 
         let surface_caps = surface.get_capabilities(&adapter);
-        // TODO: Look into this comment to see if want anything other than sRGB
-        // Shader code in this tutorial assumes an sRGB surface texture. Using a different
-        // one will result in all the colors coming out darker. If you want to support non
-        // sRGB surfaces, you'll need to account for that when drawing to the frame.
+        // The shaders write linear colors and leave the sRGB encoding to the target format. A
+        // surface without an sRGB format (e.g. WebGPU canvases) is drawn to through an sRGB view
+        // of its own format instead, which looks the same.
         let surface_format = surface_caps.formats.iter()
             .find(|f| f.is_srgb())
             .copied()
             .unwrap_or(surface_caps.formats[0]);
+        let view_formats = if surface_format.is_srgb() || surface_format.add_srgb_suffix() == surface_format {
+            vec![]
+        } else {
+            vec![surface_format.add_srgb_suffix()]
+        };
+        if !surface_format.is_srgb() && view_formats.is_empty() {
+            log::warn!("Surface format {surface_format:?} has no sRGB view, colors will look too dark");
+        }
+        // Some platforms report a 0x0 window before the first Resized event. The surface isn't
+        // configured until resize gets a real size (see is_surface_configured), but everything
+        // derived from the config here (projection, viewport, render targets) needs a valid one.
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: select_present_mode(PRESENT_MODE, &surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
+            view_formats,
             desired_maximum_frame_latency: 2,
         };
 
@@ -132,12 +233,32 @@ impl State {
             source: wgpu::ShaderSource::Wgsl(include_str!("render_shader.wgsl").into()),
         });
 
-        let depth_texture = Texture::create_depth_texture(&device, &config, "depth_texture");
+        let depth_texture = Texture::create_depth_texture(&device, &config, msaa_sample_count, "depth_texture");
+        let hdr_texture = Texture::create_hdr_texture(&device, &config, "hdr_texture");
+        let hdr_msaa_texture =
+            create_msaa_texture(&device, &config, "hdr_msaa_texture", Texture::HDR_FORMAT, msaa_sample_count);
+        let luminance_texture = Texture::create_luminance_texture(&device, &config, "luminance_texture");
+        let luminance_msaa_texture = create_msaa_texture(
+            &device,
+            &config,
+            "luminance_msaa_texture",
+            Texture::LUMINANCE_FORMAT,
+            msaa_sample_count,
+        );
+        let auto_exposure = AutoExposure::new(&device, &luminance_texture.view);
+        let density_histogram = DensityHistogram::new(&device);
+        let gpu_profiler = GpuProfiler::new(&device, &queue);
+        if GPU_PROFILING && gpu_profiler.is_none() {
+            log::warn!("GPU profiling needs timestamp queries, which the adapter does not support");
+        }
 
-        // TODO: Move these to constants
-        let camera = Camera::new((0.6125, 1.25, 2.5), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
-        let camera_controller = CameraController::new(1.0, 0.2);
-        let projection = Projection::new(config.width, config.height, cgmath::Deg(45.0), 0.1, 100.0);
+        let camera = Camera::new(
+            camera_settings.position,
+            cgmath::Deg(camera_settings.yaw),
+            cgmath::Deg(camera_settings.pitch),
+        );
+        let camera_controller = CameraController::new(camera_settings.speed, camera_settings.sensitivity);
+        let projection = Projection::new(config.width, config.height, cgmath::Deg(camera_settings.fovy), CAMERA_ZNEAR, CAMERA_ZFAR);
 
         let mut camera_uniform = CameraUniform::default();
         camera_uniform.update(&camera, &projection);
@@ -177,28 +298,41 @@ impl State {
             label: Some("Camera Bind Group"),
         });
 
-        let box_min = [0.0, 0.0, 0.0, 0.0];
-
-        let extent = GRID_DIMENSION_LENGTH as f32 * GRID_VOXEL_SIDE_LENGTH;
-        let box_max = [extent, extent, extent, 0.0];
+        let mut simulation = Simulation::new(
+            &device,
+            &queue,
+            grid_dimensions,
+            WORKGROUP_SIZE,
+            density_volume.as_deref(),
+        )?;
+        if let Some(box_size) = box_size {
+            simulation.set_box_size(box_size)?;
+        }
+        simulation.sim_params_mut().update_emission_bands(render_settings.emission_preset.bands());
+        let timestep = FixedTimestep::new(FIXED_TIMESTEP, MAX_SUBSTEPS_PER_FRAME, TIME_SCALE);
+        simulation.sim_params_mut().update_dt(Duration::from_secs_f32(timestep.step()));
 
-        let compute_params = ComputeParams::new(box_min, box_max, &config);
+        let mut render_params = RenderParams::new(simulation.box_min(), simulation.box_max(), &render_settings);
+        render_params.update_viewport(&config);
+        render_params.update_background(BACKGROUND_COLOR);
+        let slice_view = SliceView::new(simulation.grid_dimensions());
+        render_params.update_slice_view(&slice_view);
 
-        let compute_params_buffer = device.create_buffer_init(
+        let render_params_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
-                label: Some("Compute Parameters Buffer"),
-                contents: bytemuck::cast_slice(&[compute_params]),
+                label: Some("Render Parameters Buffer"),
+                contents: bytemuck::cast_slice(&[render_params]),
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             }
         );
 
-        let compute_params_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Compute Pipeline Bind Group Layout"),
+        let render_params_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Render Params Bind Group Layout"),
             entries: &[
-                // 0. Uniform buffer for compute params
+                // 0. Uniform buffer for render params. The legend's vertex shader reads the viewport.
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -209,59 +343,18 @@ impl State {
             ]
         });
 
-        let compute_params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Compute Params Bind Group"),
-            layout: &compute_params_bind_group_layout,
+        let render_params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Render Params Bind Group"),
+            layout: &render_params_bind_group_layout,
             entries: &[
-                // binding 0: Compute params
+                // binding 0: Render params
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: compute_params_buffer.as_entire_binding(),
+                    resource: render_params_buffer.as_entire_binding(),
                 },
             ],
         });
 
-        // TODO: Add note on why we're using a texture here instead of a buffer.
-        let scalar_field_texture_a = Texture::create_compute_texture(
-            &device,
-            CHANNEL_FORMAT,
-            Some("Scalar Field Texture A")
-        );
-
-        let scalar_field_texture_b = Texture::create_compute_texture(
-            &device,
-            CHANNEL_FORMAT,
-            Some("Scalar Field Texture B")
-        );
-
-        let scalar_field_ping_pong = PingPong::new(
-            scalar_field_texture_a,
-            scalar_field_texture_b,
-        );
-
-        let scalar_source_texture = Texture::create_compute_texture(
-            &device,
-            CHANNEL_FORMAT,
-            Some("Scalar Source Texture")
-        );
-
-        let velocity_vector_field_texture_a = Texture::create_compute_texture(
-            &device,
-            CHANNEL_FORMAT,
-            Some("Velocity Field Texture A")
-        );
-
-        let velocity_vector_field_texture_b = Texture::create_compute_texture(
-            &device,
-            CHANNEL_FORMAT,
-            Some("Velocity Field Texture B")
-        );
-
-        let velocity_vector_field_ping_pong = PingPong::new(
-            velocity_vector_field_texture_a,
-            velocity_vector_field_texture_b,
-        );
-
         // TODO: Rename
         let density_texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Density Texture Bind Group Layout"),
@@ -287,54 +380,157 @@ impl State {
             ]
         });
 
+        let hdr_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("HDR Bind Group Layout"),
+            entries: &[
+                // 0. HDR render target, read texel for texel
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+            ]
+        });
+        let hdr_bind_group = create_hdr_bind_group(&device, &hdr_bind_group_layout, &hdr_texture);
+
+        let nearest_sampler = SamplerConfig::NEAREST_CLAMP.create_sampler(&device, Some("render_nearest_sampler"));
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
                 bind_group_layouts: &[
                     Some(&camera_bind_group_layout),
-                    Some(&compute_params_bind_group_layout),
+                    Some(&render_params_bind_group_layout),
                     Some(&density_texture_bind_group_layout),
                 ],
                 immediate_size: 0,
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
+        // One pipeline per blend mode, so switching modes at runtime is just picking another one.
+        let render_pipelines = BlendMode::ALL.map(|blend_mode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!("Render Pipeline ({blend_mode:?})")),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &render_shader,
+                    entry_point: Some("vs_box"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &render_shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: Texture::HDR_FORMAT,
+                            blend: blend_mode.blend_state(),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                        Some(wgpu::ColorTargetState {
+                            format: Texture::LUMINANCE_FORMAT,
+                            blend: blend_mode.blend_state(),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: VOLUME_CULL_MODE,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: Some(false),
+                    depth_compare: Some(wgpu::CompareFunction::LessEqual),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                // Still shaded once per pixel, so the ray march costs no more with MSAA.
+                multisample: wgpu::MultisampleState {
+                    count: msaa_sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview_mask: None,
+                cache: None,
+            })
+        });
+
+        let box_edge_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Box Edge Pipeline Layout"),
+                bind_group_layouts: &[
+                    Some(&camera_bind_group_layout),
+                    Some(&render_params_bind_group_layout),
+                ],
+                immediate_size: 0,
+            });
+
+        // Same targets and depth buffer as the volume, which is drawn over it in the same pass.
+        // Writes depth, so the volume isn't drawn in front of the edges that are in front of it.
+        let box_edge_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Box Edge Pipeline"),
+            layout: Some(&box_edge_pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &render_shader,
-                entry_point: Some("vs_main"),
-                buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: Some("vs_box_edges"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &[("BOX_EDGE_DEPTH_OFFSET", BOX_EDGE_DEPTH_OFFSET as f64)],
+                    ..Default::default()
+                },
             },
             fragment: Some(wgpu::FragmentState {
                 module: &render_shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
+                entry_point: Some("fs_box_edges"),
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: Texture::HDR_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    // Left alone, so the edges don't count towards the auto-exposure.
+                    Some(wgpu::ColorTargetState {
+                        format: Texture::LUMINANCE_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::empty(),
+                    }),
+                ],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             }),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
+                topology: wgpu::PrimitiveTopology::LineList,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
+                cull_mode: None,
                 polygon_mode: wgpu::PolygonMode::Fill,
                 unclipped_depth: false,
                 conservative: false,
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: Some(false),
-                depth_compare: Some(wgpu::CompareFunction::LessEqual),
+                depth_write_enabled: Some(true),
+                depth_compare: Some(wgpu::CompareFunction::Less),
                 stencil: wgpu::StencilState::default(),
+                // Lines can't be biased. vs_box_edges pushes them back by BOX_EDGE_DEPTH_OFFSET instead.
                 bias: wgpu::DepthBiasState::default(),
             }),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: msaa_sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -342,272 +538,105 @@ impl State {
             cache: None,
         });
 
-        let add_source_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Add Source Bind Group Layout"),
-            entries: &[
-                //  Source texture write.
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::StorageTexture {
-                        access: wgpu::StorageTextureAccess::WriteOnly,
-                        format: CHANNEL_FORMAT,
-                        view_dimension: wgpu::TextureViewDimension::D3,
-                    },
-                    count: None,
-                },
-            ]
-        });
-
-        let add_source_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Add Source Bind Group"),
-            layout: &add_source_bind_group_layout,
-            entries: &[
-                // binding 0: Scalar field
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&scalar_source_texture.view)
-                },
-            ],
-        });
-
-        let add_source_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Add Source Pipeline Layout"),
-                bind_group_layouts: &[
-                    Some(&compute_params_bind_group_layout),
-                    Some(&add_source_bind_group_layout),
-                ],
-                immediate_size: 0,
-            });
-
-        let remove_source_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Remove Source Pipeline Layout"),
-                bind_group_layouts: &[
-                    Some(&add_source_bind_group_layout),
-                ],
-                immediate_size: 0,
-            });
-
-        let add_source_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Add Source Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("add_source.wgsl").into()),
-        });
-
-        let add_source_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Source Pipeline"),
-            layout: Some(&add_source_pipeline_layout),
-            module: &add_source_shader,
-            // Will default to @compute
-            entry_point: None,
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            cache: None,
-        });
-
-        let remove_source_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Remove Source Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("remove_source.wgsl").into()),
-        });
-
-        let remove_source_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Remove Source Pipeline"),
-            layout: Some(&remove_source_pipeline_layout),
-            module: &remove_source_shader,
-            // Will default to @compute
-            entry_point: None,
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            cache: None,
-        });
-
-        // Create advect scalars compute step
-        let advect_scalars_compute_step = create_advect_scalars_compute_step(
-            &device,
-            &compute_params_bind_group_layout
-        );
-
-        let advect_velocity_compute_step = create_advect_velocity_compute_step(
-            &device,
-            &compute_params_bind_group_layout
-        );
-
-        let add_forces_to_velocity_compute_step = create_add_forces_to_velocity_compute_step(
-            &device,
-            &compute_params_bind_group_layout
+        let box_edge_vertex_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Box Edge Vertex Buffer"),
+                contents: bytemuck::cast_slice(&box_edge_vertices(simulation.box_min(), simulation.box_max())),
+                usage: wgpu::BufferUsages::VERTEX,
+            }
         );
 
-        let compute_divergence_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Compute Divergence Bind Group Layout"),
-            entries: &[
-                // 0. Velocity vector field texture read.
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D3,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                // 1. Divergence texture write.
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::StorageTexture {
-                        access: wgpu::StorageTextureAccess::WriteOnly,
-                        format: CHANNEL_FORMAT,
-                        view_dimension: wgpu::TextureViewDimension::D3,
-                    },
-                    count: None,
-                },
-                // 2. Sampler.
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                }
-            ]
-        });
-
-        let compute_divergence_pipeline_layout =
+        // Group 2 holds the density texture in the render pipeline and isn't read here.
+        let tonemap_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Compute Divergence Pipeline Layout"),
+                label: Some("Tonemap Pipeline Layout"),
                 bind_group_layouts: &[
-                    Some(&compute_params_bind_group_layout),
-                    Some(&compute_divergence_bind_group_layout),
+                    Some(&camera_bind_group_layout),
+                    Some(&render_params_bind_group_layout),
+                    None,
+                    Some(&hdr_bind_group_layout),
                 ],
                 immediate_size: 0,
             });
 
-        let compute_divergence_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Compute Divergence Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("compute_divergence.wgsl").into()),
-        });
-
-        let compute_divergence_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Divergence Pipeline"),
-            layout: Some(&compute_divergence_pipeline_layout),
-            module: &compute_divergence_shader,
-            // Will default to @compute
-            entry_point: None,
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        let tonemap_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tonemap Pipeline"),
+            layout: Some(&tonemap_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &render_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &render_shader,
+                entry_point: Some("fs_tonemap"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: Texture::surface_view_format(&config),
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview_mask: None,
             cache: None,
         });
 
-        let divergence_texture = Texture::create_compute_texture(
-            &device,
-            CHANNEL_FORMAT,
-            Some("Divergence Texture")
-        );
-
-        let pressure_texture_a = Texture::create_compute_texture(
-            &device,
-            CHANNEL_FORMAT,
-            Some("Pressure Texture A")
-        );
-
-        let pressure_texture_b = Texture::create_compute_texture(
-            &device,
-            CHANNEL_FORMAT,
-            Some("Pressure Texture B")
-        );
-
-        let pressure_ping_pong = PingPong::new(
-            pressure_texture_a,
-            pressure_texture_b,
-        );
-
-        let compute_pressure_compute_step = create_compute_pressure_compute_step(
-            &device,
-            &compute_params_bind_group_layout
-        );
-
-        let subtract_pressure_gradient_compute_step = create_subtract_pressure_gradient_compute_step(
-            &device,
-            &compute_params_bind_group_layout
-        );
-
-        let compute_curl_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Compute Curl Bind Group Layout"),
-            entries: &[
-                // 0. Velocity vector field texture read.
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D3,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                // 1. Curl texture write.
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::StorageTexture {
-                        access: wgpu::StorageTextureAccess::WriteOnly,
-                        format: CHANNEL_FORMAT,
-                        view_dimension: wgpu::TextureViewDimension::D3,
-                    },
-                    count: None,
-                },
-                // 2. Sampler.
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                }
-            ]
-        });
-
-        let compute_curl_pipeline_layout =
+        // The overlays only read the render params, so they don't need the density texture group.
+        let legend_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Compute Curl Pipeline Layout"),
+                label: Some("Legend Pipeline Layout"),
                 bind_group_layouts: &[
-                    Some(&compute_params_bind_group_layout),
-                    Some(&compute_curl_bind_group_layout),
+                    Some(&camera_bind_group_layout),
+                    Some(&render_params_bind_group_layout),
                 ],
                 immediate_size: 0,
             });
 
-        let compute_curl_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Compute Curl Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("compute_curl.wgsl").into()),
-        });
-
-        let compute_curl_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Curl Pipeline"),
-            layout: Some(&compute_curl_pipeline_layout),
-            module: &compute_curl_shader,
-            // Will default to @compute
-            entry_point: None,
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-            cache: None,
-        });
-
-        let curl_texture = Texture::create_compute_texture(
-            &device,
-            CHANNEL_FORMAT,
-            Some("Curl Texture")
-        );
-
-        let add_vorticity_confinement_force_compute_step = create_add_vorticity_confinement_force_compute_step(
+        let legend_pipeline = create_overlay_pipeline(
             &device,
-            &compute_params_bind_group_layout
+            &legend_pipeline_layout,
+            &render_shader,
+            Texture::surface_view_format(&config),
+            "Legend Pipeline",
+            "vs_legend",
+            "fs_legend",
         );
-
-        let compute_temperature_compute_step = create_compute_temperature_compute_step(
+        // Drawn the same way as the legend, from the frame stats in the render params.
+        let stats_pipeline = create_overlay_pipeline(
             &device,
-            &compute_params_bind_group_layout
+            &legend_pipeline_layout,
+            &render_shader,
+            Texture::surface_view_format(&config),
+            "Stats Pipeline",
+            "vs_stats",
+            "fs_stats",
         );
-
-        let compute_smoke_compute_step = create_compute_smoke_compute_step(
+        // Drawn like an overlay, but over the whole screen with the full screen triangle, and
+        // with the field to slice at group 2 like the volume.
+        let slice_pipeline = create_overlay_pipeline(
             &device,
-            &compute_params_bind_group_layout
+            &render_pipeline_layout,
+            &render_shader,
+            Texture::surface_view_format(&config),
+            "Slice Pipeline",
+            "vs_main",
+            "fs_slice",
         );
 
         Ok(Self {
@@ -616,61 +645,124 @@ impl State {
             queue,
             config,
             is_surface_configured: false,
+            occluded: false,
+            minimized: false,
+            present_modes: surface_caps.present_modes,
+            msaa_sample_count,
             depth_texture,
+            hdr_texture,
+            hdr_msaa_texture,
+            luminance_msaa_texture,
+            hdr_bind_group_layout,
+            hdr_bind_group,
+            luminance_texture,
+            auto_exposure,
+            exposure: render_settings.exposure,
+            target_exposure: render_settings.exposure,
+            density_histogram,
+            smoke_density_scale: 1.0,
+            target_smoke_density_scale: 1.0,
+            density_range: None,
+            density_range_locked: false,
+            gpu_profiler,
+            gpu_profiling: GPU_PROFILING,
             camera,
             camera_controller,
             projection,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
-            render_pipeline,
+            render_params,
+            render_params_buffer,
+            render_params_bind_group,
+            render_params_dirty: false,
+            render_pipelines,
+            box_edge_pipeline,
+            box_edge_vertex_buffer,
+            tonemap_pipeline,
+            legend_pipeline,
+            stats_pipeline,
+            slice_pipeline,
             density_texture_bind_group_layout,
-            compute_params,
-            compute_params_bind_group,
-            compute_params_buffer,
-            add_source_pipeline,
-            remove_source_pipeline,
-            add_source_bind_group,
-            scalar_field_ping_pong,
-            velocity_vector_field_ping_pong,
-            scalar_source_texture,
-            advect_scalars_compute_step,
-            advect_velocity_compute_step,
-            add_forces_to_velocity_compute_step,
-            compute_divergence_bind_group_layout,
-            compute_divergence_pipeline,
-            divergence_texture,
-            compute_pressure_compute_step,
-            pressure_ping_pong,
-            subtract_pressure_gradient_compute_step,
-            compute_curl_bind_group_layout,
-            compute_curl_pipeline,
-            curl_texture,
-            add_vorticity_confinement_force_compute_step,
-            compute_temperature_compute_step,
-            compute_smoke_compute_step,
-            elapsed_time: 0.0,
-            pending_input: false,
+            nearest_sampler,
+            render_settings,
+            simulation,
+            timestep,
+            pending_substeps: 0,
+            paused,
+            single_step_requested: false,
             mouse_pressed: false,
+            mouse_look: false,
+            cursor_position: None,
+            injecting_at_cursor: false,
+            camera_hud_enabled: false,
+            clear_color: BACKGROUND_COLOR,
+            default_clear_color: BACKGROUND_COLOR,
+            stats_overlay_enabled: STATS_OVERLAY,
+            stats_frames: 0,
+            stats_elapsed: 0.0,
+            slice_view_enabled: false,
+            slice_view,
+            #[cfg(not(target_arch = "wasm32"))]
+            frame_recorder: FrameRecorder::new(FRAME_OUTPUT_DIR.into()),
             window,
         })
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
+        self.minimized = width == 0 || height == 0;
         if width > 0 && height > 0 {
             self.config.width = width;
             self.config.height = height;
             self.surface.configure(&self.device, &self.config);
             self.projection.resize(width, height);
-            self.depth_texture = Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
-            self.compute_params.update_viewport(&self.config);
+            self.depth_texture =
+                Texture::create_depth_texture(&self.device, &self.config, self.msaa_sample_count, "depth_texture");
+            self.hdr_texture = Texture::create_hdr_texture(&self.device, &self.config, "hdr_texture");
+            self.hdr_msaa_texture = create_msaa_texture(
+                &self.device,
+                &self.config,
+                "hdr_msaa_texture",
+                Texture::HDR_FORMAT,
+                self.msaa_sample_count,
+            );
+            self.hdr_bind_group = create_hdr_bind_group(&self.device, &self.hdr_bind_group_layout, &self.hdr_texture);
+            self.luminance_texture = Texture::create_luminance_texture(&self.device, &self.config, "luminance_texture");
+            self.luminance_msaa_texture = create_msaa_texture(
+                &self.device,
+                &self.config,
+                "luminance_msaa_texture",
+                Texture::LUMINANCE_FORMAT,
+                self.msaa_sample_count,
+            );
+            self.auto_exposure.resize(&self.device, &self.luminance_texture.view);
+            #[cfg(not(target_arch = "wasm32"))]
+            self.frame_recorder.resize(&self.device, &self.config);
+            self.render_params.update_viewport(&self.config);
+            self.render_params_dirty = true;
             self.is_surface_configured = true;
         }
     }
 
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+    }
+
+    /// Whether the window can't be seen, minimized or occluded. The app neither updates nor renders
+    /// while it is, so nothing is simulated and no redraws are requested until it shows again.
+    pub fn is_hidden(&self) -> bool {
+        self.occluded || self.minimized
+    }
+
     pub fn update(&mut self, dt: instant::Duration) {
         self.camera_controller.update_camera(&mut self.camera, dt);
-        self.camera_uniform.update(&self.camera, &self.projection);
+        let camera_changed = self.camera_uniform.update(&self.camera, &self.projection);
+        // Picked every frame rather than on cursor motion, so the source stays under the cursor
+        // while the camera moves.
+        let cursor_source = self.cursor_position
+            .filter(|_| self.injecting_at_cursor)
+            .and_then(|cursor| self.pick(cursor));
+        self.simulation.sim_params_mut().update_cursor_source(cursor_source);
         /*
         Potential to optimize:
         We can create a separate buffer and copy its contents to our camera_buffer. The new buffer
@@ -679,313 +771,859 @@ impl State {
         can do some speed optimizations, which it couldn't if we could access the buffer via
         the CPU.
          */
-        // TODO: Make this a fixed timestep.
-        self.elapsed_time += dt.as_secs_f32();
-        self.compute_params.update_dt(dt);
-        self.compute_params.update_elapsed_time(self.elapsed_time);
-        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
-        self.queue.write_buffer(&self.compute_params_buffer, 0, bytemuck::cast_slice(&[self.compute_params]));
-    }
-
-    pub fn handle_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode, key_state: ElementState) {
-        if code == KeyCode::Escape && key_state.is_pressed() {
-            event_loop.exit();
-        } else if code == KeyCode::KeyF && key_state.is_pressed() {
-            self.pending_input = !self.pending_input;
+        // Time doesn't accumulate while paused, so resuming doesn't run a backlog of steps.
+        self.pending_substeps = if self.paused {
+            std::mem::take(&mut self.single_step_requested) as u32
         } else {
-            self.camera_controller.process_keyboard(code, key_state);
+            self.timestep.advance(dt.as_secs_f32())
+        };
+        if self.stats_overlay_enabled {
+            self.update_frame_stats(dt.as_secs_f32());
+        }
+        if self.render_settings.auto_exposure {
+            self.update_auto_exposure(dt.as_secs_f32());
+        }
+        let auto_density_range = self.measures_density_range();
+        if auto_density_range {
+            self.update_auto_density_range(dt.as_secs_f32());
+        }
+        // A still camera (e.g. while paused) leaves the uniform as it was uploaded.
+        if camera_changed {
+            self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        }
+        // Otherwise the render params only change on input or resize, which mark them dirty.
+        if self.render_settings.auto_exposure || auto_density_range {
+            let previous = self.render_params;
+            self.render_params.update_exposure(self.exposure);
+            self.render_params.update_smoke_density_scale(self.smoke_density_scale);
+            self.render_params_dirty |= self.render_params != previous;
+        }
+        if std::mem::take(&mut self.render_params_dirty) {
+            self.queue.write_buffer(&self.render_params_buffer, 0, bytemuck::cast_slice(&[self.render_params]));
         }
     }
 
-    pub fn handle_mouse_click(&mut self, mouse_state: ElementState) {
-        self.mouse_pressed = mouse_state.is_pressed();
+    /// True while auto density range is on and not locked.
+    fn measures_density_range(&self) -> bool {
+        self.render_settings.auto_density_range && !self.density_range_locked
     }
 
-    pub fn render(&mut self) -> anyhow::Result<()> {
-        self.window.request_redraw();
+    /// Picks up the latest density histogram, if any, and moves the smoke density scale towards
+    /// the one that maps the upper percentile to the reference density. Adapts in log space like
+    /// the exposure.
+    fn update_auto_density_range(&mut self, dt: f32) {
+        if let Some(range) = self.density_histogram.poll(&self.device, AUTO_DENSITY_PERCENTILES) {
+            self.target_smoke_density_scale = (AUTO_DENSITY_REFERENCE / range.high)
+                .clamp(AUTO_DENSITY_SCALE_RANGE[0], AUTO_DENSITY_SCALE_RANGE[1]);
+            self.density_range = Some(range);
+        }
 
-        // We can't render unless the surface is configured
-        if !self.is_surface_configured {
-            return Ok(());
+        let blend = 1.0 - (-AUTO_DENSITY_ADAPTATION_RATE * dt).exp();
+        self.smoke_density_scale = (self.smoke_density_scale.ln()
+            + (self.target_smoke_density_scale.ln() - self.smoke_density_scale.ln()) * blend)
+            .exp();
+    }
+
+    /// Averages the frame time over STATS_UPDATE_INTERVAL and hands it to the stats overlay once
+    /// the interval is over, so the numbers stay readable.
+    fn update_frame_stats(&mut self, dt: f32) {
+        self.stats_frames += 1;
+        self.stats_elapsed += dt;
+        if self.stats_elapsed >= STATS_UPDATE_INTERVAL {
+            let frame_time = self.stats_elapsed / self.stats_frames as f32;
+            self.render_params.update_frame_stats(1.0 / frame_time, frame_time * 1000.0);
+            self.render_params_dirty = true;
+            self.stats_frames = 0;
+            self.stats_elapsed = 0.0;
         }
+    }
 
-        let output = match self.surface.get_current_texture() {
-            wgpu::CurrentSurfaceTexture::Success(surface_texture) => surface_texture,
-            wgpu::CurrentSurfaceTexture::Suboptimal(surface_texture) => {
-                self.surface.configure(&self.device, &self.config);
-                surface_texture
+    /// Picks up the latest luminance measurement, if any, and moves the exposure towards the one
+    /// that maps the average luminance to the key value. Adapts in log space so brightening and
+    /// darkening feel equally fast.
+    fn update_auto_exposure(&mut self, dt: f32) {
+        if let Some(average_luminance) = self.auto_exposure.poll(&self.device) {
+            self.target_exposure = (AUTO_EXPOSURE_KEY / average_luminance)
+                .clamp(AUTO_EXPOSURE_RANGE[0], AUTO_EXPOSURE_RANGE[1]);
+        }
+
+        let blend = 1.0 - (-AUTO_EXPOSURE_ADAPTATION_RATE * dt).exp();
+        self.exposure = (self.exposure.ln() + (self.target_exposure.ln() - self.exposure.ln()) * blend).exp();
+    }
+
+    pub fn handle_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode, key_state: ElementState) {
+        if code == KeyCode::Escape && key_state.is_pressed() && self.mouse_look {
+            // Gives the cursor back first, like games do. The next Escape quits.
+            self.set_mouse_look(false);
+        } else if code == KeyCode::Escape && key_state.is_pressed() {
+            self.shutdown();
+            event_loop.exit();
+        } else if code == KeyCode::KeyT && key_state.is_pressed() {
+            self.set_mouse_look(!self.mouse_look);
+        } else if code == KeyCode::KeyF && key_state.is_pressed() {
+            self.simulation.set_pending_input(!self.simulation.pending_input());
+        } else if code == KeyCode::Tab && key_state.is_pressed() {
+            self.paused = !self.paused;
+            log::info!("Simulation paused: {}", self.paused);
+        } else if code == KeyCode::Enter && key_state.is_pressed() {
+            if self.paused {
+                self.single_step_requested = true;
             }
-            wgpu::CurrentSurfaceTexture::Timeout
-            | wgpu::CurrentSurfaceTexture::Occluded
-            | wgpu::CurrentSurfaceTexture::Validation => {
-                // Skip this frame
-                return Ok(());
+        } else if code == KeyCode::KeyM && key_state.is_pressed() {
+            self.simulation.set_advection_scheme(self.simulation.advection_scheme().next());
+            log::info!("Advection scheme: {:?}", self.simulation.advection_scheme());
+        } else if code == KeyCode::KeyV && key_state.is_pressed() {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Err(e) = self.export_velocity_vtk() {
+                log::error!("Failed to export velocity field: {e}");
             }
-            wgpu::CurrentSurfaceTexture::Outdated => {
-                self.surface.configure(&self.device, &self.config);
-                return Ok(());
+        } else if code == KeyCode::KeyO && key_state.is_pressed() {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Err(e) = self.export_density() {
+                log::error!("Failed to export density field: {e}");
             }
-            wgpu::CurrentSurfaceTexture::Lost => {
-                // You could recreate the devices and all resources
-                // created with it here, but we'll just bail
-                anyhow::bail!("Lost device");
+        } else if code == KeyCode::Backspace && key_state.is_pressed() {
+            self.reset();
+        } else if code == KeyCode::KeyI && key_state.is_pressed() {
+            let obstacle = self.simulation.obstacle().next();
+            self.simulation.set_obstacle(&self.queue, obstacle);
+            log::info!("Obstacle: {obstacle:?}");
+        } else if code == KeyCode::KeyQ && key_state.is_pressed() {
+            self.cycle_background();
+        } else if code == KeyCode::F3 && key_state.is_pressed() {
+            self.stats_overlay_enabled = !self.stats_overlay_enabled;
+            // The first numbers shown cover a whole interval of frames with the overlay on.
+            self.stats_frames = 0;
+            self.stats_elapsed = 0.0;
+            self.render_params.update_frame_stats(0.0, 0.0);
+            self.render_params_dirty = true;
+            log::info!("Stats overlay: {}", self.stats_overlay_enabled);
+        } else if code == KeyCode::Backquote && key_state.is_pressed() {
+            self.slice_view_enabled = !self.slice_view_enabled;
+            log::info!("Slice view: {}", self.slice_view_enabled);
+            if self.slice_view_enabled {
+                self.log_slice_view();
             }
-        };
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
-        });
+        } else if code == KeyCode::Slash && key_state.is_pressed() {
+            self.slice_view.field = self.slice_view.field.next();
+            self.update_slice_view();
+        } else if code == KeyCode::Backslash && key_state.is_pressed() {
+            self.slice_view.next_axis(self.simulation.grid_dimensions());
+            self.update_slice_view();
+        } else if code == KeyCode::Digit7 && key_state.is_pressed() {
+            self.slice_view.offset(-1, self.simulation.grid_dimensions());
+            self.update_slice_view();
+        } else if code == KeyCode::Digit8 && key_state.is_pressed() {
+            self.slice_view.offset(1, self.simulation.grid_dimensions());
+            self.update_slice_view();
+        } else if code == KeyCode::F4 && key_state.is_pressed() {
+            self.check_fields();
+        } else if code == KeyCode::F12 && key_state.is_pressed() {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Err(e) = self.capture_frame() {
+                log::error!("Failed to capture frame: {e}");
+            }
+        } else if code == KeyCode::KeyR && key_state.is_pressed() {
+            #[cfg(not(target_arch = "wasm32"))]
+            self.toggle_frame_recording();
+        } else if code == KeyCode::KeyL && key_state.is_pressed() {
+            self.render_settings.lights_enabled = !self.render_settings.lights_enabled;
+            self.render_params.update_render_settings(&self.render_settings);
+            self.render_params_dirty = true;
+            log::info!("Lights enabled: {}", self.render_settings.lights_enabled);
+        } else if code == KeyCode::KeyY && key_state.is_pressed() {
+            self.rotate_lights(-LIGHT_ROTATION_STEP, 0.0);
+        } else if code == KeyCode::KeyU && key_state.is_pressed() {
+            self.rotate_lights(LIGHT_ROTATION_STEP, 0.0);
+        } else if code == KeyCode::Home && key_state.is_pressed() {
+            self.rotate_lights(0.0, LIGHT_ROTATION_STEP);
+        } else if code == KeyCode::End && key_state.is_pressed() {
+            self.rotate_lights(0.0, -LIGHT_ROTATION_STEP);
+        } else if code == KeyCode::KeyX && key_state.is_pressed() {
+            self.render_settings.auto_exposure = !self.render_settings.auto_exposure;
+            if !self.render_settings.auto_exposure {
+                self.exposure = self.render_settings.exposure;
+                self.target_exposure = self.render_settings.exposure;
+                self.render_params.update_exposure(self.exposure);
+                self.render_params_dirty = true;
+            }
+            log::info!("Auto-exposure enabled: {}", self.render_settings.auto_exposure);
+        } else if code == KeyCode::KeyJ && key_state.is_pressed() {
+            self.render_settings.auto_density_range = !self.render_settings.auto_density_range;
+            if !self.render_settings.auto_density_range {
+                self.smoke_density_scale = 1.0;
+                self.target_smoke_density_scale = 1.0;
+                self.density_range = None;
+                self.density_range_locked = false;
+                self.render_params.update_smoke_density_scale(self.smoke_density_scale);
+                self.render_params_dirty = true;
+            }
+            log::info!("Auto density range enabled: {}", self.render_settings.auto_density_range);
+        } else if code == KeyCode::KeyK && key_state.is_pressed() {
+            if self.render_settings.auto_density_range {
+                self.density_range_locked = !self.density_range_locked;
+                log::info!(
+                    "Density range locked: {} (smoke density scale {:.3})",
+                    self.density_range_locked,
+                    self.smoke_density_scale,
+                );
+            }
+        } else if code == KeyCode::KeyN && key_state.is_pressed() {
+            self.render_settings.nearest_sampling = !self.render_settings.nearest_sampling;
+            log::info!("Nearest neighbor rendering: {}", self.render_settings.nearest_sampling);
+        } else if code == KeyCode::KeyC && key_state.is_pressed() {
+            self.render_settings.legend_enabled = !self.render_settings.legend_enabled;
+            log::info!("Color legend: {}", self.render_settings.legend_enabled);
+        } else if code == KeyCode::F1 && key_state.is_pressed() {
+            self.cycle_present_mode();
+        } else if code == KeyCode::F2 && key_state.is_pressed() {
+            self.render_settings.box_edges_enabled = !self.render_settings.box_edges_enabled;
+            log::info!("Box edges: {}", self.render_settings.box_edges_enabled);
+        } else if code == KeyCode::KeyH && key_state.is_pressed() {
+            self.camera_hud_enabled = !self.camera_hud_enabled;
+            log::info!("Camera HUD: {}", self.camera_hud_enabled);
+        } else if code == KeyCode::KeyE && key_state.is_pressed() {
+            self.render_settings.emission_preset = self.render_settings.emission_preset.next();
+            self.simulation.sim_params_mut().update_emission_bands(self.render_settings.emission_preset.bands());
+            self.render_params.update_render_settings(&self.render_settings);
+            self.render_params_dirty = true;
+            log::info!("Emission preset: {:?}", self.render_settings.emission_preset);
+        } else if code == KeyCode::KeyG && key_state.is_pressed() {
+            if self.gpu_profiler.is_some() {
+                self.gpu_profiling = !self.gpu_profiling;
+                log::info!("GPU profiling enabled: {}", self.gpu_profiling);
+            } else {
+                log::warn!("GPU profiling needs timestamp queries, which the adapter does not support");
+            }
+        } else if code == KeyCode::KeyZ && key_state.is_pressed() {
+            self.render_settings.color_ramp = self.render_settings.color_ramp.next();
+            self.render_params.update_render_settings(&self.render_settings);
+            self.render_params_dirty = true;
+            log::info!("Color ramp: {:?}", self.render_settings.color_ramp);
+        } else if code == KeyCode::KeyB && key_state.is_pressed() {
+            self.render_settings.blend_mode = self.render_settings.blend_mode.next();
+            log::info!("Blend mode: {:?}", self.render_settings.blend_mode);
+        } else if code == KeyCode::KeyP && key_state.is_pressed() {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Err(e) = self.save_render_settings() {
+                log::error!("Failed to save render settings: {e}");
+            }
+        } else if code == KeyCode::F10 && key_state.is_pressed() {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Err(e) = self.save_snapshot(crate::config::SNAPSHOT_PATH) {
+                log::error!("Failed to save snapshot: {e}");
+            }
+        } else if code == KeyCode::F11 && key_state.is_pressed() {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Err(e) = self.load_snapshot(crate::config::SNAPSHOT_PATH) {
+                log::error!("Failed to load snapshot: {e}");
+            }
+        } else if code == KeyCode::F5 && key_state.is_pressed() {
+            #[cfg(not(target_arch = "wasm32"))]
+            match self.camera.save_to_file(crate::config::CAMERA_POSE_PATH) {
+                Ok(()) => log::info!("Saved camera pose to {}", crate::config::CAMERA_POSE_PATH),
+                Err(e) => log::error!("Failed to save camera pose: {e}"),
+            }
+        } else if code == KeyCode::F9 && key_state.is_pressed() {
+            #[cfg(not(target_arch = "wasm32"))]
+            match self.camera.load_from_file(crate::config::CAMERA_POSE_PATH) {
+                Ok(()) => {
+                    // Uploaded right away, so the view jumps on the next frame even if the
+                    // camera then stays still.
+                    self.camera_uniform.update(&self.camera, &self.projection);
+                    self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+                    log::info!("Loaded camera pose from {}", crate::config::CAMERA_POSE_PATH);
+                }
+                Err(e) => log::error!("Failed to load camera pose: {e}"),
+            }
+        } else if code == KeyCode::Minus && key_state.is_pressed() {
+            self.timestep.set_time_scale(self.timestep.time_scale() * 0.5);
+            log::info!("Time scale: {}", self.timestep.time_scale());
+        } else if code == KeyCode::Equal && key_state.is_pressed() {
+            self.timestep.set_time_scale(self.timestep.time_scale() * 2.0);
+            log::info!("Time scale: {}", self.timestep.time_scale());
+        } else if code == KeyCode::Comma && key_state.is_pressed() {
+            let params = self.simulation.sim_params_mut();
+            params.update_source_fuel(params.source_fuel() - SOURCE_FUEL_STEP);
+            log::info!("Source fuel: {}", params.source_fuel());
+        } else if code == KeyCode::Period && key_state.is_pressed() {
+            let params = self.simulation.sim_params_mut();
+            params.update_source_fuel(params.source_fuel() + SOURCE_FUEL_STEP);
+            log::info!("Source fuel: {}", params.source_fuel());
+        } else if code == KeyCode::NumpadDivide && key_state.is_pressed() {
+            let params = self.simulation.sim_params_mut();
+            params.update_source_density(params.source_density() - SOURCE_DENSITY_STEP);
+            log::info!("Source density: {}", params.source_density());
+        } else if code == KeyCode::NumpadMultiply && key_state.is_pressed() {
+            let params = self.simulation.sim_params_mut();
+            params.update_source_density(params.source_density() + SOURCE_DENSITY_STEP);
+            log::info!("Source density: {}", params.source_density());
+        } else if let Some(offset) = source_offset_for_key(code).filter(|_| key_state.is_pressed()) {
+            let params = self.simulation.sim_params_mut();
+            params.offset_sources(offset);
+            log::info!("Sources: {:?}", params.sources());
+        } else if code == KeyCode::NumpadSubtract && key_state.is_pressed() {
+            let params = self.simulation.sim_params_mut();
+            params.scale_source_radii(1.0 / SOURCE_RADIUS_SCALE);
+            log::info!("Sources: {:?}", params.sources());
+        } else if code == KeyCode::NumpadAdd && key_state.is_pressed() {
+            let params = self.simulation.sim_params_mut();
+            params.scale_source_radii(SOURCE_RADIUS_SCALE);
+            log::info!("Sources: {:?}", params.sources());
+        } else if code == KeyCode::BracketLeft && key_state.is_pressed() {
+            self.timestep.set_max_substeps_per_frame(self.timestep.max_substeps_per_frame().saturating_sub(1));
+            log::info!("Max substeps per frame: {}", self.timestep.max_substeps_per_frame());
+        } else if code == KeyCode::BracketRight && key_state.is_pressed() {
+            self.timestep.set_max_substeps_per_frame(self.timestep.max_substeps_per_frame() + 1);
+            log::info!("Max substeps per frame: {}", self.timestep.max_substeps_per_frame());
+        } else if code == KeyCode::Semicolon && key_state.is_pressed() {
+            let iterations = self.simulation.pressure_iterations().saturating_sub(PRESSURE_ITERATIONS_STEP);
+            self.simulation.set_pressure_iterations(iterations);
+            log::info!("Pressure iterations: {}", self.simulation.pressure_iterations());
+        } else if code == KeyCode::Quote && key_state.is_pressed() {
+            let iterations = self.simulation.pressure_iterations() + PRESSURE_ITERATIONS_STEP;
+            self.simulation.set_pressure_iterations(iterations);
+            log::info!("Pressure iterations: {}", self.simulation.pressure_iterations());
+        } else if code == KeyCode::PageDown && key_state.is_pressed() {
+            let steps = self.render_params.raymarch_steps().saturating_sub(RAYMARCH_STEPS_STEP);
+            self.render_params.update_raymarch_steps(steps);
+            self.render_params_dirty = true;
+            log::info!("Ray march steps: {}", self.render_params.raymarch_steps());
+        } else if code == KeyCode::PageUp && key_state.is_pressed() {
+            let steps = self.render_params.raymarch_steps() + RAYMARCH_STEPS_STEP;
+            self.render_params.update_raymarch_steps(steps);
+            self.render_params_dirty = true;
+            log::info!("Ray march steps: {}", self.render_params.raymarch_steps());
+        } else if code == KeyCode::Digit9 && key_state.is_pressed() {
+            let params = self.simulation.sim_params_mut();
+            params.update_vorticity_strength(params.vorticity_strength() - VORTICITY_STRENGTH_STEP);
+            log::info!("Vorticity strength: {}", params.vorticity_strength());
+        } else if code == KeyCode::Digit0 && key_state.is_pressed() {
+            let params = self.simulation.sim_params_mut();
+            params.update_vorticity_strength(params.vorticity_strength() + VORTICITY_STRENGTH_STEP);
+            log::info!("Vorticity strength: {}", params.vorticity_strength());
+        } else if let Some(preset) = scene_preset_for_key(code).filter(|_| key_state.is_pressed()) {
+            match self.simulation.load_scene(&self.queue, preset) {
+                Ok(()) => log::info!("Loaded scene preset: {preset:?}"),
+                Err(e) => log::error!("Failed to load scene preset {preset:?}: {e}"),
+            }
+        } else if let Some(dim) = grid_dimension_length_for_key(code).filter(|_| key_state.is_pressed()) {
+            self.set_grid_dim(dim);
+        } else {
+            self.camera_controller.process_keyboard(code, key_state);
+        }
+    }
 
-        /* Add Sources if Present */
-        if self.pending_input {
-            {
-                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-                compute_pass.set_pipeline(&self.add_source_pipeline);
+    pub fn handle_mouse_click(&mut self, mouse_state: ElementState) {
+        self.mouse_pressed = mouse_state.is_pressed();
+    }
 
-                compute_pass.set_bind_group(0, &self.compute_params_bind_group, &[]);
-                compute_pass.set_bind_group(1, &self.add_source_bind_group, &[]);
+    /// Rotates the camera while the left button is held, or always in mouse look.
+    pub fn handle_mouse_motion(&mut self, dx: f64, dy: f64) {
+        if self.mouse_pressed || self.mouse_look {
+            self.camera_controller.handle_mouse(dx, dy);
+        }
+    }
 
-                compute_pass.dispatch_workgroups(
-                    NUMBER_DISPATCHES_PER_DIMENSION,
-                    NUMBER_DISPATCHES_PER_DIMENSION,
-                    NUMBER_DISPATCHES_PER_DIMENSION
-                );
+    /// Grabs and hides the cursor for mouse look, or releases and shows it again. Stays off if the
+    /// platform can't grab the cursor.
+    pub fn set_mouse_look(&mut self, enabled: bool) {
+        if enabled == self.mouse_look {
+            return;
+        }
+        if enabled {
+            // Locked keeps the cursor in place, Confined only keeps it in the window. Platforms
+            // support one or the other.
+            let grabbed = self.window.set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(e) = grabbed {
+                log::error!("Failed to grab the cursor for mouse look: {e}");
+                return;
             }
+        } else if let Err(e) = self.window.set_cursor_grab(CursorGrabMode::None) {
+            log::error!("Failed to release the cursor: {e}");
         }
+        self.window.set_cursor_visible(!enabled);
+        self.mouse_look = enabled;
+        log::info!("Mouse look: {enabled}");
+    }
 
-        /* Simulation Steps */
+    pub fn handle_inject_button(&mut self, button_state: ElementState) {
+        self.injecting_at_cursor = button_state.is_pressed();
+    }
+
+    pub fn handle_cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.cursor_position = Some(position);
+    }
 
-        // Advect scalars
-        let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
-        let textures_read_only: [&wgpu::TextureView; 2] = [
-            self.velocity_vector_field_ping_pong.get_read(),
-            &self.scalar_source_texture.view
+    /// Point of the simulation grid under a cursor position, as a fraction of the grid (see
+    /// Simulation::pick). None if the cursor is over empty space outside the box.
+    fn pick(&self, cursor: PhysicalPosition<f64>) -> Option<[f32; 3]> {
+        let ndc = [
+            (cursor.x / self.config.width as f64 * 2.0 - 1.0) as f32,
+            (1.0 - cursor.y / self.config.height as f64 * 2.0) as f32,
         ];
+        let (origin, direction) = self.camera_uniform.ray(ndc);
+        self.simulation.pick(origin.into(), direction.into())
+    }
 
-        self.advect_scalars_compute_step.dispatch(
-            &self.device,
-            &mut encoder,
-            &self.compute_params_bind_group,
-            read_texture,
-            write_texture,
-            &textures_read_only,
-            Some(self.scalar_field_ping_pong.get_sampler()),
-            WORKGROUPS
-        );
+    /// Reads the velocity field back from the GPU and writes it to velocity_<step>.vtk in the
+    /// working directory. Velocities are converted to world units per second.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_velocity_vtk(&self) -> anyhow::Result<()> {
+        let voxels = self.simulation.velocity_field()
+            .get_read_texture()
+            .read_to_vec_f32(&self.device, &self.queue)?;
+
+        let box_min = self.simulation.box_min();
+        let box_max = self.simulation.box_max();
+        let grid = self.simulation.grid_dimensions();
+        let dimensions = [grid.width, grid.height, grid.depth_or_array_layers];
+
+        // The solver's velocities are in reference voxel lengths per second on every axis, even
+        // when the box stretches the voxels.
+        let vectors: Vec<[f32; 3]> = voxels
+            .iter()
+            .map(|v| [v[0], v[1], v[2]].map(|component| component * crate::config::GRID_VOXEL_SIDE_LENGTH))
+            .collect();
+
+        let path = std::path::PathBuf::from(format!("velocity_{}.vtk", self.simulation.step_count()));
+        crate::export::write_vtk_vector_field(&path, "velocity", &vectors, dimensions, box_min, box_max)?;
+        log::info!("Exported velocity field to {}", path.display());
 
-        self.scalar_field_ping_pong.swap();
+        Ok(())
+    }
 
-        // Compute temperature
-        let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
+    /// Reads the smoke density back from the GPU and writes it to density_<step>.vdb and
+    /// density_<step>.f32 in the working directory. Voxels at or below VDB_DENSITY_THRESHOLD are
+    /// left out of the sparse VDB grid, the raw volume has all of them (see
+    /// export::write_raw_f32_volume for its layout).
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_density(&self) -> anyhow::Result<()> {
+        let voxels = self.simulation.scalar_field()
+            .get_read_texture()
+            .read_to_vec_f32(&self.device, &self.queue)?;
+        let density: Vec<f32> = voxels.iter().map(|v| v[0]).collect();
+
+        let grid = self.simulation.grid_dimensions();
+        let dimensions = [grid.width, grid.height, grid.depth_or_array_layers];
+        let vdb_path = std::path::PathBuf::from(format!("density_{}.vdb", self.simulation.step_count()));
+        crate::export::write_vdb_scalar_field(
+            &vdb_path,
+            "density",
+            &density,
+            dimensions,
+            self.simulation.box_min(),
+            self.simulation.box_max(),
+            crate::config::VDB_DENSITY_THRESHOLD,
+        )?;
+        let raw_path = vdb_path.with_extension("f32");
+        crate::export::write_raw_f32_volume(&raw_path, &density, dimensions)?;
+        log::info!("Exported density field to {} and {}", vdb_path.display(), raw_path.display());
 
-        self.compute_temperature_compute_step.dispatch(
-            &self.device,
-            &mut encoder,
-            &self.compute_params_bind_group,
-            read_texture,
-            write_texture,
-            &[],
-            Some(self.scalar_field_ping_pong.get_sampler()),
-            WORKGROUPS
-        );
+        Ok(())
+    }
 
-        self.scalar_field_ping_pong.swap();
+    /// Logs a warning with the number of voxels that have gone NaN or infinite, which the renderer
+    /// would show as a black screen. See Simulation::non_finite_voxels.
+    fn check_fields(&self) {
+        match self.simulation.non_finite_voxels(&self.device, &self.queue) {
+            Ok((0, 0)) => log::info!("Every voxel of the scalar and velocity fields is finite"),
+            Ok((scalar, velocity)) => log::warn!(
+                "Non-finite voxels at step {}: {scalar} in the scalar field, {velocity} in the velocity field",
+                self.simulation.step_count(),
+            ),
+            Err(e) => log::error!("Failed to check the fields: {e}"),
+        }
+    }
 
-        // Decay smoke density
-        let (read_texture, write_texture) = self.scalar_field_ping_pong.get_read_and_write();
+    /// Restarts the velocity from the given initial field, as do later scene loads.
+    pub fn set_initial_velocity(&mut self, initial_velocity: InitialVelocityField) {
+        self.simulation.set_initial_velocity(&self.queue, initial_velocity);
+        log::info!("Initial velocity field: {initial_velocity:?}");
+    }
 
-        self.compute_smoke_compute_step.dispatch(
-            &self.device,
-            &mut encoder,
-            &self.compute_params_bind_group,
-            read_texture,
-            write_texture,
-            &[],
-            Some(self.scalar_field_ping_pong.get_sampler()),
-            WORKGROUPS
-        );
+    /// Restarts the velocity from the initial field generated at the given speed, as do later
+    /// scene loads.
+    pub fn set_initial_velocity_speed(&mut self, initial_velocity_speed: f32) {
+        self.simulation.set_initial_velocity_speed(&self.queue, initial_velocity_speed);
+        log::info!("Initial velocity speed: {initial_velocity_speed}");
+    }
 
-        self.scalar_field_ping_pong.swap();
+    /// Restarts the scalar field from the given initial density, as do resets until the next scene
+    /// load.
+    pub fn set_initial_density(&mut self, initial_density: InitialDensity) {
+        self.simulation.set_initial_density(&self.queue, initial_density);
+        log::info!("Initial density: {initial_density:?}");
+    }
 
-        // Advect velocity
-        let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
+    /// Starts the simulation over from the initial fields of the current scene, with injection
+    /// off. See Simulation::reset.
+    pub fn reset(&mut self) {
+        self.injecting_at_cursor = false;
+        match self.simulation.reset(&self.queue) {
+            Ok(()) => log::info!("Simulation reset"),
+            Err(e) => log::error!("Failed to reset the simulation: {e}"),
+        }
+    }
 
-        self.advect_velocity_compute_step.dispatch(
-            &self.device,
-            &mut encoder,
-            &self.compute_params_bind_group,
-            read_texture,
-            write_texture,
-            &[],
-            Some(self.velocity_vector_field_ping_pong.get_sampler()),
-            WORKGROUPS
-        );
+    /// Switches the simulation to a dim x dim x dim grid over the same box and starts the current
+    /// scene over on it. Only the field textures are recreated (see Simulation::set_grid_dimensions);
+    /// the camera, render pipelines and settings are left alone. A grid the device can't hold is
+    /// refused before anything is allocated, and the current one keeps running.
+    pub fn set_grid_dim(&mut self, dim: u32) {
+        let grid_dimensions = wgpu::Extent3d { width: dim, height: dim, depth_or_array_layers: dim };
+        if grid_dimensions == self.simulation.grid_dimensions() {
+            return;
+        }
+        self.injecting_at_cursor = false;
+        match self.simulation.set_grid_dimensions(&self.device, &self.queue, grid_dimensions) {
+            Ok(()) => log::info!("Grid: {dim}x{dim}x{dim}"),
+            Err(e) => log::error!("Failed to switch to a {dim}x{dim}x{dim} grid: {e}"),
+        }
+    }
+
+    /// Uploads a changed slice view. Changes made while it is hidden are kept for when it shows.
+    fn update_slice_view(&mut self) {
+        self.render_params.update_slice_view(&self.slice_view);
+        self.render_params_dirty = true;
+        self.log_slice_view();
+    }
 
-        self.velocity_vector_field_ping_pong.swap();
-
-        // Add forces to velocity
-        let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
-        let textures_read_only: [&wgpu::TextureView; 1] = [self.scalar_field_ping_pong.get_read()];
-
-        self.add_forces_to_velocity_compute_step.dispatch(
-            &self.device,
-            &mut encoder,
-            &self.compute_params_bind_group,
-            read_texture,
-            write_texture,
-            &textures_read_only,
-            None,
-            WORKGROUPS
+    fn log_slice_view(&self) {
+        let SliceView { field, axis, index } = self.slice_view;
+        let [low, high] = field.range();
+        log::info!(
+            "Slice view: {field:?} from {low} to {high}, slice {index} of {} along {axis:?}",
+            self.slice_view.slice_count(self.simulation.grid_dimensions()),
         );
+    }
 
-        self.velocity_vector_field_ping_pong.swap();
+    /// Replaces BACKGROUND_COLOR as the background, and as the one the background key cycles
+    /// back to.
+    pub fn set_default_background(&mut self, color: wgpu::Color) {
+        self.default_clear_color = color;
+        self.set_background(color);
+    }
 
-        // Vorticity Confinement
-        // Compute curl
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-            compute_pass.set_pipeline(&self.compute_curl_pipeline);
+    fn set_background(&mut self, color: wgpu::Color) {
+        self.clear_color = color;
+        self.render_params.update_background(color);
+        self.render_params_dirty = true;
+    }
 
-            let compute_curl_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Compute Curl Group"),
-                layout: &self.compute_curl_bind_group_layout,
-                entries: &[
-                    // binding 0: Velocity vector field read
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&self.velocity_vector_field_ping_pong.get_read())
-                    },
-                    // binding 1: Divergence scalar field write
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&self.curl_texture.view)
-                    },
-                    // binding 2: Sample
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Sampler(&self.velocity_vector_field_ping_pong.get_sampler())
-                    }
-                ],
-            });
+    /// Turns the directional lights, see Light::rotated.
+    fn rotate_lights(&mut self, yaw: f32, pitch: f32) {
+        for light in &mut self.render_settings.lights {
+            *light = light.rotated(yaw, pitch);
+        }
+        self.render_params.update_render_settings(&self.render_settings);
+        self.render_params_dirty = true;
+        let positions: Vec<[f32; 4]> = self.render_settings.lights.iter().map(|light| light.position()).collect();
+        log::info!("Lights: {positions:?}");
+    }
 
-            compute_pass.set_bind_group(0, &self.compute_params_bind_group, &[]);
-            compute_pass.set_bind_group(1, &compute_curl_bind_group, &[]);
+    /// Presents frames with the given mode, or with Fifo if the surface doesn't support it, and
+    /// logs the one picked. Takes effect on the next frame.
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        self.config.present_mode = select_present_mode(present_mode, &self.present_modes);
+        if self.is_surface_configured {
+            self.surface.configure(&self.device, &self.config);
+        }
+        log::info!("Present mode: {:?}", self.config.present_mode);
+    }
 
-            compute_pass.dispatch_workgroups(
-                NUMBER_DISPATCHES_PER_DIMENSION,
-                NUMBER_DISPATCHES_PER_DIMENSION,
-                NUMBER_DISPATCHES_PER_DIMENSION
-            );
+    /// Switches to the next present mode the surface supports.
+    fn cycle_present_mode(&mut self) {
+        let current = self.present_modes.iter().position(|mode| *mode == self.config.present_mode);
+        let next = current.map_or(0, |i| (i + 1) % self.present_modes.len());
+        self.set_present_mode(self.present_modes[next]);
+    }
+
+    /// Switches to the next of the default background, black and white that differs from the
+    /// current one.
+    fn cycle_background(&mut self) {
+        let choices = [self.default_clear_color, wgpu::Color::BLACK, wgpu::Color::WHITE];
+        let current = choices.iter().position(|color| *color == self.clear_color).unwrap_or(0);
+        let next = (1..=choices.len())
+            .map(|i| choices[(current + i) % choices.len()])
+            .find(|color| *color != self.clear_color)
+            .unwrap_or(self.clear_color);
+        self.set_background(next);
+        log::info!("Background: {:.2}, {:.2}, {:.2}", next.r, next.g, next.b);
+    }
+
+    /// Starts or stops recording frames to the frame output directory.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn toggle_frame_recording(&mut self) {
+        if self.frame_recorder.is_recording() {
+            self.frame_recorder.stop(&self.device);
+        } else if let Err(e) = self.frame_recorder.start(&self.device, &self.config) {
+            log::error!("Failed to start recording frames: {e}");
         }
+    }
 
-        // Add vorticity confinement force
-        let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
-        let textures_read_only: [&wgpu::TextureView; 1] = [&self.curl_texture.view];
-
-        self.add_vorticity_confinement_force_compute_step.dispatch(
-            &self.device,
-            &mut encoder,
-            &self.compute_params_bind_group,
-            read_texture,
-            write_texture,
-            &textures_read_only,
-            Some(self.velocity_vector_field_ping_pong.get_sampler()),
-            WORKGROUPS
-        );
+    /// Directory recorded frames are written to. Stops a recording in progress.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_frame_output_dir(&mut self, output_dir: std::path::PathBuf) {
+        self.frame_recorder.stop(&self.device);
+        self.frame_recorder = FrameRecorder::new(output_dir);
+    }
 
-        self.velocity_vector_field_ping_pong.swap();
+    /// Draws the current fields again into an offscreen texture in the surface format and saves
+    /// it to screenshot_<unix time in milliseconds>.png in the working directory. The simulation
+    /// isn't stepped, so the image is the frame on screen.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn capture_frame(&self) -> anyhow::Result<()> {
+        if !self.is_surface_configured {
+            anyhow::bail!("Nothing has been rendered yet");
+        }
 
-        // Projection
-        // Compute divergence
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-            compute_pass.set_pipeline(&self.compute_divergence_pipeline);
+        let capture_texture = Texture::create_capture_texture(&self.device, &self.config, "capture_texture");
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder"),
+        });
+        self.encode_frame(&mut encoder, &capture_texture.view);
+        self.queue.submit(std::iter::once(encoder.finish()));
 
-            let compute_divergence_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Compute Divergence Group"),
-                layout: &self.compute_divergence_bind_group_layout,
-                entries: &[
-                    // binding 0: Velocity vector field read
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&self.velocity_vector_field_ping_pong.get_read())
-                    },
-                    // binding 1: Divergence scalar field write
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&self.divergence_texture.view)
-                    },
-                    // binding 2: Sample
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Sampler(&self.velocity_vector_field_ping_pong.get_sampler())
-                    }
-                ],
-            });
+        // The surface is shown opaque, whatever alpha the tone mapping leaves in it.
+        let pixels: Vec<[u8; 4]> = capture_texture
+            .read_to_vec_rgba8(&self.device, &self.queue)?
+            .into_iter()
+            .map(|[r, g, b, _]| [r, g, b, u8::MAX])
+            .collect();
+
+        let millis = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let path = std::path::PathBuf::from(format!("screenshot_{millis}.png"));
+        crate::export::write_png_rgba8(&path, &pixels, self.config.width, self.config.height)?;
+        log::info!("Saved screenshot to {}", path.display());
+
+        Ok(())
+    }
+
+    /// Writes the simulation's fields, params and clock to a snapshot file that load_snapshot can
+    /// continue from. See Simulation::save_snapshot.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_snapshot(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.simulation.save_snapshot(&self.device, &self.queue)?)?;
+        log::info!("Saved snapshot of step {} to {}", self.simulation.step_count(), path.display());
+        Ok(())
+    }
+
+    /// Restores a snapshot written by save_snapshot. The simulation continues from it on the next
+    /// step, exactly as the saved one would have.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_snapshot(&mut self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let snapshot = std::fs::read(path)?;
+        self.simulation.load_snapshot(&self.device, &self.queue, &snapshot)?;
+        self.injecting_at_cursor = false;
+        log::info!("Loaded snapshot of step {} from {}", self.simulation.step_count(), path.display());
+        Ok(())
+    }
+
+    /// Saves the current render settings to a preset file that can be loaded with
+    /// --render-settings.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_render_settings(&self) -> anyhow::Result<()> {
+        let path = std::path::PathBuf::from(crate::config::RENDER_SETTINGS_PATH);
+        std::fs::write(&path, self.render_settings.to_preset_string())?;
+        log::info!("Saved render settings to {}", path.display());
+
+        Ok(())
+    }
+
+    /// Returns the number of simulation steps that have been run.
+    pub fn step_count(&self) -> u64 {
+        self.simulation.step_count()
+    }
+
+    /// Returns the total simulated time in seconds.
+    pub fn sim_time(&self) -> f32 {
+        self.simulation.sim_time()
+    }
 
-            compute_pass.set_bind_group(0, &self.compute_params_bind_group, &[]);
-            compute_pass.set_bind_group(1, &compute_divergence_bind_group, &[]);
+    /// Finishes outstanding GPU work before the app exits. Only waits if a readback is still in
+    /// flight, so quitting stays instant otherwise. Input recordings are flushed by App when the
+    /// event loop exits.
+    pub fn shutdown(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.frame_recorder.stop(&self.device);
+        let profiler_readback_in_flight = self.gpu_profiler.as_ref().is_some_and(GpuProfiler::readback_in_flight);
+        if self.auto_exposure.readback_in_flight()
+            || self.density_histogram.readback_in_flight()
+            || profiler_readback_in_flight
+        {
+            log::info!("Waiting for pending GPU readbacks before exiting");
+            if let Err(e) = self.device.poll(wgpu::PollType::wait_indefinitely()) {
+                log::error!("Failed to wait for the GPU before exiting: {e}");
+            }
+        }
+    }
 
-            compute_pass.dispatch_workgroups(
-                NUMBER_DISPATCHES_PER_DIMENSION,
-                NUMBER_DISPATCHES_PER_DIMENSION,
-                NUMBER_DISPATCHES_PER_DIMENSION
+    /// One line summary of the simulation, of the camera when the camera HUD is on and of the
+    /// measured density range when auto density range is on. Angles are in degrees, as
+    /// Camera::new is called with.
+    pub fn status_line(&self) -> String {
+        let mut status = format!("firesim | step {} | t = {:.2}s", self.step_count(), self.sim_time());
+        if self.paused {
+            status += " | paused";
+        }
+        if self.camera_hud_enabled {
+            let position = self.camera.position();
+            status += &format!(
+                " | camera ({:.3}, {:.3}, {:.3}) yaw {:.1} pitch {:.1}",
+                position.x,
+                position.y,
+                position.z,
+                cgmath::Deg::from(self.camera.yaw()).0,
+                cgmath::Deg::from(self.camera.pitch()).0,
+            );
+        }
+        if let Some(range) = self.density_range.filter(|_| self.render_settings.auto_density_range) {
+            status += &format!(
+                " | density {:.3} - {:.3} (p{:.0} - p{:.0}, min {:.3}, max {:.3}){}",
+                range.low,
+                range.high,
+                AUTO_DENSITY_PERCENTILES[0] * 100.0,
+                AUTO_DENSITY_PERCENTILES[1] * 100.0,
+                range.min,
+                range.max,
+                if self.density_range_locked { " locked" } else { "" },
             );
         }
+        status
+    }
 
-        // Compute pressure via Jacobi method
-        for _ in 0..JACOBI_ITERATIONS {
-            let (read_texture, write_texture) = self.pressure_ping_pong.get_read_and_write();
-            let textures_read_only: [&wgpu::TextureView; 1] = [&self.divergence_texture.view];
+    pub fn render(&mut self) -> anyhow::Result<()> {
+        self.window.request_redraw();
 
-            self.compute_pressure_compute_step.dispatch(
-                &self.device,
-                &mut encoder,
-                &self.compute_params_bind_group,
-                read_texture,
-                write_texture,
-                &textures_read_only,
-                Some(self.pressure_ping_pong.get_sampler()),
-                WORKGROUPS
-            );
+        // We can't render unless the surface is configured
+        if !self.is_surface_configured {
+            return Ok(());
+        }
+
+        let output = match self.surface.get_current_texture() {
+            wgpu::CurrentSurfaceTexture::Success(surface_texture) => surface_texture,
+            wgpu::CurrentSurfaceTexture::Suboptimal(surface_texture) => {
+                self.surface.configure(&self.device, &self.config);
+                surface_texture
+            }
+            wgpu::CurrentSurfaceTexture::Timeout
+            | wgpu::CurrentSurfaceTexture::Occluded
+            | wgpu::CurrentSurfaceTexture::Validation => {
+                // Skip this frame
+                return Ok(());
+            }
+            wgpu::CurrentSurfaceTexture::Outdated => {
+                self.surface.configure(&self.device, &self.config);
+                return Ok(());
+            }
+            wgpu::CurrentSurfaceTexture::Lost => {
+                // You could recreate the devices and all resources
+                // created with it here, but we'll just bail
+                anyhow::bail!("Lost device");
+            }
+        };
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(Texture::surface_view_format(&self.config)),
+            ..Default::default()
+        });
 
-            self.pressure_ping_pong.swap();
+        if let Some(profiler) = self.gpu_profiler.as_mut().filter(|_| self.gpu_profiling) {
+            profiler.begin_frame(&self.device);
         }
 
-        // Subtract pressure gradient from the velocity field.
-        let (read_texture, write_texture) = self.velocity_vector_field_ping_pong.get_read_and_write();
-        let textures_read_only: [&wgpu::TextureView; 1] = [&self.pressure_ping_pong.get_read()];
-
-        self.subtract_pressure_gradient_compute_step.dispatch(
-            &self.device,
-            &mut encoder,
-            &self.compute_params_bind_group,
-            read_texture,
-            write_texture,
-            &textures_read_only,
-            Some(self.velocity_vector_field_ping_pong.get_sampler()),
-            WORKGROUPS
-        );
+        for _ in 0..std::mem::take(&mut self.pending_substeps) {
+            let profiler = self.gpu_profiler.as_mut().filter(|_| self.gpu_profiling);
+            self.simulation.step_with_profiler(&self.device, &self.queue, profiler);
+        }
 
-        self.velocity_vector_field_ping_pong.swap();
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
 
-        /* Remove Sources if Present */
+        if !self.slice_view_enabled {
+            self.mark_gpu_stage(&mut encoder, "ray march");
+            self.encode_ray_march(&mut encoder);
+        }
+        self.mark_gpu_stage(&mut encoder, "tone mapping");
+        self.encode_resolve(&mut encoder, &view);
+
+        // Recorded frames are tone mapped again into the recorder's own target, since the surface
+        // texture can't be copied from.
+        #[cfg(not(target_arch = "wasm32"))]
+        let recorded_frame = self.frame_recorder.begin_frame(&self.device);
+        #[cfg(not(target_arch = "wasm32"))]
+        if recorded_frame {
+            self.mark_gpu_stage(&mut encoder, "frame recording");
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if recorded_frame && let Some(capture_view) = self.frame_recorder.capture_view() {
+            self.encode_resolve(&mut encoder, capture_view);
+            self.frame_recorder.encode_copy(&mut encoder);
+        }
 
-        if self.pending_input {
-            {
-                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-                compute_pass.set_pipeline(&self.remove_source_pipeline);
+        self.mark_gpu_stage(&mut encoder, "measurements");
+        // The luminance target isn't drawn to while the slice view is on.
+        let measured_luminance = self.render_settings.auto_exposure
+            && !self.slice_view_enabled
+            && self.auto_exposure.encode(&mut encoder);
+        let measured_density = self.measures_density_range()
+            && self.density_histogram.encode(&self.device, &mut encoder, self.simulation.scalar_field().get_read());
+
+        let profiled = self.gpu_profiler().is_some_and(|profiler| {
+            profiler.end(&mut encoder);
+            profiler.encode_resolve(&mut encoder)
+        });
 
-                compute_pass.set_bind_group(0, &self.add_source_bind_group, &[]);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        if profiled && let Some(profiler) = &self.gpu_profiler {
+            profiler.request_readback();
+        }
+        if measured_luminance {
+            self.auto_exposure.request_readback();
+        }
+        if measured_density {
+            self.density_histogram.request_readback();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if recorded_frame {
+            self.frame_recorder.request_readback();
+        }
+        output.present();
 
-                compute_pass.dispatch_workgroups(
-                    NUMBER_DISPATCHES_PER_DIMENSION,
-                    NUMBER_DISPATCHES_PER_DIMENSION,
-                    NUMBER_DISPATCHES_PER_DIMENSION
-                );
-            }
+        Ok(())
+    }
+
+    /// The GPU profiler, while GPU profiling is on.
+    fn gpu_profiler(&mut self) -> Option<&mut GpuProfiler> {
+        self.gpu_profiler.as_mut().filter(|_| self.gpu_profiling)
+    }
+
+    /// Starts timing a stage of the frame, if GPU profiling is on.
+    fn mark_gpu_stage(&mut self, encoder: &mut wgpu::CommandEncoder, stage: &'static str) {
+        if let Some(profiler) = self.gpu_profiler() {
+            profiler.mark(encoder, stage);
+        }
+    }
+
+    /// Encodes a frame of the current fields: the ray march into the HDR and luminance targets,
+    /// then the tone mapping and overlays into the given view, which must be in the surface
+    /// format. The slice view skips the ray march.
+    fn encode_frame(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        if !self.slice_view_enabled {
+            self.encode_ray_march(encoder);
         }
+        self.encode_resolve(encoder, view);
+    }
 
+    /// Ray marches the current fields into the HDR and luminance targets.
+    fn encode_ray_march(&self, encoder: &mut wgpu::CommandEncoder) {
         /* Render simulation result */
 
         {
@@ -993,22 +1631,9 @@ impl State {
                 label: Some("Render Pass"),
                 color_attachments: &[
                     // This is what @location(0) in the fragment shader targets
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(
-                                wgpu::Color {
-                                    r: 0.0,
-                                    g: 0.0,
-                                    b: 0.0,
-                                    a: 1.0,
-                                }
-                            ),
-                            store: wgpu::StoreOp::Store,
-                        },
-                        depth_slice: None,
-                    })
+                    Some(msaa_color_attachment(&self.hdr_texture, self.hdr_msaa_texture.as_ref())),
+                    // This is what @location(1) in the fragment shader targets
+                    Some(msaa_color_attachment(&self.luminance_texture, self.luminance_msaa_texture.as_ref())),
                 ],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.depth_texture.view,
@@ -1023,6 +1648,12 @@ impl State {
                 multiview_mask: None,
             });
 
+            let render_sampler = if self.render_settings.nearest_sampling {
+                &self.nearest_sampler
+            } else {
+                self.simulation.scalar_field().get_sampler()
+            };
+
             let density_texture_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some("Texture Bind Group"),
                 layout: &self.density_texture_bind_group_layout,
@@ -1030,634 +1661,330 @@ impl State {
                     // binding 0: Density scalar field read
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&self.scalar_field_ping_pong.get_read())
+                        resource: wgpu::BindingResource::TextureView(self.simulation.scalar_field().get_read())
                     },
                     // binding 1: Sampler for density scalar field (either a or b work)
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&self.scalar_field_ping_pong.get_sampler())
+                        resource: wgpu::BindingResource::Sampler(render_sampler)
                     },
                 ],
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.compute_params_bind_group, &[]);
-            render_pass.set_bind_group(2, &density_texture_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.render_params_bind_group, &[]);
 
-            // Full screen triangle, no vertex/index buffer.
-            render_pass.draw(0..3, 0..1);
-        }
+            if self.render_settings.box_edges_enabled {
+                render_pass.set_pipeline(&self.box_edge_pipeline);
+                render_pass.set_vertex_buffer(0, self.box_edge_vertex_buffer.slice(..));
+                render_pass.draw(0..24, 0..1);
+            }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+            render_pass.set_pipeline(&self.render_pipelines[self.render_settings.blend_mode.index()]);
+            render_pass.set_bind_group(2, &density_texture_bind_group, &[]);
 
-        Ok(())
+            // The box's 12 triangles, no vertex/index buffer.
+            render_pass.draw(0..36, 0..1);
+        }
     }
-}
-
-// TODO: Find a better way to organize this code.
-/* Helper functions to create each compute step */
 
-fn create_advect_scalars_compute_step(device: &Device, compute_params_bind_group_layout: &wgpu::BindGroupLayout) -> ComputeStep {
-    let advect_scalars_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Advect Scalars Bind Group Layout"),
-        entries: &[
-            // 0. Scalar field texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 1. Scalar field texture write.
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::StorageTexture {
-                    access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: CHANNEL_FORMAT,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                },
-                count: None,
-            },
-            // 2. Velocity vector field texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+    /// Bind group of the field the slice view shows, in the layout of the volume's density texture
+    /// group. fs_slice loads texels directly, so the sampler is only there to fill the layout.
+    fn create_slice_field_bind_group(&self) -> wgpu::BindGroup {
+        let field = if self.slice_view.field.reads_velocity() {
+            self.simulation.velocity_field()
+        } else {
+            self.simulation.scalar_field()
+        };
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Slice Field Bind Group"),
+            layout: &self.density_texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(field.get_read()),
                 },
-                count: None,
-            },
-            // 3. Density source texture input.
-            wgpu::BindGroupLayoutEntry {
-                binding: 3,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.nearest_sampler),
                 },
-                count: None,
-            },
-            // 4. Sampler.
-            wgpu::BindGroupLayoutEntry {
-                binding: 4,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            }
-        ]
-    });
-
-    let advect_scalars_pipeline_layout =
-        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Advect Scalars Pipeline Layout"),
-            bind_group_layouts: &[
-                Some(compute_params_bind_group_layout),
-                Some(&advect_scalars_bind_group_layout),
             ],
-            immediate_size: 0,
-        });
-
-    let advect_scalars_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Advect Scalars Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("advect_scalars.wgsl").into()),
-    });
-
-    let advect_scalars_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Advect Scalars Pipeline"),
-        layout: Some(&advect_scalars_pipeline_layout),
-        module: &advect_scalars_shader,
-        // Will default to @compute
-        entry_point: None,
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
-        cache: None,
-    });
+        })
+    }
 
-    ComputeStep::new(
-        "Advect Scalars Compute Step",
-        advect_scalars_pipeline,
-        advect_scalars_bind_group_layout,
-    )
-}
+    /// Tone maps the HDR target into the given view, which must be in the surface format, or draws
+    /// the slice view there instead, and draws the overlays on top.
+    fn encode_resolve(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        // The overlays are drawn after the ray march so they stay out of the luminance target that
+        // auto-exposure reads.
+        {
+            let mut resolve_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Resolve Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
 
-fn create_advect_velocity_compute_step(device: &Device, compute_params_bind_group_layout: &wgpu::BindGroupLayout) -> ComputeStep {
-    let advect_velocity_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Advect Velocity Bind Group Layout"),
-        entries: &[
-            // 0. Velocity vector field texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 1. Vector velocity field texture write.
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::StorageTexture {
-                    access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: CHANNEL_FORMAT,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                },
-                count: None,
-            },
-            // 2. Sampler.
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
+            resolve_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            resolve_pass.set_bind_group(1, &self.render_params_bind_group, &[]);
+            if self.slice_view_enabled {
+                resolve_pass.set_pipeline(&self.slice_pipeline);
+                resolve_pass.set_bind_group(2, &self.create_slice_field_bind_group(), &[]);
+            } else {
+                resolve_pass.set_pipeline(&self.tonemap_pipeline);
+                resolve_pass.set_bind_group(3, &self.hdr_bind_group, &[]);
             }
-        ]
-    });
-
-    let advect_velocity_pipeline_layout =
-        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Advect Velocity Pipeline Layout"),
-            bind_group_layouts: &[
-                Some(compute_params_bind_group_layout),
-                Some(&advect_velocity_bind_group_layout),
-            ],
-            immediate_size: 0,
-        });
 
-    let advect_velocity_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Advect Velocity Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("advect_velocity.wgsl").into()),
-    });
-
-    let advect_velocity_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Advect Velocity Pipeline"),
-        layout: Some(&advect_velocity_pipeline_layout),
-        module: &advect_velocity_shader,
-        // Will default to @compute
-        entry_point: None,
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
-        cache: None,
-    });
-
-    ComputeStep::new(
-        "Advect Velocity Compute Step",
-        advect_velocity_pipeline,
-        advect_velocity_bind_group_layout,
-    )
-}
-
-fn create_add_forces_to_velocity_compute_step(device: &Device, compute_params_bind_group_layout: &wgpu::BindGroupLayout) -> ComputeStep {
-    let add_forces_to_velocity_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Add Forces to Velocity Bind Group Layout"),
-        entries: &[
-            // 0. Velocity vector field texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 1. Vector velocity field texture write.
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::StorageTexture {
-                    access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: CHANNEL_FORMAT,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                },
-                count: None,
-            },
-            // 2. Scalar texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-        ]
-    });
-
-    let add_forces_to_velocity_pipeline_layout =
-        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Add Forces to Velocity Pipeline Layout"),
-            bind_group_layouts: &[
-                Some(compute_params_bind_group_layout),
-                Some(&add_forces_to_velocity_bind_group_layout),
-            ],
-            immediate_size: 0,
-        });
-
-    let add_forces_to_velocity_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Add Forces to Velocity Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("add_forces_to_velocity.wgsl").into()),
-    });
-
-    let add_forces_to_velocity_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Add Forces to Velocity Pipeline"),
-        layout: Some(&add_forces_to_velocity_pipeline_layout),
-        module: &add_forces_to_velocity_shader,
-        // Will default to @compute
-        entry_point: None,
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
-        cache: None,
-    });
+            // Full screen triangle, no vertex/index buffer.
+            resolve_pass.draw(0..3, 0..1);
 
-    ComputeStep::new(
-        "Add Forces to Velocity Compute Step",
-        add_forces_to_velocity_pipeline,
-        add_forces_to_velocity_bind_group_layout,
-    )
-}
+            if self.render_settings.legend_enabled {
+                resolve_pass.set_pipeline(&self.legend_pipeline);
 
-fn create_compute_pressure_compute_step(device: &Device, compute_params_bind_group_layout: &wgpu::BindGroupLayout) -> ComputeStep {
-    let compute_pressure_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Compute Pressure Bind Group Layout"),
-        entries: &[
-            // 0. Pressure texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 1. Pressure texture write.
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::StorageTexture {
-                    access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: CHANNEL_FORMAT,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                },
-                count: None,
-            },
-            // 2. Divergence texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 3. Sampler,
-            wgpu::BindGroupLayoutEntry {
-                binding: 3,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
+                // Quad as two triangles, no vertex/index buffer.
+                resolve_pass.draw(0..6, 0..1);
             }
-        ]
-    });
-
-    let compute_pressure_pipeline_layout =
-        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute Pressure Pipeline Layout"),
-            bind_group_layouts: &[
-                Some(compute_params_bind_group_layout),
-                Some(&compute_pressure_bind_group_layout),
-            ],
-            immediate_size: 0,
-        });
-
-    let compute_pressure_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Compute Pressure Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("compute_pressure.wgsl").into()),
-    });
-
-    let compute_pressure_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Add Forces to Velocity Pipeline"),
-        layout: Some(&compute_pressure_pipeline_layout),
-        module: &compute_pressure_shader,
-        // Will default to @compute
-        entry_point: None,
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
-        cache: None,
-    });
 
-    ComputeStep::new(
-        "Compute Pressure Compute Step",
-        compute_pressure_pipeline,
-        compute_pressure_bind_group_layout,
-    )
-}
+            if self.stats_overlay_enabled {
+                resolve_pass.set_pipeline(&self.stats_pipeline);
 
-fn create_subtract_pressure_gradient_compute_step(device: &Device, compute_params_bind_group_layout: &wgpu::BindGroupLayout) -> ComputeStep {
-    let subtract_pressure_gradient_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Subtract Pressure Gradient Bind Group Layout"),
-        entries: &[
-            // 0. Velocity vector field texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 1. Vector velocity field texture write.
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::StorageTexture {
-                    access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: CHANNEL_FORMAT,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                },
-                count: None,
-            },
-            // 2. Pressure texture,
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 3. Sampler.
-            wgpu::BindGroupLayoutEntry {
-                binding: 3,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
+                // Quad as two triangles, no vertex/index buffer.
+                resolve_pass.draw(0..6, 0..1);
             }
-        ]
-    });
-
-    let subtract_pressure_gradient_pipeline_layout =
-        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Subtract Gradient Pipeline Layout"),
-            bind_group_layouts: &[
-                Some(compute_params_bind_group_layout),
-                Some(&subtract_pressure_gradient_bind_group_layout),
-            ],
-            immediate_size: 0,
-        });
+        }
+    }
+}
 
-    let subtract_pressure_gradient_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Subtract Pressure Gradient Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("subtract_pressure_gradient.wgsl").into()),
-    });
+/// Requests an adapter that can present to the surface, falling back to a software adapter if
+/// there is no hardware one. The error names the backends tried, since the adapter request only
+/// says that nothing was found.
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'_>,
+    backends: wgpu::Backends,
+) -> anyhow::Result<wgpu::Adapter> {
+    let mut options = wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        compatible_surface: Some(surface),
+        force_fallback_adapter: false,
+    };
+    let error = match instance.request_adapter(&options).await {
+        Ok(adapter) => return Ok(adapter),
+        Err(e) => e,
+    };
+    log::warn!("No adapter found ({error}), trying a fallback adapter");
+
+    options.force_fallback_adapter = true;
+    instance.request_adapter(&options).await.map_err(|fallback_error| {
+        anyhow::anyhow!(
+            "No GPU adapter can render to the window. Tried the {backends:?} backends: {error}, \
+            and a fallback adapter: {fallback_error}. Set WGPU_BACKEND to try others, e.g. \
+            WGPU_BACKEND=gl or WGPU_BACKEND=vulkan, and check that the graphics drivers are installed"
+        )
+    })
+}
 
-    let subtract_pressure_gradient_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Subtract Pressure Gradient Pipeline"),
-        layout: Some(&subtract_pressure_gradient_pipeline_layout),
-        module: &subtract_pressure_gradient_shader,
-        // Will default to @compute
-        entry_point: None,
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
+/// Creates a pipeline that draws an overlay quad of the render shader straight to the surface,
+/// from six vertices and no buffers, like the color legend.
+fn create_overlay_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    label: &str,
+    vertex_entry_point: &str,
+    fragment_entry_point: &str,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some(vertex_entry_point),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(fragment_entry_point),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview_mask: None,
         cache: None,
-    });
-
-    ComputeStep::new(
-        "Subtract Pressure Gradient Compute Step",
-        subtract_pressure_gradient_pipeline,
-        subtract_pressure_gradient_bind_group_layout,
-    )
+    })
 }
 
-fn create_add_vorticity_confinement_force_compute_step(device: &Device, compute_params_bind_group_layout: &wgpu::BindGroupLayout) -> ComputeStep {
-    let add_vorticity_confinement_force_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Add Vorticity Confinement Force Bind Group Layout"),
-        entries: &[
-            // 0. Velocity vector field texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 1. Vector velocity field texture write.
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::StorageTexture {
-                    access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: CHANNEL_FORMAT,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                },
-                count: None,
-            },
-            // 2. Curl texture,
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 3. Sampler.
-            wgpu::BindGroupLayoutEntry {
-                binding: 3,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            }
-        ]
-    });
-
-    let add_vorticity_confinement_force_pipeline_layout =
-        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Add Vorticity Confinement Pipeline Layout"),
-            bind_group_layouts: &[
-                Some(compute_params_bind_group_layout),
-                Some(&add_vorticity_confinement_force_bind_group_layout),
-            ],
-            immediate_size: 0,
-        });
-
-    let add_vorticity_confinement_force_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Add Vorticity Confinement Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("add_vorticity_confinement_force.wgsl").into()),
-    });
-
-    let subtract_pressure_gradient_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Subtract Pressure Gradient Pipeline"),
-        layout: Some(&add_vorticity_confinement_force_pipeline_layout),
-        module: &add_vorticity_confinement_force_shader,
-        // Will default to @compute
-        entry_point: None,
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
-        cache: None,
-    });
+/// Multisampled render target standing in for a single sampled one, or None with a sample count
+/// of 1.
+fn create_msaa_texture(
+    device: &Device,
+    config: &SurfaceConfiguration,
+    label: &str,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> Option<Texture> {
+    (sample_count > 1).then(|| Texture::create_multisampled_texture(device, config, label, format, sample_count))
+}
 
-    ComputeStep::new(
-        "Subtract Pressure Gradient Compute Step",
-        subtract_pressure_gradient_pipeline,
-        add_vorticity_confinement_force_bind_group_layout,
-    )
+/// Cleared color attachment that ends up in target: drawn to directly, or drawn to msaa_target and
+/// resolved into it. The samples themselves aren't needed once resolved.
+fn msaa_color_attachment<'a>(
+    target: &'a Texture,
+    msaa_target: Option<&'a Texture>,
+) -> wgpu::RenderPassColorAttachment<'a> {
+    let (view, resolve_target, store) = match msaa_target {
+        Some(msaa_target) => (&msaa_target.view, Some(&target.view), wgpu::StoreOp::Discard),
+        None => (&target.view, None, wgpu::StoreOp::Store),
+    };
+    wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target,
+        ops: wgpu::Operations {
+            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            store,
+        },
+        depth_slice: None,
+    }
 }
 
-fn create_compute_temperature_compute_step(device: &Device, compute_params_bind_group_layout: &wgpu::BindGroupLayout) -> ComputeStep {
-    let compute_temperature_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Compute Temperature Bind Group Layout"),
+fn create_hdr_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, hdr_texture: &Texture) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("HDR Bind Group"),
+        layout,
         entries: &[
-            // 0. Scalar field texture read.
-            wgpu::BindGroupLayoutEntry {
+            // binding 0: HDR render target
+            wgpu::BindGroupEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 1. Scalar field texture write.
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::StorageTexture {
-                    access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: CHANNEL_FORMAT,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                },
-                count: None,
+                resource: wgpu::BindingResource::TextureView(&hdr_texture.view),
             },
-            // 2. Sampler.
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            }
-        ]
-    });
-
-    let compute_temperature_pipeline_layout =
-        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute Temperature Pipeline Layout"),
-            bind_group_layouts: &[
-                Some(compute_params_bind_group_layout),
-                Some(&compute_temperature_bind_group_layout),
-            ],
-            immediate_size: 0,
-        });
-
-    let compute_temperature_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Compute Temperature Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("compute_temperature.wgsl").into()),
-    });
-
-    let compute_temperature_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Compute Temperature Pipeline"),
-        layout: Some(&compute_temperature_pipeline_layout),
-        module: &compute_temperature_shader,
-        // Will default to @compute
-        entry_point: None,
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
-        cache: None,
-    });
+        ],
+    })
+}
 
-    ComputeStep::new(
-        "Advect Scalars Compute Step",
-        compute_temperature_pipeline,
-        compute_temperature_bind_group_layout,
-    )
+/// Both corners of each edge of the box from box_min to box_max, for a line list.
+fn box_edge_vertices(box_min: [f32; 3], box_max: [f32; 3]) -> Vec<[f32; 3]> {
+    // Corners are indexed x + 2y + 4z, like in vs_box.
+    let corner = |index: usize| -> [f32; 3] {
+        std::array::from_fn(|i| if index >> i & 1 == 1 { box_max[i] } else { box_min[i] })
+    };
+    // Every edge joins two corners that differ along one axis.
+    (0..8)
+        .flat_map(|index| (0..3).map(move |axis| (index, axis)))
+        .filter(|&(index, axis)| index >> axis & 1 == 0)
+        .flat_map(|(index, axis)| [corner(index), corner(index | 1 << axis)])
+        .collect()
 }
 
-fn create_compute_smoke_compute_step(device: &Device, compute_params_bind_group_layout: &wgpu::BindGroupLayout) -> ComputeStep {
-    let compute_smoke_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Compute Smoke Bind Group Layout"),
-        entries: &[
-            // 0. Scalar field texture read.
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            // 1. Scalar field texture write.
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::StorageTexture {
-                    access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: CHANNEL_FORMAT,
-                    view_dimension: wgpu::TextureViewDimension::D3,
-                },
-                count: None,
-            },
-            // 2. Sampler.
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            }
-        ]
-    });
+/// The numpad moves the scene's sources: 4 and 6 along x, 2 and 8 along y, 3 and 9 along z.
+fn source_offset_for_key(code: KeyCode) -> Option<[f32; 3]> {
+    let step = SOURCE_NUDGE_STEP;
+    Some(match code {
+        KeyCode::Numpad4 => [-step, 0.0, 0.0],
+        KeyCode::Numpad6 => [step, 0.0, 0.0],
+        KeyCode::Numpad2 => [0.0, -step, 0.0],
+        KeyCode::Numpad8 => [0.0, step, 0.0],
+        KeyCode::Numpad3 => [0.0, 0.0, -step],
+        KeyCode::Numpad9 => [0.0, 0.0, step],
+        _ => return None,
+    })
+}
 
-    let compute_smoke_pipeline_layout =
-        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Compute Smoke Pipeline Layout"),
-            bind_group_layouts: &[
-                Some(compute_params_bind_group_layout),
-                Some(&compute_smoke_bind_group_layout),
-            ],
-            immediate_size: 0,
-        });
+/// The requested present mode if the surface supports it, otherwise Fifo, which every surface
+/// does. The Auto modes are resolved by wgpu and always accepted.
+fn select_present_mode(requested: wgpu::PresentMode, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    let resolved_by_wgpu = matches!(requested, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync);
+    if resolved_by_wgpu || supported.contains(&requested) {
+        requested
+    } else {
+        log::warn!("Present mode {requested:?} isn't supported by the surface (it has {supported:?}), using Fifo");
+        wgpu::PresentMode::Fifo
+    }
+}
 
-    let compute_smoke_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("Compute Smoke Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("compute_smoke.wgsl").into()),
+/// The requested MSAA sample count if the render pass targets can be drawn to and resolved with it,
+/// otherwise the closest lower one of 8, 4 and 1 that they can. Also returns the features the
+/// device needs for it, as counts beyond the WebGPU guarantees need the adapter's own format
+/// features.
+fn select_msaa_sample_count(requested: u32, adapter: &wgpu::Adapter) -> (u32, wgpu::Features) {
+    const FORMATS: [wgpu::TextureFormat; 3] = [Texture::HDR_FORMAT, Texture::LUMINANCE_FORMAT, Texture::DEPTH_FORMAT];
+    let supports = |features: &dyn Fn(wgpu::TextureFormat) -> wgpu::TextureFormatFeatures, count: u32| {
+        FORMATS.iter().all(|&format| {
+            let flags = features(format).flags;
+            // The depth buffer is only tested against, never resolved.
+            let resolvable = count == 1
+                || format.is_depth_stencil_format()
+                || flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE);
+            flags.sample_count_supported(count) && resolvable
+        })
+    };
+    let adapter_specific = adapter.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+
+    let selected = [8, 4, 1].into_iter().filter(|&count| count <= requested).find_map(|count| {
+        if supports(&|format| format.guaranteed_format_features(wgpu::Features::empty()), count) {
+            Some((count, wgpu::Features::empty()))
+        } else if adapter_specific && supports(&|format| adapter.get_texture_format_features(format), count) {
+            Some((count, wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES))
+        } else {
+            None
+        }
     });
+    let (count, features) = selected.unwrap_or((1, wgpu::Features::empty()));
+    if count != requested {
+        log::warn!("MSAA with {requested} samples isn't supported by the adapter, using {count}");
+    }
+    (count, features)
+}
 
-    let compute_smoke_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("Compute Smoke Pipeline"),
-        layout: Some(&compute_smoke_pipeline_layout),
-        module: &compute_smoke_shader,
-        // Will default to @compute
-        entry_point: None,
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
-        cache: None,
-    });
+fn grid_dimension_length_for_key(code: KeyCode) -> Option<u32> {
+    let index = match code {
+        KeyCode::F6 => 0,
+        KeyCode::F7 => 1,
+        KeyCode::F8 => 2,
+        _ => return None,
+    };
+    RUNTIME_GRID_DIMENSION_LENGTHS.get(index).copied()
+}
 
-    ComputeStep::new(
-        "Compute Smoke Compute Step",
-        compute_smoke_pipeline,
-        compute_smoke_bind_group_layout,
-    )
+/// Number keys 1-6 load the scene presets in the order of ScenePreset::ALL.
+fn scene_preset_for_key(code: KeyCode) -> Option<ScenePreset> {
+    let index = match code {
+        KeyCode::Digit1 => 0,
+        KeyCode::Digit2 => 1,
+        KeyCode::Digit3 => 2,
+        KeyCode::Digit4 => 3,
+        KeyCode::Digit5 => 4,
+        KeyCode::Digit6 => 5,
+        _ => return None,
+    };
+    ScenePreset::ALL.get(index).copied()
 }
@@ -1,16 +1,21 @@
 // Uniform buffers
-struct Params {
+struct SimParams {
     dt: f32,
     width: u32,
     height: u32,
     depth: u32,
-    box_min: vec4<f32>,
-    box_max: vec4<f32>,
-    viewport: vec2<f32>,
-    _pad0: vec2<f32>
+    elapsed_time: f32,
+    turbulence_strength: f32,
+    turbulence_scale: f32,
+    drain_strength: f32,
+    drain: vec4<f32>,
+    ignition_temperature: f32,
+    soot_yield: f32,
+    _pad0: vec2<f32>,
+    cell_size: vec3<f32>,
 }
 @group(0) @binding(0)
-var<uniform> params: Params;
+var<uniform> params: SimParams;
 
 @group(1) @binding(0)
 var velocity_vector_field_read: texture_3d<f32>;
@@ -19,10 +24,17 @@ var velocity_vector_field_write: texture_storage_3d<rgba16float, write>;
 @group(1) @binding(2)
 var pressure: texture_3d<f32>;
 @group(1) @binding(3)
+var obstacles: texture_3d<f32>;
+@group(1) @binding(4)
 var field_sampler: sampler;
 
+// Workgroup dimensions, overridden from Rust (see WorkgroupSize in compute_step.rs).
+override WG_X: u32 = 4;
+override WG_Y: u32 = 4;
+override WG_Z: u32 = 4;
+
 @compute
-@workgroup_size(4, 4, 4)
+@workgroup_size(WG_X, WG_Y, WG_Z)
 fn main (
     @builtin(global_invocation_id) gid: vec3<u32>
 ) {
@@ -42,6 +54,12 @@ fn main (
         return;
     }
 
+    // Obstacles are static, so nothing moves inside them.
+    if (is_solid(gid)) {
+        textureStore(velocity_vector_field_write, vec3<i32>(gid), vec4<f32>(0.0));
+        return;
+    }
+
     let uvw = voxel_center_uvw(gid);
     let unstable_velocity = textureSampleLevel(velocity_vector_field_read, field_sampler, uvw, 0.0).xyz;
 
@@ -50,22 +68,41 @@ fn main (
     textureStore(
         velocity_vector_field_write,
         vec3<i32>(gid),
-        vec4<f32>(unstable_velocity - pressure_gradient, 0.0)
+        vec4<f32>((unstable_velocity - pressure_gradient) * get_velocity_mask(gid), 0.0)
     );
 }
 
+// Zero along each axis with a solid neighbour on either side, one elsewhere. Masking those
+// components leaves no velocity normal to an obstacle's surface, so fluid flows around it.
+fn get_velocity_mask(gid: vec3<u32>) -> vec3<f32> {
+    var mask = vec3<f32>(1.0);
+    if (is_solid(vec3<u32>(gid.x + 1, gid.y, gid.z)) || is_solid(vec3<u32>(gid.x - 1, gid.y, gid.z))) {
+        mask.x = 0.0;
+    }
+    if (is_solid(vec3<u32>(gid.x, gid.y + 1, gid.z)) || is_solid(vec3<u32>(gid.x, gid.y - 1, gid.z))) {
+        mask.y = 0.0;
+    }
+    if (is_solid(vec3<u32>(gid.x, gid.y, gid.z + 1)) || is_solid(vec3<u32>(gid.x, gid.y, gid.z - 1))) {
+        mask.z = 0.0;
+    }
+    return mask;
+}
+
+// Solid neighbours take the center's pressure, matching the pressure solve.
 fn get_pressure_gradient(gid: vec3<u32>) -> vec3<f32> {
-    let right_pressure = get_pressure(vec3<u32>(gid.x + 1, gid.y, gid.z));
-    let left_pressure = get_pressure(vec3<u32>(gid.x - 1, gid.y, gid.z));
-    let x_finite_partial = (right_pressure - left_pressure) * 0.5;
+    let center_pressure = get_pressure(gid);
+
+    let right_pressure = get_neighbour_pressure(vec3<u32>(gid.x + 1, gid.y, gid.z), center_pressure);
+    let left_pressure = get_neighbour_pressure(vec3<u32>(gid.x - 1, gid.y, gid.z), center_pressure);
+    let x_finite_partial = (right_pressure - left_pressure) / (2.0 * params.cell_size.x);
 
-    let up_pressure = get_pressure(vec3<u32>(gid.x, gid.y + 1, gid.z));
-    let down_pressure = get_pressure(vec3<u32>(gid.x, gid.y - 1, gid.z));
-    let y_finite_partial = (up_pressure - down_pressure) * 0.5;
+    let up_pressure = get_neighbour_pressure(vec3<u32>(gid.x, gid.y + 1, gid.z), center_pressure);
+    let down_pressure = get_neighbour_pressure(vec3<u32>(gid.x, gid.y - 1, gid.z), center_pressure);
+    let y_finite_partial = (up_pressure - down_pressure) / (2.0 * params.cell_size.y);
 
-    let front_pressure = get_pressure(vec3<u32>(gid.x, gid.y, gid.z + 1));
-    let back_pressure = get_pressure(vec3<u32>(gid.x, gid.y, gid.z - 1));
-    let z_finite_partial = (front_pressure - back_pressure) * 0.5;
+    let front_pressure = get_neighbour_pressure(vec3<u32>(gid.x, gid.y, gid.z + 1), center_pressure);
+    let back_pressure = get_neighbour_pressure(vec3<u32>(gid.x, gid.y, gid.z - 1), center_pressure);
+    let z_finite_partial = (front_pressure - back_pressure) / (2.0 * params.cell_size.z);
 
     return vec3<f32>(x_finite_partial, y_finite_partial, z_finite_partial);
 }
@@ -85,4 +122,16 @@ fn voxel_center_uvw(gid: vec3<u32>) -> vec3<f32> {
 fn get_pressure(index: vec3<u32>) -> f32 {
     let uvw = voxel_center_uvw(index);
     return textureSampleLevel(pressure, field_sampler, uvw, 0.0).x;
+}
+
+fn get_neighbour_pressure(index: vec3<u32>, center_pressure: f32) -> f32 {
+    if (is_solid(index)) {
+        return center_pressure;
+    }
+    return get_pressure(index);
+}
+
+fn is_solid(index: vec3<u32>) -> bool {
+    let uvw = voxel_center_uvw(index);
+    return textureSampleLevel(obstacles, field_sampler, uvw, 0.0).x > 0.5;
 }
\ No newline at end of file
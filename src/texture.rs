@@ -1,5 +1,51 @@
 use half::f16;
-use crate::config::{GRID_DIMENSIONS, GRID_DIMENSION_LENGTH, _VELOCITY_SCALE};
+use crate::initial_velocity::InitialVelocityField;
+use crate::scene::SourceFalloff;
+
+/// Filtering and addressing of a sampler.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SamplerConfig {
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    /// Address modes along u, v and w.
+    pub address_modes: [wgpu::AddressMode; 3],
+}
+
+impl SamplerConfig {
+    /// Trilinear interpolation, clamped to the edge of the grid. What advection needs.
+    pub const LINEAR_CLAMP: SamplerConfig = SamplerConfig {
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        address_modes: [wgpu::AddressMode::ClampToEdge; 3],
+    };
+
+    /// Nearest neighbor, clamped to the edge of the grid. Shows raw voxels, which is what debug
+    /// views want since trilinear blurring hides indexing bugs.
+    pub const NEAREST_CLAMP: SamplerConfig = SamplerConfig {
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        address_modes: [wgpu::AddressMode::ClampToEdge; 3],
+    };
+
+    pub fn create_sampler(&self, device: &wgpu::Device, label: Option<&str>) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_modes[0],
+            address_mode_v: self.address_modes[1],
+            address_mode_w: self.address_modes[2],
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        })
+    }
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self::LINEAR_CLAMP
+    }
+}
 
 pub struct Texture {
     #[allow(unused)]
@@ -10,9 +56,21 @@ pub struct Texture {
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    pub const LUMINANCE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    /// Usage of the simulation's 3D textures: sampled, written by compute passes, uploaded to and
+    /// read back.
+    pub const COMPUTE_TEXTURE_USAGE: wgpu::TextureUsages = wgpu::TextureUsages::TEXTURE_BINDING
+        .union(wgpu::TextureUsages::STORAGE_BINDING)
+        .union(wgpu::TextureUsages::COPY_DST)
+        .union(wgpu::TextureUsages::COPY_SRC);
+    /// Usage of the screen sized targets one pass renders and a later one reads.
+    const SCREEN_TARGET_USAGE: wgpu::TextureUsages =
+        wgpu::TextureUsages::RENDER_ATTACHMENT.union(wgpu::TextureUsages::TEXTURE_BINDING);
 
     /// Creates a texture, texture view, and sample for the compute pipeline.
-    /// The returned texture represents 3D grid for the simulation, indexed by u, v, and w.
+    /// The returned texture represents 3D grid for the simulation with the given dimensions,
+    /// indexed by u, v, and w.
     /// Depending on the inputted format, the color channels and their precision can be used for
     /// compute pipelines.
     ///
@@ -23,18 +81,34 @@ impl Texture {
     /// Depending on the number of channels need and their precision, use the appropriate format for
     /// memory efficiency. Although, the format may not be available on your machine for the texture
     /// usages.
-    pub fn create_compute_texture(device: &wgpu::Device, format: wgpu::TextureFormat, label: Option<&str>) -> Self {
+    ///
+    /// The sampler does trilinear interpolation clamped to the edge of the grid. Use
+    /// create_compute_texture_with_sampler for anything else.
+    pub fn create_compute_texture(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        dimensions: wgpu::Extent3d,
+        label: Option<&str>,
+    ) -> Self {
+        Self::create_compute_texture_with_sampler(device, format, dimensions, SamplerConfig::default(), label)
+    }
+
+    /// Same as create_compute_texture, but with the given sampler filtering and address modes.
+    pub fn create_compute_texture_with_sampler(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        dimensions: wgpu::Extent3d,
+        sampler_config: SamplerConfig,
+        label: Option<&str>,
+    ) -> Self {
         let desc = wgpu::TextureDescriptor {
             label,
-            size: GRID_DIMENSIONS,
+            size: dimensions,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D3,
             format,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::STORAGE_BINDING
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::COPY_SRC,
+            usage: Self::COMPUTE_TEXTURE_USAGE,
             view_formats: &[],
         };
         let texture = device.create_texture(&desc);
@@ -44,22 +118,20 @@ impl Texture {
             ..Default::default()
         });
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("density_linear_clamp"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            // Setting these to linear will do trilinear interpolation semi-Lagrangian advection.
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
-            ..Default::default()
-        });
+        // Linear filtering does trilinear interpolation for semi-Lagrangian advection.
+        let sampler = sampler_config.create_sampler(device, Some("compute_texture_sampler"));
 
         Self { texture, view, sampler }
     }
 
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+    /// Creates the screen sized depth buffer of the render pass, with as many samples per pixel as
+    /// its color targets.
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
         let size = wgpu::Extent3d {
             width: config.width.max(1),
             height: config.height.max(1),
@@ -69,7 +141,7 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT
@@ -97,71 +169,342 @@ impl Texture {
         Self { texture, view, sampler }
     }
 
-    /// Writes a tornado velocity vector field to the given texture with a rgba16f format.
-    /// The velocity's x, y, and z components will be written to the texture's r, g, and b channels
-    /// respectively.
-    pub fn _write_velocity_3d_rgba16f_tornado(
+    /// Creates a screen sized single channel render target that the render pass writes the
+    /// pre-exposure luminance of each pixel to. Read by the auto-exposure reduction.
+    pub fn create_luminance_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+        Self::create_screen_texture(device, config, label, Self::LUMINANCE_FORMAT, Self::SCREEN_TARGET_USAGE, 1)
+    }
+
+    /// Creates a screen sized render target that the render pass writes the linear radiance of
+    /// each pixel to, before exposure and tone mapping. Read by the tone mapping pass.
+    pub fn create_hdr_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+        Self::create_screen_texture(device, config, label, Self::HDR_FORMAT, Self::SCREEN_TARGET_USAGE, 1)
+    }
+
+    /// Creates a screen sized render target in the surface view format that a frame can be drawn
+    /// to instead of the surface, and then copied back with read_to_vec_rgba8.
+    pub fn create_capture_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+        Self::create_screen_texture(device, config, label, Self::surface_view_format(config), usage, 1)
+    }
+
+    /// Creates a screen sized multisampled render target that a render pass draws to in place of
+    /// a single sampled one of the same format, and resolves into it at the end of the pass.
+    pub fn create_multisampled_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        Self::create_screen_texture(device, config, label, format, usage, sample_count)
+    }
+
+    /// Format frames are drawn to the surface in: the sRGB view format a non-sRGB surface is
+    /// configured with, so the hardware encodes the linear colors the shaders write either way,
+    /// or the surface format itself.
+    pub fn surface_view_format(config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+        config.view_formats.first().copied().unwrap_or(config.format)
+    }
+
+    /// Screen sized render target that later passes read texel for texel, or resolve from.
+    fn create_screen_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+        sample_count: u32,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&desc);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        Self { texture, view, sampler }
+    }
+
+    /// Fills an rgba16f velocity texture with one of the initial velocity fields, generated at the
+    /// given speed. The velocity's x, y, and z components will be written to the texture's r, g,
+    /// and b channels respectively.
+    pub fn write_initial_velocity_rgba16f(
         &self,
-        queue: &wgpu::Queue
+        queue: &wgpu::Queue,
+        field: InitialVelocityField,
+        speed: f32,
     ) {
-        // TODO: Assert that the format is rgba16f.
+        let wgpu::Extent3d { width, height, depth_or_array_layers: depth } = self.texture.size();
 
-        let width = GRID_DIMENSION_LENGTH;
-        let height = GRID_DIMENSION_LENGTH;
-        let depth = GRID_DIMENSION_LENGTH;
+        // Map a voxel center to [-1, 1] along each axis.
+        // e.g. for i in (0, 1,..., 63) maps to voxel center scaled within (-1, 1).
+        let to_unit = |center: f32, n: u32| -> f32 { (center / n as f32) * 2.0 - 1.0 };
 
-        // RGBA16F = 4 channels * 2 bytes = 8 bytes per voxel
-        let bytes_per_voxel: usize = 8;
-        let voxel_count = (width as usize) * (height as usize) * (depth as usize);
-        let mut data = vec![0u8; voxel_count * bytes_per_voxel];
+        self.write_voxels(queue, "initial velocity", |p| {
+            let [vx, vy, vz] = field.velocity_at([to_unit(p[0], width), to_unit(p[1], height), to_unit(p[2], depth)], speed);
+            [vx, vy, vz, 0.0]
+        });
+    }
 
-        // Map integer index to [-1, 1] at voxel center.
-        // e.g. for i in (0, 1,..., 63) maps to voxel center scaled within (-1, 1).
-        let to_unit = |i: u32, n: u32| -> f32 {
-            let fi = i as f32 + 0.5;
-            let fn_ = n as f32;
-            (fi / fn_) * 2.0 - 1.0
+    /// Copies the texture back to the CPU and returns every voxel's four channels as f32.
+    /// Voxels are returned x-major, then y, then z.
+    ///
+    /// Rgba16Float textures are unpacked from f16, which is exact: every f16 value is an f32 value.
+    /// The result is only as precise as the f16 the field was stored in though, about 3 significant
+    /// decimal digits up to a magnitude of 65504, with values below ~6e-5 losing precision further.
+    /// Rgba32Float textures are returned as stored. R32Float ones (e.g. a pressure field) fill the
+    /// missing channels the way the shaders read them: zero, and one for alpha.
+    ///
+    /// Blocks until the GPU has finished all submitted work, so this is meant for tooling such as
+    /// exporters rather than per-frame use.
+    pub fn read_to_vec_f32(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Vec<[f32; 4]>> {
+        let format = self.texture.format();
+        let decode_channel: fn(&[u8]) -> f32 = match format {
+            wgpu::TextureFormat::Rgba16Float => |bytes| f16::from_le_bytes([bytes[0], bytes[1]]).to_f32(),
+            wgpu::TextureFormat::Rgba32Float | wgpu::TextureFormat::R32Float => {
+                |bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+            }
+            _ => anyhow::bail!("Only Rgba16Float, Rgba32Float and R32Float textures can be read back, got {format:?}"),
         };
 
-        let eps: f32 = 1e-6;
+        let bytes_per_voxel = format.block_copy_size(None).expect("float formats have a block size");
+        let bytes_per_channel = bytes_per_voxel / format.components() as u32;
+        let bytes = self.read_texel_bytes(device, queue, bytes_per_voxel)?;
 
-        for z in 0..depth {
-            for y in 0..height {
-                for x in 0..width {
-                    let px = to_unit(x, width);
-                    let pz = to_unit(z, depth);
-
-                    // Tangent around Y axis: (pz, 0, -px) normalized
-                    let r2 = px * px + pz * pz;
-
-                    let (vx, vy, vz) = if r2 < eps {
-                        // On the axis: direction undefined; set to zero (or choose a fixed direction).
-                        (0.0, 0.0, 0.0)
-                    } else {
-                        let inv_r = 1.0 / r2.sqrt();
-                        (pz * inv_r * _VELOCITY_SCALE, 0.0, -px * inv_r * _VELOCITY_SCALE)
-                    };
-
-                    let r16 = f16::from_f32(vx).to_bits();
-                    let g16 = f16::from_f32(vy).to_bits();
-                    let b16 = f16::from_f32(vz).to_bits();
-                    let a16 = f16::from_f32(0.0).to_bits();
-
-                    let i = (x as usize)
-                        + (width as usize) * ((y as usize) + (height as usize) * (z as usize));
-                    let base = i * bytes_per_voxel;
-
-                    data[base + 0..base + 2].copy_from_slice(&r16.to_le_bytes());
-                    data[base + 2..base + 4].copy_from_slice(&g16.to_le_bytes());
-                    data[base + 4..base + 6].copy_from_slice(&b16.to_le_bytes());
-                    data[base + 6..base + 8].copy_from_slice(&a16.to_le_bytes());
+        Ok(bytes
+            .chunks_exact(bytes_per_voxel as usize)
+            .map(|texel| {
+                let mut voxel = [0.0, 0.0, 0.0, 1.0];
+                for (channel, bytes) in voxel.iter_mut().zip(texel.chunks_exact(bytes_per_channel as usize)) {
+                    *channel = decode_channel(bytes);
                 }
+                voxel
+            })
+            .collect())
+    }
+
+    /// Copies the texture back to the CPU as a raw volume of texels in its own format, in the
+    /// layout write_raw takes, so the two round trip exactly. Blocks until the GPU has finished all
+    /// submitted work, like read_to_vec_f32.
+    pub fn read_raw(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Vec<u8>> {
+        let format = self.texture.format();
+        let bytes_per_texel = format
+            .block_copy_size(None)
+            .ok_or_else(|| anyhow::anyhow!("{format:?} textures can't be read back texel by texel"))?;
+        self.read_texel_bytes(device, queue, bytes_per_texel)
+    }
+
+    /// Copies an 8-bit color texture, such as one from create_capture_texture, back to the CPU
+    /// and returns every pixel as RGBA, row by row from the top left. BGRA formats are swizzled,
+    /// and sRGB ones are returned as stored (sRGB encoded).
+    ///
+    /// Blocks until the GPU has finished all submitted work, like read_to_vec_f32.
+    pub fn read_to_vec_rgba8(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<Vec<[u8; 4]>> {
+        let format = self.texture.format();
+        // Checked before the copy, which can't fail on the format.
+        Self::decode_rgba8(format, &[])?;
+        let bytes = self.read_texel_bytes(device, queue, 4)?;
+        Self::decode_rgba8(format, &bytes)
+    }
+
+    /// Converts tightly packed texels of an 8-bit RGBA or BGRA format to RGBA. See
+    /// read_to_vec_rgba8.
+    pub fn decode_rgba8(format: wgpu::TextureFormat, bytes: &[u8]) -> anyhow::Result<Vec<[u8; 4]>> {
+        let bgra = match format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            _ => anyhow::bail!("Only 8-bit RGBA and BGRA textures can be read back as RGBA8, got {format:?}"),
+        };
+
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|texel| match bgra {
+                true => [texel[2], texel[1], texel[0], texel[3]],
+                false => [texel[0], texel[1], texel[2], texel[3]],
+            })
+            .collect())
+    }
+
+    /// Copies the whole texture into a staging buffer and returns its texels tightly packed,
+    /// without the row padding the copy needs.
+    fn read_texel_bytes(&self, device: &wgpu::Device, queue: &wgpu::Queue, bytes_per_texel: u32) -> anyhow::Result<Vec<u8>> {
+        let size = self.texture.size();
+        let unpadded_bytes_per_row = size.width * bytes_per_texel;
+        // Rows in the staging buffer must be aligned for the copy.
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let rows = size.height * size.depth_or_array_layers;
+
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture Readback Buffer"),
+            size: padded_bytes_per_row as u64 * rows as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Texture Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &staging_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            size,
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = staging_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::wait_indefinitely())?;
+        receiver.recv()??;
+
+        let mapped = slice.get_mapped_range();
+        let mut bytes = Vec::with_capacity((unpadded_bytes_per_row * rows) as usize);
+        for row in mapped.chunks_exact(padded_bytes_per_row as usize) {
+            bytes.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        drop(mapped);
+        staging_buffer.unmap();
+
+        Ok(bytes)
+    }
+
+    /// Returns the number of bytes a raw RGBA16F volume covering a grid of the given dimensions
+    /// must have.
+    pub fn raw_volume_len(dimensions: wgpu::Extent3d) -> usize {
+        // RGBA16F = 4 channels * 2 bytes = 8 bytes per voxel
+        dimensions.width as usize * dimensions.height as usize * dimensions.depth_or_array_layers as usize * 8
+    }
+
+    /// Converts a raw .f32 density volume, as written by the O key (see README), to a raw RGBA16F
+    /// volume for write_from_raw_f16: the density goes to the smoke channel and the others start
+    /// at zero. Fails if the file is malformed or its dimensions don't match the given grid.
+    pub fn raw_volume_from_f32_density(bytes: &[u8], dimensions: wgpu::Extent3d) -> anyhow::Result<Vec<u8>> {
+        let header: [u32; 3] = std::array::from_fn(|i| {
+            bytes
+                .get(i * 4..i * 4 + 4)
+                .map_or(0, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        });
+        let expected = [dimensions.width, dimensions.height, dimensions.depth_or_array_layers];
+        if bytes.len() < 12 || header != expected {
+            anyhow::bail!(
+                "Density file is {}x{}x{} but the simulation grid is {}x{}x{}",
+                header[0],
+                header[1],
+                header[2],
+                expected[0],
+                expected[1],
+                expected[2],
+            );
+        }
+
+        let voxels = expected.iter().map(|&n| n as usize).product::<usize>();
+        let data = &bytes[12..];
+        if data.len() != voxels * 4 {
+            anyhow::bail!(
+                "Density file has {} bytes of data but a {}x{}x{} grid needs {} (one f32 per voxel)",
+                data.len(),
+                expected[0],
+                expected[1],
+                expected[2],
+                voxels * 4,
+            );
+        }
+
+        let mut volume = Vec::with_capacity(Self::raw_volume_len(dimensions));
+        for value in data.chunks_exact(4) {
+            let density = f32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+            if !density.is_finite() {
+                anyhow::bail!("Density file has a non-finite value: {density}");
+            }
+            for channel in [density, 0.0, 0.0, 0.0] {
+                volume.extend_from_slice(&f16::from_f32(channel).to_le_bytes());
             }
         }
+        Ok(volume)
+    }
 
-        // 8 bytes per texel
-        let bytes_per_row = width * 8;
-        let rows_per_image = height;
+    /// Uploads a caller-provided raw RGBA16F volume to the given texture.
+    /// The data must be tightly packed, x-major then y then z (the same layout the write helpers
+    /// use), and its dimensions must match the texture.
+    pub fn write_from_raw_f16(
+        &self,
+        queue: &wgpu::Queue,
+        data: &[u8],
+        dimensions: wgpu::Extent3d,
+    ) -> anyhow::Result<()> {
+        self.assert_rgba16f("raw f16 volume");
+
+        let size = self.texture.size();
+        if dimensions != size {
+            anyhow::bail!(
+                "Volume dimensions {}x{}x{} do not match the simulation grid {}x{}x{}",
+                dimensions.width,
+                dimensions.height,
+                dimensions.depth_or_array_layers,
+                size.width,
+                size.height,
+                size.depth_or_array_layers,
+            );
+        }
+
+        self.write_raw(queue, data)
+    }
+
+    /// Overwrites the whole texture with a raw volume of texels in its own format, tightly packed
+    /// x-major then y then z, as read_raw returns them.
+    pub fn write_raw(&self, queue: &wgpu::Queue, data: &[u8]) -> anyhow::Result<()> {
+        let size = self.texture.size();
+        let format = self.texture.format();
+        let bytes_per_texel = format
+            .block_copy_size(None)
+            .ok_or_else(|| anyhow::anyhow!("{format:?} textures can't be written texel by texel"))?;
+
+        let expected_len = (size.width * size.height * size.depth_or_array_layers) as usize * bytes_per_texel as usize;
+        if data.len() != expected_len {
+            anyhow::bail!(
+                "Raw {format:?} volume is {} bytes but a {}x{}x{} grid needs {} bytes",
+                data.len(),
+                size.width,
+                size.height,
+                size.depth_or_array_layers,
+                expected_len,
+            );
+        }
 
         queue.write_texture(
             wgpu::TexelCopyTextureInfo {
@@ -170,34 +513,33 @@ impl Texture {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            &data,
+            data,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
-                bytes_per_row: Some(bytes_per_row),
-                rows_per_image: Some(rows_per_image),
-            },
-            wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: depth,
+                bytes_per_row: Some(size.width * bytes_per_texel),
+                rows_per_image: Some(size.height),
             },
+            size,
         );
+
+        Ok(())
     }
 
-    /// Clears the given texture's channels to all 0.0 with a rgba16f format.
-    pub fn _clear(&self, queue: &wgpu::Queue) {
-        let width = GRID_DIMENSION_LENGTH;
-        let height = GRID_DIMENSION_LENGTH;
-        let depth = GRID_DIMENSION_LENGTH;
+    /// Clears the given texture's channels to all 0.0. Zero bits are 0.0 in every float format, so
+    /// any uncompressed color format works.
+    pub fn clear(&self, queue: &wgpu::Queue) {
+        let size = self.texture.size();
+        let format = self.texture.format();
+        let bytes_per_voxel = format
+            .block_copy_size(None)
+            .unwrap_or_else(|| panic!("cannot clear a {format:?} texture texel by texel"));
 
         // Zero out textures
-        let bytes_per_voxel = 8usize;
-        let voxel_count = (width * height * depth) as usize;
-        let data = vec![0u8; voxel_count * bytes_per_voxel];
+        let voxel_count = (size.width * size.height * size.depth_or_array_layers) as usize;
+        let data = vec![0u8; voxel_count * bytes_per_voxel as usize];
 
-        // 8 bytes per texel
-        let bytes_per_row = width * 8;
-        let rows_per_image = height;
+        let bytes_per_row = size.width * bytes_per_voxel;
+        let rows_per_image = size.height;
 
         queue.write_texture(
             wgpu::TexelCopyTextureInfo {
@@ -212,11 +554,17 @@ impl Texture {
                 bytes_per_row: Some(bytes_per_row),
                 rows_per_image: Some(rows_per_image),
             },
-            wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: depth,
-            },
+            size,
+        );
+    }
+
+    /// Panics unless the texture is rgba16f, which the write helpers' byte layout assumes. Writing
+    /// 8 byte texels into any other format would upload garbage without an error.
+    fn assert_rgba16f(&self, what: &str) {
+        assert_eq!(
+            self.texture.format(),
+            wgpu::TextureFormat::Rgba16Float,
+            "{what} can only be written to a rgba16f texture"
         );
     }
 
@@ -228,9 +576,9 @@ impl Texture {
         voxel: [u32; 3],
         density: f32,
     ) {
-        let width = GRID_DIMENSION_LENGTH;
-        let height = GRID_DIMENSION_LENGTH;
-        let depth = GRID_DIMENSION_LENGTH;
+        self.assert_rgba16f("density single voxel");
+
+        let wgpu::Extent3d { width, height, depth_or_array_layers: depth } = self.texture.size();
 
         let bytes_per_voxel: usize = 8; // RGBA16F
         let voxel_count = (width as usize) * (height as usize) * (depth as usize);
@@ -250,7 +598,7 @@ impl Texture {
         let b = f16::from_f32(0.0).to_bits();
         let a = f16::from_f32(0.0).to_bits();
 
-        data[base + 0..base + 2].copy_from_slice(&r.to_le_bytes());
+        data[base..base + 2].copy_from_slice(&r.to_le_bytes());
         data[base + 2..base + 4].copy_from_slice(&g.to_le_bytes());
         data[base + 4..base + 6].copy_from_slice(&b.to_le_bytes());
         data[base + 6..base + 8].copy_from_slice(&a.to_le_bytes());
@@ -279,7 +627,7 @@ impl Texture {
         );
     }
 
-    /// Write a smooth density blob into the entire 3D RGBA16F texture.
+    /// Write a density blob with the given falloff into the entire 3D RGBA16F texture.
     /// This overwrites the whole texture (good for init / reset).
     pub fn _write_density_blob_rgba16f(
         &self,
@@ -287,45 +635,98 @@ impl Texture {
         center: [f32; 3],
         radius: f32,
         peak: f32,
+        falloff: SourceFalloff,
+    ) {
+        let radius = radius.max(1e-6);
+        self.write_voxels(queue, "density blob", |p| {
+            [peak * falloff.weight(distance(p, center) / radius), 0.0, 0.0, 0.0]
+        });
+    }
+
+    /// Write a temperature blob with the given falloff into the temperature (y) channel of the
+    /// entire 3D RGBA16F scalar texture, with no smoke, fuel or soot. The center and radius are in
+    /// voxels, and the blob rises as soon as the simulation steps, which makes buoyancy easy to
+    /// see on its own. Temperatures under the ambient temperature are raised to it by the next
+    /// step.
+    /// This overwrites the whole texture (good for init / reset).
+    pub fn write_temperature_blob_rgba16f(
+        &self,
+        queue: &wgpu::Queue,
+        center: [f32; 3],
+        radius: f32,
+        peak_temperature: f32,
+        falloff: SourceFalloff,
     ) {
-        let width = GRID_DIMENSION_LENGTH;
-        let height = GRID_DIMENSION_LENGTH;
-        let depth = GRID_DIMENSION_LENGTH;
+        let radius = radius.max(1e-6);
+        self.write_voxels(queue, "temperature blob", |p| {
+            [0.0, peak_temperature * falloff.weight(distance(p, center) / radius), 0.0, 0.0]
+        });
+    }
+
+    /// Write a solid sphere into the x channel of the entire 3D RGBA16F obstacle texture: 1.0 in
+    /// the voxels whose centers lie within the radius, 0.0 everywhere else. The center and radius
+    /// are in voxels.
+    /// This overwrites the whole texture (good for init / reset).
+    pub fn write_obstacle_sphere(&self, queue: &wgpu::Queue, center: [f32; 3], radius: f32) {
+        let radius2 = radius * radius;
+        self.write_obstacle_mask(queue, "obstacle sphere", |p| {
+            let d = [p[0] - center[0], p[1] - center[1], p[2] - center[2]];
+            d[0]*d[0] + d[1]*d[1] + d[2]*d[2] <= radius2
+        });
+    }
+
+    /// Write a solid axis aligned box between the min and max corners (in voxels) into the x
+    /// channel of the entire 3D RGBA16F obstacle texture: 1.0 in the voxels whose centers lie
+    /// inside, 0.0 everywhere else.
+    /// This overwrites the whole texture (good for init / reset).
+    pub fn write_obstacle_box(&self, queue: &wgpu::Queue, min: [f32; 3], max: [f32; 3]) {
+        self.write_obstacle_mask(queue, "obstacle box", |p| {
+            (0..3).all(|axis| p[axis] >= min[axis] && p[axis] <= max[axis])
+        });
+    }
+
+    /// Writes 1.0 into the x channel of the voxels whose centers is_solid accepts, and 0.0 into
+    /// every other channel and voxel.
+    fn write_obstacle_mask(&self, queue: &wgpu::Queue, what: &str, is_solid: impl Fn([f32; 3]) -> bool) {
+        self.write_voxels(queue, what, |p| [if is_solid(p) { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0]);
+    }
+
+    /// Write a spherical shell of constant density into the entire 3D RGBA16F texture.
+    /// Voxels whose centers lie between the inner and outer radius (in voxels) are set to value,
+    /// everything else is zeroed. Hollow structures make numerical diffusion easy to see.
+    /// This overwrites the whole texture (good for init / reset).
+    pub fn write_density_sphere_shell(
+        &self,
+        queue: &wgpu::Queue,
+        center: [f32; 3],
+        inner_radius: f32,
+        outer_radius: f32,
+        value: f32,
+    ) {
+        self.write_voxels(queue, "density sphere shell", |p| {
+            let d = distance(p, center);
+            [if d >= inner_radius && d <= outer_radius { value } else { 0.0 }, 0.0, 0.0, 0.0]
+        });
+    }
+
+    /// Writes the four channels voxel returns for the center of each voxel (in voxels) into the
+    /// entire 3D RGBA16F texture, packed x-major then y then z like write_from_raw_f16.
+    /// This overwrites the whole texture (good for init / reset).
+    fn write_voxels(&self, queue: &wgpu::Queue, what: &str, voxel: impl Fn([f32; 3]) -> [f32; 4]) {
+        self.assert_rgba16f(what);
+
+        let wgpu::Extent3d { width, height, depth_or_array_layers: depth } = self.texture.size();
 
         let bytes_per_voxel: usize = 8; // RGBA16F
         let voxel_count = (width as usize) * (height as usize) * (depth as usize);
-        let mut data = vec![0u8; voxel_count * bytes_per_voxel];
-
-        let r2 = radius * radius;
-        let sigma2 = (radius * 0.35).max(1e-6).powi(2);
+        let mut data = Vec::with_capacity(voxel_count * bytes_per_voxel);
 
         for z in 0..depth {
             for y in 0..height {
                 for x in 0..width {
-                    let dx = x as f32 + 0.5 - center[0];
-                    let dy = y as f32 + 0.5 - center[1];
-                    let dz = z as f32 + 0.5 - center[2];
-                    let dist2 = dx*dx + dy*dy + dz*dz;
-
-                    let val = if dist2 <= r2 {
-                        peak * (-dist2 / (2.0 * sigma2)).exp()
-                    } else {
-                        0.0
-                    };
-
-                    let r = f16::from_f32(val).to_bits();
-                    let g = f16::from_f32(0.0).to_bits();
-                    let b = f16::from_f32(0.0).to_bits();
-                    let a = f16::from_f32(0.0).to_bits();
-
-                    let i = (x as usize)
-                        + (width as usize) * ((y as usize) + (height as usize) * (z as usize));
-                    let base = i * bytes_per_voxel;
-
-                    data[base + 0..base + 2].copy_from_slice(&r.to_le_bytes());
-                    data[base + 2..base + 4].copy_from_slice(&g.to_le_bytes());
-                    data[base + 4..base + 6].copy_from_slice(&b.to_le_bytes());
-                    data[base + 6..base + 8].copy_from_slice(&a.to_le_bytes());
+                    for value in voxel([x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5]) {
+                        data.extend_from_slice(&f16::from_f32(value).to_bits().to_le_bytes());
+                    }
                 }
             }
         }
@@ -343,11 +744,12 @@ impl Texture {
                 bytes_per_row: Some(width * 8),
                 rows_per_image: Some(height),
             },
-            wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: depth,
-            },
+            self.texture.size(),
         );
     }
 }
+
+/// Distance between two points.
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
@@ -0,0 +1,85 @@
+/// Turns variable frame times into a whole number of fixed simulation steps.
+///
+/// Frame time is scaled by the time scale and accumulated, and every full step's worth runs one
+/// simulation step. When a frame needs more than max_substeps_per_frame steps, only the maximum
+/// runs and the rest of the backlog is dropped, so a slow frame can't make the next one even
+/// slower. The simulation then runs slower than time_scale asks for, and a warning is logged
+/// until it keeps up again. Raising the time scale raises the number of steps every frame needs,
+/// so it reaches the cap sooner.
+pub struct FixedTimestep {
+    /// Simulated seconds per step.
+    step: f32,
+    max_substeps_per_frame: u32,
+    /// Simulated seconds per real second.
+    time_scale: f32,
+    /// Scaled time not yet simulated, in seconds.
+    accumulator: f32,
+    /// Whether the last frame hit the substep cap.
+    falling_behind: bool,
+}
+
+impl FixedTimestep {
+    pub const MAX_SUBSTEPS_RANGE: [u32; 2] = [1, 16];
+    pub const TIME_SCALE_RANGE: [f32; 2] = [0.125, 8.0];
+
+    pub fn new(step: f32, max_substeps_per_frame: u32, time_scale: f32) -> Self {
+        let mut timestep = Self {
+            step,
+            max_substeps_per_frame: 1,
+            time_scale: 1.0,
+            accumulator: 0.0,
+            falling_behind: false,
+        };
+        timestep.set_max_substeps_per_frame(max_substeps_per_frame);
+        timestep.set_time_scale(time_scale);
+        timestep
+    }
+
+    /// Adds a frame's worth of time and returns the number of steps to run this frame.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time * self.time_scale;
+        let steps = (self.accumulator / self.step).floor() as u32;
+
+        if steps > self.max_substeps_per_frame {
+            if !self.falling_behind {
+                log::warn!(
+                    "Simulation is falling behind real time: a frame needed {steps} steps but at most {} run per frame",
+                    self.max_substeps_per_frame,
+                );
+            }
+            self.falling_behind = true;
+            self.accumulator %= self.step;
+            return self.max_substeps_per_frame;
+        }
+
+        if self.falling_behind {
+            log::info!("Simulation caught up with real time");
+        }
+        self.falling_behind = false;
+        self.accumulator -= steps as f32 * self.step;
+        steps
+    }
+
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    pub fn max_substeps_per_frame(&self) -> u32 {
+        self.max_substeps_per_frame
+    }
+
+    /// Clamped to MAX_SUBSTEPS_RANGE.
+    pub fn set_max_substeps_per_frame(&mut self, max_substeps_per_frame: u32) {
+        self.max_substeps_per_frame = max_substeps_per_frame
+            .clamp(Self::MAX_SUBSTEPS_RANGE[0], Self::MAX_SUBSTEPS_RANGE[1]);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Clamped to TIME_SCALE_RANGE.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.clamp(Self::TIME_SCALE_RANGE[0], Self::TIME_SCALE_RANGE[1]);
+    }
+}
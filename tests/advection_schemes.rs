@@ -0,0 +1,111 @@
+//! Checks that BFECC and MacCormack keep a blob of smoke sharper than semi-Lagrangian advection.
+//!
+//! The same Gaussian blob is advected with each scheme through a uniform velocity that moves it a
+//! fraction of a voxel per step, which is where the trilinear interpolation of semi-Lagrangian
+//! advection smears the most. The error compensating schemes must end with a higher peak density
+//! than semi-Lagrangian. Since both clamp their correction to the neighbourhood of the backtrace,
+//! none may end with a peak above the starting one by more than the f16 storage of the field. A
+//! scheme that quietly falls back to plain semi-Lagrangian advection ends with the same peak and
+//! fails.
+//!
+//! Needs a GPU. Skipped when no adapter is found, unless FIRESIM_REQUIRE_GPU is set (see common).
+
+mod common;
+
+use std::time::Duration;
+
+use cgmath::Vector3;
+use firesim::{AdvectionScheme, InitialVelocityField, Simulation, WorkgroupSize};
+use half::f16;
+
+const GRID_SIZE: u32 = 32;
+const DT: f32 = 0.1;
+/// In voxel lengths per second, so 0.37 and 0.23 voxels a step, off the voxel grid along both axes.
+const VELOCITY: [f32; 3] = [3.7, 2.3, 0.0];
+const STEPS: u32 = 24;
+/// Center of the blob in voxels, on a voxel center so the blob starts with a peak of exactly 1,
+/// and far enough from the walls that it stays clear of them.
+const BLOB_CENTER: [f32; 3] = [10.5, 12.5, 16.5];
+/// Standard deviation of the blob in voxels.
+const BLOB_RADIUS: f32 = 2.0;
+/// Overshoot allowed above the starting peak of 1, a little over the f16 spacing at 1.
+const PEAK_TOLERANCE: f32 = 2e-3;
+
+#[test]
+fn error_compensation_keeps_blob_sharper() {
+    pollster::block_on(error_compensation_keeps_blob_sharper_async());
+}
+
+async fn error_compensation_keeps_blob_sharper_async() {
+    let Some((device, queue)) = common::gpu_device(|_| Ok(wgpu::Features::empty())).await else {
+        return;
+    };
+
+    let semi_lagrangian = advected_peak(&device, &queue, AdvectionScheme::SemiLagrangian);
+    let bfecc = advected_peak(&device, &queue, AdvectionScheme::Bfecc);
+    let maccormack = advected_peak(&device, &queue, AdvectionScheme::MacCormack);
+
+    for (scheme, peak) in [("semi-Lagrangian", semi_lagrangian), ("BFECC", bfecc), ("MacCormack", maccormack)] {
+        assert!(
+            peak.is_finite() && peak > 0.0 && peak <= 1.0 + PEAK_TOLERANCE,
+            "{scheme} ended with a peak density of {peak}",
+        );
+    }
+    assert!(
+        bfecc > semi_lagrangian,
+        "BFECC peak {bfecc} isn't above the semi-Lagrangian peak {semi_lagrangian}",
+    );
+    assert!(
+        maccormack > semi_lagrangian,
+        "MacCormack peak {maccormack} isn't above the semi-Lagrangian peak {semi_lagrangian}",
+    );
+}
+
+/// Peak smoke density after advecting the blob for STEPS steps with the given scheme.
+fn advected_peak(device: &wgpu::Device, queue: &wgpu::Queue, advection_scheme: AdvectionScheme) -> f32 {
+    let grid_dimensions = common::cube_grid(GRID_SIZE);
+    let initial_field = gaussian_blob_f16();
+    let mut simulation = Simulation::new(
+        device,
+        queue,
+        grid_dimensions,
+        WorkgroupSize::new(4, 4, 4),
+        Some(&initial_field),
+    )
+    .expect("failed to create the simulation");
+    simulation.set_advection_scheme(advection_scheme);
+    simulation.set_initial_velocity(queue, InitialVelocityField::Uniform(Vector3::from(VELOCITY)));
+    simulation.sim_params_mut().update_dt(Duration::from_secs_f32(DT));
+
+    for _ in 0..STEPS {
+        simulation.advect_scalars(device, queue);
+    }
+
+    let advected = simulation
+        .scalar_field()
+        .get_read_texture()
+        .read_to_vec_f32(device, queue)
+        .expect("failed to read back the scalar field");
+    advected.iter().map(|voxel| voxel[0]).fold(f32::NEG_INFINITY, f32::max)
+}
+
+/// Raw f16 volume with a Gaussian blob of smoke peaking at 1, and no temperature, fuel or soot.
+fn gaussian_blob_f16() -> Vec<u8> {
+    let mut bytes = Vec::with_capacity((GRID_SIZE * GRID_SIZE * GRID_SIZE * 8) as usize);
+    for z in 0..GRID_SIZE {
+        for y in 0..GRID_SIZE {
+            for x in 0..GRID_SIZE {
+                let squared_distance = [x, y, z]
+                    .iter()
+                    .zip(BLOB_CENTER)
+                    .map(|(i, center)| (*i as f32 + 0.5 - center).powi(2))
+                    .sum::<f32>();
+                let smoke = (-squared_distance / (2.0 * BLOB_RADIUS * BLOB_RADIUS)).exp();
+                for value in [smoke, 0.0, 0.0, 0.0] {
+                    bytes.extend_from_slice(&f16::from_f32(value).to_le_bytes());
+                }
+            }
+        }
+    }
+    bytes
+}
@@ -0,0 +1,114 @@
+//! Movement and rotation math of CameraController::update_camera.
+
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+
+use cgmath::{Deg, Point3, Rad};
+use winit::event::{ElementState, MouseScrollDelta};
+use winit::keyboard::KeyCode;
+
+use firesim::{Camera, CameraController};
+
+const SPEED: f32 = 2.0;
+const SENSITIVITY: f32 = 0.5;
+const EPSILON: f32 = 1e-5;
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < EPSILON, "expected {expected}, got {actual}");
+}
+
+fn assert_position(camera: &Camera, expected: [f32; 3]) {
+    let position = camera.position();
+    for (actual, expected) in [position.x, position.y, position.z].into_iter().zip(expected) {
+        assert_close(actual, expected);
+    }
+}
+
+#[test]
+fn forward_moves_along_the_yaw_on_the_xz_plane() {
+    // Looking along +z and down, so a move that followed the pitch would change y.
+    let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(90.0), Deg(-30.0));
+    let mut controller = CameraController::new(SPEED, SENSITIVITY);
+
+    controller.process_keyboard(KeyCode::KeyW, ElementState::Pressed);
+    controller.update_camera(&mut camera, Duration::from_secs_f32(0.5));
+    assert_position(&camera, [0.0, 0.0, SPEED * 0.5]);
+
+    // Still held, so it keeps moving until released.
+    controller.update_camera(&mut camera, Duration::from_secs_f32(0.5));
+    assert_position(&camera, [0.0, 0.0, SPEED]);
+
+    controller.process_keyboard(KeyCode::KeyW, ElementState::Released);
+    controller.update_camera(&mut camera, Duration::from_secs_f32(0.5));
+    assert_position(&camera, [0.0, 0.0, SPEED]);
+}
+
+#[test]
+fn strafe_and_vertical_movement() {
+    let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(0.0), Deg(0.0));
+    let mut controller = CameraController::new(SPEED, SENSITIVITY);
+
+    controller.process_keyboard(KeyCode::KeyD, ElementState::Pressed);
+    controller.process_keyboard(KeyCode::Space, ElementState::Pressed);
+    controller.update_camera(&mut camera, Duration::from_secs_f32(0.25));
+
+    // At zero yaw forward is +x, so right is +z.
+    assert_position(&camera, [0.0, SPEED * 0.25, SPEED * 0.25]);
+}
+
+#[test]
+fn mouse_rotates_yaw_and_pitch_once() {
+    let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(0.0), Deg(0.0));
+    let mut controller = CameraController::new(SPEED, SENSITIVITY);
+    let dt = 0.1;
+
+    controller.handle_mouse(10.0, 4.0);
+    controller.update_camera(&mut camera, Duration::from_secs_f32(dt));
+    assert_close(camera.yaw().0, 10.0 * SENSITIVITY * dt);
+    // Moving the mouse down looks down.
+    assert_close(camera.pitch().0, -4.0 * SENSITIVITY * dt);
+
+    // The delta is consumed, so the next update doesn't rotate again.
+    controller.update_camera(&mut camera, Duration::from_secs_f32(dt));
+    assert_close(camera.yaw().0, 10.0 * SENSITIVITY * dt);
+    assert_close(camera.pitch().0, -4.0 * SENSITIVITY * dt);
+}
+
+#[test]
+fn pitch_is_clamped_short_of_straight_up_and_down() {
+    let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(0.0), Deg(0.0));
+    let mut controller = CameraController::new(SPEED, SENSITIVITY);
+
+    controller.handle_mouse(0.0, -1.0e4);
+    controller.update_camera(&mut camera, Duration::from_secs_f32(1.0));
+    assert!(camera.pitch().0 < FRAC_PI_2);
+    assert_close(camera.pitch().0, FRAC_PI_2 - 1e-4);
+
+    controller.handle_mouse(0.0, 1.0e4);
+    controller.update_camera(&mut camera, Duration::from_secs_f32(1.0));
+    assert!(camera.pitch().0 > -FRAC_PI_2);
+    assert_close(camera.pitch().0, -(FRAC_PI_2 - 1e-4));
+
+    camera.set_pitch(Rad(10.0));
+    assert!(camera.pitch().0 < FRAC_PI_2);
+    assert_close(Camera::new(Point3::new(0.0, 0.0, 0.0), Deg(0.0), Deg(120.0)).pitch().0, FRAC_PI_2 - 1e-4);
+}
+
+#[test]
+fn scroll_moves_along_the_view_direction_once() {
+    // Looking up at 45 degrees along +x, so the move has equal x and y parts.
+    let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(0.0), Deg(45.0));
+    let mut controller = CameraController::new(SPEED, SENSITIVITY);
+    let dt = 0.01;
+
+    // One line scrolls 100 pixels, and scrolling up moves backwards.
+    controller.handle_mouse_scroll(&MouseScrollDelta::LineDelta(0.0, 1.0));
+    controller.update_camera(&mut camera, Duration::from_secs_f32(dt));
+    let distance = -100.0 * SPEED * SENSITIVITY * dt;
+    let axis = distance * std::f32::consts::FRAC_1_SQRT_2;
+    assert_position(&camera, [axis, axis, 0.0]);
+
+    // The scroll is consumed, so the next update doesn't move again.
+    controller.update_camera(&mut camera, Duration::from_secs_f32(dt));
+    assert_position(&camera, [axis, axis, 0.0]);
+}
@@ -0,0 +1,59 @@
+//! GPU setup shared by the tests that step the simulation headless.
+//!
+//! Without a suitable adapter these tests print why and return early, which the test harness
+//! reports as a pass. Setting FIRESIM_REQUIRE_GPU makes them fail instead, for machines that are
+//! expected to have a GPU (e.g. CI runners with one), where a silent skip would hide a broken
+//! setup.
+
+/// Makes a missing or unsuitable adapter fail the tests instead of skipping them.
+pub const REQUIRE_GPU_VAR: &str = "FIRESIM_REQUIRE_GPU";
+
+/// Device and queue on the default adapter, created with the features `required_features` picks
+/// for it. It returns the reason the adapter can't run the test instead, if it can't. None when
+/// the test should be skipped; panics instead if REQUIRE_GPU_VAR is set.
+pub async fn gpu_device(
+    required_features: impl FnOnce(&wgpu::Adapter) -> Result<wgpu::Features, String>,
+) -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::PRIMARY,
+        flags: Default::default(),
+        memory_budget_thresholds: Default::default(),
+        backend_options: Default::default(),
+        display: None,
+    });
+    let Ok(adapter) = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await else {
+        skip("no graphics adapter");
+        return None;
+    };
+    let required_features = match required_features(&adapter) {
+        Ok(required_features) => required_features,
+        Err(reason) => {
+            skip(&reason);
+            return None;
+        }
+    };
+    let device = adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            required_features,
+            ..Default::default()
+        })
+        .await
+        .expect("failed to create a device");
+    Some(device)
+}
+
+/// Cube grid with the given number of voxels along each axis.
+pub fn cube_grid(size: u32) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: size,
+        height: size,
+        depth_or_array_layers: size,
+    }
+}
+
+fn skip(reason: &str) {
+    if std::env::var_os(REQUIRE_GPU_VAR).is_some() {
+        panic!("{reason}, and {REQUIRE_GPU_VAR} is set");
+    }
+    eprintln!("Skipping: {reason} (set {REQUIRE_GPU_VAR} to fail instead)");
+}
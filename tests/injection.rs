@@ -0,0 +1,73 @@
+//! Checks that fuel injected by the source survives a full simulation step.
+//!
+//! One step runs the add source pass, scalar advection (which adds the source texture to what it
+//! writes), the swap that makes that the read side, and the reaction passes. With the velocity
+//! still zero, advection doesn't move anything, so after the step the fuel must sit inside the
+//! source sphere and nowhere else. A pass reading the wrong side of a ping pong, or the source
+//! being cleared before advection reads it, shows up as missing fuel.
+//!
+//! Needs a GPU. Skipped when no adapter is found, unless FIRESIM_REQUIRE_GPU is set (see common).
+
+mod common;
+
+use std::time::Duration;
+
+use firesim::{Simulation, WorkgroupSize};
+
+const GRID_SIZE: u32 = 32;
+/// Campfire source of the default scene: center as a fraction of the grid and radius as a
+/// fraction of the grid width.
+const SOURCE_CENTER: [f32; 3] = [0.5, 0.25, 0.5];
+const SOURCE_RADIUS: f32 = 0.1875;
+
+#[test]
+fn injected_fuel_survives_one_step() {
+    pollster::block_on(injected_fuel_survives_one_step_async());
+}
+
+async fn injected_fuel_survives_one_step_async() {
+    let Some((device, queue)) = common::gpu_device(|_| Ok(wgpu::Features::empty())).await else {
+        return;
+    };
+
+    let grid_dimensions = common::cube_grid(GRID_SIZE);
+    let mut simulation = Simulation::new(&device, &queue, grid_dimensions, WorkgroupSize::new(4, 4, 4), None)
+        .expect("failed to create the simulation");
+    simulation.sim_params_mut().update_dt(Duration::from_secs_f32(1.0 / 60.0));
+    simulation.set_pending_input(true);
+
+    simulation.step(&device, &queue);
+
+    let voxels = simulation
+        .scalar_field()
+        .get_read_texture()
+        .read_to_vec_f32(&device, &queue)
+        .expect("failed to read back the scalar field");
+
+    let center = SOURCE_CENTER.map(|c| c * GRID_SIZE as f32);
+    let radius = SOURCE_RADIUS * GRID_SIZE as f32;
+    let mut max_fuel: f32 = 0.0;
+    for (index, voxel) in voxels.iter().enumerate() {
+        let index = index as u32;
+        let position = [
+            (index % GRID_SIZE) as f32 + 0.5,
+            (index / GRID_SIZE % GRID_SIZE) as f32 + 0.5,
+            (index / (GRID_SIZE * GRID_SIZE)) as f32 + 0.5,
+        ];
+        let distance = position
+            .iter()
+            .zip(center)
+            .map(|(p, c)| (p - c) * (p - c))
+            .sum::<f32>()
+            .sqrt();
+
+        let fuel = voxel[2];
+        if distance > radius + 1.0 {
+            assert_eq!(fuel, 0.0, "fuel {fuel} at {position:?}, outside of the source");
+        }
+        max_fuel = max_fuel.max(fuel);
+    }
+
+    // The source injects SOURCE_FUEL (1.5) at its peak, and the noise can at most halve it.
+    assert!(max_fuel > 0.5, "injected fuel didn't survive the step (max fuel {max_fuel})");
+}
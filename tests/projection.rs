@@ -0,0 +1,156 @@
+//! Checks the pressure projection with f32 pressure against f16 pressure.
+//!
+//! The Jacobi solve starts every step from the last step's pressure, so its error carries over
+//! from step to step. Stored as f16, that rounding builds up over long runs; PRESSURE_FORMAT and
+//! DIVERGENCE_FORMAT store both fields as R32Float instead where the adapter can filter them. One
+//! test runs the default scene with its sources on for twenty simulated seconds and requires every
+//! field to stay finite, with a pressure field the solve actually wrote. The other runs the same
+//! scene on a second device without FLOAT32_FILTERABLE, which falls back to Rgba16Float, with
+//! enough iterations that the rounding rather than the iteration count limits the solve, and
+//! requires the f32 run to leave less divergence in the velocity.
+//!
+//! Needs a GPU with FLOAT32_FILTERABLE. Skipped otherwise, unless FIRESIM_REQUIRE_GPU is set (see
+//! common).
+
+mod common;
+
+use std::time::Duration;
+
+use firesim::{Simulation, WorkgroupSize};
+
+const GRID_SIZE: u32 = 32;
+const STEPS: u32 = 1200;
+const DT: f32 = 1.0 / 60.0;
+/// Steps of the f32 against f16 comparison, two simulated seconds.
+const COMPARISON_STEPS: u32 = 120;
+
+#[test]
+fn long_projection_stays_finite_with_f32_pressure() {
+    pollster::block_on(long_projection_stays_finite_with_f32_pressure_async());
+}
+
+async fn long_projection_stays_finite_with_f32_pressure_async() {
+    let Some((device, queue)) = common::gpu_device(f32_pressure_features).await else {
+        return;
+    };
+
+    let simulation = run_default_scene(&device, &queue, wgpu::TextureFormat::R32Float, None, STEPS);
+
+    let (scalar, velocity) = simulation
+        .non_finite_voxels(&device, &queue)
+        .expect("failed to read back the fields");
+    assert_eq!((scalar, velocity), (0, 0), "non-finite (scalar, velocity) voxels after {STEPS} steps");
+
+    let pressure = simulation
+        .pressure_field()
+        .get_read_texture()
+        .read_to_vec_f32(&device, &queue)
+        .expect("failed to read back the pressure field");
+    assert!(
+        pressure.iter().all(|voxel| voxel[0].is_finite()),
+        "non-finite pressure after {STEPS} steps",
+    );
+    // Guards against a solve that never ran, which would leave zeros that are trivially finite.
+    assert!(pressure.iter().any(|voxel| voxel[0] != 0.0), "the pressure solve didn't write anything");
+}
+
+#[test]
+fn f32_pressure_leaves_less_divergence_than_f16() {
+    pollster::block_on(f32_pressure_leaves_less_divergence_than_f16_async());
+}
+
+async fn f32_pressure_leaves_less_divergence_than_f16_async() {
+    let Some((device_f32, queue_f32)) = common::gpu_device(f32_pressure_features).await else {
+        return;
+    };
+    let f16_pressure_features = |adapter: &wgpu::Adapter| {
+        f32_pressure_features(adapter).map(|required_features| required_features - wgpu::Features::FLOAT32_FILTERABLE)
+    };
+    let Some((device_f16, queue_f16)) = common::gpu_device(f16_pressure_features).await else {
+        return;
+    };
+
+    let iterations = Some(Simulation::PRESSURE_ITERATIONS_RANGE[1]);
+    let simulation_f32 =
+        run_default_scene(&device_f32, &queue_f32, wgpu::TextureFormat::R32Float, iterations, COMPARISON_STEPS);
+    let simulation_f16 =
+        run_default_scene(&device_f16, &queue_f16, wgpu::TextureFormat::Rgba16Float, iterations, COMPARISON_STEPS);
+
+    let divergence_f32 = mean_abs_divergence(&simulation_f32, &device_f32, &queue_f32);
+    let divergence_f16 = mean_abs_divergence(&simulation_f16, &device_f16, &queue_f16);
+    assert!(
+        divergence_f32.is_finite() && divergence_f32 > 0.0,
+        "mean divergence {divergence_f32} with f32 pressure",
+    );
+    assert!(
+        divergence_f32 < divergence_f16,
+        "mean divergence {divergence_f32} with f32 pressure isn't below {divergence_f16} with f16 pressure",
+    );
+}
+
+/// Features for a device that stores pressure as R32Float. Skips adapters that can't filter it.
+fn f32_pressure_features(adapter: &wgpu::Adapter) -> Result<wgpu::Features, String> {
+    let required_features = Simulation::required_features(adapter).expect("the adapter can't run the simulation");
+    if required_features.contains(wgpu::Features::FLOAT32_FILTERABLE) {
+        Ok(required_features)
+    } else {
+        Err("the adapter can't filter f32 textures".to_string())
+    }
+}
+
+/// Runs the default scene with its sources on for the given number of steps, after checking the
+/// device stores pressure in the expected format. Keeps the default pressure iterations if none
+/// are given.
+fn run_default_scene(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pressure_format: wgpu::TextureFormat,
+    pressure_iterations: Option<u32>,
+    steps: u32,
+) -> Simulation {
+    let grid_dimensions = common::cube_grid(GRID_SIZE);
+    let mut simulation = Simulation::new(device, queue, grid_dimensions, WorkgroupSize::new(4, 4, 4), None)
+        .expect("failed to create the simulation");
+    assert_eq!(simulation.pressure_field().get_read_texture().texture.format(), pressure_format);
+    simulation.sim_params_mut().update_dt(Duration::from_secs_f32(DT));
+    if let Some(pressure_iterations) = pressure_iterations {
+        simulation.set_pressure_iterations(pressure_iterations);
+    }
+    simulation.set_pending_input(true);
+
+    for _ in 0..steps {
+        simulation.step(device, queue);
+    }
+    simulation
+}
+
+/// Mean absolute central-difference divergence of the velocity over the interior voxels, per
+/// second. Boundary voxels are left out, since one of their neighbours is the wall rather than
+/// the fluid.
+fn mean_abs_divergence(simulation: &Simulation, device: &wgpu::Device, queue: &wgpu::Queue) -> f32 {
+    let velocity = simulation
+        .velocity_field()
+        .get_read_texture()
+        .read_to_vec_f32(device, queue)
+        .expect("failed to read back the velocity field");
+    let n = GRID_SIZE as usize;
+    let index = |x: usize, y: usize, z: usize| x + n * (y + n * z);
+
+    let mut total = 0.0;
+    let mut count = 0;
+    for z in 1..n - 1 {
+        for y in 1..n - 1 {
+            for x in 1..n - 1 {
+                let divergence = (velocity[index(x + 1, y, z)][0] - velocity[index(x - 1, y, z)][0]
+                    + velocity[index(x, y + 1, z)][1]
+                    - velocity[index(x, y - 1, z)][1]
+                    + velocity[index(x, y, z + 1)][2]
+                    - velocity[index(x, y, z - 1)][2])
+                    * 0.5;
+                total += divergence.abs();
+                count += 1;
+            }
+        }
+    }
+    total / count as f32
+}
@@ -0,0 +1,113 @@
+//! Checks that the viscosity solve smooths a step in the velocity more with more iterations.
+//!
+//! The lower half of the grid moves along +x and the upper half is still, a shear step that
+//! advection along x leaves as it is and that is already divergence free. With every other force
+//! off, one step with viscosity runs the Jacobi solve of the implicit diffusion, which starts
+//! from the undiffused velocity and spreads the step further with each iteration. The largest
+//! velocity difference between neighbouring voxels across the step must be below the undiffused
+//! one, and shrink with every increase in the number of iterations. A solve that never ran, or
+//! one whose extra iterations don't reach the field, leaves it where it was.
+//!
+//! Needs a GPU. Skipped when no adapter is found, unless FIRESIM_REQUIRE_GPU is set (see common).
+
+mod common;
+
+use std::time::Duration;
+
+use firesim::{Simulation, WorkgroupSize};
+use half::f16;
+
+const GRID_SIZE: u32 = 16;
+const DT: f32 = 1.0 / 60.0;
+/// Speed of the lower half of the grid, in voxel lengths per second.
+const SPEED: f32 = 10.0;
+/// Iteration counts to compare, in increasing order.
+const ITERATIONS: [u32; 3] = [1, 4, 16];
+/// Voxels left out next to every wall, where the walls rather than the solve shape the velocity.
+const MARGIN: u32 = 2;
+
+#[test]
+fn more_iterations_smooth_velocity_step() {
+    pollster::block_on(more_iterations_smooth_velocity_step_async());
+}
+
+async fn more_iterations_smooth_velocity_step_async() {
+    let Some((device, queue)) = common::gpu_device(|_| Ok(wgpu::Features::empty())).await else {
+        return;
+    };
+
+    let mut previous = (0, SPEED);
+    for iterations in ITERATIONS {
+        let gradient = max_gradient_after_step(&device, &queue, iterations);
+        assert!(gradient.is_finite(), "non-finite velocity after {iterations} iterations");
+        assert!(
+            gradient < previous.1,
+            "max gradient {gradient} after {iterations} iterations isn't below {} after {}",
+            previous.1,
+            previous.0,
+        );
+        previous = (iterations, gradient);
+    }
+}
+
+/// Largest difference in x velocity between vertical neighbours away from the walls, after one
+/// step from the shear step with the given number of viscosity iterations.
+fn max_gradient_after_step(device: &wgpu::Device, queue: &wgpu::Queue, iterations: u32) -> f32 {
+    let grid_dimensions = common::cube_grid(GRID_SIZE);
+    let mut simulation = Simulation::new(device, queue, grid_dimensions, WorkgroupSize::new(4, 4, 4), None)
+        .expect("failed to create the simulation");
+    simulation.set_projection_enabled(false);
+    simulation.set_diffusion_iterations(iterations);
+    let sim_params = simulation.sim_params_mut();
+    sim_params.update_dt(Duration::from_secs_f32(DT));
+    sim_params.update_buoyancy_coefficient(0.0);
+    sim_params.update_gravity(0.0);
+    sim_params.update_vorticity_strength(0.0);
+    // Weights each neighbour as much as the voxel itself, so a single iteration already spreads
+    // the step.
+    let cell_size = sim_params.cell_size()[1];
+    sim_params.update_viscosity(cell_size * cell_size / DT);
+
+    simulation
+        .velocity_field()
+        .get_read_texture()
+        .write_from_raw_f16(queue, &shear_step_f16(), grid_dimensions)
+        .expect("failed to write the velocity step");
+    simulation.step(device, queue);
+
+    let velocity = simulation
+        .velocity_field()
+        .get_read_texture()
+        .read_to_vec_f32(device, queue)
+        .expect("failed to read back the velocity field");
+    let n = GRID_SIZE as usize;
+    let index = |x: u32, y: u32, z: u32| x as usize + n * (y as usize + n * z as usize);
+
+    let interior = MARGIN..GRID_SIZE - MARGIN;
+    let mut max_gradient: f32 = 0.0;
+    for z in interior.clone() {
+        for y in MARGIN..GRID_SIZE - MARGIN - 1 {
+            for x in interior.clone() {
+                let gradient = (velocity[index(x, y + 1, z)][0] - velocity[index(x, y, z)][0]).abs();
+                max_gradient = max_gradient.max(gradient);
+            }
+        }
+    }
+    max_gradient
+}
+
+/// Raw f16 velocity moving along +x at SPEED below mid-height and still above it.
+fn shear_step_f16() -> Vec<u8> {
+    let mut bytes = Vec::with_capacity((GRID_SIZE * GRID_SIZE * GRID_SIZE * 8) as usize);
+    for _z in 0..GRID_SIZE {
+        for y in 0..GRID_SIZE {
+            for _x in 0..GRID_SIZE {
+                let speed = if y < GRID_SIZE / 2 { SPEED } else { 0.0 };
+                for value in [speed, 0.0, 0.0, 0.0] {
+                    bytes.extend_from_slice(&f16::from_f32(value).to_le_bytes());
+                }
+            }
+        }
+    }
+    bytes
+}